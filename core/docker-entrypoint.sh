@@ -16,4 +16,4 @@ else
 fi
 
 # Run the application with the appropriate service type
-exec OpenRDX-Core
\ No newline at end of file
+exec OpenRDX-Core "$@"
\ No newline at end of file
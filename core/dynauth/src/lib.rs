@@ -0,0 +1,463 @@
+//! RFC 5176 Dynamic Authorization client: builds signed CoA-Request and Disconnect-Request packets for a
+//! NAS, retransmits them until the NAS answers, and decodes the ACK or NAK with its Error-Cause.
+//!
+//! Shared by the core (Simultaneous-Use disconnects) and the northbound API (operator-initiated CoA/DM).
+
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use md5::{Digest, Md5};
+use tokio::net::UdpSocket;
+use tracing::warn;
+
+/// Port NASes listen on for dynamic authorization requests (RFC 5176, section 3)
+pub const DEFAULT_COA_PORT: u16 = 3799;
+
+const CODE_DISCONNECT_REQUEST: u8 = 40;
+const CODE_DISCONNECT_ACK: u8 = 41;
+const CODE_DISCONNECT_NAK: u8 = 42;
+const CODE_COA_REQUEST: u8 = 43;
+const CODE_COA_ACK: u8 = 44;
+const CODE_COA_NAK: u8 = 45;
+
+const ATTR_USER_NAME: u8 = 1;
+const ATTR_NAS_IP_ADDRESS: u8 = 4;
+const ATTR_NAS_PORT: u8 = 5;
+const ATTR_FRAMED_IP_ADDRESS: u8 = 8;
+const ATTR_CALLING_STATION_ID: u8 = 31;
+const ATTR_NAS_IDENTIFIER: u8 = 32;
+const ATTR_ACCT_SESSION_ID: u8 = 44;
+const ATTR_MESSAGE_AUTHENTICATOR: u8 = 80;
+const ATTR_ERROR_CAUSE: u8 = 101;
+
+const MAX_PACKET_LEN: usize = 4096;
+const MAX_ATTRIBUTE_VALUE_LEN: usize = 253;
+
+#[derive(Debug)]
+pub enum DynAuthError {
+    /// The request names no session attribute, so the NAS would act on all of them
+    NoSessionIdentification,
+    AttributeTooLong(u8),
+    PacketTooLarge(usize),
+    Io(io::Error),
+    /// No valid reply after every retransmission
+    Timeout { target: SocketAddr, attempts: u32 },
+    InvalidReply(String),
+}
+
+impl fmt::Display for DynAuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DynAuthError::NoSessionIdentification => write!(f, "no session identification attribute in the request"),
+            DynAuthError::AttributeTooLong(typ) => write!(f, "value of attribute {} is longer than {} bytes", typ, MAX_ATTRIBUTE_VALUE_LEN),
+            DynAuthError::PacketTooLarge(len) => write!(f, "packet of {} bytes exceeds {} bytes", len, MAX_PACKET_LEN),
+            DynAuthError::Io(e) => write!(f, "{}", e),
+            DynAuthError::Timeout { target, attempts } => write!(f, "no reply from {} after {} attempts", target, attempts),
+            DynAuthError::InvalidReply(reason) => write!(f, "invalid reply: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for DynAuthError {}
+
+impl From<io::Error> for DynAuthError {
+    fn from(e: io::Error) -> Self {
+        DynAuthError::Io(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestKind {
+    /// Disconnect-Request: end the session
+    Disconnect,
+    /// CoA-Request: change the session's authorization
+    Coa,
+}
+
+impl RequestKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            RequestKind::Disconnect => "Disconnect-Request",
+            RequestKind::Coa => "CoA-Request",
+        }
+    }
+
+    fn codes(self) -> (u8, u8, u8) {
+        match self {
+            RequestKind::Disconnect => (CODE_DISCONNECT_REQUEST, CODE_DISCONNECT_ACK, CODE_DISCONNECT_NAK),
+            RequestKind::Coa => (CODE_COA_REQUEST, CODE_COA_ACK, CODE_COA_NAK),
+        }
+    }
+}
+
+/// Attributes identifying the session(s) a request applies to (RFC 5176, section 3)
+#[derive(Debug, Clone, Default)]
+pub struct SessionSelector {
+    pub acct_session_id: Option<String>,
+    pub username: Option<String>,
+    pub framed_ip: Option<Ipv4Addr>,
+    pub nas_port: Option<u32>,
+    pub calling_station_id: Option<String>,
+    /// NAS identification, which the NAS checks against itself
+    pub nas_ip: Option<Ipv4Addr>,
+    pub nas_identifier: Option<String>,
+}
+
+impl SessionSelector {
+    fn attributes(&self) -> Vec<Attribute> {
+        let text = |typ: u8, value: &Option<String>| value.as_ref().map(|value| Attribute::new(typ, value.as_bytes()));
+        [
+            text(ATTR_USER_NAME, &self.username),
+            self.nas_ip.map(|ip| Attribute::new(ATTR_NAS_IP_ADDRESS, &ip.octets())),
+            self.nas_port.map(|port| Attribute::new(ATTR_NAS_PORT, &port.to_be_bytes())),
+            self.framed_ip.map(|ip| Attribute::new(ATTR_FRAMED_IP_ADDRESS, &ip.octets())),
+            text(ATTR_CALLING_STATION_ID, &self.calling_station_id),
+            text(ATTR_NAS_IDENTIFIER, &self.nas_identifier),
+            text(ATTR_ACCT_SESSION_ID, &self.acct_session_id),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    fn identifies_session(&self) -> bool {
+        self.acct_session_id.is_some() || self.username.is_some() || self.framed_ip.is_some()
+            || self.nas_port.is_some() || self.calling_station_id.is_some()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attribute {
+    pub typ: u8,
+    pub value: Vec<u8>,
+}
+
+impl Attribute {
+    pub fn new(typ: u8, value: &[u8]) -> Self {
+        Self { typ, value: value.to_vec() }
+    }
+}
+
+/// Error-Cause values (RFC 5176, section 3.6)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorCause(pub u32);
+
+impl ErrorCause {
+    pub fn description(self) -> &'static str {
+        match self.0 {
+            201 => "Residual Session Context Removed",
+            202 => "Invalid EAP Packet (Ignored)",
+            401 => "Unsupported Attribute",
+            402 => "Missing Attribute",
+            403 => "NAS Identification Mismatch",
+            404 => "Invalid Request",
+            405 => "Unsupported Service",
+            406 => "Unsupported Extension",
+            407 => "Invalid Attribute Value",
+            501 => "Administratively Prohibited",
+            502 => "Request Not Routable (Proxy)",
+            503 => "Session Context Not Found",
+            504 => "Session Context Not Removable",
+            505 => "Other Proxy Processing Error",
+            506 => "Resources Unavailable",
+            507 => "Request Initiated",
+            508 => "Multiple Session Selection Unsupported",
+            _ => "Unknown",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.description(), self.0)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub kind: RequestKind,
+    pub session: SessionSelector,
+    /// Authorization changes of a CoA-Request (Session-Timeout, Filter-Id, VSAs, ...), sent after the session attributes
+    pub attributes: Vec<Attribute>,
+    /// Adds a Message-Authenticator, which some NASes require on every request
+    pub message_authenticator: bool,
+}
+
+impl Request {
+    pub fn disconnect(session: SessionSelector) -> Self {
+        Self { kind: RequestKind::Disconnect, session, attributes: Vec::new(), message_authenticator: false }
+    }
+
+    pub fn coa(session: SessionSelector, attributes: Vec<Attribute>) -> Self {
+        Self { kind: RequestKind::Coa, session, attributes, message_authenticator: false }
+    }
+
+    /// The request as sent, signed with the NAS's secret
+    pub fn encode(&self, identifier: u8, secret: &str) -> Result<Vec<u8>, DynAuthError> {
+        if !self.session.identifies_session() {
+            return Err(DynAuthError::NoSessionIdentification);
+        }
+
+        let mut packet = vec![self.kind.codes().0, identifier, 0, 0];
+        packet.extend_from_slice(&[0u8; 16]);
+        for attribute in self.session.attributes().iter().chain(&self.attributes) {
+            if attribute.value.len() > MAX_ATTRIBUTE_VALUE_LEN {
+                return Err(DynAuthError::AttributeTooLong(attribute.typ));
+            }
+            packet.push(attribute.typ);
+            packet.push((attribute.value.len() + 2) as u8);
+            packet.extend_from_slice(&attribute.value);
+        }
+        let message_authenticator = self.message_authenticator.then(|| {
+            packet.extend_from_slice(&[ATTR_MESSAGE_AUTHENTICATOR, 18]);
+            packet.len()
+        });
+        packet.extend_from_slice(&[0u8; 16][..if message_authenticator.is_some() { 16 } else { 0 }]);
+        if packet.len() > MAX_PACKET_LEN {
+            return Err(DynAuthError::PacketTooLarge(packet.len()));
+        }
+        let length = packet.len() as u16;
+        packet[2..4].copy_from_slice(&length.to_be_bytes());
+
+        // Message-Authenticator over the packet with a zero Request Authenticator (RFC 5176, section 3.5),
+        // then the Request Authenticator over the packet including it, as for Accounting-Request
+        if let Some(offset) = message_authenticator {
+            let mut mac = <Hmac<Md5> as Mac>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+            mac.update(&packet);
+            packet[offset..offset + 16].copy_from_slice(&mac.finalize().into_bytes());
+        }
+        let mut hasher = Md5::new();
+        hasher.update(&packet);
+        hasher.update(secret.as_bytes());
+        packet[4..20].copy_from_slice(&hasher.finalize());
+        Ok(packet)
+    }
+}
+
+/// A NAS's answer to a request
+#[derive(Debug, Clone)]
+pub struct Reply {
+    /// ACK, as opposed to NAK
+    pub acked: bool,
+    pub error_cause: Option<ErrorCause>,
+    pub attributes: Vec<Attribute>,
+}
+
+/// Checks and decodes the reply to `request`, a packet built by `Request::encode`
+pub fn parse_reply(reply: &[u8], request: &[u8], secret: &str) -> Result<Reply, DynAuthError> {
+    if reply.len() < 20 || request.len() < 20 {
+        return Err(DynAuthError::InvalidReply(format!("{} bytes is too short", reply.len())));
+    }
+    let length = u16::from_be_bytes([reply[2], reply[3]]) as usize;
+    if length < 20 || length > reply.len() {
+        return Err(DynAuthError::InvalidReply(format!("length field {} for {} bytes", length, reply.len())));
+    }
+    let reply = &reply[..length];
+    if reply[1] != request[1] {
+        return Err(DynAuthError::InvalidReply(format!("identifier {} instead of {}", reply[1], request[1])));
+    }
+
+    let (_, ack, nak) = match request[0] {
+        CODE_DISCONNECT_REQUEST => RequestKind::Disconnect.codes(),
+        _ => RequestKind::Coa.codes(),
+    };
+    let acked = match reply[0] {
+        code if code == ack => true,
+        code if code == nak => false,
+        code => return Err(DynAuthError::InvalidReply(format!("unexpected code {}", code))),
+    };
+
+    let mut hasher = Md5::new();
+    hasher.update(&reply[..4]);
+    hasher.update(&request[4..20]);
+    hasher.update(&reply[20..]);
+    hasher.update(secret.as_bytes());
+    if hasher.finalize().as_slice() != &reply[4..20] {
+        return Err(DynAuthError::InvalidReply("response authenticator mismatch, check the secret".to_string()));
+    }
+
+    let mut attributes = Vec::new();
+    let mut message_authenticator = None;
+    let mut pos = 20;
+    while pos < reply.len() {
+        let len = reply.get(pos + 1).copied().unwrap_or(0) as usize;
+        if len < 2 || pos + len > reply.len() {
+            return Err(DynAuthError::InvalidReply(format!("malformed attribute at offset {}", pos)));
+        }
+        if reply[pos] == ATTR_MESSAGE_AUTHENTICATOR {
+            if len != 18 {
+                return Err(DynAuthError::InvalidReply(format!("Message-Authenticator of {} bytes", len - 2)));
+            }
+            message_authenticator = Some(pos + 2);
+        }
+        attributes.push(Attribute::new(reply[pos], &reply[pos + 2..pos + len]));
+        pos += len;
+    }
+
+    // Computed over the reply with the Request Authenticator in place of the Response Authenticator
+    if let Some(offset) = message_authenticator {
+        let mut signed = reply.to_vec();
+        signed[4..20].copy_from_slice(&request[4..20]);
+        signed[offset..offset + 16].fill(0);
+        let mut mac = <Hmac<Md5> as Mac>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+        mac.update(&signed);
+        if mac.verify_slice(&reply[offset..offset + 16]).is_err() {
+            return Err(DynAuthError::InvalidReply("Message-Authenticator mismatch".to_string()));
+        }
+    }
+    let error_cause = attributes.iter()
+        .find(|attr| attr.typ == ATTR_ERROR_CAUSE)
+        .and_then(|attr| <[u8; 4]>::try_from(attr.value.as_slice()).ok())
+        .map(|value| ErrorCause(u32::from_be_bytes(value)));
+
+    Ok(Reply { acked, error_cause, attributes })
+}
+
+/// Sends requests from an ephemeral port, retransmitting the same packet when the NAS doesn't answer in time
+#[derive(Debug, Clone)]
+pub struct Client {
+    timeout: Duration,
+    retries: u32,
+}
+
+impl Client {
+    /// `timeout` is per attempt; `retries` is the number of retransmissions after the first attempt
+    pub fn new(timeout: Duration, retries: u32) -> Self {
+        Self { timeout, retries }
+    }
+
+    pub async fn send(&self, target: SocketAddr, secret: &str, request: &Request) -> Result<Reply, DynAuthError> {
+        let packet = request.encode(rand::random(), secret)?;
+        let bind: SocketAddr = match target.ip() {
+            IpAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            IpAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let socket = UdpSocket::bind(bind).await?;
+        socket.connect(target).await?;
+
+        let attempts = self.retries + 1;
+        let mut buf = [0u8; MAX_PACKET_LEN];
+        for _ in 0..attempts {
+            socket.send(&packet).await?;
+            let deadline = tokio::time::Instant::now() + self.timeout;
+            // Stray or forged datagrams don't end the attempt, nor say anything about the NAS;
+            // only a valid reply or the deadline does
+            while let Ok(received) = tokio::time::timeout_at(deadline, socket.recv(&mut buf)).await {
+                match parse_reply(&buf[..received?], &packet, secret) {
+                    Ok(reply) => return Ok(reply),
+                    Err(e) => warn!("Ignoring a datagram from {} that isn't a valid reply: {}", target, e),
+                }
+            }
+        }
+        Err(DynAuthError::Timeout { target, attempts })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reply_to(request: &[u8], code: u8, attributes: &[u8], secret: &str) -> Vec<u8> {
+        let mut reply = vec![code, request[1]];
+        reply.extend_from_slice(&((20 + attributes.len()) as u16).to_be_bytes());
+        let mut hasher = Md5::new();
+        hasher.update(&reply);
+        hasher.update(&request[4..20]);
+        hasher.update(attributes);
+        hasher.update(secret.as_bytes());
+        reply.extend_from_slice(&hasher.finalize());
+        reply.extend_from_slice(attributes);
+        reply
+    }
+
+    #[tokio::test]
+    async fn test_retransmits_until_answered() {
+        let secret = "coa-secret";
+        let nas = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let target = nas.local_addr().unwrap();
+
+        // A NAS that drops the first request, checks the authenticators of the retransmission and NAKs it
+        let responder = tokio::spawn(async move {
+            let mut buf = [0u8; 4096];
+            let (first, _) = nas.recv_from(&mut buf).await.unwrap();
+            let first = buf[..first].to_vec();
+            let (len, peer) = nas.recv_from(&mut buf).await.unwrap();
+            let request = buf[..len].to_vec();
+            assert_eq!(request, first);
+
+            let mut zeroed = request.clone();
+            zeroed[4..20].fill(0);
+            let mut hasher = Md5::new();
+            hasher.update(&zeroed);
+            hasher.update(secret.as_bytes());
+            assert_eq!(hasher.finalize().as_slice(), &request[4..20]);
+
+            let offset = request.len() - 16;
+            assert_eq!(&request[offset - 2..offset], &[ATTR_MESSAGE_AUTHENTICATOR, 18]);
+            let mut zeroed = request.clone();
+            zeroed[4..20].fill(0);
+            zeroed[offset..].fill(0);
+            let mut mac = <Hmac<Md5> as Mac>::new_from_slice(secret.as_bytes()).unwrap();
+            mac.update(&zeroed);
+            assert_eq!(mac.finalize().into_bytes().as_slice(), &request[offset..]);
+
+            let error_cause = [ATTR_ERROR_CAUSE, 6, 0, 0, 0x01, 0xf7];
+            nas.send_to(&reply_to(&request, CODE_COA_NAK, &error_cause, secret), peer).await.unwrap();
+            request
+        });
+
+        let session = SessionSelector {
+            acct_session_id: Some("5A3F0001".to_string()),
+            framed_ip: Some(Ipv4Addr::new(100, 64, 0, 10)),
+            ..Default::default()
+        };
+        let mut request = Request::coa(session, vec![Attribute::new(27, &3600u32.to_be_bytes())]);
+        request.message_authenticator = true;
+        let reply = Client::new(Duration::from_millis(200), 2).send(target, secret, &request).await.unwrap();
+        assert!(!reply.acked);
+        assert_eq!(reply.error_cause, Some(ErrorCause(503)));
+        assert_eq!(reply.error_cause.unwrap().to_string(), "Session Context Not Found (503)");
+
+        let sent = responder.await.unwrap();
+        assert_eq!(sent[0], CODE_COA_REQUEST);
+        assert_eq!(&sent[20..26], &[ATTR_FRAMED_IP_ADDRESS, 6, 100, 64, 0, 10]);
+
+        assert!(matches!(Request::disconnect(SessionSelector::default()).encode(1, secret), Err(DynAuthError::NoSessionIdentification)));
+        let packet = Request::disconnect(SessionSelector { username: Some("alice".to_string()), ..Default::default() }).encode(7, secret).unwrap();
+        assert!(parse_reply(&reply_to(&packet, CODE_DISCONNECT_ACK, &[], "wrong"), &packet, secret).is_err());
+        assert!(parse_reply(&reply_to(&packet, CODE_DISCONNECT_ACK, &[], secret), &packet, secret).unwrap().acked);
+
+        // A reply's Message-Authenticator is checked too
+        let mut message_authenticator = vec![ATTR_MESSAGE_AUTHENTICATOR, 18];
+        message_authenticator.extend_from_slice(&[0u8; 16]);
+        let mut unsigned = reply_to(&packet, CODE_DISCONNECT_ACK, &message_authenticator, secret);
+        assert!(parse_reply(&unsigned, &packet, secret).is_err());
+        unsigned[4..20].copy_from_slice(&packet[4..20]);
+        let mut mac = <Hmac<Md5> as Mac>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(&unsigned);
+        message_authenticator[2..].copy_from_slice(&mac.finalize().into_bytes());
+        assert!(parse_reply(&reply_to(&packet, CODE_DISCONNECT_ACK, &message_authenticator, secret), &packet, secret).unwrap().acked);
+    }
+
+    #[tokio::test]
+    async fn test_unverifiable_replies_time_out() {
+        let nas = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let target = nas.local_addr().unwrap();
+
+        // Every attempt gets an answer, signed with another secret
+        tokio::spawn(async move {
+            let mut buf = [0u8; 4096];
+            while let Ok((len, peer)) = nas.recv_from(&mut buf).await {
+                let forged = reply_to(&buf[..len], CODE_DISCONNECT_ACK, &[], "forged");
+                nas.send_to(&forged, peer).await.unwrap();
+            }
+        });
+
+        let request = Request::disconnect(SessionSelector { username: Some("alice".to_string()), ..Default::default() });
+        let result = Client::new(Duration::from_millis(100), 1).send(target, "coa-secret", &request).await;
+        assert!(matches!(result, Err(DynAuthError::Timeout { attempts: 2, .. })));
+    }
+}
@@ -0,0 +1,14 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use openrdx_core::accounting::AccountingPacket;
+use openrdx_core::auth::RadiusPacket;
+
+// The accounting service walks attributes on its own; it must agree with the authentication parser
+fuzz_target!(|data: &[u8]| {
+    let accounting = AccountingPacket::parse(data)
+        .map(|packet| packet.attributes.into_iter().map(|attr| (attr.typ, attr.value)).collect::<Vec<_>>());
+    let radius = RadiusPacket::parse(data)
+        .map(|packet| packet.attributes.into_iter().map(|attr| (attr.typ, attr.value)).collect::<Vec<_>>());
+    assert_eq!(accounting, radius);
+});
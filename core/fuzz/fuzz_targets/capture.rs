@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use openrdx_core::replay;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(datagrams) = replay::read_datagrams(data) {
+        let _ = replay::pair_exchanges(&datagrams);
+    }
+});
@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use openrdx_core::auth::RadiusPacket;
+use openrdx_core::client;
+
+// Reply checking and the attribute decoder behind `format_packet` see whatever a server sends back
+fuzz_target!(|data: &[u8]| {
+    let identifier = data.get(1).copied().unwrap_or(0);
+    let request = RadiusPacket { code: 1, identifier, length: 20, authenticator: [7; 16], attributes: Vec::new() };
+    let _ = client::check_reply(&request, data, "testing123");
+    if let Ok(packet) = RadiusPacket::parse(data) {
+        let _ = client::format_packet(&packet);
+    }
+});
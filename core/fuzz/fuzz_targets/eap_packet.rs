@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use openrdx_core::auth::EapPacket;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(packet) = EapPacket::parse(data) {
+        assert!(packet.length as usize <= data.len());
+        let reparsed = EapPacket::parse(&packet.encode()).expect("encoded packet parses");
+        assert_eq!((reparsed.code, reparsed.identifier, reparsed.type_), (packet.code, packet.identifier, packet.type_));
+        assert_eq!(reparsed.data, packet.data);
+    }
+});
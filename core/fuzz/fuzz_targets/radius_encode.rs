@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use openrdx_core::auth::{RadiusAttribute, RadiusPacket};
+
+// Whatever the encoder produces, including truncated attributes and packets capped at 4096 bytes,
+// must parse and encode to the same bytes again
+fuzz_target!(|input: (u8, u8, [u8; 16], Vec<(u8, Vec<u8>)>)| {
+    let (code, identifier, authenticator, attributes) = input;
+    let attributes = attributes.into_iter().map(|(typ, value)| RadiusAttribute { typ, value }).collect();
+    let encoded = RadiusPacket { code, identifier, length: 0, authenticator, attributes }.encode();
+    let parsed = RadiusPacket::parse(&encoded).expect("encoded packet parses");
+    assert_eq!(parsed.length as usize, encoded.len());
+    assert_eq!(parsed.encode(), encoded);
+});
@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use openrdx_core::auth::RadiusPacket;
+
+// Whatever parses must encode back to the bytes it came from
+fuzz_target!(|data: &[u8]| {
+    if let Ok(packet) = RadiusPacket::parse(data) {
+        assert_eq!(packet.encode(), &data[..packet.length as usize]);
+    }
+});
@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use openrdx_core::status_server::{self, RadiusStats, CODE_ACCESS_ACCEPT};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = status_server::handle_status_server(data, "testing123", CODE_ACCESS_ACCEPT, &RadiusStats::default());
+});
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use crate::auth::ParseError;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Session {
@@ -30,6 +32,8 @@ pub struct Quota {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AccountingPacket {
     pub packet_type: u8,
+    /// Request Authenticator, which the Response Authenticator is computed over
+    pub authenticator: [u8; 16],
     pub session_id: String,
     pub username: String,
     pub nas_ip: String,
@@ -45,14 +49,47 @@ pub struct AccountingAttribute {
 }
 
 impl AccountingPacket {
-    pub fn parse(data: &[u8]) -> Option<Self> {
-        if data.len() < 20 { return None; }
-        
+    /// Value of an integer attribute (Acct-Status-Type, Acct-Session-Time, ...)
+    pub fn integer(&self, typ: u8) -> Option<u32> {
+        self.attributes.iter()
+            .find(|attr| attr.typ == typ)
+            .and_then(|attr| <[u8; 4]>::try_from(attr.value.as_slice()).ok())
+            .map(u32::from_be_bytes)
+    }
+
+    /// Value of an IPv4 address attribute (NAS-IP-Address, Framed-IP-Address)
+    pub fn ipv4(&self, typ: u8) -> Option<Ipv4Addr> {
+        self.attributes.iter()
+            .find(|attr| attr.typ == typ)
+            .and_then(|attr| <[u8; 4]>::try_from(attr.value.as_slice()).ok())
+            .map(Ipv4Addr::from)
+    }
+
+    /// Prefixes of an ipv6prefix attribute (Framed-IPv6-Prefix, Delegated-IPv6-Prefix), without their lengths
+    pub fn ipv6_prefixes(&self, typ: u8) -> Vec<Ipv6Addr> {
+        self.attributes.iter()
+            .filter(|attr| attr.typ == typ && attr.value.len() >= 2 && attr.value.len() <= 18)
+            .map(|attr| {
+                let mut octets = [0u8; 16];
+                octets[..attr.value.len() - 2].copy_from_slice(&attr.value[2..]);
+                Ipv6Addr::from(octets)
+            })
+            .collect()
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self, ParseError> {
+        if data.len() < 20 {
+            return Err(ParseError::TooShort(data.len()));
+        }
+
         //let code = data[0];
         let identifier = data[1];
         let length = u16::from_be_bytes([data[2], data[3]]);
-        if data.len() < length as usize { return None; }
-        
+        if length < 20 || data.len() < length as usize {
+            return Err(ParseError::BadLength { length, received: data.len() });
+        }
+        let data = &data[..length as usize];
+
         let mut pos = 20;
         let mut attributes = Vec::new();
         let mut session_id = String::new();
@@ -60,12 +97,12 @@ impl AccountingPacket {
         let mut nas_ip = String::new();
         let mut nas_port = 0u32;
 
-        while pos < length as usize {
-            if data.len() < pos + 2 { break; }
-            let typ = data[pos];
-            let len = data[pos + 1] as usize;
-            if data.len() < pos + len { break; }
-            
+        while pos < data.len() {
+            let (typ, len) = match data.get(pos..pos + 2) {
+                Some(&[typ, len]) if len >= 2 && pos + len as usize <= data.len() => (typ, len as usize),
+                _ => return Err(ParseError::BadAttribute { offset: pos }),
+            };
+
             let value = data[pos + 2..pos + len].to_vec();
             attributes.push(AccountingAttribute { typ, value: value.clone() });
 
@@ -97,8 +134,12 @@ impl AccountingPacket {
             pos += len;
         }
 
-        Some(Self {
+        let mut authenticator = [0u8; 16];
+        authenticator.copy_from_slice(&data[4..20]);
+
+        Ok(Self {
             packet_type: identifier,
+            authenticator,
             session_id,
             username,
             nas_ip,
@@ -107,4 +148,37 @@ impl AccountingPacket {
             attributes,
         })
     }
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::{RadiusAttribute, RadiusPacket};
+    use proptest::prelude::*;
+
+    fn arb_attribute() -> impl Strategy<Value = RadiusAttribute> {
+        (any::<u8>(), prop::collection::vec(any::<u8>(), 0..=253)).prop_map(|(typ, value)| RadiusAttribute { typ, value })
+    }
+
+    proptest! {
+        #[test]
+        fn prop_parse_agrees_with_radius_packet(data in prop::collection::vec(any::<u8>(), 0..512)) {
+            let accounting = AccountingPacket::parse(&data)
+                .map(|packet| packet.attributes.into_iter().map(|attr| (attr.typ, attr.value)).collect::<Vec<_>>());
+            let radius = RadiusPacket::parse(&data)
+                .map(|packet| packet.attributes.into_iter().map(|attr| (attr.typ, attr.value)).collect::<Vec<_>>());
+            prop_assert_eq!(accounting, radius);
+        }
+
+        #[test]
+        fn prop_parse_encoded_request(identifier: u8, session_id in "[0-9A-F]{1,16}",
+                                      attributes in prop::collection::vec(arb_attribute(), 0..15)) {
+            let mut attributes: Vec<RadiusAttribute> = attributes.into_iter().filter(|attr| attr.typ != 44).collect();
+            attributes.truncate(14);
+            attributes.push(RadiusAttribute { typ: 44, value: session_id.clone().into_bytes() });
+            let request = RadiusPacket { code: 4, identifier, length: 0, authenticator: [0; 16], attributes };
+            let packet = AccountingPacket::parse(&request.encode()).unwrap();
+            prop_assert_eq!(packet.session_id, session_id);
+            prop_assert_eq!(packet.attributes.len(), request.attributes.len());
+        }
+    }
+}
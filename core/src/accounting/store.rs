@@ -0,0 +1,194 @@
+use std::sync::{Arc, Mutex};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mongodb::Database;
+use mongodb::bson::{doc, DateTime as BsonDateTime, Document};
+
+use crate::auth::BackendError;
+use super::models::Session;
+
+/// Counters and stop details reported by an Interim-Update or Stop; None leaves a field as it is
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SessionUpdate {
+    pub session_time: Option<u64>,
+    pub input_octets: Option<u64>,
+    pub output_octets: Option<u64>,
+    pub input_packets: Option<u64>,
+    pub output_packets: Option<u64>,
+    pub termination_cause: Option<String>,
+    pub stop_time: Option<DateTime<Utc>>,
+}
+
+impl SessionUpdate {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn apply(&self, session: &mut Session) {
+        session.session_time = self.session_time.unwrap_or(session.session_time);
+        session.input_octets = self.input_octets.unwrap_or(session.input_octets);
+        session.output_octets = self.output_octets.unwrap_or(session.output_octets);
+        session.input_packets = self.input_packets.unwrap_or(session.input_packets);
+        session.output_packets = self.output_packets.unwrap_or(session.output_packets);
+        if self.termination_cause.is_some() {
+            session.termination_cause = self.termination_cause.clone();
+        }
+        if self.stop_time.is_some() {
+            session.stop_time = self.stop_time;
+        }
+    }
+
+    fn to_document(&self) -> Document {
+        let mut update = doc! {};
+        let counters = [
+            ("session_time", self.session_time),
+            ("input_octets", self.input_octets),
+            ("output_octets", self.output_octets),
+            ("input_packets", self.input_packets),
+            ("output_packets", self.output_packets),
+        ];
+        for (field, value) in counters {
+            if let Some(value) = value {
+                update.insert(field, value as i64);
+            }
+        }
+        if let Some(cause) = &self.termination_cause {
+            update.insert("termination_cause", cause);
+        }
+        if let Some(stop_time) = self.stop_time {
+            update.insert("stop_time", BsonDateTime::from_system_time(stop_time.into()));
+        }
+        update
+    }
+}
+
+/// Where accounting sessions are recorded
+#[async_trait]
+pub trait SessionStore: Send + Sync {
+    async fn create(&self, session: Session) -> Result<(), BackendError>;
+
+    async fn update(&self, session_id: &str, update: &SessionUpdate) -> Result<(), BackendError>;
+
+    /// Stops every open session of a NAS, returning how many there were
+    async fn close_nas(&self, nas_ip: &str, stop_time: DateTime<Utc>, cause: &str) -> Result<u64, BackendError>;
+}
+
+/// The `sessions` collection, which the Simultaneous-Use check reads too
+pub struct MongoSessionStore {
+    db: Database,
+}
+
+impl MongoSessionStore {
+    pub fn new(db: Database) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl SessionStore for MongoSessionStore {
+    async fn create(&self, session: Session) -> Result<(), BackendError> {
+        let db_timer = crate::metrics::DB_QUERY_DURATION.with_label_values(&["session_insert"]).start_timer();
+        self.db.collection::<Session>("sessions").insert_one(session).await?;
+        db_timer.observe_duration();
+        Ok(())
+    }
+
+    async fn update(&self, session_id: &str, update: &SessionUpdate) -> Result<(), BackendError> {
+        let db_timer = crate::metrics::DB_QUERY_DURATION.with_label_values(&["session_update"]).start_timer();
+        self.db.collection::<Session>("sessions")
+            .update_one(doc! { "session_id": session_id }, doc! { "$set": update.to_document() })
+            .await?;
+        db_timer.observe_duration();
+        Ok(())
+    }
+
+    async fn close_nas(&self, nas_ip: &str, stop_time: DateTime<Utc>, cause: &str) -> Result<u64, BackendError> {
+        let db_timer = crate::metrics::DB_QUERY_DURATION.with_label_values(&["session_close_nas"]).start_timer();
+        let result = self.db.collection::<Session>("sessions").update_many(
+            doc! { "nas_ip": nas_ip, "stop_time": null },
+            doc! { "$set": { "stop_time": BsonDateTime::from_system_time(stop_time.into()), "termination_cause": cause } },
+        ).await?;
+        db_timer.observe_duration();
+        Ok(result.modified_count)
+    }
+}
+
+/// Sessions kept in memory, for running the accounting service without MongoDB.
+/// Clones share the same sessions, so a test can keep one and inspect what the server recorded.
+#[derive(Debug, Clone, Default)]
+pub struct MemorySessionStore {
+    sessions: Arc<Mutex<Vec<Session>>>,
+}
+
+impl MemorySessionStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Recorded sessions, oldest first
+    pub fn sessions(&self) -> Vec<Session> {
+        self.sessions.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl SessionStore for MemorySessionStore {
+    async fn create(&self, session: Session) -> Result<(), BackendError> {
+        self.sessions.lock().unwrap().push(session);
+        Ok(())
+    }
+
+    async fn update(&self, session_id: &str, update: &SessionUpdate) -> Result<(), BackendError> {
+        if let Some(session) = self.sessions.lock().unwrap().iter_mut().find(|session| session.session_id == session_id) {
+            update.apply(session);
+        }
+        Ok(())
+    }
+
+    async fn close_nas(&self, nas_ip: &str, stop_time: DateTime<Utc>, cause: &str) -> Result<u64, BackendError> {
+        let mut closed = 0;
+        for session in self.sessions.lock().unwrap().iter_mut()
+            .filter(|session| session.nas_ip == nas_ip && session.stop_time.is_none()) {
+            session.stop_time = Some(stop_time);
+            session.termination_cause = Some(cause.to_string());
+            closed += 1;
+        }
+        Ok(closed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_session_store() {
+        let store = MemorySessionStore::new();
+        for (session_id, nas_ip) in [("a", "192.0.2.1"), ("b", "192.0.2.1"), ("c", "192.0.2.2")] {
+            store.create(Session {
+                session_id: session_id.to_string(),
+                username: "alice".to_string(),
+                nas_ip: nas_ip.to_string(),
+                nas_port: 0,
+                start_time: Utc::now(),
+                stop_time: None,
+                input_octets: 0,
+                output_octets: 0,
+                input_packets: 0,
+                output_packets: 0,
+                session_time: 0,
+                termination_cause: None,
+            }).await.unwrap();
+        }
+
+        let update = SessionUpdate { session_time: Some(60), input_octets: Some(1 << 32), ..Default::default() };
+        store.update("a", &update).await.unwrap();
+        assert_eq!(store.close_nas("192.0.2.1", Utc::now(), "NAS-Reboot").await.unwrap(), 2);
+
+        let sessions = store.sessions();
+        assert_eq!((sessions[0].session_time, sessions[0].input_octets), (60, 1 << 32));
+        assert_eq!(sessions[1].termination_cause.as_deref(), Some("NAS-Reboot"));
+        assert!(sessions[2].stop_time.is_none());
+        assert_eq!(update.to_document().len(), 2);
+    }
+}
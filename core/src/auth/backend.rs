@@ -0,0 +1,148 @@
+use std::sync::Arc;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tracing::debug;
+
+use super::subscriber_cache::SubscriberCache;
+
+pub type BackendError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Which store credentials are checked against
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    #[default]
+    Postgres,
+    Ldap,
+}
+
+impl std::str::FromStr for BackendKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "postgres" => Ok(BackendKind::Postgres),
+            "ldap" => Ok(BackendKind::Ldap),
+            _ => Err(format!("unknown backend {:?}, expected postgres or ldap", s)),
+        }
+    }
+}
+
+/// A user as found in an authentication backend
+#[derive(Debug, Clone, PartialEq)]
+pub struct Identity {
+    pub username: String,
+    /// `user_identifiers.id`, for identities stored in Postgres
+    pub identifier_id: Option<i64>,
+    /// Directory entry, for identities found in LDAP
+    pub dn: Option<String>,
+    pub is_enabled: bool,
+    /// Cleartext password, only when the backend can reveal it (CHAP needs it)
+    pub cleartext_password: Option<String>,
+}
+
+/// Source of identities and credentials for Access-Request handling.
+///
+/// Backends that can't reveal a cleartext password or an NT hash make CHAP,
+/// MS-CHAP and MS-CHAPv2 fail for their users; PAP always works through `verify_pap`.
+#[async_trait]
+pub trait AuthBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn lookup_identity(&self, username: &str) -> Result<Option<Identity>, BackendError>;
+
+    async fn verify_pap(&self, identity: &Identity, password: &str) -> Result<bool, BackendError>;
+
+    /// NT hash (MD4 of the UTF-16LE password) used by MS-CHAP and MS-CHAPv2
+    async fn fetch_nt_hash(&self, identity: &Identity) -> Result<Option<Vec<u8>>, BackendError>;
+
+    async fn fetch_groups(&self, identity: &Identity) -> Result<Vec<String>, BackendError>;
+
+    /// Name of the `AuthAttributeGroup` whose attributes go into the Access-Accept
+    async fn attribute_group(&self, identity: &Identity) -> Result<Option<String>, BackendError>;
+}
+
+/// Identifiers and passwords from the `user_identifiers` table, read through the subscriber cache
+pub struct PostgresBackend {
+    pool: PgPool,
+    subscribers: Arc<SubscriberCache>,
+}
+
+impl PostgresBackend {
+    pub fn new(pool: PgPool, subscribers: Arc<SubscriberCache>) -> Self {
+        Self { pool, subscribers }
+    }
+}
+
+#[async_trait]
+impl AuthBackend for PostgresBackend {
+    fn name(&self) -> &'static str {
+        "postgres"
+    }
+
+    async fn lookup_identity(&self, username: &str) -> Result<Option<Identity>, BackendError> {
+        let record = self.subscribers.get_identifier(&self.pool, username).await?;
+        Ok(record.map(|record| Identity {
+            username: username.to_string(),
+            identifier_id: Some(record.id),
+            dn: None,
+            is_enabled: record.is_enabled,
+            cleartext_password: record.plain_password,
+        }))
+    }
+
+    async fn verify_pap(&self, identity: &Identity, password: &str) -> Result<bool, BackendError> {
+        Ok(identity.cleartext_password.as_deref().is_some_and(|stored| stored == password))
+    }
+
+    async fn fetch_nt_hash(&self, identity: &Identity) -> Result<Option<Vec<u8>>, BackendError> {
+        Ok(identity.cleartext_password.as_deref().map(nt_hash_of))
+    }
+
+    async fn fetch_groups(&self, identity: &Identity) -> Result<Vec<String>, BackendError> {
+        let Some(identifier_id) = identity.identifier_id else {
+            return Ok(Vec::new());
+        };
+
+        let groups = sqlx::query_scalar::<_, String>(
+            r#"
+            SELECT g.name
+            FROM user_identifiers ui
+            JOIN users_groups ug ON ug.user_id = ui.user_id
+            JOIN user_groups g ON g.id = ug.usergroup_id
+            WHERE ui.id = $1
+            ORDER BY g.name
+            "#
+        )
+        .bind(identifier_id)
+        .fetch_all(&self.pool)
+        .await?;
+        debug!("Groups for identifier ID {}: {:?}", identifier_id, groups);
+        Ok(groups)
+    }
+
+    async fn attribute_group(&self, identity: &Identity) -> Result<Option<String>, BackendError> {
+        let record = self.subscribers.get_identifier(&self.pool, &identity.username).await?;
+        Ok(record.and_then(|record| record.attribute_group(chrono::Utc::now()).map(str::to_string)))
+    }
+}
+
+/// NT hash of a cleartext password
+pub fn nt_hash_of(password: &str) -> Vec<u8> {
+    use md4::{Digest, Md4};
+
+    let utf16: Vec<u8> = password.encode_utf16().flat_map(|c| c.to_le_bytes()).collect();
+    Md4::digest(&utf16).to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nt_hash_of() {
+        // RFC 2759, section 9.2
+        assert_eq!(hex::encode(nt_hash_of("clientPass")), "44ebba8d5312b8d611474411f56989ae");
+    }
+}
@@ -0,0 +1,171 @@
+use std::str::FromStr;
+use md5::{Digest, Md5};
+
+const VENDOR_MICROSOFT: u32 = 311;
+const VENDOR_ATTR_MS_MPPE_SEND_KEY: u8 = 16;
+const VENDOR_ATTR_MS_MPPE_RECV_KEY: u8 = 17;
+const ATTR_TUNNEL_PASSWORD: u8 = 69;
+
+// Longest plaintext a hidden value can carry: 128 bytes of User-Password style output,
+// and what fits in a Salt-encrypted string once the salt, key length and tag are added
+const MAX_HIDDEN_LEN: usize = 128;
+const MAX_SALTED_LEN: usize = 239;
+
+/// Reversible encryption schemes for attribute values, as numbered by `encrypt=` in RADIUS dictionaries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encryption {
+    /// RFC 2865, section 5.2 (`encrypt=1`)
+    UserPassword,
+    /// RFC 2868, section 3.5 and RFC 2548, section 2.4.2 (`encrypt=2`)
+    Salt,
+    /// Ascend-Send-Secret and friends (`encrypt=3`)
+    AscendSecret,
+}
+
+impl FromStr for Encryption {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "user-password" => Ok(Encryption::UserPassword),
+            "salt" => Ok(Encryption::Salt),
+            "ascend-secret" => Ok(Encryption::AscendSecret),
+            _ => Err(format!("unknown attribute encryption {:?}", name)),
+        }
+    }
+}
+
+impl Encryption {
+    /// Scheme the RFCs mandate for an attribute, for attributes the dictionary says nothing about
+    pub fn default_for(vendor_id: u32, attribute_id: u8) -> Option<Self> {
+        match (vendor_id, attribute_id) {
+            (0, ATTR_TUNNEL_PASSWORD) => Some(Encryption::Salt),
+            (VENDOR_MICROSOFT, VENDOR_ATTR_MS_MPPE_SEND_KEY | VENDOR_ATTR_MS_MPPE_RECV_KEY) => Some(Encryption::Salt),
+            _ => None,
+        }
+    }
+
+    /// Encrypts a reply attribute value. `salt_offset` keeps the salts of one packet apart (RFC 2868 requires them
+    /// to be unique); it is ignored by the other schemes.
+    pub fn encrypt(self, plain: &[u8], authenticator: &[u8], secret: &str, salt_offset: u8) -> Option<Vec<u8>> {
+        match self {
+            Encryption::UserPassword => (plain.len() <= MAX_HIDDEN_LEN).then(|| hide_password(plain, authenticator, secret)),
+            Encryption::Salt => (plain.len() <= MAX_SALTED_LEN).then(|| salt_hide(plain, authenticator, secret, new_salt(salt_offset))),
+            Encryption::AscendSecret => Some(ascend_secret(plain, authenticator, secret)),
+        }
+    }
+}
+
+fn trim_padding(mut value: Vec<u8>) -> Vec<u8> {
+    while value.last() == Some(&0) {
+        value.pop();
+    }
+    value
+}
+
+/// The MD5 block chain shared by the User-Password and Salt schemes: each block of `data` is XORed with
+/// MD5(secret + previous block), starting from `seed`. `encrypting` picks which side is the previous block.
+fn md5_chain(data: &[u8], seed: &[u8], secret: &str, encrypting: bool) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut last_block = seed.to_vec();
+    for chunk in data.chunks(16) {
+        let mut hasher = Md5::new();
+        hasher.update(secret.as_bytes());
+        hasher.update(&last_block);
+        let hash = hasher.finalize();
+        let block: Vec<u8> = chunk.iter().zip(hash.iter()).map(|(a, b)| a ^ b).collect();
+        last_block = if encrypting { block.clone() } else { chunk.to_vec() };
+        out.extend_from_slice(&block);
+    }
+    out
+}
+
+/// User-Password hiding (RFC 2865, section 5.2)
+pub fn hide_password(plain: &[u8], authenticator: &[u8], secret: &str) -> Vec<u8> {
+    let mut padded = plain.to_vec();
+    padded.resize(plain.len().div_ceil(16).max(1) * 16, 0);
+    md5_chain(&padded, authenticator, secret, true)
+}
+
+/// Reverses `hide_password`, keeping the NUL padding so the value can be re-hidden as is
+pub fn unhide_password(hidden: &[u8], authenticator: &[u8], secret: &str) -> Vec<u8> {
+    md5_chain(hidden, authenticator, secret, false)
+}
+
+/// Clear-text User-Password of an Access-Request
+pub fn decode_user_password(hidden: &[u8], authenticator: &[u8], secret: &str) -> Result<String, std::string::FromUtf8Error> {
+    String::from_utf8(trim_padding(unhide_password(hidden, authenticator, secret)))
+}
+
+/// A salt with the high bit set, as RFC 2868 requires; `offset` goes into the next four bits
+/// so the salts of one packet never collide
+pub fn new_salt(offset: u8) -> [u8; 2] {
+    let random: u16 = rand::random();
+    [0x80 | ((offset & 0x0f) << 3) | (random as u8 & 0x07), (random >> 8) as u8]
+}
+
+/// Salt-encryption of an already formatted string (key length, data and padding), without the salt
+pub fn salt_encrypt(plain: &[u8], authenticator: &[u8], secret: &str, salt: &[u8]) -> Vec<u8> {
+    let mut padded = plain.to_vec();
+    padded.resize(plain.len().div_ceil(16) * 16, 0);
+    md5_chain(&padded, &[authenticator, salt].concat(), secret, true)
+}
+
+pub fn salt_decrypt(cipher: &[u8], authenticator: &[u8], secret: &str, salt: &[u8]) -> Vec<u8> {
+    md5_chain(cipher, &[authenticator, salt].concat(), secret, false)
+}
+
+/// Salt, then the encrypted key length, value and padding: the wire format of Tunnel-Password
+/// (after its tag) and MS-MPPE-Send-Key/Recv-Key
+pub fn salt_hide(plain: &[u8], authenticator: &[u8], secret: &str, salt: [u8; 2]) -> Vec<u8> {
+    let mut string = Vec::with_capacity(plain.len() + 1);
+    string.push(plain.len() as u8);
+    string.extend_from_slice(plain);
+    [&salt[..], &salt_encrypt(&string, authenticator, secret, &salt)].concat()
+}
+
+/// Ascend-Send-Secret style: one block XORed with MD5(authenticator + secret). The value is
+/// padded or cut to 16 bytes; applying it twice gives the value back.
+pub fn ascend_secret(value: &[u8], authenticator: &[u8], secret: &str) -> Vec<u8> {
+    let mut hasher = Md5::new();
+    hasher.update(authenticator);
+    hasher.update(secret.as_bytes());
+    let hash = hasher.finalize();
+    let mut block = [0u8; 16];
+    for (i, byte) in value.iter().take(16).enumerate() {
+        block[i] = *byte;
+    }
+    block.iter().zip(hash.iter()).map(|(a, b)| a ^ b).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schemes_roundtrip() {
+        let authenticator = [9u8; 16];
+        let secret = "testing123";
+
+        let hidden = Encryption::UserPassword.encrypt(b"tunnel secret", &authenticator, secret, 0).unwrap();
+        assert_eq!(hidden.len(), 16);
+        assert_eq!(decode_user_password(&hidden, &authenticator, secret).unwrap(), "tunnel secret");
+
+        let hidden = Encryption::AscendSecret.encrypt(b"tunnel secret", &authenticator, secret, 0).unwrap();
+        assert_eq!(&ascend_secret(&hidden, &authenticator, secret)[..13], b"tunnel secret");
+
+        // Salt, then the key length byte and 16 bytes of key in two blocks
+        let hidden = Encryption::Salt.encrypt(&[0xaa; 16], &authenticator, secret, 1).unwrap();
+        assert_eq!(hidden.len(), 34);
+        assert_eq!(hidden[0] & 0xf8, 0x88);
+        let string = salt_decrypt(&hidden[2..], &authenticator, secret, &hidden[..2]);
+        assert_eq!(string[0], 16);
+        assert_eq!(&string[1..17], &[0xaa; 16]);
+        assert!(string[17..].iter().all(|b| *b == 0));
+
+        assert_eq!("salt".parse::<Encryption>(), Ok(Encryption::Salt));
+        assert!("rot13".parse::<Encryption>().is_err());
+        assert_eq!(Encryption::default_for(0, ATTR_TUNNEL_PASSWORD), Some(Encryption::Salt));
+        assert_eq!(Encryption::default_for(0, 1), None);
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+use sqlx::PgPool;
+use sqlx::types::JsonValue;
+
+use super::radius_server::{
+    RadiusAttribute, ATTR_SERVICE_TYPE, SERVICE_TYPE_ADMINISTRATIVE_USER, SERVICE_TYPE_CALLBACK_ADMINISTRATIVE,
+    SERVICE_TYPE_CALLBACK_LOGIN_USER, SERVICE_TYPE_CALLBACK_NAS_PROMPT, SERVICE_TYPE_LOGIN_USER,
+    SERVICE_TYPE_NAS_PROMPT_USER,
+};
+use super::reply::{AttributeEdit, AttributeOp};
+use super::vendor::{VendorProfile, MAX_PRIVILEGE_LEVEL};
+
+/// Device administration rights a user group grants on switches, routers and firewalls
+#[derive(Debug, Clone)]
+pub struct DeviceAdminProfile {
+    pub name: String,
+    /// 0 to 15; only 15 allows Administrative-User logins
+    pub privilege_level: u8,
+    /// Role names replacing the built-in ones of a vendor profile, keyed by vendor ("mikrotik", "juniper", ...)
+    pub vendor_roles: HashMap<String, String>,
+}
+
+impl DeviceAdminProfile {
+    pub fn role_for(&self, vendor: Option<VendorProfile>) -> Option<&str> {
+        self.vendor_roles.get(vendor?.name()).map(String::as_str)
+    }
+
+    /// Service-Type to reply with: Administrative-User at the top level, NAS-Prompt-User below it
+    pub fn service_type(&self) -> u32 {
+        if self.privilege_level >= MAX_PRIVILEGE_LEVEL {
+            SERVICE_TYPE_ADMINISTRATIVE_USER
+        } else {
+            SERVICE_TYPE_NAS_PROMPT_USER
+        }
+    }
+
+    pub(super) fn service_type_edit(&self) -> AttributeEdit {
+        AttributeEdit {
+            op: AttributeOp::Set,
+            vendor_id: 0,
+            attribute_id: ATTR_SERVICE_TYPE,
+            attribute: Some(RadiusAttribute { typ: ATTR_SERVICE_TYPE, value: self.service_type().to_be_bytes().to_vec() }),
+        }
+    }
+}
+
+/// Kind of management login an Access-Request asks for, from its Service-Type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdminRequest {
+    /// Network access, or no Service-Type at all
+    NotRequested,
+    Login,
+    NasPrompt,
+    Administrative,
+}
+
+impl AdminRequest {
+    pub fn of(service_type: Option<u32>) -> Self {
+        match service_type {
+            Some(SERVICE_TYPE_LOGIN_USER | SERVICE_TYPE_CALLBACK_LOGIN_USER) => AdminRequest::Login,
+            Some(SERVICE_TYPE_NAS_PROMPT_USER | SERVICE_TYPE_CALLBACK_NAS_PROMPT) => AdminRequest::NasPrompt,
+            Some(SERVICE_TYPE_ADMINISTRATIVE_USER | SERVICE_TYPE_CALLBACK_ADMINISTRATIVE) => AdminRequest::Administrative,
+            _ => AdminRequest::NotRequested,
+        }
+    }
+
+    pub fn is_requested(self) -> bool {
+        self != AdminRequest::NotRequested
+    }
+
+    /// Whether a user with `profile` may have this login; the reject message when not.
+    /// Every management login, plain Login-User included, needs a profile.
+    pub fn entitled(self, profile: Option<&DeviceAdminProfile>) -> Result<(), &'static str> {
+        match (self, profile) {
+            (AdminRequest::Login | AdminRequest::NasPrompt | AdminRequest::Administrative, None) => {
+                Err("Not authorized for device administration")
+            }
+            (AdminRequest::Administrative, Some(profile)) if profile.privilege_level < MAX_PRIVILEGE_LEVEL => {
+                Err("Not authorized for administrative access")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Highest device-admin profile among the user groups of an identity.
+///
+/// `groups` are the names of directory groups, for identities that don't live in `user_identifiers`.
+pub async fn load_profile(pool: &PgPool, identifier_id: Option<i64>, groups: &[String])
+    -> Result<Option<DeviceAdminProfile>, sqlx::Error> {
+    #[derive(sqlx::FromRow)]
+    struct ProfileRow {
+        name: String,
+        privilege_level: i16,
+        vendor_roles: JsonValue,
+    }
+
+    let row = sqlx::query_as::<_, ProfileRow>(
+        r#"
+        SELECT p.name, p.privilege_level, p.vendor_roles
+        FROM radius_device_admin_profile p
+        JOIN user_groups g ON g.device_admin_profile_id = p.id
+        WHERE g.name = ANY($2) OR g.id IN (
+            SELECT ug.usergroup_id
+            FROM user_identifiers ui
+            JOIN users_groups ug ON ug.user_id = ui.user_id
+            WHERE ui.id = $1
+        )
+        ORDER BY p.privilege_level DESC, p.name
+        LIMIT 1
+        "#
+    )
+    .bind(identifier_id)
+    .bind(groups)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|row| DeviceAdminProfile {
+        name: row.name,
+        privilege_level: row.privilege_level.clamp(0, MAX_PRIVILEGE_LEVEL as i16) as u8,
+        vendor_roles: row.vendor_roles.as_object()
+            .map(|roles| roles.iter()
+                .filter_map(|(vendor, role)| Some((vendor.to_lowercase(), role.as_str()?.to_string())))
+                .collect())
+            .unwrap_or_default(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entitlement() {
+        let operator = DeviceAdminProfile {
+            name: "noc".to_string(),
+            privilege_level: 7,
+            vendor_roles: HashMap::from([("juniper".to_string(), "noc-operator".to_string())]),
+        };
+        let admin = DeviceAdminProfile { privilege_level: 15, ..operator.clone() };
+
+        assert_eq!(AdminRequest::of(Some(SERVICE_TYPE_CALLBACK_NAS_PROMPT)), AdminRequest::NasPrompt);
+        assert_eq!(AdminRequest::of(Some(2)), AdminRequest::NotRequested);
+        assert!(AdminRequest::NotRequested.entitled(None).is_ok());
+        assert!(AdminRequest::Login.entitled(None).is_err());
+        assert!(AdminRequest::Login.entitled(Some(&operator)).is_ok());
+        assert!(AdminRequest::NasPrompt.entitled(None).is_err());
+        assert!(AdminRequest::NasPrompt.entitled(Some(&operator)).is_ok());
+        assert!(AdminRequest::Administrative.entitled(Some(&operator)).is_err());
+        assert!(AdminRequest::Administrative.entitled(Some(&admin)).is_ok());
+
+        assert_eq!(operator.service_type(), SERVICE_TYPE_NAS_PROMPT_USER);
+        assert_eq!(admin.service_type(), SERVICE_TYPE_ADMINISTRATIVE_USER);
+        assert_eq!(operator.role_for(Some(VendorProfile::Juniper)), Some("noc-operator"));
+        assert_eq!(operator.role_for(Some(VendorProfile::Cisco)), None);
+    }
+}
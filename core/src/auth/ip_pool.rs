@@ -0,0 +1,577 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+use sqlx::postgres::PgPoolOptions;
+use tracing::{debug, info, warn};
+
+use super::radius_server::RadiusAttribute;
+use super::reply::{self, AttributeEdit, AttributeOp};
+use crate::config::EnvOverrides;
+
+const ATTR_FRAMED_IP_ADDRESS: u8 = 8;
+const ATTR_FRAMED_IP_NETMASK: u8 = 9;
+const ATTR_FRAMED_INTERFACE_ID: u8 = 96;
+const ATTR_FRAMED_IPV6_PREFIX: u8 = 97;
+const ATTR_DELEGATED_IPV6_PREFIX: u8 = 123;
+
+// Prefix pools are searched for a free slot one by one; larger pools are used up to this many prefixes
+const MAX_PREFIX_SLOTS: i64 = 1 << 24;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IpPoolConfig {
+    pub enabled: bool,
+}
+
+impl Default for IpPoolConfig {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+impl IpPoolConfig {
+    pub fn apply_env(&mut self, env: &mut EnvOverrides) {
+        env.set_bool("IP_POOL_ENABLED", &mut self.enabled);
+    }
+}
+
+/// What a pool hands out: IPv4 addresses from `radius_ip_pool`, or prefixes from `radius_ipv6_prefix_pool`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoolKind {
+    /// Framed-IP-Address
+    Ipv4,
+    /// Framed-IPv6-Prefix for the link to the subscriber, usually a /64
+    FramedIpv6,
+    /// Delegated-IPv6-Prefix (RFC 4818) for the subscriber's own network
+    DelegatedIpv6,
+}
+
+impl PoolKind {
+    pub const ALL: [PoolKind; 3] = [PoolKind::Ipv4, PoolKind::FramedIpv6, PoolKind::DelegatedIpv6];
+
+    /// The reply attribute carrying the assignment; when already set, the user has a static one
+    pub fn attribute(self) -> u8 {
+        match self {
+            PoolKind::Ipv4 => ATTR_FRAMED_IP_ADDRESS,
+            PoolKind::FramedIpv6 => ATTR_FRAMED_IPV6_PREFIX,
+            PoolKind::DelegatedIpv6 => ATTR_DELEGATED_IPV6_PREFIX,
+        }
+    }
+
+    /// `kind` of the prefix pools
+    fn prefix_kind(self) -> &'static str {
+        match self {
+            PoolKind::FramedIpv6 => "framed",
+            _ => "delegated",
+        }
+    }
+}
+
+/// Who an address is being allocated to, and which pools apply to them
+#[derive(Debug, Clone, Default)]
+pub struct LeaseRequest {
+    pub username: String,
+    pub calling_station_id: String,
+    pub nas_ip: Option<Ipv4Addr>,
+    pub identifier_id: Option<i64>,
+    /// Names of directory groups, for identities that don't live in `user_identifiers`
+    pub groups: Vec<String>,
+    pub nas_id: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lease {
+    pub pool: String,
+    pub kind: PoolKind,
+    /// The address, or the first address of the prefix
+    pub address: IpAddr,
+    /// Netmask length of IPv4 addresses, length of IPv6 prefixes
+    pub prefix_length: u8,
+    /// Framed-Interface-Id sent along with Framed-IPv6-Prefix
+    pub interface_id: Option<[u8; 8]>,
+}
+
+impl Lease {
+    /// Edits handing the address or prefix out
+    pub(super) fn edits(&self) -> Vec<AttributeEdit> {
+        let mut attributes = match self.address {
+            IpAddr::V4(address) => vec![
+                (ATTR_FRAMED_IP_ADDRESS, address.octets().to_vec()),
+                (ATTR_FRAMED_IP_NETMASK, netmask(self.prefix_length).octets().to_vec()),
+            ],
+            IpAddr::V6(prefix) => reply::encode_ipv6_prefix(prefix, self.prefix_length)
+                .map(|value| vec![(self.kind.attribute(), value)])
+                .unwrap_or_default(),
+        };
+        if let Some(interface_id) = self.interface_id {
+            attributes.push((ATTR_FRAMED_INTERFACE_ID, interface_id.to_vec()));
+        }
+        attributes.into_iter()
+            .map(|(attribute_id, value)| AttributeEdit {
+                op: AttributeOp::Set,
+                vendor_id: 0,
+                attribute_id,
+                attribute: Some(RadiusAttribute { typ: attribute_id, value }),
+            })
+            .collect()
+    }
+}
+
+fn netmask(length: u8) -> Ipv4Addr {
+    Ipv4Addr::from(u32::MAX.checked_shl(32 - length.min(32) as u32).unwrap_or(0))
+}
+
+/// The `slot`-th prefix of `length` bits inside `network`
+fn nth_prefix(network: Ipv6Addr, length: u8, slot: i64) -> Option<Ipv6Addr> {
+    let offset = (slot as u128).checked_shl(128 - length as u32)?;
+    u128::from(network).checked_add(offset).map(Ipv6Addr::from)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Allocation {
+    /// None of the pools applies to the user and NAS
+    NoPool,
+    /// Every applicable pool is full
+    Exhausted,
+    Leased(Lease),
+}
+
+#[derive(sqlx::FromRow)]
+struct PoolRow {
+    id: i64,
+    name: String,
+    /// IPv4 netmask, or the network of a prefix pool
+    network: String,
+    prefix_length: i16,
+    delegated_length: i16,
+    interface_id: String,
+}
+
+// Pools whose user group (when set) has the user as a member, and whose NAS group (when set) has the NAS
+const POOL_SCOPE: &str = r#"
+      AND (p.user_group_id IS NOT NULL OR p.nas_group_id IS NOT NULL)
+      AND (p.user_group_id IS NULL OR p.user_group_id IN (
+          SELECT g.id
+          FROM user_groups g
+          WHERE g.name = ANY($2) OR g.id IN (
+              SELECT ug.usergroup_id
+              FROM user_identifiers ui
+              JOIN users_groups ug ON ug.user_id = ui.user_id
+              WHERE ui.id = $1
+          )
+      ))
+      AND (p.nas_group_id IS NULL OR p.nas_group_id IN (
+          SELECT ng.nasgroup_id FROM nas_nas_groups ng WHERE ng.nas_id = $3
+      ))
+    ORDER BY p.user_group_id IS NULL, p.priority, p.id
+"#;
+
+/// Address and prefix leases of the IP pools, shared by the auth service (which allocates them)
+/// and the accounting service (which binds them to sessions and releases them).
+///
+/// A lease lives until `expires_at`, which each Accounting-Start and Interim-Update pushes back by the
+/// pool's lease timeout; an expired lease stays in the table so the same user gets the address back.
+pub struct IpPools {
+    pool: Option<PgPool>,
+}
+
+impl IpPools {
+    pub fn new(config: &IpPoolConfig, pool: PgPool) -> Self {
+        if !config.enabled {
+            info!("IP pools disabled");
+        }
+        Self { pool: config.enabled.then_some(pool) }
+    }
+
+    /// For the accounting service, which has no Postgres pool of its own; connects on first use
+    pub fn connect_lazy(config: &IpPoolConfig, postgres_url: &str, max_connections: u32)
+        -> Result<Self, sqlx::Error> {
+        if !config.enabled {
+            return Ok(Self { pool: None });
+        }
+        let pool = PgPoolOptions::new().max_connections(max_connections).connect_lazy(postgres_url)?;
+        Ok(Self { pool: Some(pool) })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.pool.is_some()
+    }
+
+    /// Leases an address or prefix of `kind` from the first applicable pool with room: user group pools
+    /// before NAS group ones, then by priority. Pools naming both a user group and a NAS group apply only
+    /// when both match.
+    pub async fn allocate(&self, request: &LeaseRequest, kind: PoolKind) -> Result<Allocation, sqlx::Error> {
+        let Some(pool) = &self.pool else {
+            return Ok(Allocation::NoPool);
+        };
+
+        let query = match kind {
+            PoolKind::Ipv4 => format!(
+                r#"
+                SELECT p.id, p.name, host(p.netmask) AS network, 0::smallint AS prefix_length,
+                       0::smallint AS delegated_length, '' AS interface_id
+                FROM radius_ip_pool p
+                WHERE p.is_active {}
+                "#,
+                POOL_SCOPE
+            ),
+            _ => format!(
+                r#"
+                SELECT p.id, p.name, host(p.network) AS network, p.prefix_length, p.delegated_length, p.interface_id
+                FROM radius_ipv6_prefix_pool p
+                WHERE p.is_active AND p.kind = $4 {}
+                "#,
+                POOL_SCOPE
+            ),
+        };
+        let mut pools = sqlx::query_as::<_, PoolRow>(&query)
+            .bind(request.identifier_id)
+            .bind(&request.groups)
+            .bind(request.nas_id);
+        if kind != PoolKind::Ipv4 {
+            pools = pools.bind(kind.prefix_kind());
+        }
+        let pools = pools.fetch_all(pool).await?;
+        if pools.is_empty() {
+            return Ok(Allocation::NoPool);
+        }
+
+        for row in pools {
+            let db_timer = crate::metrics::DB_QUERY_DURATION.with_label_values(&["ip_pool_allocate"]).start_timer();
+            let lease = match kind {
+                PoolKind::Ipv4 => allocate_address(pool, &row, request).await?,
+                _ => allocate_prefix(pool, &row, kind, request).await?,
+            };
+            db_timer.observe_duration();
+            match lease {
+                Some(lease) => {
+                    debug!("Leased {}/{} from pool {} to {}", lease.address, lease.prefix_length, row.name, request.username);
+                    return Ok(Allocation::Leased(lease));
+                }
+                None => debug!("IP pool {} is full", row.name),
+            }
+        }
+        Ok(Allocation::Exhausted)
+    }
+
+    /// Binds the lease of an address to an accounting session and extends it; Accounting-Start and Interim-Update.
+    /// A lease not bound yet is only taken while it is live and leased to `username`.
+    pub async fn refresh(&self, address: Ipv4Addr, session_id: &str, username: &str, nas_ip: Option<Ipv4Addr>)
+        -> Result<bool, sqlx::Error> {
+        self.refresh_lease("radius_ip_pool_lease", "radius_ip_pool", IpAddr::V4(address), session_id, username, nas_ip).await
+    }
+
+    /// `refresh` for the lease of a Framed-IPv6-Prefix or Delegated-IPv6-Prefix
+    pub async fn refresh_prefix(&self, prefix: Ipv6Addr, session_id: &str, username: &str, nas_ip: Option<Ipv4Addr>)
+        -> Result<bool, sqlx::Error> {
+        self.refresh_lease("radius_ipv6_prefix_lease", "radius_ipv6_prefix_pool", IpAddr::V6(prefix), session_id, username, nas_ip).await
+    }
+
+    async fn refresh_lease(&self, lease_table: &str, pool_table: &str, address: IpAddr, session_id: &str, username: &str,
+                           nas_ip: Option<Ipv4Addr>) -> Result<bool, sqlx::Error> {
+        let Some(pool) = &self.pool else {
+            return Ok(false);
+        };
+        let query = format!(
+            r#"
+            UPDATE {} l
+            SET acct_session_id = $2,
+                expires_at = now() + make_interval(secs => p.lease_timeout)
+            FROM {} p
+            WHERE p.id = l.pool_id
+              AND l.address = $1::inet
+              AND (l.acct_session_id = $2 OR (l.acct_session_id = '' AND l.expires_at > now() AND l.username = $4))
+              AND (l.nas_ip IS NULL OR $3::inet IS NULL OR l.nas_ip = $3::inet)
+            "#,
+            lease_table, pool_table
+        );
+        let result = sqlx::query(&query)
+            .bind(address.to_string())
+            .bind(session_id)
+            .bind(nas_ip.map(|ip| ip.to_string()))
+            .bind(username)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Expires the leases of a session that stopped; Accounting-Stop
+    pub async fn release_session(&self, session_id: &str, nas_ip: Option<Ipv4Addr>) -> Result<u64, sqlx::Error> {
+        let Some(pool) = &self.pool else {
+            return Ok(0);
+        };
+        let mut released = 0;
+        for table in ["radius_ip_pool_lease", "radius_ipv6_prefix_lease"] {
+            let query = format!(
+                r#"
+                UPDATE {}
+                SET expires_at = now()
+                WHERE acct_session_id = $1
+                  AND expires_at > now()
+                  AND (nas_ip IS NULL OR $2::inet IS NULL OR nas_ip = $2::inet)
+                "#,
+                table
+            );
+            released += sqlx::query(&query)
+                .bind(session_id)
+                .bind(nas_ip.map(|ip| ip.to_string()))
+                .execute(pool)
+                .await?
+                .rows_affected();
+        }
+        Ok(released)
+    }
+
+    /// Expires leases just handed to `username` for a login that was rejected after all
+    pub async fn release(&self, leases: &[Lease], username: &str) -> Result<u64, sqlx::Error> {
+        let Some(pool) = &self.pool else {
+            return Ok(0);
+        };
+        let mut released = 0;
+        for lease in leases {
+            let table = match lease.kind {
+                PoolKind::Ipv4 => "radius_ip_pool_lease",
+                _ => "radius_ipv6_prefix_lease",
+            };
+            let query = format!(
+                r#"
+                UPDATE {}
+                SET expires_at = now()
+                WHERE address = $1::inet
+                  AND username = $2
+                  AND acct_session_id = ''
+                  AND expires_at > now()
+                "#,
+                table
+            );
+            released += sqlx::query(&query)
+                .bind(lease.address.to_string())
+                .bind(username)
+                .execute(pool)
+                .await?
+                .rows_affected();
+        }
+        Ok(released)
+    }
+
+    /// Expires every lease handed out through a NAS that restarted or went offline; Accounting-On and Off
+    pub async fn release_nas(&self, nas_ip: Ipv4Addr) -> Result<u64, sqlx::Error> {
+        let Some(pool) = &self.pool else {
+            return Ok(0);
+        };
+        let mut released = 0;
+        for table in ["radius_ip_pool_lease", "radius_ipv6_prefix_lease"] {
+            let query = format!("UPDATE {} SET expires_at = now() WHERE nas_ip = $1::inet AND expires_at > now()", table);
+            released += sqlx::query(&query)
+                .bind(nas_ip.to_string())
+                .execute(pool)
+                .await?
+                .rows_affected();
+        }
+        Ok(released)
+    }
+}
+
+/// Takes over an existing lease of the pool: the user's previous one when `sticky`, otherwise the one
+/// expired longest ago. A re-authentication before Accounting-Start gets the lease it got moments ago.
+async fn reuse_lease(tx: &mut Transaction<'_, Postgres>, lease_table: &str, pool_table: &str, pool_id: i64,
+                     request: &LeaseRequest, sticky: bool) -> Result<Option<String>, sqlx::Error> {
+    let (filter, order) = if sticky {
+        ("username = $2 AND (expires_at <= now() OR (acct_session_id = '' AND calling_station_id = $3))", "DESC")
+    } else {
+        ("expires_at <= now()", "ASC")
+    };
+    let query = format!(
+        r#"
+        UPDATE {lease_table} l
+        SET username = $2, calling_station_id = $3, nas_ip = $4::inet, acct_session_id = '',
+            allocated_at = now(), expires_at = now() + make_interval(secs => p.lease_timeout)
+        FROM {pool_table} p
+        WHERE p.id = l.pool_id AND l.id = (
+            SELECT id FROM {lease_table}
+            WHERE pool_id = $1 AND {filter}
+            ORDER BY expires_at {order}
+            LIMIT 1
+        )
+        RETURNING host(l.address)
+        "#
+    );
+    sqlx::query_scalar::<_, String>(&query)
+        .bind(pool_id)
+        .bind(&request.username)
+        .bind(&request.calling_station_id)
+        .bind(request.nas_ip.map(|ip| ip.to_string()))
+        .fetch_optional(&mut **tx)
+        .await
+}
+
+/// One allocation attempt in an IPv4 pool, with the pool row locked so concurrent logins can't take the same
+/// address. Prefers the user's previous address, then an address never leased, then the longest expired lease.
+async fn allocate_address(pool: &PgPool, row: &PoolRow, request: &LeaseRequest) -> Result<Option<Lease>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("SELECT id FROM radius_ip_pool WHERE id = $1 FOR UPDATE")
+        .bind(row.id)
+        .execute(&mut *tx)
+        .await?;
+
+    let mut address = reuse_lease(&mut tx, "radius_ip_pool_lease", "radius_ip_pool", row.id, request, true).await?;
+    if address.is_none() {
+        address = sqlx::query_scalar::<_, String>(
+            r#"
+            INSERT INTO radius_ip_pool_lease
+                (pool_id, address, username, calling_station_id, nas_ip, acct_session_id, allocated_at, expires_at)
+            SELECT p.id, p.range_start + n, $2, $3, $4::inet, '', now(), now() + make_interval(secs => p.lease_timeout)
+            FROM radius_ip_pool p, generate_series(0, p.range_end - p.range_start) AS n
+            WHERE p.id = $1
+              AND NOT EXISTS (
+                  SELECT 1 FROM radius_ip_pool_lease l WHERE l.pool_id = p.id AND l.address = p.range_start + n
+              )
+            LIMIT 1
+            RETURNING host(address)
+            "#
+        )
+        .bind(row.id)
+        .bind(&request.username)
+        .bind(&request.calling_station_id)
+        .bind(request.nas_ip.map(|ip| ip.to_string()))
+        .fetch_optional(&mut *tx)
+        .await?;
+    }
+    if address.is_none() {
+        address = reuse_lease(&mut tx, "radius_ip_pool_lease", "radius_ip_pool", row.id, request, false).await?;
+    }
+    tx.commit().await?;
+
+    let netmask: Ipv4Addr = row.network.parse().unwrap_or(Ipv4Addr::BROADCAST);
+    Ok(address.and_then(|address| address.parse::<Ipv4Addr>().ok()).map(|address| Lease {
+        pool: row.name.clone(),
+        kind: PoolKind::Ipv4,
+        address: IpAddr::V4(address),
+        prefix_length: u32::from(netmask).leading_ones() as u8,
+        interface_id: None,
+    }))
+}
+
+/// `allocate_address` for prefix pools. Slots are numbered from the start of the pool's network, and the
+/// prefix of a new slot is worked out here since Postgres can't add more than 64 bits to an inet.
+async fn allocate_prefix(pool: &PgPool, row: &PoolRow, kind: PoolKind, request: &LeaseRequest)
+    -> Result<Option<Lease>, sqlx::Error> {
+    let (Ok(network), Ok(prefix_length), Ok(delegated_length)) =
+        (row.network.parse::<Ipv6Addr>(), u8::try_from(row.prefix_length), u8::try_from(row.delegated_length)) else {
+        warn!("Skipping IPv6 prefix pool {}: invalid network {}/{}", row.name, row.network, row.prefix_length);
+        return Ok(None);
+    };
+    if prefix_length > delegated_length || delegated_length > 128 {
+        warn!("Skipping IPv6 prefix pool {}: cannot split a /{} into /{}", row.name, prefix_length, delegated_length);
+        return Ok(None);
+    }
+    let interface_id = match row.interface_id.trim() {
+        "" => None,
+        text => reply::parse_interface_id(text),
+    };
+    let slots = 1i64.checked_shl((delegated_length - prefix_length) as u32)
+        .filter(|slots| *slots > 0)
+        .map_or(MAX_PREFIX_SLOTS, |slots| slots.min(MAX_PREFIX_SLOTS));
+
+    let mut tx = pool.begin().await?;
+    sqlx::query("SELECT id FROM radius_ipv6_prefix_pool WHERE id = $1 FOR UPDATE")
+        .bind(row.id)
+        .execute(&mut *tx)
+        .await?;
+
+    let mut prefix = reuse_lease(&mut tx, "radius_ipv6_prefix_lease", "radius_ipv6_prefix_pool", row.id, request, true).await?;
+    if prefix.is_none() {
+        let slot = sqlx::query_scalar::<_, i64>(
+            r#"
+            SELECT n
+            FROM generate_series(0, $2 - 1) AS n
+            WHERE NOT EXISTS (SELECT 1 FROM radius_ipv6_prefix_lease l WHERE l.pool_id = $1 AND l.slot = n)
+            LIMIT 1
+            "#
+        )
+        .bind(row.id)
+        .bind(slots)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if let Some(slot) = slot
+            && let Some(address) = nth_prefix(network, delegated_length, slot)
+        {
+            sqlx::query(
+                r#"
+                INSERT INTO radius_ipv6_prefix_lease
+                    (pool_id, slot, address, username, calling_station_id, nas_ip, acct_session_id, allocated_at, expires_at)
+                SELECT p.id, $2, $3::inet, $4, $5, $6::inet, '', now(), now() + make_interval(secs => p.lease_timeout)
+                FROM radius_ipv6_prefix_pool p
+                WHERE p.id = $1
+                "#
+            )
+            .bind(row.id)
+            .bind(slot)
+            .bind(address.to_string())
+            .bind(&request.username)
+            .bind(&request.calling_station_id)
+            .bind(request.nas_ip.map(|ip| ip.to_string()))
+            .execute(&mut *tx)
+            .await?;
+            prefix = Some(address.to_string());
+        }
+    }
+    if prefix.is_none() {
+        prefix = reuse_lease(&mut tx, "radius_ipv6_prefix_lease", "radius_ipv6_prefix_pool", row.id, request, false).await?;
+    }
+    tx.commit().await?;
+
+    Ok(prefix.and_then(|prefix| prefix.parse::<Ipv6Addr>().ok()).map(|prefix| Lease {
+        pool: row.name.clone(),
+        kind,
+        address: IpAddr::V6(prefix),
+        prefix_length: delegated_length,
+        interface_id: if kind == PoolKind::FramedIpv6 { interface_id } else { None },
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lease_edits() {
+        let lease = Lease {
+            pool: "pppoe".to_string(),
+            kind: PoolKind::Ipv4,
+            address: IpAddr::V4(Ipv4Addr::new(100, 64, 0, 10)),
+            prefix_length: 32,
+            interface_id: None,
+        };
+        let mut attributes = vec![RadiusAttribute { typ: ATTR_FRAMED_IP_ADDRESS, value: vec![10, 0, 0, 1] }];
+        for edit in lease.edits() {
+            edit.apply(&mut attributes);
+        }
+        let summary: Vec<(u8, Vec<u8>)> = attributes.into_iter().map(|attr| (attr.typ, attr.value)).collect();
+        assert_eq!(summary, vec![
+            (ATTR_FRAMED_IP_ADDRESS, vec![100, 64, 0, 10]),
+            (ATTR_FRAMED_IP_NETMASK, vec![255, 255, 255, 255]),
+        ]);
+
+        let network: Ipv6Addr = "2001:db8:1000::".parse().unwrap();
+        let prefix = nth_prefix(network, 56, 3).unwrap();
+        assert_eq!(prefix, "2001:db8:1000:300::".parse::<Ipv6Addr>().unwrap());
+        let lease = Lease {
+            kind: PoolKind::FramedIpv6,
+            address: IpAddr::V6(prefix),
+            prefix_length: 56,
+            interface_id: Some([0, 0, 0, 0, 0, 0, 0, 1]),
+            ..lease
+        };
+        let summary: Vec<(u8, Vec<u8>)> = lease.edits().into_iter()
+            .filter_map(|edit| edit.attribute)
+            .map(|attr| (attr.typ, attr.value))
+            .collect();
+        assert_eq!(summary, vec![
+            (ATTR_FRAMED_IPV6_PREFIX, vec![0, 56, 0x20, 0x01, 0x0d, 0xb8, 0x10, 0, 0x03]),
+            (ATTR_FRAMED_INTERFACE_ID, vec![0, 0, 0, 0, 0, 0, 0, 1]),
+        ]);
+        assert_eq!(netmask(24), Ipv4Addr::new(255, 255, 255, 0));
+        assert_eq!(nth_prefix(network, 0, 1), None);
+    }
+}
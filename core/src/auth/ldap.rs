@@ -0,0 +1,334 @@
+use std::time::Duration;
+use async_trait::async_trait;
+use ldap3::{ldap_escape, Ldap, LdapConnAsync, LdapConnSettings, Scope, SearchEntry};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, Semaphore, SemaphorePermit};
+use tracing::{debug, warn};
+
+use super::backend::{AuthBackend, BackendError, Identity};
+use crate::config::EnvOverrides;
+
+// Active Directory userAccountControl flag for disabled accounts
+const UF_ACCOUNTDISABLE: u32 = 0x2;
+// LDAP result code for a failed simple bind
+const RC_INVALID_CREDENTIALS: u32 = 49;
+
+/// Maps a directory group (full DN or CN) to the attribute group sent in the Access-Accept
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GroupMapping {
+    pub ldap_group: String,
+    pub attribute_group: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LdapConfig {
+    /// ldap:// or ldaps:// URL of the directory
+    pub url: String,
+    pub starttls: bool,
+    /// Service account used for searches; anonymous when unset
+    pub bind_dn: Option<String>,
+    pub bind_password: Option<String>,
+    pub base_dn: String,
+    /// `{username}` is replaced by the escaped User-Name
+    pub user_filter: String,
+    /// Attribute on the user entry listing its groups
+    pub group_attribute: String,
+    /// When set, groups are searched instead; `{dn}` is replaced by the user DN
+    pub group_filter: Option<String>,
+    pub group_base_dn: Option<String>,
+    /// Attribute holding the hex NT hash (sambaNTPassword), needed for MS-CHAP
+    pub nt_hash_attribute: Option<String>,
+    /// First matching mapping wins
+    pub group_mappings: Vec<GroupMapping>,
+    pub pool_size: usize,
+    pub timeout_ms: u64,
+}
+
+impl Default for LdapConfig {
+    fn default() -> Self {
+        Self {
+            url: "ldap://localhost:389".to_string(),
+            starttls: false,
+            bind_dn: None,
+            bind_password: None,
+            base_dn: String::new(),
+            user_filter: "(uid={username})".to_string(),
+            group_attribute: "memberOf".to_string(),
+            group_filter: None,
+            group_base_dn: None,
+            nt_hash_attribute: None,
+            group_mappings: Vec::new(),
+            pool_size: 4,
+            timeout_ms: 3000,
+        }
+    }
+}
+
+impl LdapConfig {
+    pub fn apply_env(&mut self, env: &mut EnvOverrides) {
+        env.set_string("LDAP_URL", &mut self.url);
+        env.set_bool("LDAP_STARTTLS", &mut self.starttls);
+        if let Some(bind_dn) = env.get("LDAP_BIND_DN") {
+            self.bind_dn = Some(bind_dn);
+        }
+        if let Some(bind_password) = env.get("LDAP_BIND_PASSWORD") {
+            self.bind_password = Some(bind_password);
+        }
+        env.set_string("LDAP_BASE_DN", &mut self.base_dn);
+        env.set_string("LDAP_USER_FILTER", &mut self.user_filter);
+        env.set("LDAP_POOL_SIZE", &mut self.pool_size);
+    }
+
+    fn user_filter_for(&self, username: &str) -> String {
+        self.user_filter.replace("{username}", &ldap_escape(username))
+    }
+}
+
+struct PooledConnection<'a> {
+    ldap: Ldap,
+    _slot: SemaphorePermit<'a>,
+}
+
+/// Users and groups from an LDAP directory or Active Directory.
+/// PAP is verified by binding as the user; searches reuse pooled service connections.
+pub struct LdapBackend {
+    config: LdapConfig,
+    timeout: Duration,
+    idle: Mutex<Vec<Ldap>>,
+    slots: Semaphore,
+}
+
+impl LdapBackend {
+    pub fn new(config: LdapConfig) -> Self {
+        Self {
+            timeout: Duration::from_millis(config.timeout_ms),
+            slots: Semaphore::new(config.pool_size.max(1)),
+            idle: Mutex::new(Vec::new()),
+            config,
+        }
+    }
+
+    async fn connect(&self) -> Result<Ldap, BackendError> {
+        let settings = LdapConnSettings::new()
+            .set_conn_timeout(self.timeout)
+            .set_starttls(self.config.starttls);
+        let (conn, mut ldap) = LdapConnAsync::with_settings(settings, &self.config.url).await?;
+        ldap3::drive!(conn);
+        self.bind_service(&mut ldap).await?;
+        debug!("Opened LDAP connection to {}", self.config.url);
+        Ok(ldap)
+    }
+
+    async fn bind_service(&self, ldap: &mut Ldap) -> Result<(), BackendError> {
+        let bind_dn = self.config.bind_dn.as_deref().unwrap_or("");
+        let bind_password = self.config.bind_password.as_deref().unwrap_or("");
+        ldap.with_timeout(self.timeout).simple_bind(bind_dn, bind_password).await?.success()?;
+        Ok(())
+    }
+
+    async fn checkout(&self) -> Result<PooledConnection<'_>, BackendError> {
+        let slot = self.slots.acquire().await?;
+        let pooled = self.idle.lock().await.pop();
+        let ldap = match pooled {
+            Some(mut ldap) => {
+                if ldap.is_closed() { self.connect().await? } else { ldap }
+            }
+            None => self.connect().await?,
+        };
+        Ok(PooledConnection { ldap, _slot: slot })
+    }
+
+    /// Returns a healthy connection to the pool; connections that saw an error are just dropped
+    async fn checkin(&self, conn: PooledConnection<'_>) {
+        self.idle.lock().await.push(conn.ldap);
+    }
+
+    async fn search(&self, base: &str, scope: Scope, filter: &str, attrs: Vec<String>)
+        -> Result<Vec<SearchEntry>, BackendError> {
+        let mut conn = self.checkout().await?;
+        let (entries, _) = conn.ldap.with_timeout(self.timeout).search(base, scope, filter, attrs).await?.success()?;
+        self.checkin(conn).await;
+        Ok(entries.into_iter().map(SearchEntry::construct).collect())
+    }
+
+    async fn user_entry(&self, identity: &Identity, attrs: Vec<String>) -> Result<Option<SearchEntry>, BackendError> {
+        let Some(dn) = &identity.dn else {
+            return Ok(None);
+        };
+        Ok(self.search(dn, Scope::Base, "(objectClass=*)", attrs).await?.into_iter().next())
+    }
+}
+
+#[async_trait]
+impl AuthBackend for LdapBackend {
+    fn name(&self) -> &'static str {
+        "ldap"
+    }
+
+    async fn lookup_identity(&self, username: &str) -> Result<Option<Identity>, BackendError> {
+        let filter = self.config.user_filter_for(username);
+        let entries = self.search(&self.config.base_dn, Scope::Subtree, &filter,
+                                  vec!["userAccountControl".to_string()]).await?;
+        if entries.len() > 1 {
+            warn!("LDAP filter {} matched {} entries, refusing to pick one", filter, entries.len());
+            return Ok(None);
+        }
+
+        Ok(entries.into_iter().next().map(|entry| Identity {
+            username: username.to_string(),
+            identifier_id: None,
+            is_enabled: !is_disabled(&entry),
+            dn: Some(entry.dn),
+            cleartext_password: None,
+        }))
+    }
+
+    async fn verify_pap(&self, identity: &Identity, password: &str) -> Result<bool, BackendError> {
+        // An empty password would be an unauthenticated bind, which always succeeds
+        let Some(dn) = identity.dn.as_deref().filter(|_| !password.is_empty()) else {
+            return Ok(false);
+        };
+
+        let mut conn = self.checkout().await?;
+        let result = conn.ldap.with_timeout(self.timeout).simple_bind(dn, password).await?;
+        // Give the connection back to the service account before it returns to the pool
+        self.bind_service(&mut conn.ldap).await?;
+        self.checkin(conn).await;
+        match result.rc {
+            0 => Ok(true),
+            RC_INVALID_CREDENTIALS => Ok(false),
+            _ => Err(Box::new(result)),
+        }
+    }
+
+    async fn fetch_nt_hash(&self, identity: &Identity) -> Result<Option<Vec<u8>>, BackendError> {
+        let Some(attribute) = &self.config.nt_hash_attribute else {
+            return Ok(None);
+        };
+        let entry = self.user_entry(identity, vec![attribute.clone()]).await?;
+        Ok(entry
+            .and_then(|entry| first_value(&entry, attribute))
+            .and_then(|value| hex::decode(value.trim()).ok())
+            .filter(|hash| hash.len() == 16))
+    }
+
+    async fn fetch_groups(&self, identity: &Identity) -> Result<Vec<String>, BackendError> {
+        let Some(dn) = &identity.dn else {
+            return Ok(Vec::new());
+        };
+
+        if let Some(group_filter) = &self.config.group_filter {
+            let base = self.config.group_base_dn.as_deref().unwrap_or(&self.config.base_dn);
+            let filter = group_filter.replace("{dn}", &ldap_escape(dn.as_str()));
+            let entries = self.search(base, Scope::Subtree, &filter, vec!["1.1".to_string()]).await?;
+            return Ok(entries.into_iter().map(|entry| entry.dn).collect());
+        }
+
+        let attribute = &self.config.group_attribute;
+        let entry = self.user_entry(identity, vec![attribute.clone()]).await?;
+        Ok(entry.map(|entry| all_values(&entry, attribute)).unwrap_or_default())
+    }
+
+    async fn attribute_group(&self, identity: &Identity) -> Result<Option<String>, BackendError> {
+        if self.config.group_mappings.is_empty() {
+            return Ok(None);
+        }
+        let groups = self.fetch_groups(identity).await?;
+        Ok(map_attribute_group(&self.config.group_mappings, &groups))
+    }
+}
+
+fn first_value(entry: &SearchEntry, attribute: &str) -> Option<String> {
+    all_values(entry, attribute).into_iter().next()
+}
+
+// Attribute names are case-insensitive, but the server echoes its own spelling
+fn all_values(entry: &SearchEntry, attribute: &str) -> Vec<String> {
+    entry.attrs.iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(attribute))
+        .map(|(_, values)| values.clone())
+        .unwrap_or_default()
+}
+
+fn is_disabled(entry: &SearchEntry) -> bool {
+    first_value(entry, "userAccountControl")
+        .and_then(|value| value.parse::<u32>().ok())
+        .is_some_and(|flags| flags & UF_ACCOUNTDISABLE != 0)
+}
+
+/// CN of a group DN such as `cn=staff,ou=groups,dc=example,dc=org`
+fn group_cn(dn: &str) -> Option<&str> {
+    let first = dn.split(',').next()?;
+    let (name, value) = first.split_once('=')?;
+    name.trim().eq_ignore_ascii_case("cn").then(|| value.trim())
+}
+
+fn map_attribute_group(mappings: &[GroupMapping], groups: &[String]) -> Option<String> {
+    mappings.iter()
+        .find(|mapping| groups.iter().any(|group| {
+            group.eq_ignore_ascii_case(&mapping.ldap_group)
+                || group_cn(group).is_some_and(|cn| cn.eq_ignore_ascii_case(&mapping.ldap_group))
+        }))
+        .map(|mapping| mapping.attribute_group.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_filter_escapes_username() {
+        let config = LdapConfig::default();
+        assert_eq!(config.user_filter_for("alice"), "(uid=alice)");
+        assert_eq!(config.user_filter_for("*)(uid=*"), "(uid=\\2a\\29\\28uid=\\2a)");
+    }
+
+    #[test]
+    fn test_map_attribute_group() {
+        let mappings = vec![
+            GroupMapping { ldap_group: "cn=admins,ou=groups,dc=example,dc=org".to_string(), attribute_group: "Admin".to_string() },
+            GroupMapping { ldap_group: "staff".to_string(), attribute_group: "Staff".to_string() },
+        ];
+        let groups = vec!["CN=Staff,OU=Groups,DC=example,DC=org".to_string()];
+        assert_eq!(map_attribute_group(&mappings, &groups).as_deref(), Some("Staff"));
+
+        let groups = vec![
+            "cn=staff,ou=groups,dc=example,dc=org".to_string(),
+            "cn=admins,ou=groups,dc=example,dc=org".to_string(),
+        ];
+        assert_eq!(map_attribute_group(&mappings, &groups).as_deref(), Some("Admin"));
+        assert_eq!(map_attribute_group(&mappings, &["cn=guests,dc=example,dc=org".to_string()]), None);
+    }
+
+    /// Runs against a local slapd seeded with tests/ldap/seed.ldif, e.g.
+    /// `docker run -p 3389:389 -e LDAP_DOMAIN=example.org -e LDAP_ADMIN_PASSWORD=admin osixia/openldap`
+    /// followed by `ldapadd -H ldap://127.0.0.1:3389 -D cn=admin,dc=example,dc=org -w admin -f tests/ldap/seed.ldif`
+    #[tokio::test]
+    #[ignore]
+    async fn test_against_local_slapd() {
+        let backend = LdapBackend::new(LdapConfig {
+            url: std::env::var("LDAP_TEST_URL").unwrap_or_else(|_| "ldap://127.0.0.1:3389".to_string()),
+            bind_dn: Some("cn=admin,dc=example,dc=org".to_string()),
+            bind_password: Some("admin".to_string()),
+            base_dn: "ou=people,dc=example,dc=org".to_string(),
+            group_filter: Some("(&(objectClass=groupOfNames)(member={dn}))".to_string()),
+            group_base_dn: Some("ou=groups,dc=example,dc=org".to_string()),
+            nt_hash_attribute: Some("sambaNTPassword".to_string()),
+            group_mappings: vec![GroupMapping { ldap_group: "wifi-staff".to_string(), attribute_group: "Staff".to_string() }],
+            pool_size: 2,
+            ..LdapConfig::default()
+        });
+
+        let identity = backend.lookup_identity("alice").await.unwrap().expect("alice is seeded");
+        assert!(identity.is_enabled);
+        assert!(backend.verify_pap(&identity, "alicePass").await.unwrap());
+        assert!(!backend.verify_pap(&identity, "wrong").await.unwrap());
+        assert!(!backend.verify_pap(&identity, "").await.unwrap());
+        // The pooled connection must be usable for searches again after a user bind
+        assert_eq!(backend.fetch_nt_hash(&identity).await.unwrap(), Some(super::super::backend::nt_hash_of("alicePass")));
+        assert_eq!(backend.attribute_group(&identity).await.unwrap().as_deref(), Some("Staff"));
+        assert!(backend.lookup_identity("nobody").await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,298 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use redis::AsyncCommands;
+use redis::aio::MultiplexedConnection;
+use tracing::{debug, warn};
+
+use crate::config::EnvOverrides;
+
+const REDIS_CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+const REDIS_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Brute-force protection settings.
+/// A `max_failures` of 0 disables lockout entirely.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LockoutPolicy {
+    pub max_failures: u32,
+    pub window_secs: u64,
+    pub lockout_secs: u64,
+}
+
+impl Default for LockoutPolicy {
+    fn default() -> Self {
+        Self {
+            max_failures: 0,
+            window_secs: 300,
+            lockout_secs: 900,
+        }
+    }
+}
+
+impl LockoutPolicy {
+    pub fn apply_env(&mut self, env: &mut EnvOverrides) {
+        env.set("LOCKOUT_MAX_FAILURES", &mut self.max_failures);
+        env.set("LOCKOUT_WINDOW_SECS", &mut self.window_secs);
+        env.set("LOCKOUT_DURATION_SECS", &mut self.lockout_secs);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.max_failures > 0
+    }
+}
+
+#[derive(Debug)]
+struct LocalCounter {
+    failures: u32,
+    window_started: Instant,
+    locked_until: Option<Instant>,
+}
+
+/// Failure counters shared between core replicas through Redis.
+/// Falls back to process-local counters while Redis is unreachable.
+pub struct LockoutTracker {
+    policy: LockoutPolicy,
+    redis: Option<redis::Client>,
+    connection: tokio::sync::Mutex<Option<MultiplexedConnection>>,
+    retry_at: std::sync::Mutex<Option<Instant>>,
+    local: std::sync::Mutex<HashMap<String, LocalCounter>>,
+}
+
+impl LockoutTracker {
+    pub fn new(policy: LockoutPolicy, redis_url: &str) -> Self {
+        let redis = if policy.is_enabled() {
+            match redis::Client::open(redis_url) {
+                Ok(client) => Some(client),
+                Err(e) => {
+                    warn!("Invalid REDIS_URL for lockout counters, using in-memory counters: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        Self {
+            policy,
+            redis,
+            connection: tokio::sync::Mutex::new(None),
+            retry_at: std::sync::Mutex::new(None),
+            local: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.policy.is_enabled()
+    }
+
+    pub fn identifier_key(username: &str) -> String {
+        format!("lockout:user:{}", username)
+    }
+
+    pub fn station_key(nas_ip: IpAddr, calling_station_id: &str) -> String {
+        format!("lockout:station:{}:{}", nas_ip, calling_station_id)
+    }
+
+    /// Returns true when any of the given keys is currently locked out
+    pub async fn is_locked(&self, keys: &[String]) -> bool {
+        if !self.is_enabled() {
+            return false;
+        }
+
+        if let Some(mut conn) = self.connection().await {
+            let mut locked = false;
+            for key in keys {
+                match conn.exists::<_, bool>(format!("{}:locked", key)).await {
+                    Ok(exists) => locked |= exists,
+                    Err(e) => {
+                        self.connection_failed(&e).await;
+                        return self.is_locked_local(keys);
+                    }
+                }
+            }
+            return locked;
+        }
+
+        self.is_locked_local(keys)
+    }
+
+    /// Counts a failed attempt against every key, locking those that reach the limit
+    pub async fn record_failure(&self, keys: &[String]) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        if let Some(mut conn) = self.connection().await {
+            for key in keys {
+                if let Err(e) = self.record_failure_redis(&mut conn, key).await {
+                    self.connection_failed(&e).await;
+                    self.record_failure_local(keys);
+                    return;
+                }
+            }
+            return;
+        }
+
+        self.record_failure_local(keys);
+    }
+
+    /// Clears the failure counter for a key after a successful login
+    pub async fn reset(&self, key: &str) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        if let Some(mut conn) = self.connection().await
+            && let Err(e) = conn.del::<_, ()>(format!("{}:failures", key)).await
+        {
+            self.connection_failed(&e).await;
+        }
+
+        self.local.lock().unwrap().remove(key);
+    }
+
+    async fn record_failure_redis(&self, conn: &mut MultiplexedConnection, key: &str) -> redis::RedisResult<()> {
+        let failures_key = format!("{}:failures", key);
+        let failures: u32 = conn.incr(&failures_key, 1).await?;
+        if failures == 1 {
+            conn.expire::<_, ()>(&failures_key, self.policy.window_secs as i64).await?;
+        }
+
+        if failures >= self.policy.max_failures {
+            warn!("Locking out {} for {}s after {} failed attempts", key, self.policy.lockout_secs, failures);
+            conn.set_ex::<_, _, ()>(format!("{}:locked", key), 1, self.policy.lockout_secs).await?;
+            conn.del::<_, ()>(&failures_key).await?;
+        }
+        Ok(())
+    }
+
+    fn is_locked_local(&self, keys: &[String]) -> bool {
+        let now = Instant::now();
+        let local = self.local.lock().unwrap();
+        keys.iter().any(|key| {
+            local.get(key)
+                .and_then(|counter| counter.locked_until)
+                .is_some_and(|until| until > now)
+        })
+    }
+
+    fn record_failure_local(&self, keys: &[String]) {
+        let now = Instant::now();
+        let window = Duration::from_secs(self.policy.window_secs);
+        let mut local = self.local.lock().unwrap();
+
+        // Drop counters whose window and lockout have both run out
+        local.retain(|_, counter| {
+            now.duration_since(counter.window_started) < window
+                || counter.locked_until.is_some_and(|until| until > now)
+        });
+
+        for key in keys {
+            let counter = local.entry(key.clone()).or_insert(LocalCounter {
+                failures: 0,
+                window_started: now,
+                locked_until: None,
+            });
+
+            if now.duration_since(counter.window_started) >= window {
+                counter.failures = 0;
+                counter.window_started = now;
+            }
+
+            counter.failures += 1;
+            if counter.failures >= self.policy.max_failures {
+                warn!("Locking out {} for {}s after {} failed attempts (in-memory)",
+                      key, self.policy.lockout_secs, counter.failures);
+                counter.locked_until = Some(now + Duration::from_secs(self.policy.lockout_secs));
+                counter.failures = 0;
+                counter.window_started = now;
+            }
+        }
+    }
+
+    async fn connection(&self) -> Option<MultiplexedConnection> {
+        let client = self.redis.as_ref()?;
+        let mut connection = self.connection.lock().await;
+        if let Some(conn) = connection.as_ref() {
+            return Some(conn.clone());
+        }
+
+        if self.retry_at.lock().unwrap().is_some_and(|at| at > Instant::now()) {
+            return None;
+        }
+
+        match tokio::time::timeout(REDIS_CONNECT_TIMEOUT, client.get_multiplexed_async_connection()).await {
+            Ok(Ok(conn)) => {
+                debug!("Connected to Redis for lockout counters");
+                *connection = Some(conn.clone());
+                Some(conn)
+            }
+            Ok(Err(e)) => {
+                warn!("Redis unavailable, using in-memory lockout counters: {}", e);
+                *self.retry_at.lock().unwrap() = Some(Instant::now() + REDIS_RETRY_INTERVAL);
+                None
+            }
+            Err(_) => {
+                warn!("Timed out connecting to Redis, using in-memory lockout counters");
+                *self.retry_at.lock().unwrap() = Some(Instant::now() + REDIS_RETRY_INTERVAL);
+                None
+            }
+        }
+    }
+
+    async fn connection_failed(&self, e: &redis::RedisError) {
+        warn!("Redis error on lockout counters, falling back to in-memory: {}", e);
+        *self.connection.lock().await = None;
+        *self.retry_at.lock().unwrap() = Some(Instant::now() + REDIS_RETRY_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker() -> LockoutTracker {
+        let policy = LockoutPolicy { max_failures: 3, window_secs: 300, lockout_secs: 900 };
+        // Nothing listens on the discard port, so every call uses the in-memory counters
+        LockoutTracker::new(policy, "redis://127.0.0.1:9/")
+    }
+
+    #[tokio::test]
+    async fn test_locks_after_max_failures() {
+        let tracker = tracker();
+        let keys = vec![LockoutTracker::identifier_key("alice")];
+
+        tracker.record_failure(&keys).await;
+        tracker.record_failure(&keys).await;
+        assert!(!tracker.is_locked(&keys).await);
+
+        tracker.record_failure(&keys).await;
+        assert!(tracker.is_locked(&keys).await);
+        assert!(!tracker.is_locked(&[LockoutTracker::identifier_key("bob")]).await);
+    }
+
+    #[tokio::test]
+    async fn test_reset_clears_failures() {
+        let tracker = tracker();
+        let key = LockoutTracker::station_key("10.0.0.1".parse().unwrap(), "AA-BB-CC-DD-EE-FF");
+        let keys = vec![key.clone()];
+
+        tracker.record_failure(&keys).await;
+        tracker.record_failure(&keys).await;
+        tracker.reset(&key).await;
+        tracker.record_failure(&keys).await;
+        assert!(!tracker.is_locked(&keys).await);
+    }
+
+    #[tokio::test]
+    async fn test_disabled_policy_never_locks() {
+        let tracker = LockoutTracker::new(LockoutPolicy::default(), "redis://127.0.0.1:9/");
+        let keys = vec![LockoutTracker::identifier_key("alice")];
+        for _ in 0..10 {
+            tracker.record_failure(&keys).await;
+        }
+        assert!(!tracker.is_locked(&keys).await);
+    }
+}
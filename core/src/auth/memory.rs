@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use async_trait::async_trait;
+use ipnetwork::IpNetwork;
+
+use super::backend::{nt_hash_of, AuthBackend, BackendError, Identity};
+use super::models::NasDevice;
+use super::store::{ClientSecret, ClientStore};
+
+#[derive(Debug, Clone)]
+struct MemoryUser {
+    password: String,
+    is_enabled: bool,
+}
+
+/// NAS devices, secrets and users held in memory, for running the services without Postgres:
+/// integration tests and lab setups. Its users have no identifier ID or groups, so password
+/// ageing, schedules and the other per-user settings kept in Postgres don't apply to them.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    nas_devices: Vec<NasDevice>,
+    secrets: Vec<ClientSecret>,
+    users: HashMap<String, MemoryUser>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_nas(&mut self, device: NasDevice) -> &mut Self {
+        self.nas_devices.push(device);
+        self
+    }
+
+    pub fn add_secret(&mut self, subnet: IpNetwork, secret: &str) -> &mut Self {
+        self.secrets.push(ClientSecret { secret: secret.to_string(), subnets: vec![subnet] });
+        self
+    }
+
+    pub fn add_user(&mut self, username: &str, password: &str) -> &mut Self {
+        self.users.insert(username.to_string(), MemoryUser {
+            password: password.to_string(),
+            is_enabled: true,
+        });
+        self
+    }
+
+    /// Enables or disables a user added before; unknown users are ignored
+    pub fn set_enabled(&mut self, username: &str, is_enabled: bool) -> &mut Self {
+        if let Some(user) = self.users.get_mut(username) {
+            user.is_enabled = is_enabled;
+        }
+        self
+    }
+}
+
+#[async_trait]
+impl ClientStore for MemoryStore {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    async fn load_nas_devices(&self) -> Result<Vec<NasDevice>, BackendError> {
+        Ok(self.nas_devices.iter().filter(|device| device.is_active).cloned().collect())
+    }
+
+    async fn load_secrets(&self) -> Result<Vec<ClientSecret>, BackendError> {
+        Ok(self.secrets.clone())
+    }
+}
+
+#[async_trait]
+impl AuthBackend for MemoryStore {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    async fn lookup_identity(&self, username: &str) -> Result<Option<Identity>, BackendError> {
+        Ok(self.users.get(username).map(|user| Identity {
+            username: username.to_string(),
+            identifier_id: None,
+            dn: None,
+            is_enabled: user.is_enabled,
+            cleartext_password: Some(user.password.clone()),
+        }))
+    }
+
+    async fn verify_pap(&self, identity: &Identity, password: &str) -> Result<bool, BackendError> {
+        Ok(identity.cleartext_password.as_deref() == Some(password))
+    }
+
+    async fn fetch_nt_hash(&self, identity: &Identity) -> Result<Option<Vec<u8>>, BackendError> {
+        Ok(identity.cleartext_password.as_deref().map(nt_hash_of))
+    }
+
+    /// Groups live in Postgres, where schedules and session limits are looked up by group name
+    async fn fetch_groups(&self, _identity: &Identity) -> Result<Vec<String>, BackendError> {
+        Ok(Vec::new())
+    }
+
+    async fn attribute_group(&self, _identity: &Identity) -> Result<Option<String>, BackendError> {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_store() {
+        let mut store = MemoryStore::new();
+        store.add_user("alice", "wonderland")
+            .add_user("bob", "builder")
+            .set_enabled("bob", false)
+            .add_secret("127.0.0.0/8".parse().unwrap(), "testing123");
+
+        let alice = store.lookup_identity("alice").await.unwrap().unwrap();
+        assert!(alice.is_enabled && alice.identifier_id.is_none());
+        assert!(store.verify_pap(&alice, "wonderland").await.unwrap());
+        assert!(!store.verify_pap(&alice, "looking-glass").await.unwrap());
+        assert!(!store.lookup_identity("bob").await.unwrap().unwrap().is_enabled);
+        assert!(store.lookup_identity("carol").await.unwrap().is_none());
+
+        let secrets = store.load_secrets().await.unwrap();
+        assert_eq!(ClientSecret::lookup(&secrets, "127.0.0.1".parse().unwrap()), Some("testing123"));
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use serde::{Serialize, Deserialize};
 use sqlx::types::JsonValue;
 
@@ -16,7 +17,18 @@ pub struct NasDevice {
     pub id: i64,
     pub name: String,
     pub nas_identifier: String,
+    pub ip_address: String,
+    /// IANA number of the NAS's vendor, selecting its reply profile
+    pub vendor_id: Option<u32>,
     pub is_active: bool,
+    pub coa_enabled: bool,
+    pub coa_port: u16,
+    /// IANA name of the NAS's timezone, e.g. "Europe/Paris"
+    pub timezone_name: Option<String>,
+    /// Fixed UTC offset in minutes, used when the name isn't a known zone
+    pub timezone_offset: Option<i32>,
+    /// VLAN names this NAS knows under other IDs, lowercased name to ID
+    pub vlan_map: HashMap<String, String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
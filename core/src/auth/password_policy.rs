@@ -0,0 +1,105 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Duration, Utc};
+
+use crate::config::EnvOverrides;
+
+/// Password ageing policy applied to identifier passwords.
+/// A `max_age_days` of 0 disables ageing entirely.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PasswordPolicy {
+    pub max_age_days: u32,
+    pub warning_days: u32,
+    pub grace_logins: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PasswordStatus {
+    Valid,
+    Expiring { days_left: i64 },
+    Grace { remaining: u32 },
+    Expired,
+}
+
+impl PasswordPolicy {
+    pub fn apply_env(&mut self, env: &mut EnvOverrides) {
+        env.set("PASSWORD_MAX_AGE_DAYS", &mut self.max_age_days);
+        env.set("PASSWORD_WARNING_DAYS", &mut self.warning_days);
+        env.set("PASSWORD_GRACE_LOGINS", &mut self.grace_logins);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.max_age_days > 0
+    }
+
+    pub fn evaluate(&self, changed_at: DateTime<Utc>, grace_logins_used: u32, now: DateTime<Utc>) -> PasswordStatus {
+        if !self.is_enabled() {
+            return PasswordStatus::Valid;
+        }
+
+        let expires_at = changed_at + Duration::days(self.max_age_days as i64);
+        if now < expires_at {
+            // Round up so a password expiring later today still reports one day
+            let days_left = ((expires_at - now).num_seconds() + 86_399) / 86_400;
+            if days_left <= self.warning_days as i64 {
+                return PasswordStatus::Expiring { days_left };
+            }
+            return PasswordStatus::Valid;
+        }
+
+        if grace_logins_used < self.grace_logins {
+            return PasswordStatus::Grace { remaining: self.grace_logins - grace_logins_used - 1 };
+        }
+
+        PasswordStatus::Expired
+    }
+}
+
+impl PasswordStatus {
+    /// Reply-Message text for logins that are accepted but should warn the user
+    pub fn warning(&self) -> Option<String> {
+        match self {
+            PasswordStatus::Expiring { days_left: 1 } => Some("password expires in 1 day".to_string()),
+            PasswordStatus::Expiring { days_left } => Some(format!("password expires in {} days", days_left)),
+            PasswordStatus::Grace { remaining } => {
+                Some(format!("password expired, {} grace logins remaining", remaining))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> PasswordPolicy {
+        PasswordPolicy { max_age_days: 90, warning_days: 14, grace_logins: 2 }
+    }
+
+    #[test]
+    fn test_disabled_policy_is_always_valid() {
+        let now = Utc::now();
+        let status = PasswordPolicy::default().evaluate(now - Duration::days(1000), 0, now);
+        assert_eq!(status, PasswordStatus::Valid);
+    }
+
+    #[test]
+    fn test_warning_window() {
+        let now = Utc::now();
+        assert_eq!(policy().evaluate(now - Duration::days(10), 0, now), PasswordStatus::Valid);
+
+        let status = policy().evaluate(now - Duration::days(85), 0, now);
+        assert_eq!(status, PasswordStatus::Expiring { days_left: 5 });
+        assert_eq!(status.warning().as_deref(), Some("password expires in 5 days"));
+    }
+
+    #[test]
+    fn test_grace_logins_then_expired() {
+        let now = Utc::now();
+        let changed_at = now - Duration::days(91);
+        assert_eq!(policy().evaluate(changed_at, 0, now), PasswordStatus::Grace { remaining: 1 });
+        assert_eq!(policy().evaluate(changed_at, 1, now), PasswordStatus::Grace { remaining: 0 });
+        assert_eq!(policy().evaluate(changed_at, 2, now), PasswordStatus::Expired);
+    }
+}
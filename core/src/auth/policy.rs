@@ -0,0 +1,565 @@
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use chrono::{Datelike, NaiveDateTime, NaiveTime, Weekday};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use sqlx::types::JsonValue;
+use tracing::{debug, info, warn};
+
+use super::radius_server::RadiusPacket;
+use super::reply::{AttributeEdit, AttributeEditSpec};
+use crate::config::EnvOverrides;
+
+const ATTR_USER_NAME: u8 = 1;
+const ATTR_NAS_IP_ADDRESS: u8 = 4;
+const ATTR_CALLED_STATION_ID: u8 = 30;
+const ATTR_CALLING_STATION_ID: u8 = 31;
+const ATTR_NAS_IDENTIFIER: u8 = 32;
+const ATTR_NAS_PORT_TYPE: u8 = 61;
+
+const DEFAULT_REJECT_MESSAGE: &str = "Rejected by policy";
+
+// NAS-Port-Type names accepted in conditions (RFC 2865, section 5.41)
+const NAS_PORT_TYPES: [(&str, u32); 8] = [
+    ("async", 0),
+    ("sync", 1),
+    ("virtual", 5),
+    ("ethernet", 15),
+    ("xdsl", 16),
+    ("cable", 17),
+    ("wireless-other", 18),
+    ("wireless-802.11", 19),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PolicyConfig {
+    pub enabled: bool,
+    /// Evaluate the rules and log the decision, without enforcing it
+    pub dry_run: bool,
+    /// Seconds between reloads of `radius_authorization_rule`
+    pub reload_secs: u64,
+}
+
+impl Default for PolicyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            dry_run: false,
+            reload_secs: 60,
+        }
+    }
+}
+
+impl PolicyConfig {
+    pub fn apply_env(&mut self, env: &mut EnvOverrides) {
+        env.set_bool("POLICY_ENABLED", &mut self.enabled);
+        env.set_bool("POLICY_DRY_RUN", &mut self.dry_run);
+        env.set("POLICY_RELOAD_SECS", &mut self.reload_secs);
+    }
+}
+
+/// What a condition looks at
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Field {
+    Username,
+    AuthMethod,
+    NasPortType,
+    CalledStationId,
+    /// SSID part of Called-Station-Id (`AA-BB-CC-DD-EE-FF:SSID`, RFC 3580)
+    Ssid,
+    CallingStationId,
+    NasIdentifier,
+    NasIpAddress,
+    NasGroup,
+    UserGroup,
+    /// Local time of the request, as `HH:MM-HH:MM` with the between operator
+    TimeOfDay,
+    /// Three-letter day names (mon, tue, ...)
+    DayOfWeek,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Operator {
+    #[default]
+    Equals,
+    NotEquals,
+    In,
+    NotIn,
+    Matches,
+    Between,
+}
+
+/// A condition as stored in the `conditions` JSON array of a rule
+#[derive(Debug, Deserialize)]
+struct ConditionSpec {
+    field: Field,
+    #[serde(default)]
+    operator: Operator,
+    value: JsonValue,
+}
+
+#[derive(Debug)]
+enum Test {
+    OneOf(Vec<String>),
+    Pattern(Regex),
+    TimeRange(NaiveTime, NaiveTime),
+    Days(Vec<Weekday>),
+}
+
+#[derive(Debug)]
+struct Condition {
+    field: Field,
+    test: Test,
+    negate: bool,
+    /// Source text, for traces
+    text: String,
+}
+
+impl Condition {
+    fn compile(spec: ConditionSpec) -> Result<Self, String> {
+        let text = format!("{:?} {:?} {}", spec.field, spec.operator, spec.value);
+        let values = || -> Result<Vec<String>, String> {
+            let values = match &spec.value {
+                JsonValue::Array(items) => items.iter().map(json_text).collect::<Option<Vec<_>>>(),
+                other => json_text(other).map(|value| vec![value]),
+            };
+            values.filter(|values| !values.is_empty()).ok_or_else(|| format!("{}: value must be a string, number or list of them", text))
+        };
+
+        let test = match (spec.field, spec.operator) {
+            (Field::TimeOfDay, Operator::Between) => {
+                let range = spec.value.as_str().unwrap_or_default();
+                let parsed = range.split_once('-').and_then(|(start, end)| {
+                    Some((NaiveTime::parse_from_str(start.trim(), "%H:%M").ok()?, NaiveTime::parse_from_str(end.trim(), "%H:%M").ok()?))
+                });
+                let (start, end) = parsed.ok_or_else(|| format!("{}: expected a range like 08:00-18:00", text))?;
+                Test::TimeRange(start, end)
+            }
+            (Field::TimeOfDay, _) => return Err(format!("{}: time_of_day only supports between", text)),
+            (_, Operator::Between) => return Err(format!("{}: between only applies to time_of_day", text)),
+            (_, Operator::Matches) => {
+                let pattern = spec.value.as_str().ok_or_else(|| format!("{}: pattern must be a string", text))?;
+                Test::Pattern(Regex::new(pattern).map_err(|e| format!("{}: {}", text, e))?)
+            }
+            (Field::DayOfWeek, _) => {
+                let days = values()?.iter()
+                    .map(|day| day.parse::<Weekday>().map_err(|_| format!("{}: unknown day {:?}", text, day)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Test::Days(days)
+            }
+            (Field::NasPortType, _) => Test::OneOf(values()?.into_iter().map(|value| nas_port_type_number(&value)).collect()),
+            _ => Test::OneOf(values()?),
+        };
+
+        Ok(Self {
+            field: spec.field,
+            test,
+            negate: matches!(spec.operator, Operator::NotEquals | Operator::NotIn),
+            text,
+        })
+    }
+
+    fn matches(&self, context: &PolicyContext) -> bool {
+        let found = match &self.test {
+            Test::OneOf(expected) => context.values(self.field).iter()
+                .any(|value| expected.iter().any(|candidate| candidate.eq_ignore_ascii_case(value))),
+            Test::Pattern(pattern) => context.values(self.field).iter().any(|value| pattern.is_match(value)),
+            Test::TimeRange(start, end) => {
+                let time = context.local_time.time();
+                if start <= end {
+                    *start <= time && time < *end
+                } else {
+                    // Ranges like 22:00-06:00 wrap past midnight
+                    time >= *start || time < *end
+                }
+            }
+            Test::Days(days) => days.contains(&context.local_time.weekday()),
+        };
+        found != self.negate
+    }
+}
+
+fn json_text(value: &JsonValue) -> Option<String> {
+    match value {
+        JsonValue::String(text) => Some(text.clone()),
+        JsonValue::Number(number) => Some(number.to_string()),
+        _ => None,
+    }
+}
+
+fn nas_port_type_number(value: &str) -> String {
+    NAS_PORT_TYPES.iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(value))
+        .map(|(_, number)| number.to_string())
+        .unwrap_or_else(|| value.to_string())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum RuleAction {
+    /// Apply the attribute edits and keep evaluating
+    Continue,
+    Accept,
+    Reject,
+}
+
+#[derive(Debug)]
+struct Rule {
+    name: String,
+    priority: i32,
+    conditions: Vec<Condition>,
+    action: RuleAction,
+    reply_message: Option<String>,
+    edits: Vec<AttributeEdit>,
+    vlan: Option<String>,
+}
+
+/// Everything a rule can look at for one Access-Request
+#[derive(Debug, Clone)]
+pub struct PolicyContext {
+    pub username: Option<String>,
+    pub auth_method: String,
+    pub nas_port_type: Option<String>,
+    pub called_station_id: Option<String>,
+    pub calling_station_id: Option<String>,
+    pub nas_identifier: Option<String>,
+    pub nas_ip_address: Option<String>,
+    pub nas_groups: Vec<String>,
+    pub user_groups: Vec<String>,
+    pub local_time: NaiveDateTime,
+}
+
+impl PolicyContext {
+    pub fn from_request(packet: &RadiusPacket, auth_method: &str, local_time: NaiveDateTime) -> Self {
+        let text = |typ: u8| packet.attributes.iter()
+            .find(|attr| attr.typ == typ)
+            .map(|attr| String::from_utf8_lossy(&attr.value).to_string());
+        let integer = |typ: u8| packet.attributes.iter()
+            .find(|attr| attr.typ == typ && attr.value.len() == 4)
+            .map(|attr| u32::from_be_bytes([attr.value[0], attr.value[1], attr.value[2], attr.value[3]]));
+
+        Self {
+            username: text(ATTR_USER_NAME),
+            auth_method: auth_method.to_string(),
+            nas_port_type: integer(ATTR_NAS_PORT_TYPE).map(|value| value.to_string()),
+            called_station_id: text(ATTR_CALLED_STATION_ID),
+            calling_station_id: text(ATTR_CALLING_STATION_ID),
+            nas_identifier: text(ATTR_NAS_IDENTIFIER),
+            nas_ip_address: integer(ATTR_NAS_IP_ADDRESS).map(|value| std::net::Ipv4Addr::from(value).to_string()),
+            nas_groups: Vec::new(),
+            user_groups: Vec::new(),
+            local_time,
+        }
+    }
+
+    fn values(&self, field: Field) -> Vec<&str> {
+        fn single(value: &Option<String>) -> Vec<&str> {
+            value.as_deref().into_iter().collect()
+        }
+        match field {
+            Field::Username => single(&self.username),
+            Field::AuthMethod => vec![self.auth_method.as_str()],
+            Field::NasPortType => single(&self.nas_port_type),
+            Field::CalledStationId => single(&self.called_station_id),
+            Field::Ssid => self.called_station_id.as_deref().and_then(ssid).into_iter().collect(),
+            Field::CallingStationId => single(&self.calling_station_id),
+            Field::NasIdentifier => single(&self.nas_identifier),
+            Field::NasIpAddress => single(&self.nas_ip_address),
+            Field::NasGroup => self.nas_groups.iter().map(String::as_str).collect(),
+            Field::UserGroup => self.user_groups.iter().map(String::as_str).collect(),
+            Field::TimeOfDay | Field::DayOfWeek => Vec::new(),
+        }
+    }
+}
+
+/// SSID of a `<MAC>:<SSID>` Called-Station-Id (RFC 3580, section 3.20). The MAC's octets may be
+/// separated by `-` or `:`, so it is stripped as a whole rather than split at the first colon.
+fn ssid(called: &str) -> Option<&str> {
+    let bytes = called.as_bytes();
+    let mac = bytes.len() >= 17
+        && (0..6).all(|octet| bytes[octet * 3].is_ascii_hexdigit() && bytes[octet * 3 + 1].is_ascii_hexdigit())
+        && (0..5).all(|octet| bytes[octet * 3 + 2] == bytes[2] && matches!(bytes[2], b'-' | b':'));
+    if mac {
+        return called[17..].strip_prefix(':');
+    }
+    called.split_once(':').map(|(_, ssid)| ssid)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PolicyDecision {
+    Accept,
+    Reject(String),
+}
+
+/// Result of evaluating the rules, with one trace line per rule considered
+#[derive(Debug)]
+pub(super) struct PolicyOutcome {
+    pub decision: PolicyDecision,
+    pub edits: Vec<AttributeEdit>,
+    /// VLAN of the last matching rule that assigns one
+    pub vlan: Option<String>,
+    pub trace: Vec<String>,
+}
+
+/// Authorization rules from `radius_authorization_rule`, evaluated in priority order
+/// after authentication succeeded. The first accept or reject rule that matches ends
+/// the evaluation; continue rules only contribute attribute edits.
+pub struct PolicyEngine {
+    config: PolicyConfig,
+    rules: RwLock<Arc<Vec<Rule>>>,
+}
+
+impl PolicyEngine {
+    pub fn new(config: PolicyConfig) -> Self {
+        Self {
+            config,
+            rules: RwLock::new(Arc::new(Vec::new())),
+        }
+    }
+
+    /// Whether any rule looks at `field`, so callers can skip lookups nobody needs
+    pub fn uses(&self, field: Field) -> bool {
+        self.rules.read().unwrap().iter()
+            .any(|rule| rule.conditions.iter().any(|condition| condition.field == field))
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.config.enabled && !self.rules.read().unwrap().is_empty()
+    }
+
+    pub async fn reload(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+
+        #[derive(sqlx::FromRow)]
+        struct RuleRow {
+            id: i64,
+            name: String,
+            priority: i32,
+            conditions: JsonValue,
+            action: String,
+            reply_message: String,
+            attribute_edits: JsonValue,
+            vlan: String,
+        }
+
+        let db_timer = crate::metrics::DB_QUERY_DURATION.with_label_values(&["load_policy_rules"]).start_timer();
+        let rows = sqlx::query_as::<_, RuleRow>(
+            r#"
+            SELECT id, name, priority, conditions, action, reply_message, attribute_edits, vlan
+            FROM radius_authorization_rule
+            WHERE is_enabled = true
+            ORDER BY priority, id
+            "#
+        )
+        .fetch_all(pool)
+        .await?;
+        db_timer.observe_duration();
+
+        let mut rules = Vec::with_capacity(rows.len());
+        for row in rows {
+            match compile_rule(&row.name, row.priority, row.conditions, &row.action, row.reply_message, row.attribute_edits, row.vlan) {
+                Ok(rule) => rules.push(rule),
+                Err(e) => warn!("Skipping authorization rule {} ({}): {}", row.id, row.name, e),
+            }
+        }
+        debug!("Loaded {} authorization rules", rules.len());
+        *self.rules.write().unwrap() = Arc::new(rules);
+        Ok(())
+    }
+
+    /// Reloads the rules every `reload_secs`
+    pub async fn run_reloader(&self, pool: PgPool) {
+        if !self.config.enabled || self.config.reload_secs == 0 {
+            return;
+        }
+        let mut interval = tokio::time::interval(Duration::from_secs(self.config.reload_secs));
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.reload(&pool).await {
+                warn!("Failed to reload authorization rules: {}", e);
+            }
+        }
+    }
+
+    pub(super) fn evaluate(&self, context: &PolicyContext) -> PolicyOutcome {
+        let rules = self.rules.read().unwrap().clone();
+        let mut edits = Vec::new();
+        let mut vlan = None;
+        let mut trace = Vec::new();
+
+        for rule in rules.iter() {
+            if let Some(failed) = rule.conditions.iter().find(|condition| !condition.matches(context)) {
+                trace.push(format!("rule {:?} (priority {}): no match, {} is false", rule.name, rule.priority, failed.text));
+                continue;
+            }
+
+            edits.extend(rule.edits.iter().cloned());
+            if rule.vlan.is_some() {
+                vlan = rule.vlan.clone();
+            }
+            trace.push(format!("rule {:?} (priority {}): matched, {:?} with {} attribute edits",
+                               rule.name, rule.priority, rule.action, rule.edits.len()));
+            match rule.action {
+                RuleAction::Continue => {}
+                RuleAction::Accept => return PolicyOutcome { decision: PolicyDecision::Accept, edits, vlan, trace },
+                RuleAction::Reject => {
+                    let message = rule.reply_message.clone().unwrap_or_else(|| DEFAULT_REJECT_MESSAGE.to_string());
+                    return PolicyOutcome { decision: PolicyDecision::Reject(message), edits: Vec::new(), vlan: None, trace };
+                }
+            }
+        }
+
+        trace.push("no accept or reject rule matched, accepting".to_string());
+        PolicyOutcome { decision: PolicyDecision::Accept, edits, vlan, trace }
+    }
+
+    /// Evaluates the rules for a request; in dry-run mode the outcome is only logged
+    pub(super) fn authorize(&self, context: &PolicyContext) -> PolicyOutcome {
+        let outcome = self.evaluate(context);
+        let user = context.username.as_deref().unwrap_or("-");
+        if self.config.dry_run {
+            info!("Policy dry run for {}: {:?}\n  {}", user, outcome.decision, outcome.trace.join("\n  "));
+            return PolicyOutcome { decision: PolicyDecision::Accept, edits: Vec::new(), vlan: None, trace: outcome.trace };
+        }
+        debug!("Policy for {}: {:?}\n  {}", user, outcome.decision, outcome.trace.join("\n  "));
+        outcome
+    }
+}
+
+fn compile_rule(name: &str, priority: i32, conditions: JsonValue, action: &str, reply_message: String,
+                attribute_edits: JsonValue, vlan: String) -> Result<Rule, String> {
+    let conditions = serde_json::from_value::<Vec<ConditionSpec>>(conditions)
+        .map_err(|e| format!("conditions: {}", e))?
+        .into_iter()
+        .map(Condition::compile)
+        .collect::<Result<Vec<_>, _>>()?;
+    let action = serde_json::from_value::<RuleAction>(JsonValue::String(action.to_string()))
+        .map_err(|e| format!("action: {}", e))?;
+    let edits = serde_json::from_value::<Vec<AttributeEditSpec>>(attribute_edits)
+        .map_err(|e| format!("attribute_edits: {}", e))?
+        .iter()
+        .map(AttributeEditSpec::compile)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Rule {
+        name: name.to_string(),
+        priority,
+        conditions,
+        action,
+        reply_message: Some(reply_message).filter(|message| !message.is_empty()),
+        edits,
+        vlan: Some(vlan.trim().to_string()).filter(|vlan| !vlan.is_empty()),
+    })
+}
+
+/// Names of the groups a NAS belongs to
+pub async fn load_nas_groups(pool: &PgPool, nas_id: i64) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar::<_, String>(
+        r#"
+        SELECT g.name
+        FROM nas_nas_groups ng
+        JOIN nas_nas_group g ON g.id = ng.nasgroup_id
+        WHERE ng.nas_id = $1
+        ORDER BY g.name
+        "#
+    )
+    .bind(nas_id)
+    .fetch_all(pool)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn engine(rules: Vec<Rule>) -> PolicyEngine {
+        let engine = PolicyEngine::new(PolicyConfig::default());
+        *engine.rules.write().unwrap() = Arc::new(rules);
+        engine
+    }
+
+    fn context(time: &str) -> PolicyContext {
+        PolicyContext {
+            username: Some("alice".to_string()),
+            auth_method: "PAP".to_string(),
+            nas_port_type: Some("19".to_string()),
+            called_station_id: Some("00-11-22-33-44-55:Corp-WiFi".to_string()),
+            calling_station_id: Some("AA-BB-CC-DD-EE-FF".to_string()),
+            nas_identifier: Some("ap-1".to_string()),
+            nas_ip_address: Some("10.0.0.1".to_string()),
+            nas_groups: vec!["Campus".to_string()],
+            user_groups: vec!["Staff".to_string()],
+            local_time: NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_rules_in_priority_order() {
+        let engine = engine(vec![
+            compile_rule("vlan for staff", 10, json!([
+                { "field": "user_group", "value": "staff" },
+                { "field": "nas_port_type", "value": "Wireless-802.11" },
+            ]), "continue", String::new(), json!([
+                { "attribute_id": 81, "value": "100" },
+            ]), "staff".to_string()).unwrap(),
+            compile_rule("guests after hours", 20, json!([
+                { "field": "ssid", "operator": "in", "value": ["Guest", "Corp-WiFi"] },
+                { "field": "time_of_day", "operator": "between", "value": "18:00-08:00" },
+            ]), "reject", "Outside office hours".to_string(), json!([]), String::new()).unwrap(),
+            compile_rule("everyone else", 30, json!([
+                { "field": "calling_station_id", "operator": "matches", "value": "^aa-" },
+            ]), "accept", String::new(), json!([]), String::new()).unwrap(),
+        ]);
+
+        let outcome = engine.evaluate(&context("2026-03-02 23:30"));
+        assert_eq!(outcome.decision, PolicyDecision::Reject("Outside office hours".to_string()));
+        assert!(outcome.edits.is_empty());
+        assert_eq!(outcome.trace.len(), 2);
+
+        // The case-sensitive pattern doesn't match the upper-case MAC, so nothing decides
+        let outcome = engine.evaluate(&context("2026-03-02 10:00"));
+        assert_eq!(outcome.decision, PolicyDecision::Accept);
+        assert_eq!(outcome.edits.len(), 1);
+        assert_eq!(outcome.vlan.as_deref(), Some("staff"));
+        assert!(outcome.trace[1].contains("no match"));
+        assert!(outcome.trace[2].contains("no match"));
+        assert_eq!(outcome.trace[3], "no accept or reject rule matched, accepting");
+    }
+
+    #[test]
+    fn test_invalid_conditions() {
+        let compile = |conditions| compile_rule("bad", 1, conditions, "accept", String::new(), json!([]), String::new());
+        assert!(compile(json!([{ "field": "time_of_day", "value": "09:00" }])).is_err());
+        assert!(compile(json!([{ "field": "ssid", "operator": "between", "value": "a-b" }])).is_err());
+        assert!(compile(json!([{ "field": "username", "operator": "matches", "value": "(" }])).is_err());
+        assert!(compile(json!([{ "field": "day_of_week", "operator": "in", "value": ["mon", "someday"] }])).is_err());
+        assert!(compile(json!([{ "field": "shoe_size", "value": "42" }])).is_err());
+        assert!(compile_rule("bad", 1, json!([]), "maybe", String::new(), json!([]), String::new()).is_err());
+
+        let weekend = compile(json!([{ "field": "day_of_week", "operator": "not_in", "value": ["sat", "sun"] }])).unwrap();
+        // 2026-03-07 is a Saturday
+        assert!(!weekend.conditions[0].matches(&context("2026-03-07 12:00")));
+        assert!(weekend.conditions[0].matches(&context("2026-03-09 12:00")));
+    }
+
+    #[test]
+    fn test_ssid_after_either_mac_format() {
+        let rule = compile_rule("guests", 1, json!([{ "field": "ssid", "value": "Guest:5G" }]),
+                                "accept", String::new(), json!([]), String::new()).unwrap();
+        let mut context = context("2026-03-02 10:00");
+        for called in ["00-11-22-33-44-55:Guest:5G", "00:11:22:33:44:55:Guest:5G", "ap-1:Guest:5G"] {
+            context.called_station_id = Some(called.to_string());
+            assert!(rule.conditions[0].matches(&context), "{}", called);
+        }
+        assert_eq!(ssid("00:11:22:33:44:55"), None);
+        assert_eq!(ssid("00:11-22:33:44:55:Guest"), Some("11-22:33:44:55:Guest"));
+    }
+}
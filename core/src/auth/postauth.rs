@@ -0,0 +1,302 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+use mongodb::{Client, Collection, IndexModel, bson::{doc, DateTime as BsonDateTime}, options::IndexOptions};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+
+use super::radius_server::{RadiusAttribute, RadiusPacket};
+use crate::config::EnvOverrides;
+
+const ATTR_USER_NAME: u8 = 1;
+const ATTR_USER_PASSWORD: u8 = 2;
+const ATTR_CHAP_PASSWORD: u8 = 3;
+const ATTR_NAS_IP_ADDRESS: u8 = 4;
+const ATTR_REPLY_MESSAGE: u8 = 18;
+const ATTR_VENDOR_SPECIFIC: u8 = 26;
+const ATTR_NAS_IDENTIFIER: u8 = 32;
+const ATTR_TUNNEL_PASSWORD: u8 = 69;
+const ATTR_MESSAGE_AUTHENTICATOR: u8 = 80;
+
+const VENDOR_MICROSOFT: u32 = 311;
+// MS-CHAP-NT-Enc-PW, MS-CHAP-MPPE-Keys, MS-MPPE-Send-Key, MS-MPPE-Recv-Key
+const MICROSOFT_SECRET_ATTRIBUTES: [u8; 4] = [6, 12, 16, 17];
+
+const MASKED_VALUE: &str = "********";
+// Records are dropped rather than slowing down authentication when Mongo falls behind
+const QUEUE_SIZE: usize = 10_000;
+
+fn default_enabled() -> bool { true }
+fn default_ttl_days() -> u64 { 30 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostAuthConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_ttl_days")]
+    pub ttl_days: u64,
+}
+
+impl Default for PostAuthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            ttl_days: default_ttl_days(),
+        }
+    }
+}
+
+impl PostAuthConfig {
+    pub fn apply_env(&mut self, env: &mut EnvOverrides) {
+        env.set_bool("POSTAUTH_ENABLED", &mut self.enabled);
+        env.set("POSTAUTH_TTL_DAYS", &mut self.ttl_days);
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PostAuthAttribute {
+    #[serde(rename = "type")]
+    pub typ: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vendor_id: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vendor_type: Option<u8>,
+    /// RFC 2868 tag of tunnel attributes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<u8>,
+    pub value: String,
+}
+
+/// One authentication decision as stored in the `postauth` collection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostAuthRecord {
+    pub timestamp: BsonDateTime,
+    pub nas: Option<String>,
+    pub source_ip: String,
+    pub username: Option<String>,
+    pub identifier_id: Option<i64>,
+    pub auth_method: String,
+    pub result: String,
+    pub reject_reason: Option<String>,
+    pub reply_attributes: Vec<PostAuthAttribute>,
+}
+
+impl PostAuthRecord {
+    /// Builds a record from an Access-Request and the reply sent for it
+    pub fn from_exchange(request: &RadiusPacket, src: SocketAddr, auth_method: &str, response: &[u8]) -> Self {
+        let username = request.attributes.iter()
+            .find(|attr| attr.typ == ATTR_USER_NAME)
+            .map(|attr| String::from_utf8_lossy(&attr.value).to_string());
+
+        let nas = request.attributes.iter()
+            .find(|attr| attr.typ == ATTR_NAS_IDENTIFIER)
+            .map(|attr| String::from_utf8_lossy(&attr.value).to_string())
+            .or_else(|| {
+                request.attributes.iter()
+                    .find(|attr| attr.typ == ATTR_NAS_IP_ADDRESS && attr.value.len() == 4)
+                    .map(|attr| format!("{}.{}.{}.{}", attr.value[0], attr.value[1], attr.value[2], attr.value[3]))
+            });
+
+        let reply = RadiusPacket::parse(response).ok();
+        let code = reply.as_ref().map(|packet| packet.code).unwrap_or(0);
+        let result = match code {
+            2 => "Access-Accept".to_string(),
+            3 => "Access-Reject".to_string(),
+            11 => "Access-Challenge".to_string(),
+            other => format!("Code-{}", other),
+        };
+
+        let reply_attributes: Vec<PostAuthAttribute> = reply.as_ref()
+            .map(|packet| packet.attributes.iter().map(|attr| describe_attribute(attr.typ, &attr.value)).collect())
+            .unwrap_or_default();
+
+        // Rejects carry their reason in Reply-Message
+        let reject_reason = if code == 3 {
+            reply_attributes.iter()
+                .filter(|attr| attr.typ == ATTR_REPLY_MESSAGE)
+                .map(|attr| attr.value.clone())
+                .next()
+        } else {
+            None
+        };
+
+        Self {
+            timestamp: BsonDateTime::now(),
+            nas,
+            source_ip: src.ip().to_string(),
+            username,
+            identifier_id: None,
+            auth_method: auth_method.to_string(),
+            result,
+            reject_reason,
+            reply_attributes,
+        }
+    }
+}
+
+/// Printable form of an attribute, with passwords and keys masked
+pub(super) fn describe_attribute(typ: u8, value: &[u8]) -> PostAuthAttribute {
+    if typ == ATTR_VENDOR_SPECIFIC && value.len() >= 6 {
+        let vendor_id = u32::from_be_bytes([value[0], value[1], value[2], value[3]]);
+        let vendor_type = value[4];
+        let masked = vendor_id == VENDOR_MICROSOFT && MICROSOFT_SECRET_ATTRIBUTES.contains(&vendor_type);
+        return PostAuthAttribute {
+            typ,
+            vendor_id: Some(vendor_id),
+            vendor_type: Some(vendor_type),
+            tag: None,
+            value: if masked { MASKED_VALUE.to_string() } else { display_value(&value[6..]) },
+        };
+    }
+
+    let masked = matches!(typ, ATTR_USER_PASSWORD | ATTR_CHAP_PASSWORD | ATTR_TUNNEL_PASSWORD | ATTR_MESSAGE_AUTHENTICATOR);
+    let (tag, value) = match (RadiusAttribute { typ, value: value.to_vec() }).tagged_value() {
+        Some((tag, value)) => (Some(tag), value),
+        None => (None, value.to_vec()),
+    };
+    PostAuthAttribute {
+        typ,
+        vendor_id: None,
+        vendor_type: None,
+        tag,
+        value: if masked { MASKED_VALUE.to_string() } else { display_value(&value) },
+    }
+}
+
+fn display_value(value: &[u8]) -> String {
+    if !value.is_empty() && value.iter().all(|b| (0x20..0x7f).contains(b)) {
+        String::from_utf8_lossy(value).to_string()
+    } else if value.len() == 4 {
+        u32::from_be_bytes([value[0], value[1], value[2], value[3]]).to_string()
+    } else {
+        format!("0x{}", hex::encode(value))
+    }
+}
+
+/// Queues post-auth records and writes them to MongoDB from a background task
+pub struct PostAuthLogger {
+    sender: Option<mpsc::Sender<PostAuthRecord>>,
+}
+
+impl PostAuthLogger {
+    pub async fn new(config: &PostAuthConfig, mongo_url: &str, mongo_db_name: &str, db_pool: PgPool)
+        -> Result<Self, Box<dyn std::error::Error>> {
+        if !config.enabled {
+            info!("Post-auth logging disabled");
+            return Ok(Self { sender: None });
+        }
+
+        let client = Client::with_uri_str(mongo_url).await?;
+        let collection = client.database(mongo_db_name).collection::<PostAuthRecord>("postauth");
+        let (sender, receiver) = mpsc::channel(QUEUE_SIZE);
+        tokio::spawn(write_records(collection, receiver, db_pool, config.ttl_days));
+
+        info!("Post-auth logging to {}.postauth (TTL {} days)", mongo_db_name, config.ttl_days);
+        Ok(Self { sender: Some(sender) })
+    }
+
+    pub fn log(&self, record: PostAuthRecord) {
+        if let Some(sender) = &self.sender
+            && let Err(e) = sender.try_send(record)
+        {
+            warn!("Dropping post-auth record: {}", e);
+        }
+    }
+}
+
+async fn write_records(collection: Collection<PostAuthRecord>, mut receiver: mpsc::Receiver<PostAuthRecord>,
+                       db_pool: PgPool, ttl_days: u64) {
+    let ttl_index = IndexModel::builder()
+        .keys(doc! { "timestamp": 1 })
+        .options(IndexOptions::builder()
+            .name("postauth_ttl".to_string())
+            .expire_after(Duration::from_secs(ttl_days * 86_400))
+            .build())
+        .build();
+    let username_index = IndexModel::builder()
+        .keys(doc! { "username": 1, "timestamp": -1 })
+        .build();
+    if let Err(e) = collection.create_indexes([ttl_index, username_index]).await {
+        warn!("Failed to create post-auth indexes: {}", e);
+    }
+
+    while let Some(mut record) = receiver.recv().await {
+        if let Some(username) = &record.username {
+            record.identifier_id = sqlx::query_scalar::<_, i64>(
+                "SELECT id FROM user_identifiers WHERE value = $1 AND identifier_type_id = 1"
+            )
+            .bind(username)
+            .fetch_optional(&db_pool)
+            .await
+            .unwrap_or_else(|e| {
+                warn!("Failed to resolve identifier for post-auth record: {}", e);
+                None
+            });
+        }
+
+        if let Err(e) = collection.insert_one(&record).await {
+            error!("Failed to write post-auth record: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_masks_secrets() {
+        let request = RadiusPacket {
+            code: 1,
+            identifier: 1,
+            length: 0,
+            authenticator: [0u8; 16],
+            attributes: vec![
+                RadiusAttribute { typ: ATTR_USER_NAME, value: b"alice".to_vec() },
+                RadiusAttribute { typ: ATTR_USER_PASSWORD, value: vec![1u8; 16] },
+                RadiusAttribute { typ: ATTR_NAS_IDENTIFIER, value: b"edge-1".to_vec() },
+            ],
+        };
+
+        let mut mppe_key = VENDOR_MICROSOFT.to_be_bytes().to_vec();
+        mppe_key.extend_from_slice(&[16, 20, 0x80, 0x01]);
+        mppe_key.extend_from_slice(&[0xAA; 16]);
+        let response = RadiusPacket {
+            code: 2,
+            identifier: 1,
+            length: 0,
+            authenticator: [0u8; 16],
+            attributes: vec![
+                RadiusAttribute { typ: 27, value: 3600u32.to_be_bytes().to_vec() },
+                RadiusAttribute { typ: ATTR_VENDOR_SPECIFIC, value: mppe_key },
+                RadiusAttribute { typ: ATTR_MESSAGE_AUTHENTICATOR, value: vec![0x55; 16] },
+            ],
+        }.encode();
+
+        let record = PostAuthRecord::from_exchange(&request, "10.0.0.1:5000".parse().unwrap(), "PAP", &response);
+        assert_eq!(record.username.as_deref(), Some("alice"));
+        assert_eq!(record.nas.as_deref(), Some("edge-1"));
+        assert_eq!(record.result, "Access-Accept");
+        assert_eq!(record.reply_attributes[0].value, "3600");
+        assert_eq!(record.reply_attributes[1].value, MASKED_VALUE);
+        assert_eq!(record.reply_attributes[2].value, MASKED_VALUE);
+    }
+
+    #[test]
+    fn test_record_reject_reason() {
+        let request = RadiusPacket { code: 1, identifier: 2, length: 0, authenticator: [0u8; 16], attributes: Vec::new() };
+        let response = RadiusPacket {
+            code: 3,
+            identifier: 2,
+            length: 0,
+            authenticator: [0u8; 16],
+            attributes: vec![RadiusAttribute { typ: ATTR_REPLY_MESSAGE, value: b"Invalid password".to_vec() }],
+        }.encode();
+
+        let record = PostAuthRecord::from_exchange(&request, "10.0.0.1:5000".parse().unwrap(), "PAP", &response);
+        assert_eq!(record.result, "Access-Reject");
+        assert_eq!(record.reject_reason.as_deref(), Some("Invalid password"));
+        assert!(record.username.is_none());
+    }
+}
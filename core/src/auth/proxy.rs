@@ -0,0 +1,511 @@
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU32, AtomicU8, Ordering};
+use std::time::{Duration, Instant};
+use md5::{Digest, Md5};
+use rand::RngCore;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tokio::net::UdpSocket;
+use tracing::{debug, info, warn};
+
+use super::crypto::{hide_password, salt_decrypt, salt_encrypt, unhide_password};
+use super::radius_server::{RadiusAttribute, RadiusPacket};
+use super::reply::{encode_response, message_authenticator_offset, sign_message_authenticator};
+use crate::config::EnvOverrides;
+
+const ATTR_USER_NAME: u8 = 1;
+const ATTR_USER_PASSWORD: u8 = 2;
+const ATTR_CHAP_PASSWORD: u8 = 3;
+const ATTR_VENDOR_SPECIFIC: u8 = 26;
+const ATTR_PROXY_STATE: u8 = 33;
+const ATTR_CHAP_CHALLENGE: u8 = 60;
+const ATTR_TUNNEL_PASSWORD: u8 = 69;
+const ATTR_MESSAGE_AUTHENTICATOR: u8 = 80;
+
+const VENDOR_MICROSOFT: u32 = 311;
+const VENDOR_ATTR_MS_MPPE_SEND_KEY: u8 = 16;
+const VENDOR_ATTR_MS_MPPE_RECV_KEY: u8 = 17;
+
+// How long a home server that stopped answering is skipped before being tried again
+const HOME_SERVER_DEAD_TIME: Duration = Duration::from_secs(30);
+
+fn default_timeout_ms() -> u64 { 3000 }
+fn default_retries() -> u32 { 2 }
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProxyConfig {
+    pub realms: Vec<RealmConfig>,
+}
+
+/// A realm routed to upstream RADIUS servers, matched either by the part of the
+/// User-Name after '@' or by a regular expression over the whole User-Name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RealmConfig {
+    #[serde(default)]
+    pub realm: Option<String>,
+    #[serde(default)]
+    pub pattern: Option<String>,
+    #[serde(default)]
+    pub strip_realm: bool,
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+    pub home_servers: Vec<HomeServerConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HomeServerConfig {
+    pub address: String,
+    pub secret: String,
+}
+
+impl ProxyConfig {
+    /// Replaces the realms with those in the JSON file named by PROXY_REALMS_FILE, if any
+    pub fn apply_env(&mut self, env: &mut EnvOverrides) {
+        let Some(path) = env.get("PROXY_REALMS_FILE").filter(|path| !path.is_empty()) else {
+            return;
+        };
+        info!("Loading proxy realms from file: {}", path);
+        let loaded = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| serde_json::from_str::<Self>(&contents).map_err(|e| e.to_string()));
+        match loaded {
+            Ok(config) => *self = config,
+            Err(e) => env.problem(format!("PROXY_REALMS_FILE: {}: {}", path, e)),
+        }
+    }
+}
+
+struct HomeServer {
+    address: SocketAddr,
+    secret: String,
+    dead_until: std::sync::Mutex<Option<Instant>>,
+}
+
+impl HomeServer {
+    fn is_alive(&self) -> bool {
+        self.dead_until.lock().unwrap().is_none_or(|until| until <= Instant::now())
+    }
+
+    fn mark_dead(&self) {
+        *self.dead_until.lock().unwrap() = Some(Instant::now() + HOME_SERVER_DEAD_TIME);
+    }
+
+    fn mark_alive(&self) {
+        *self.dead_until.lock().unwrap() = None;
+    }
+}
+
+pub struct Realm {
+    name: String,
+    realm: Option<String>,
+    pattern: Option<Regex>,
+    strip_realm: bool,
+    timeout: Duration,
+    retries: u32,
+    home_servers: Vec<HomeServer>,
+}
+
+impl Realm {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn matches(&self, username: &str) -> bool {
+        if let Some(realm) = &self.realm
+            && let Some((_, user_realm)) = username.rsplit_once('@')
+            && user_realm.eq_ignore_ascii_case(realm)
+        {
+            return true;
+        }
+        self.pattern.as_ref().is_some_and(|pattern| pattern.is_match(username))
+    }
+}
+
+pub struct RadiusProxy {
+    realms: Vec<Realm>,
+    next_identifier: AtomicU8,
+    next_state: AtomicU32,
+}
+
+impl RadiusProxy {
+    pub fn new(config: &ProxyConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut realms = Vec::new();
+        for realm in &config.realms {
+            if realm.realm.is_none() && realm.pattern.is_none() {
+                return Err("Proxy realm needs either a realm name or a pattern".into());
+            }
+            if realm.home_servers.is_empty() {
+                return Err(format!("Proxy realm {:?} has no home servers", realm.realm.as_ref().or(realm.pattern.as_ref())).into());
+            }
+
+            let mut home_servers = Vec::new();
+            for server in &realm.home_servers {
+                home_servers.push(HomeServer {
+                    address: server.address.parse()?,
+                    secret: server.secret.clone(),
+                    dead_until: std::sync::Mutex::new(None),
+                });
+            }
+
+            let name = realm.realm.clone().or(realm.pattern.clone()).unwrap_or_default();
+            debug!("Configured proxy realm {} with {} home servers", name, home_servers.len());
+            realms.push(Realm {
+                name,
+                realm: realm.realm.clone(),
+                pattern: realm.pattern.as_deref().map(Regex::new).transpose()?,
+                strip_realm: realm.strip_realm,
+                timeout: Duration::from_millis(realm.timeout_ms),
+                retries: realm.retries,
+                home_servers,
+            });
+        }
+
+        Ok(Self {
+            realms,
+            next_identifier: AtomicU8::new(rand::random()),
+            next_state: AtomicU32::new(rand::random()),
+        })
+    }
+
+    /// Finds the first realm whose name or pattern matches the User-Name
+    pub fn route(&self, username: &str) -> Option<&Realm> {
+        self.realms.iter().find(|realm| realm.matches(username))
+    }
+
+    /// Forwards an Access-Request to the realm's home servers and returns the reply
+    /// re-signed for the NAS, or an error when no home server answered.
+    pub async fn forward(&self, realm: &Realm, request: &RadiusPacket, client_secret: &str)
+        -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let proxy_state = self.next_state.fetch_add(1, Ordering::Relaxed).to_be_bytes().to_vec();
+
+        // When every home server is marked dead, try them all rather than failing outright
+        let mut candidates: Vec<&HomeServer> = realm.home_servers.iter().filter(|server| server.is_alive()).collect();
+        if candidates.is_empty() {
+            candidates = realm.home_servers.iter().collect();
+        }
+
+        for server in candidates {
+            let identifier = self.next_identifier.fetch_add(1, Ordering::Relaxed);
+            let mut authenticator = [0u8; 16];
+            rand::thread_rng().fill_bytes(&mut authenticator);
+
+            let upstream = build_upstream_request(request, realm, client_secret, &server.secret,
+                                                  identifier, authenticator, &proxy_state);
+
+            match send_with_retransmit(&upstream, server.address, realm.timeout, realm.retries).await {
+                Ok(reply) => {
+                    if !verify_reply(&reply, identifier, &authenticator, &server.secret) {
+                        warn!("Discarding reply from home server {} with bad authenticator", server.address);
+                        server.mark_dead();
+                        continue;
+                    }
+                    server.mark_alive();
+                    debug!("Home server {} answered for realm {}", server.address, realm.name);
+                    return build_client_reply(&reply, request, client_secret, &server.secret,
+                                              &authenticator, &proxy_state);
+                }
+                Err(e) => {
+                    warn!("Home server {} for realm {} failed: {}", server.address, realm.name, e);
+                    server.mark_dead();
+                }
+            }
+        }
+
+        Err(format!("No home server available for realm {}", realm.name).into())
+    }
+}
+
+async fn send_with_retransmit(packet: &[u8], address: SocketAddr, timeout: Duration, retries: u32)
+    -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let bind_addr = if address.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+    let socket = UdpSocket::bind(bind_addr).await?;
+    socket.connect(address).await?;
+
+    let mut buf = vec![0u8; 4096];
+    for attempt in 0..=retries {
+        if attempt > 0 {
+            debug!("Retransmitting to home server {} (attempt {})", address, attempt + 1);
+        }
+        socket.send(packet).await?;
+
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            match tokio::time::timeout_at(deadline, socket.recv(&mut buf)).await {
+                // Ignore stray datagrams that don't answer this request
+                Ok(Ok(len)) if len >= 20 && buf[1] == packet[1] => return Ok(buf[..len].to_vec()),
+                Ok(Ok(_)) => continue,
+                Ok(Err(e)) => return Err(e.into()),
+                Err(_) => break,
+            }
+        }
+    }
+
+    Err("timed out".into())
+}
+
+fn build_upstream_request(request: &RadiusPacket, realm: &Realm, client_secret: &str, home_secret: &str,
+                          identifier: u8, authenticator: [u8; 16], proxy_state: &[u8]) -> Vec<u8> {
+    let mut attributes = Vec::new();
+    let mut has_msg_auth = false;
+    let mut has_chap_challenge = false;
+
+    for attr in &request.attributes {
+        match attr.typ {
+            ATTR_USER_NAME if realm.strip_realm => {
+                let username = String::from_utf8_lossy(&attr.value);
+                let stripped = username.rsplit_once('@').map(|(user, _)| user).unwrap_or(&username);
+                attributes.push(RadiusAttribute { typ: ATTR_USER_NAME, value: stripped.as_bytes().to_vec() });
+            }
+            ATTR_USER_PASSWORD => {
+                let password = unhide_password(&attr.value, &request.authenticator, client_secret);
+                attributes.push(RadiusAttribute {
+                    typ: ATTR_USER_PASSWORD,
+                    value: hide_password(&password, &authenticator, home_secret),
+                });
+            }
+            ATTR_MESSAGE_AUTHENTICATOR => has_msg_auth = true,
+            ATTR_CHAP_CHALLENGE => {
+                has_chap_challenge = true;
+                attributes.push(attr.clone());
+            }
+            _ => attributes.push(attr.clone()),
+        }
+    }
+
+    // Without CHAP-Challenge the NAS used its Request Authenticator as the challenge,
+    // which the home server can only see if we pass it along explicitly
+    if !has_chap_challenge && attributes.iter().any(|attr| attr.typ == ATTR_CHAP_PASSWORD) {
+        attributes.push(RadiusAttribute { typ: ATTR_CHAP_CHALLENGE, value: request.authenticator.to_vec() });
+    }
+
+    attributes.push(RadiusAttribute { typ: ATTR_PROXY_STATE, value: proxy_state.to_vec() });
+    if has_msg_auth {
+        attributes.push(RadiusAttribute { typ: ATTR_MESSAGE_AUTHENTICATOR, value: vec![0u8; 16] });
+    }
+
+    let packet = RadiusPacket {
+        code: request.code,
+        identifier,
+        length: 0,
+        authenticator,
+        attributes,
+    };
+    let mut encoded = packet.encode();
+    if has_msg_auth {
+        sign_message_authenticator(&mut encoded, home_secret);
+    }
+    encoded
+}
+
+fn verify_reply(reply: &[u8], identifier: u8, request_authenticator: &[u8; 16], secret: &str) -> bool {
+    if reply[1] != identifier {
+        return false;
+    }
+    let mut hasher = Md5::new();
+    hasher.update(&reply[0..4]);
+    hasher.update(request_authenticator);
+    hasher.update(&reply[20..]);
+    hasher.update(secret.as_bytes());
+    if hasher.finalize().as_slice() != &reply[4..20] {
+        return false;
+    }
+
+    // A Message-Authenticator, when present, is computed over the request authenticator (RFC 3579, section 3.2)
+    let Some(pos) = message_authenticator_offset(reply) else {
+        return RadiusPacket::parse(reply)
+            .is_ok_and(|packet| packet.attributes.iter().all(|attr| attr.typ != ATTR_MESSAGE_AUTHENTICATOR));
+    };
+    let mut signed = reply.to_vec();
+    signed[4..20].copy_from_slice(request_authenticator);
+    sign_message_authenticator(&mut signed, secret);
+    signed[pos + 2..pos + 18] == reply[pos + 2..pos + 18]
+}
+
+fn build_client_reply(reply: &[u8], request: &RadiusPacket, client_secret: &str, home_secret: &str,
+                      upstream_authenticator: &[u8; 16], proxy_state: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let reply = RadiusPacket::parse(reply).map_err(|e| format!("Malformed reply from home server: {}", e))?;
+    let mut attributes = Vec::new();
+    let mut has_msg_auth = false;
+    let mut stripped_state = false;
+
+    for attr in reply.attributes {
+        match attr.typ {
+            ATTR_PROXY_STATE if !stripped_state && attr.value == proxy_state => stripped_state = true,
+            ATTR_MESSAGE_AUTHENTICATOR => has_msg_auth = true,
+            ATTR_VENDOR_SPECIFIC if is_mppe_key(&attr.value) => {
+                // MS-MPPE keys are salt-encrypted with the secret and the upstream authenticator
+                let mut value = attr.value.clone();
+                let key = salt_decrypt(&value[8..], upstream_authenticator, home_secret, &value[6..8]);
+                let encrypted = salt_encrypt(&key, &request.authenticator, client_secret, &value[6..8]);
+                value.truncate(8);
+                value.extend_from_slice(&encrypted);
+                value[5] = (value.len() - 4) as u8;
+                attributes.push(RadiusAttribute { typ: attr.typ, value });
+            }
+            ATTR_TUNNEL_PASSWORD if attr.value.len() > 3 => {
+                // Tag, salt, then the password salt-encrypted like the MS-MPPE keys
+                let mut value = attr.value.clone();
+                let password = salt_decrypt(&value[3..], upstream_authenticator, home_secret, &value[1..3]);
+                let encrypted = salt_encrypt(&password, &request.authenticator, client_secret, &value[1..3]);
+                value.truncate(3);
+                value.extend_from_slice(&encrypted);
+                attributes.push(RadiusAttribute { typ: attr.typ, value });
+            }
+            _ => attributes.push(attr),
+        }
+    }
+
+    if has_msg_auth {
+        attributes.push(RadiusAttribute { typ: ATTR_MESSAGE_AUTHENTICATOR, value: vec![0u8; 16] });
+    }
+
+    let packet = RadiusPacket {
+        code: reply.code,
+        identifier: request.identifier,
+        length: 0,
+        authenticator: request.authenticator,
+        attributes,
+    };
+    Ok(encode_response(packet, request, client_secret))
+}
+
+fn is_mppe_key(value: &[u8]) -> bool {
+    value.len() > 8
+        && u32::from_be_bytes([value[0], value[1], value[2], value[3]]) == VENDOR_MICROSOFT
+        && matches!(value[4], VENDOR_ATTR_MS_MPPE_SEND_KEY | VENDOR_ATTR_MS_MPPE_RECV_KEY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn realm_config(address: &str) -> ProxyConfig {
+        ProxyConfig {
+            realms: vec![RealmConfig {
+                realm: Some("partner.example".to_string()),
+                pattern: None,
+                strip_realm: true,
+                timeout_ms: 500,
+                retries: 1,
+                home_servers: vec![HomeServerConfig { address: address.to_string(), secret: "home-secret".to_string() }],
+            }],
+        }
+    }
+
+    #[test]
+    fn test_route_by_realm_and_pattern() {
+        let mut config = realm_config("127.0.0.1:1812");
+        config.realms.push(RealmConfig {
+            realm: None,
+            pattern: Some(r"^host/.*\.corp$".to_string()),
+            strip_realm: false,
+            timeout_ms: 500,
+            retries: 0,
+            home_servers: vec![HomeServerConfig { address: "127.0.0.1:1812".to_string(), secret: "s".to_string() }],
+        });
+        let proxy = RadiusProxy::new(&config).unwrap();
+
+        assert_eq!(proxy.route("alice@Partner.Example").map(Realm::name), Some("partner.example"));
+        assert_eq!(proxy.route("host/laptop.corp").map(Realm::name), Some(r"^host/.*\.corp$"));
+        assert!(proxy.route("alice@local.example").is_none());
+        assert!(proxy.route("alice").is_none());
+    }
+
+    #[test]
+    fn test_password_hiding_roundtrip() {
+        let authenticator = [3u8; 16];
+        let hidden = hide_password(b"a fairly long password", &authenticator, "secret");
+        assert_eq!(hidden.len(), 32);
+        let plain = unhide_password(&hidden, &authenticator, "secret");
+        assert_eq!(&plain[..22], b"a fairly long password");
+        assert!(plain[22..].iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn test_verify_reply_message_authenticator() {
+        let request_authenticator = [5u8; 16];
+        let reply = |msg_auth: Option<[u8; 16]>| {
+            let mut attributes = vec![RadiusAttribute { typ: ATTR_USER_NAME, value: b"alice".to_vec() }];
+            if msg_auth.is_some() {
+                attributes.push(RadiusAttribute { typ: ATTR_MESSAGE_AUTHENTICATOR, value: vec![0u8; 16] });
+            }
+            let mut encoded = RadiusPacket {
+                code: 2,
+                identifier: 12,
+                length: 0,
+                authenticator: request_authenticator,
+                attributes,
+            }.encode();
+            sign_message_authenticator(&mut encoded, "home-secret");
+            if let Some(forged) = msg_auth.filter(|forged| *forged != [0u8; 16]) {
+                encoded[29..45].copy_from_slice(&forged);
+            }
+            let mut hasher = Md5::new();
+            hasher.update(&encoded);
+            hasher.update(b"home-secret");
+            let response_auth = hasher.finalize();
+            encoded[4..20].copy_from_slice(&response_auth);
+            encoded
+        };
+
+        assert!(verify_reply(&reply(None), 12, &request_authenticator, "home-secret"));
+        assert!(verify_reply(&reply(Some([0u8; 16])), 12, &request_authenticator, "home-secret"));
+        assert!(!verify_reply(&reply(Some([1u8; 16])), 12, &request_authenticator, "home-secret"));
+    }
+
+    #[tokio::test]
+    async fn test_forward_to_home_server() {
+        let home = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let proxy = RadiusProxy::new(&realm_config(&home.local_addr().unwrap().to_string())).unwrap();
+
+        tokio::spawn(async move {
+            let mut buf = vec![0u8; 4096];
+            let (len, peer) = home.recv_from(&mut buf).await.unwrap();
+            let request = RadiusPacket::parse(&buf[..len]).unwrap();
+
+            let username = request.attributes.iter().find(|a| a.typ == ATTR_USER_NAME).unwrap();
+            assert_eq!(username.value, b"alice");
+            let password = request.attributes.iter().find(|a| a.typ == ATTR_USER_PASSWORD).unwrap();
+            let plain = unhide_password(&password.value, &request.authenticator, "home-secret");
+            assert_eq!(&plain[..6], b"secret");
+
+            let proxy_state = request.attributes.iter().find(|a| a.typ == ATTR_PROXY_STATE).unwrap().clone();
+            let mut reply = RadiusPacket {
+                code: 2,
+                identifier: request.identifier,
+                length: 0,
+                authenticator: request.authenticator,
+                attributes: vec![proxy_state],
+            }.encode();
+            let mut hasher = Md5::new();
+            hasher.update(&reply);
+            hasher.update(b"home-secret");
+            let response_auth = hasher.finalize();
+            reply[4..20].copy_from_slice(&response_auth);
+            home.send_to(&reply, peer).await.unwrap();
+        });
+
+        let nas_authenticator = [9u8; 16];
+        let request = RadiusPacket {
+            code: 1,
+            identifier: 77,
+            length: 0,
+            authenticator: nas_authenticator,
+            attributes: vec![
+                RadiusAttribute { typ: ATTR_USER_NAME, value: b"alice@partner.example".to_vec() },
+                RadiusAttribute { typ: ATTR_USER_PASSWORD, value: hide_password(b"secret", &nas_authenticator, "nas-secret") },
+            ],
+        };
+
+        let realm = proxy.route("alice@partner.example").unwrap();
+        let reply = proxy.forward(realm, &request, "nas-secret").await.unwrap();
+
+        assert_eq!(reply[0], 2);
+        assert_eq!(reply[1], 77);
+        assert_eq!(reply.len(), 20, "our Proxy-State must be stripped");
+        assert!(verify_reply(&reply, 77, &nas_authenticator, "nas-secret"));
+    }
+}
@@ -1,10 +1,21 @@
-use crate::warn;
 use tokio::net::UdpSocket;
-use tracing::{info, debug, error};
+use tracing::{info, debug, error, warn};
 use std::sync::Arc;
 use std::fs::File;
 use std::io::BufReader;
-use crate::auth::AuthServer;
+use crate::auth::{AuthServer, BackendKind, Identity, LockoutTracker, PasswordStatus, PostAuthRecord};
+use crate::auth::backend::BackendError;
+use crate::auth::crypto;
+use crate::auth::reply::{self, AttributeEdit, AttributeGroup};
+use crate::auth::policy::{self, Field, PolicyContext, PolicyDecision, PolicyOutcome};
+use crate::auth::rest_hook::HookVerdict;
+use crate::auth::schedule::{self, AccessWindow, NasClock};
+use crate::auth::simultaneous_use;
+use crate::auth::device_admin::{self, AdminRequest};
+use crate::auth::ip_pool::{Allocation, Lease, LeaseRequest, PoolKind};
+use crate::auth::vendor::{self, ReplyIntents, VendorProfile};
+use crate::auth::vlan;
+use crate::status_server::{self, RadiusStats, CODE_ACCESS_ACCEPT, CODE_STATUS_SERVER};
 use hmac::{Hmac, Mac};
 use md5::{Md5};
 use hex;
@@ -20,10 +31,7 @@ type HmacMd5 = Hmac<Md5>; // alias for clarity
 const ATTR_USER_PASSWORD: u8 = 2;      // PAP
 const ATTR_CHAP_PASSWORD: u8 = 3;      // CHAP
 const VENDOR_MICROSOFT: u32 = 311;       // Microsoft's Vendor-ID
-const VENDOR_MIKROTIK: u32 = 14988;     // MikroTik's Vendor-ID
-const VENDOR_ATTR_MIKROTIK_GROUP: u8 = 3; // MikroTik-Group attribute type
 
-const VENDOR_ATTR_MS_CHAP_CHALLENGE: u8 = 11;  // Microsoft's MS-CHAP-Challenge
 const VENDOR_ATTR_MS_CHAP_RESPONSE: u8 = 1;    // Microsoft's MS-CHAP-Response
 
 const ATTR_VENDOR_SPECIFIC: u8 = 26;   // VSA attribute type
@@ -33,6 +41,9 @@ const ATTR_MS_CHAP2_RESPONSE: u8 = 25; // MS-CHAPv2
 const VENDOR_ATTR_MS_CHAP2_RESPONSE: u8 = 25;    // Microsoft's MS-CHAPv2-Response
 const VENDOR_ATTR_MS_CHAP2_CHALLENGE: u8 = 11;   // Microsoft's MS-CHAPv2-Challenge
 const VENDOR_ATTR_MS_CHAP2_SUCCESS: u8 = 26;     // Microsoft's MS-CHAP2-Success
+const VENDOR_ATTR_MS_CHAP2_CPW: u8 = 27;         // Microsoft's MS-CHAP2-CPW (change password)
+const VENDOR_ATTR_MS_CHAP_NT_ENC_PW: u8 = 6;     // Microsoft's MS-CHAP-NT-Enc-PW (new password fragments)
+const VENDOR_ATTR_MS_CHAP_ERROR: u8 = 2;         // Microsoft's MS-CHAP-Error
 const VENDOR_ATTR_MS_MPPE_ENCRYPTION_POLICY: u8 = 7;  // Microsoft's MS-MPPE-Encryption-Policy
 const VENDOR_ATTR_MS_MPPE_ENCRYPTION_TYPES: u8 = 8;   // Microsoft's MS-MPPE-Encryption-Types
 const VENDOR_ATTR_MS_CHAP_MPPE_KEYS: u8 = 12;         // Microsoft's MS-CHAP-MPPE-Keys (combined, old format)
@@ -41,24 +52,39 @@ const VENDOR_ATTR_MS_MPPE_RECV_KEY: u8 = 17;          // Microsoft's MS-MPPE-Rec
 
 const ATTR_USER_NAME: u8 = 1;
 const ATTR_NAS_IDENTIFIER: u8 = 32;  // NAS-Identifier attribute type
+const ATTR_CALLING_STATION_ID: u8 = 31;  // Calling-Station-Id attribute type
 
 const ATTR_REPLY_MESSAGE: u8 = 18;  // Reply-Message attribute type
-const ATTR_SERVICE_TYPE: u8 = 6;    // Service-Type attribute type
+pub(super) const ATTR_SERVICE_TYPE: u8 = 6;    // Service-Type attribute type
 const ATTR_SESSION_TIMEOUT: u8 = 27; // Session-Timeout attribute type
 const ATTR_ACCT_INTERIM_INTERVAL: u8 = 85; // Acct-Interim-Interval attribute type
 
+// RFC 2868 tunnel attributes that may carry a tag
+const ATTR_TUNNEL_TYPE: u8 = 64;               // Tunnel-Type (integer, always tagged)
+const ATTR_TUNNEL_MEDIUM_TYPE: u8 = 65;        // Tunnel-Medium-Type (integer, always tagged)
+const ATTR_TUNNEL_CLIENT_ENDPOINT: u8 = 66;    // Tunnel-Client-Endpoint
+const ATTR_TUNNEL_SERVER_ENDPOINT: u8 = 67;    // Tunnel-Server-Endpoint
+const ATTR_TUNNEL_PASSWORD: u8 = 69;           // Tunnel-Password (always tagged)
+const ATTR_TUNNEL_PRIVATE_GROUP_ID: u8 = 81;   // Tunnel-Private-Group-Id
+const ATTR_TUNNEL_ASSIGNMENT_ID: u8 = 82;      // Tunnel-Assignment-Id
+const ATTR_TUNNEL_PREFERENCE: u8 = 83;         // Tunnel-Preference (integer, always tagged)
+const ATTR_TUNNEL_CLIENT_AUTH_ID: u8 = 90;     // Tunnel-Client-Auth-Id
+const ATTR_TUNNEL_SERVER_AUTH_ID: u8 = 91;     // Tunnel-Server-Auth-Id
+// Tags group the attributes of one tunnel; 0x01-0x1F (RFC 2868, section 3)
+const MAX_TUNNEL_TAG: u8 = 0x1F;
+
 // Service-Type values
-const SERVICE_TYPE_LOGIN_USER: u32 = 1;          // Login-User
+pub(super) const SERVICE_TYPE_LOGIN_USER: u32 = 1;          // Login-User
 const SERVICE_TYPE_FRAMED_USER: u32 = 2;          // Framed-User
-const SERVICE_TYPE_CALLBACK_LOGIN_USER: u32 = 3;  // Callback-Login-User
+pub(super) const SERVICE_TYPE_CALLBACK_LOGIN_USER: u32 = 3;  // Callback-Login-User
 const SERVICE_TYPE_CALLBACK_FRAMED_USER: u32 = 4; // Callback-Framed-User
 const SERVICE_TYPE_OUTBOUND_USER: u32 = 5;         // Outbound-User
-const SERVICE_TYPE_ADMINISTRATIVE_USER: u32 = 6; // Administrative-User
-const SERVICE_TYPE_NAS_PROMPT_USER: u32 = 7;      // NAS-Prompt-User
+pub(super) const SERVICE_TYPE_ADMINISTRATIVE_USER: u32 = 6; // Administrative-User
+pub(super) const SERVICE_TYPE_NAS_PROMPT_USER: u32 = 7;      // NAS-Prompt-User
 const SERVICE_TYPE_AUTHENTICATE_ONLY: u32 = 8;   // Authenticate-Only
-const SERVICE_TYPE_CALLBACK_NAS_PROMPT: u32 = 9;  // Callback-NAS-Prompt
+pub(super) const SERVICE_TYPE_CALLBACK_NAS_PROMPT: u32 = 9;  // Callback-NAS-Prompt
 const SERVICE_TYPE_CALL_CHECK: u32 = 10;         // Call-Check
-const SERVICE_TYPE_CALLBACK_ADMINISTRATIVE: u32 = 11; // Callback-Administrative
+pub(super) const SERVICE_TYPE_CALLBACK_ADMINISTRATIVE: u32 = 11; // Callback-Administrative
 
 // EAP-related constants
 const ATTR_EAP_MESSAGE: u8 = 79;      // EAP-Message attribute
@@ -94,6 +120,38 @@ const EAP_RESPONSE: u8 = 2;
 const EAP_SUCCESS: u8 = 3;
 const EAP_FAILURE: u8 = 4;
 
+// MS-CHAP error codes (RFC 2759, section 6)
+const MSCHAP_ERROR_ACCOUNT_DISABLED: u32 = 647;
+const MSCHAP_ERROR_PASSWORD_EXPIRED: u32 = 648;
+const MSCHAP_ERROR_AUTHENTICATION_FAILURE: u32 = 691;
+const MSCHAP_ERROR_CHANGING_PASSWORD: u32 = 709;
+
+// Size of the NewPasswordEncryptedWithOldNtPasswordHash block (RFC 2759, section 8.9)
+const MSCHAP_PW_BLOCK_SIZE: usize = 516;
+
+/// Why received bytes are not a well-formed RADIUS or EAP packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    /// Fewer bytes than the fixed header
+    TooShort(usize),
+    /// The Length field is below the header size or beyond the bytes received
+    BadLength { length: u16, received: usize },
+    /// An attribute at this offset has a length below 2 or runs past the end of the packet
+    BadAttribute { offset: usize },
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::TooShort(len) => write!(f, "packet too short ({} bytes)", len),
+            ParseError::BadLength { length, received } => write!(f, "length field {} does not fit the {} bytes received", length, received),
+            ParseError::BadAttribute { offset } => write!(f, "malformed attribute at offset {}", offset),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
 #[derive(Debug, Clone)]
 pub struct RadiusAttribute {
     pub typ: u8,
@@ -103,9 +161,11 @@ pub struct RadiusAttribute {
 #[derive(Debug)]
 pub enum AuthResult {
     Success,
+    SuccessWithWarning(String),
     UserNotFound,
     InvalidPassword,
     AccountDisabled,
+    PasswordExpired,
     DatabaseError(sqlx::Error),
 }
 
@@ -114,6 +174,65 @@ pub struct Mschapv2Result {
     pub authenticator_response: Option<Vec<u8>>,
     pub password_hash: Option<Vec<u8>>,
 }
+
+/// How the credentials of an Access-Request fared; only failures here count towards a lockout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CredentialCheck {
+    /// Rejected before the password was checked: malformed, disabled or a backend error
+    NotChecked,
+    Passed,
+    /// Unknown user or wrong password
+    Failed,
+}
+
+impl CredentialCheck {
+    fn of<E>(result: Result<&AuthResult, E>) -> Self {
+        match result {
+            // An expired password was still the right one
+            Ok(AuthResult::Success | AuthResult::SuccessWithWarning(_) | AuthResult::PasswordExpired) => Self::Passed,
+            Ok(AuthResult::UserNotFound | AuthResult::InvalidPassword) => Self::Failed,
+            Ok(AuthResult::AccountDisabled | AuthResult::DatabaseError(_)) | Err(_) => Self::NotChecked,
+        }
+    }
+}
+
+/// What goes into the Access-Accept of a successful MS-CHAPv2 exchange
+struct MsChap2Success<'a> {
+    /// Ident of the MS-CHAP2-Response or MS-CHAP2-CPW being answered
+    ident: u8,
+    authenticator_response: &'a [u8],
+    /// NT password hash and NT-Response the MPPE keys are derived from
+    password_hash: &'a [u8],
+    nt_response: &'a [u8],
+    /// Replaces the default Reply-Message, e.g. with a password expiry warning
+    reply_message: Option<&'a str>,
+}
+
+/// Contents of an MS-CHAP2-CPW attribute (RFC 2548, section 2.3.2)
+#[derive(Debug, Clone)]
+pub struct MsChap2ChangePassword {
+    pub identifier: u8,
+    pub encrypted_hash: Vec<u8>,
+    pub peer_challenge: Vec<u8>,
+    pub nt_response: Vec<u8>,
+}
+
+impl MsChap2ChangePassword {
+    pub fn parse(vendor_data: &[u8]) -> Option<Self> {
+        // Code (1) + Ident (1) + Encrypted-Hash (16) + Peer-Challenge (16)
+        // + Reserved (8) + NT-Response (24) + Flags (2)
+        if vendor_data.len() < 68 || vendor_data[0] != 7 {
+            return None;
+        }
+        Some(Self {
+            identifier: vendor_data[1],
+            encrypted_hash: vendor_data[2..18].to_vec(),
+            peer_challenge: vendor_data[18..34].to_vec(),
+            nt_response: vendor_data[42..66].to_vec(),
+        })
+    }
+}
+
 impl RadiusAttribute {
     pub fn parse(data: &[u8]) -> Option<(Self, usize)> {
         if data.len() < 2 { return None; }
@@ -149,6 +268,49 @@ impl RadiusAttribute {
 
         out
     }
+
+    /// Whether `typ` is an RFC 2868 attribute with a tag field
+    pub fn is_tagged_type(typ: u8) -> bool {
+        matches!(typ, ATTR_TUNNEL_TYPE | ATTR_TUNNEL_MEDIUM_TYPE | ATTR_TUNNEL_CLIENT_ENDPOINT | ATTR_TUNNEL_SERVER_ENDPOINT
+            | ATTR_TUNNEL_PASSWORD | ATTR_TUNNEL_PRIVATE_GROUP_ID | ATTR_TUNNEL_ASSIGNMENT_ID | ATTR_TUNNEL_PREFERENCE
+            | ATTR_TUNNEL_CLIENT_AUTH_ID | ATTR_TUNNEL_SERVER_AUTH_ID)
+    }
+
+    /// Builds a tagged tunnel attribute. Integer attributes give up their high octet to the tag,
+    /// so `value` must be a 32-bit integer below 2^24; string attributes get the tag as a prefix.
+    pub fn tagged(typ: u8, tag: u8, value: &[u8]) -> Option<Self> {
+        if !Self::is_tagged_type(typ) || tag > MAX_TUNNEL_TAG {
+            return None;
+        }
+        let value = match typ {
+            ATTR_TUNNEL_TYPE | ATTR_TUNNEL_MEDIUM_TYPE | ATTR_TUNNEL_PREFERENCE => {
+                if value.len() != 4 || value[0] != 0 {
+                    return None;
+                }
+                [&[tag], &value[1..]].concat()
+            }
+            // An untagged string only needs the tag octet when its first byte would look like one
+            _ if tag == 0 && value.first().is_some_and(|first| *first > MAX_TUNNEL_TAG) && typ != ATTR_TUNNEL_PASSWORD => value.to_vec(),
+            _ => [&[tag], value].concat(),
+        };
+        (value.len() <= 253).then_some(Self { typ, value })
+    }
+
+    /// Tag and value of a tunnel attribute, with integer values widened back to 32 bits.
+    /// None for attributes without a tag field.
+    pub fn tagged_value(&self) -> Option<(u8, Vec<u8>)> {
+        match self.typ {
+            ATTR_TUNNEL_TYPE | ATTR_TUNNEL_MEDIUM_TYPE | ATTR_TUNNEL_PREFERENCE => {
+                (self.value.len() == 4).then(|| (self.value[0], [&[0], &self.value[1..]].concat()))
+            }
+            ATTR_TUNNEL_PASSWORD => self.value.split_first().map(|(tag, rest)| (*tag, rest.to_vec())),
+            typ if Self::is_tagged_type(typ) => match self.value.first() {
+                Some(first) if *first <= MAX_TUNNEL_TAG => Some((*first, self.value[1..].to_vec())),
+                _ => Some((0, self.value.clone())),
+            },
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -161,25 +323,28 @@ pub struct RadiusPacket {
 }
 
 impl RadiusPacket {
-    pub fn parse(data: &[u8]) -> Option<Self> {
-        if data.len() < 20 { return None; }
+    /// Parses a packet, ignoring any bytes past its Length field (RFC 2865, section 3)
+    pub fn parse(data: &[u8]) -> Result<Self, ParseError> {
+        if data.len() < 20 {
+            return Err(ParseError::TooShort(data.len()));
+        }
         let code = data[0];
         let identifier = data[1];
         let length = u16::from_be_bytes([data[2], data[3]]);
-        if data.len() < length as usize { return None; }
+        if length < 20 || data.len() < length as usize {
+            return Err(ParseError::BadLength { length, received: data.len() });
+        }
+        let data = &data[..length as usize];
         let mut authenticator = [0u8; 16];
         authenticator.copy_from_slice(&data[4..20]);
         let mut pos = 20;
         let mut attributes = Vec::new();
-        while pos < length as usize {
-            if let Some((attr, used)) = RadiusAttribute::parse(&data[pos..]) {
-                attributes.push(attr);
-                pos += used;
-            } else {
-                break;
-            }
+        while pos < data.len() {
+            let (attr, used) = RadiusAttribute::parse(&data[pos..]).ok_or(ParseError::BadAttribute { offset: pos })?;
+            attributes.push(attr);
+            pos += used;
         }
-        Some(Self { code, identifier, length, authenticator, attributes })
+        Ok(Self { code, identifier, length, authenticator, attributes })
     }
     pub fn encode(&self) -> Vec<u8> {
         // Calculate total length first
@@ -243,11 +408,42 @@ impl RadiusPacket {
     }
 }
 
+/// What authorization decides about, or adds to, an Access-Accept
+struct Authorization {
+    attributes: Vec<RadiusAttribute>,
+    /// Tunnel-* attributes of the assigned VLAN, and the vendor attributes carrying the group's intents
+    intent_edits: Vec<AttributeEdit>,
+    /// Reply profile of the NAS's vendor, if it has one
+    vendor: Option<VendorProfile>,
+    /// Whether the user may have the management login the request asks for
+    device_admin: Result<(), &'static str>,
+    policy: PolicyOutcome,
+    window: AccessWindow,
+    /// Simultaneous-Use limit of the identity or its groups
+    max_sessions: Option<u32>,
+    /// Who to lease a Framed-IP-Address to, when IP pools are enabled
+    lease_request: Option<LeaseRequest>,
+}
+
+/// A home server's answer to a proxied Access-Request, handed back to the listener to send on
+struct ProxiedReply {
+    request: RadiusPacket,
+    src: std::net::SocketAddr,
+    secret: String,
+    nas_label: String,
+    /// The reply re-signed for the NAS, or why no home server gave one
+    result: Result<Vec<u8>, String>,
+}
+
 pub struct RadiusAuthServer {
     socket: UdpSocket,
     auth_server: Arc<AuthServer>,
     // Add connection tracking
     connections: Arc<tokio::sync::Mutex<std::collections::HashMap<String, std::time::Instant>>>,
+    stats: Arc<RadiusStats>,
+    // Proxied requests are forwarded in tasks of their own, so a slow home server doesn't hold up the listener
+    proxied_tx: tokio::sync::mpsc::UnboundedSender<ProxiedReply>,
+    proxied_rx: tokio::sync::Mutex<tokio::sync::mpsc::UnboundedReceiver<ProxiedReply>>,
 }
 
 impl RadiusAuthServer {
@@ -260,13 +456,22 @@ impl RadiusAuthServer {
         let local_addr = socket.local_addr()?;
         info!("RADIUS Auth server listening on {}", local_addr);
 
+        let (proxied_tx, proxied_rx) = tokio::sync::mpsc::unbounded_channel();
         Ok(Self {
             socket,
             auth_server,
             connections: Arc::new(tokio::sync::Mutex::new(std::collections::HashMap::new())),
+            stats: Arc::new(RadiusStats::default()),
+            proxied_tx,
+            proxied_rx: tokio::sync::Mutex::new(proxied_rx),
         })
     }
 
+    /// Address the server is bound to, with the actual port when bound to port 0
+    pub fn local_addr(&self) -> std::io::Result<std::net::SocketAddr> {
+        self.socket.local_addr()
+    }
+
     fn detect_auth_method(&self, packet: &RadiusPacket) -> String {
         // Check for EAP first
         if packet.attributes.iter().any(|attr| attr.typ == ATTR_EAP_MESSAGE) {
@@ -290,6 +495,16 @@ impl RadiusAuthServer {
             false
         });
 
+        // MS-CHAP2-CPW replaces MS-CHAP2-Response when the client changes its password
+        let has_ms_cpw = packet.attributes.iter().any(|attr| {
+            attr.typ == ATTR_VENDOR_SPECIFIC && attr.value.len() >= 6
+                && u32::from_be_bytes([attr.value[0], attr.value[1], attr.value[2], attr.value[3]]) == VENDOR_MICROSOFT
+                && attr.value[4] == VENDOR_ATTR_MS_CHAP2_CPW
+        });
+        if has_ms_cpw {
+            return "MS-CHAPv2-CPW".to_string();
+        }
+
         if has_ms_auth {
             // Now determine if it's MS-CHAP or MS-CHAPv2
             let is_v2 = packet.attributes.iter().any(|attr| {
@@ -324,59 +539,33 @@ impl RadiusAuthServer {
 
     async fn authenticate_user(&self, username: &str, password: Vec<u8>, authenticator: &[u8],
                                secret: &str
-    ) -> Result<AuthResult, sqlx::Error> {
-        let pool = self.auth_server.get_pool();
+    ) -> Result<AuthResult, BackendError> {
+        let backend = &self.auth_server.backend;
 
-        // Query the user_identifiers table
-        let result = sqlx::query!(
-            r#"
-            SELECT id, plain_password, is_enabled
-            FROM user_identifiers 
-            WHERE value = $1 AND identifier_type_id = 1
-            "#,
-            username
-        )
-        .fetch_optional(pool)
-        .await?;
+        match backend.lookup_identity(username).await? {
+            Some(identity) => {
+                debug!("User found: {} in {} backend", username, backend.name());
 
-        match result {
-            Some(record) => {
-                debug!("User found: {} with ID {}", username, record.id);
-
-                // First, check if an account is enabled - handle Option<bool>
-                if !record.is_enabled {  // If is_enabled is just a bool
+                if !identity.is_enabled {
                     return Ok(AuthResult::AccountDisabled);
                 }
 
-                // Then check the password
-                match &record.plain_password {
-                    Some(stored_pass) => {
-                        // Decode the RADIUS PAP password
-                        let decoded_password = match decode_pap_password(password, authenticator, secret) {
-                            Ok(decoded) => decoded,
-                            Err(e) => {
-                                debug!("Failed to decode PAP password: {}", e);
-                                return Ok(AuthResult::InvalidPassword);
-                            }
-                        };
-
-                        debug!("Comparing passwords for user {}: stored_length={}, decoded_length={}",
-                        username, stored_pass.len(), decoded_password.len());
-
-                        if *stored_pass == decoded_password {
-                            debug!("Password match successful for user: {}", username);
-                            Ok(AuthResult::Success)
-                        } else {
-                            debug!("Password mismatch for user: {}. Check PAP decoding", username);
-                            Ok(AuthResult::InvalidPassword)
-                        }
-                    },
-                    None => {
-                        debug!("No password stored for user: {}", username);
-                        Ok(AuthResult::InvalidPassword)
+                // Decode the RADIUS PAP password
+                let decoded_password = match crypto::decode_user_password(&password, authenticator, secret) {
+                    Ok(decoded) => decoded,
+                    Err(e) => {
+                        debug!("Failed to decode PAP password: {}", e);
+                        return Ok(AuthResult::InvalidPassword);
                     }
-                }
+                };
 
+                if backend.verify_pap(&identity, &decoded_password).await? {
+                    debug!("Password match successful for user: {}", username);
+                    Ok(self.check_password_age(&identity).await?)
+                } else {
+                    debug!("Password mismatch for user: {}", username);
+                    Ok(AuthResult::InvalidPassword)
+                }
             }
             None => {
                 debug!("User not found: {}", username);
@@ -387,6 +576,33 @@ impl RadiusAuthServer {
     }
 
     fn create_access_reject(&self, request: &RadiusPacket, secret: &str, reason: &str) -> Vec<u8> {
+        self.create_access_reject_with_attributes(request, secret, reason, Vec::new())
+    }
+
+    /// Access-Reject carrying an MS-CHAP-Error so MS-CHAPv2 clients can react to the failure
+    /// (retry, or change an expired password)
+    fn create_access_reject_mschap_error(&self, request: &RadiusPacket, secret: &str, ms_chap_ident: u8,
+                                         error_code: u32, reason: &str) -> Vec<u8> {
+        let error_value = mschap_error_value(error_code);
+        debug!("MS-CHAP-Error: ident={}, value={}", ms_chap_ident, error_value);
+
+        let data = [&[ms_chap_ident][..], error_value.as_bytes()].concat();
+        let vsa_value = [
+            &VENDOR_MICROSOFT.to_be_bytes()[..],
+            &[VENDOR_ATTR_MS_CHAP_ERROR, (2 + data.len()) as u8],
+            &data[..],
+        ].concat();
+
+        self.create_access_reject_with_attributes(request, secret, reason, vec![
+            RadiusAttribute {
+                typ: ATTR_VENDOR_SPECIFIC,
+                value: vsa_value,
+            },
+        ])
+    }
+
+    fn create_access_reject_with_attributes(&self, request: &RadiusPacket, secret: &str, reason: &str,
+                                            extra_attributes: Vec<RadiusAttribute>) -> Vec<u8> {
         use hmac::{Hmac, Mac};
         use md5::Md5;
         use digest::KeyInit;
@@ -403,6 +619,7 @@ impl RadiusAuthServer {
                 value: reply_msg,
             },
         ];
+        attributes.extend(extra_attributes);
 
         let has_msg_auth = request.attributes.iter().any(|attr| attr.typ == ATTR_MESSAGE_AUTHENTICATOR);
         if has_msg_auth {
@@ -423,7 +640,7 @@ impl RadiusAuthServer {
         let mut encoded = response.encode();
 
         if has_msg_auth {
-            if let Some(pos) = encoded.windows(2).position(|w| w[0] == ATTR_MESSAGE_AUTHENTICATOR && w[1] == 18) {
+            if let Some(pos) = reply::message_authenticator_offset(&encoded) {
                 let mut temp = encoded.clone();
                 for i in 0..16 {
                     temp[pos + 2 + i] = 0;
@@ -468,13 +685,25 @@ impl RadiusAuthServer {
         encoded
     }
 
-    async fn handle_packet(&self, data: &[u8], src: std::net::SocketAddr, secret: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    /// The response to send, or None when there is none to send now: the packet is silently
+    /// discarded, or proxied and answered once the home server replies
+    async fn handle_packet(&self, data: &[u8], src: std::net::SocketAddr, secret: &str)
+        -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
         // Parse the packet
         let packet = match RadiusPacket::parse(data) {
-            Some(p) => p,
-            None => return Err("Invalid packet format".into()),
+            Ok(p) => p,
+            Err(e) => {
+                RadiusStats::increment(&self.stats.auth_malformed);
+                return Err(format!("Invalid packet format: {}", e).into());
+            }
         };
 
+        // Status-Server (RFC 5997) is answered before any per-NAS processing
+        if packet.code == CODE_STATUS_SERVER {
+            debug!("Received Status-Server from {}", src);
+            return status_server::handle_status_server(data, secret, CODE_ACCESS_ACCEPT, &self.stats).map(Some);
+        }
+
         // Extract NAS-Identifier from packet
         let nas_identifier = packet.attributes.iter()
             .find(|attr| attr.typ == ATTR_NAS_IDENTIFIER)
@@ -493,6 +722,11 @@ impl RadiusAuthServer {
             debug!("No NAS-Identifier found in packet, falling back to IP-based matching");
         }
 
+        // Metrics are labelled by the NAS configured for the source address, never by what the packet claims
+        let nas_label = self.auth_server.find_nas_device_by_address(&src.ip().to_string())
+            .map(|device| device.name.clone())
+            .unwrap_or_else(|| src.ip().to_string());
+
         // Check for Message-Authenticator
         let mut has_msg_auth = false;
         let mut msg_auth_value = None;
@@ -522,20 +756,29 @@ impl RadiusAuthServer {
                 let is_valid = self.validate_message_authenticator(&packet, secret, auth_value);
                 if !is_valid {
                     error!("Invalid Message-Authenticator in packet from {}", src);
-                    return Err("Invalid Message-Authenticator".into());
+                    RadiusStats::increment(&self.stats.auth_invalid);
+                    crate::metrics::INVALID_MESSAGE_AUTHENTICATOR.with_label_values(&["auth", &nas_label]).inc();
+                    // RFC 3579, section 3.2: silently discarded, not rejected
+                    return Ok(None);
                 } else {
                     debug!("Message-Authenticator validation successful");
                 }
             }
         }
+        crate::metrics::REQUESTS.with_label_values(&["auth", &nas_label, &packet.code.to_string()]).inc();
 
         // Process the packet based on its code
         match packet.code {
             1 => { // Access-Request
-                Ok(self.handle_access_request(&packet, secret, msg_auth_value).await)
+                RadiusStats::increment(&self.stats.access_requests);
+                let Some(response) = self.handle_access_request(&packet, src, secret, msg_auth_value, &nas_label).await else {
+                    return Ok(None);
+                };
+                self.record_access_response(&packet, src, &nas_label, &response);
+                Ok(Some(response))
             }
             4 => { // Accounting-Request
-                Ok(self.create_accounting_response(&packet))
+                Ok(Some(self.create_accounting_response(&packet)))
             }
             _ => {
                 debug!("Unsupported packet code: {}", packet.code);
@@ -544,6 +787,46 @@ impl RadiusAuthServer {
         }
     }
 
+    /// Counts an Access-Request's outcome and logs it to the post-auth log
+    fn record_access_response(&self, packet: &RadiusPacket, src: std::net::SocketAddr, nas_label: &str, response: &[u8]) {
+        self.stats.record_auth_response(response[0]);
+        let auth_method = self.detect_auth_method(packet);
+        crate::metrics::AUTH_RESULTS
+            .with_label_values(&[nas_label, &auth_method, crate::metrics::result_name(response[0])])
+            .inc();
+        self.auth_server.postauth.log(PostAuthRecord::from_exchange(packet, src, &auth_method, response));
+    }
+
+    /// Forwards an Access-Request for a partner realm in a task of its own; the reply comes back
+    /// through `proxied_tx`
+    fn spawn_proxy(&self, packet: &RadiusPacket, src: std::net::SocketAddr, secret: &str, nas_label: &str, username: String) {
+        let auth_server = self.auth_server.clone();
+        let replies = self.proxied_tx.clone();
+        let (request, secret, nas_label) = (packet.clone(), secret.to_string(), nas_label.to_string());
+        tokio::spawn(async move {
+            let Some(realm) = auth_server.proxy.route(&username) else {
+                return;
+            };
+            debug!("Proxying Access-Request to realm {}", realm.name());
+            let result = auth_server.proxy.forward(realm, &request, &secret).await.map_err(|e| {
+                error!("Proxying to realm {} failed: {}", realm.name(), e);
+                e.to_string()
+            });
+            let _ = replies.send(ProxiedReply { request, src, secret, nas_label, result });
+        });
+    }
+
+    async fn send_proxied_reply(&self, proxied: ProxiedReply) {
+        let ProxiedReply { request, src, secret, nas_label, result } = proxied;
+        let response = result.unwrap_or_else(|_| self.create_access_reject(&request, &secret, "Proxy: no home server available"));
+        self.record_access_response(&request, src, &nas_label, &response);
+        if let Err(e) = self.socket.send_to(&response, src).await {
+            error!("Failed to send proxied reply: {}", e);
+        } else {
+            debug!("Successfully sent proxied reply to {}", src);
+        }
+    }
+
     fn validate_message_authenticator(&self, packet: &RadiusPacket, secret: &str, received_auth: &[u8]) -> bool {
         use hmac::{Hmac, Mac};
         use md5::Md5;
@@ -592,12 +875,20 @@ impl RadiusAuthServer {
 
     pub async fn run(&self) -> Result<(), Box<dyn std::error::Error>> {
         debug!("Starting RADIUS server loop");
+        let mut proxied_replies = self.proxied_rx.lock().await;
 
         loop {
             // Create a new buffer for each request to prevent data corruption
             let mut buf = vec![0u8; 4096];
             debug!("Waiting for incoming packets...");
-            match self.socket.recv_from(&mut buf).await {
+            let received = tokio::select! {
+                received = self.socket.recv_from(&mut buf) => received,
+                Some(proxied) = proxied_replies.recv() => {
+                    self.send_proxied_reply(proxied).await;
+                    continue;
+                }
+            };
+            match received {
                 Ok((size, src)) => {
                     debug!("Received {} bytes from {}", size, src);
 
@@ -605,7 +896,7 @@ impl RadiusAuthServer {
                     debug!("Source IP: {}", ip);
 
                     // Parse packet to extract NAS-Identifier
-                    let nas_identifier = if let Some(packet) = RadiusPacket::parse(&buf[..size]) {
+                    let nas_identifier = if let Ok(packet) = RadiusPacket::parse(&buf[..size]) {
                         packet.attributes.iter()
                             .find(|attr| attr.typ == ATTR_NAS_IDENTIFIER)
                             .and_then(|attr| String::from_utf8(attr.value.clone()).ok())
@@ -625,13 +916,18 @@ impl RadiusAuthServer {
                     };
 
                     if let Some(secret) = secret {
-                        debug!("Found secret for IP {}: {}", ip, secret);
+                        debug!("Found secret for IP {}", ip);
 
                         // Create a copy of the received data to ensure it's not modified by subsequent requests
                         let request_data = buf[..size].to_vec();
 
-                        match self.handle_packet(&request_data, src, secret).await {
-                            Ok(response) => {
+                        let latency_timer = crate::metrics::REQUEST_DURATION.with_label_values(&["auth"]).start_timer();
+                        let result = self.handle_packet(&request_data, src, secret).await;
+                        latency_timer.observe_duration();
+
+                        match result {
+                            Ok(None) => debug!("No immediate reply to {}", src),
+                            Ok(Some(response)) => {
                                 debug!("Response packet size: {} bytes", response.len());
                                 debug!("Response packet: {:?}", response);
 
@@ -655,8 +951,11 @@ impl RadiusAuthServer {
                             }
                             Err(e) => {
                                 error!("Error handling packet: {}", e);
-                                // Optionally send Access-Reject for certain errors
-                                if let Some(packet) = RadiusPacket::parse(&request_data) {
+                                // Only Access-Requests get a reject; anything else (e.g. an
+                                // unauthenticated Status-Server) is silently discarded
+                                if let Ok(packet) = RadiusPacket::parse(&request_data)
+                                    && packet.code == 1
+                                {
                                     let reject = self.create_access_reject(&packet, secret, &format!("Error: {}", e));
                                     debug!("Reject packet size: {} bytes", reject.len());
                                     debug!("Reject packet: {:?}", reject);
@@ -671,6 +970,8 @@ impl RadiusAuthServer {
                         }
                     } else {
                         error!("No NAS secret found for {}", ip);
+                        RadiusStats::increment(&self.stats.auth_dropped);
+                        crate::metrics::UNKNOWN_CLIENT_DROPS.with_label_values(&["auth"]).inc();
                         continue;
                     }
                 }
@@ -681,7 +982,378 @@ impl RadiusAuthServer {
         }
     }
 
-    async fn handle_access_request(&self, packet: &RadiusPacket, secret: &str, msg_auth_value: Option<Vec<u8>>) -> Vec<u8> {
+    /// The reply to an Access-Request, or None when it was handed to a proxy task
+    async fn handle_access_request(&self, packet: &RadiusPacket, src: std::net::SocketAddr, secret: &str,
+                                   msg_auth_value: Option<Vec<u8>>, nas_label: &str) -> Option<Vec<u8>> {
+        let lockout = &self.auth_server.lockout;
+        let lockout_keys = self.lockout_keys(packet, src);
+        if lockout.is_enabled() && !lockout_keys.is_empty() && lockout.is_locked(&lockout_keys).await {
+            warn!("Rejecting Access-Request from {}: locked out after repeated failures", src);
+            return Some(self.create_access_reject_locked(packet, secret));
+        }
+
+        // Users of partner realms are authenticated by their own home servers
+        let username = packet.attributes.iter()
+            .find(|attr| attr.typ == ATTR_USER_NAME)
+            .map(|attr| String::from_utf8_lossy(&attr.value).to_string());
+        if let Some(username) = username && self.auth_server.proxy.route(&username).is_some() {
+            self.spawn_proxy(packet, src, secret, nas_label, username);
+            return None;
+        }
+
+        let (mut response, credentials) = self.authenticate_access_request(packet, secret, msg_auth_value).await;
+        if response.first() == Some(&2) {
+            response = self.authorize_access_accept(packet, src, secret, response).await;
+        }
+
+        // Rejects by policy, schedules, session limits and the like say nothing about guessed passwords
+        if lockout.is_enabled() && !lockout_keys.is_empty() {
+            match credentials {
+                CredentialCheck::Passed => lockout.reset(&lockout_keys[0]).await,
+                CredentialCheck::Failed => lockout.record_failure(&lockout_keys).await,
+                CredentialCheck::NotChecked => {}
+            }
+        }
+
+        Some(response)
+    }
+
+    /// Authorization stage: adds the reply attributes of the user's attribute group to an
+    /// Access-Accept, then lets the authorization rules and the REST hook reject it or edit
+    /// its attributes. Users without a static address or IPv6 prefix get one leased from their IP pools.
+    /// Vendor-specific attributes the NAS's vendor doesn't understand are dropped.
+    async fn authorize_access_accept(&self, packet: &RadiusPacket, src: std::net::SocketAddr, secret: &str,
+                                     response: Vec<u8>) -> Vec<u8> {
+        let Some(username) = packet.attributes.iter()
+            .find(|attr| attr.typ == ATTR_USER_NAME)
+            .map(|attr| String::from_utf8_lossy(&attr.value).to_string()) else {
+            return response;
+        };
+        let auth_method = self.detect_auth_method(packet);
+        let Authorization { attributes, intent_edits, vendor, device_admin, policy, window, max_sessions, lease_request } = match self.identity_authorization(packet, src, secret, &username, &auth_method).await {
+            Ok(authorization) => authorization,
+            Err(e) => {
+                error!("Authorization failed for user {}: {}", username, e);
+                return self.create_access_reject(packet, secret, "Internal server error");
+            }
+        };
+        if let PolicyDecision::Reject(message) = &policy.decision {
+            debug!("Authorization rules rejected user {}: {}", username, message);
+            return self.create_access_reject(packet, secret, message);
+        }
+        if let Err(message) = device_admin {
+            debug!("User {} isn't entitled to the requested Service-Type: {}", username, message);
+            return self.create_access_reject(packet, secret, message);
+        }
+        let session_limit = match window {
+            AccessWindow::Outside => {
+                debug!("User {} is outside their access schedule", username);
+                return self.create_access_reject(packet, secret, "Outside permitted access hours");
+            }
+            AccessWindow::Until(seconds) => Some(seconds),
+            AccessWindow::Unrestricted => None,
+        };
+
+        let hook_edits = match self.auth_server.rest_hook.authorize(packet, src, &auth_method).await {
+            HookVerdict::Accept(edits) => edits,
+            HookVerdict::Reject(message) => {
+                debug!("REST hook rejected user {}: {}", username, message);
+                return self.create_access_reject(packet, secret, &message);
+            }
+        };
+        if let Some(max_sessions) = max_sessions {
+            match self.admit_session(&username, max_sessions).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    crate::metrics::SIMULTANEOUS_USE.with_label_values(&["rejected"]).inc();
+                    return self.create_access_reject(packet, secret, "Maximum number of sessions reached");
+                }
+                // The session store being down shouldn't lock everyone out
+                Err(e) => warn!("Simultaneous-Use check failed for user {}, admitting: {}", username, e),
+            }
+        }
+
+        // Addresses and prefixes set by the attribute group, rules or hook are static assignments and
+        // take precedence over the pools
+        let mut lease_edits = Vec::new();
+        let mut leases = Vec::new();
+        for kind in PoolKind::ALL {
+            let Some(request) = &lease_request else {
+                break;
+            };
+            let assigned = attributes.iter().any(|attr| attr.typ == kind.attribute())
+                || intent_edits.iter().chain(&policy.edits).chain(&hook_edits)
+                    .any(|edit| edit.vendor_id == 0 && edit.attribute_id == kind.attribute() && edit.attribute.is_some());
+            if assigned {
+                continue;
+            }
+            match self.auth_server.ip_pools.allocate(request, kind).await {
+                Ok(Allocation::Leased(lease)) => {
+                    crate::metrics::IP_POOL_ALLOCATIONS.with_label_values(&["leased"]).inc();
+                    info!("Leased {}/{} from pool {} to user {}", lease.address, lease.prefix_length, lease.pool, username);
+                    lease_edits.extend(lease.edits());
+                    leases.push(lease);
+                }
+                Ok(Allocation::NoPool) => {}
+                Ok(Allocation::Exhausted) => {
+                    crate::metrics::IP_POOL_ALLOCATIONS.with_label_values(&["exhausted"]).inc();
+                    warn!("No free {:?} lease in the IP pools of user {}", kind, username);
+                    self.release_leases(&leases, &username).await;
+                    return self.create_access_reject(packet, secret, "No IP address available");
+                }
+                Err(e) => {
+                    crate::metrics::IP_POOL_ALLOCATIONS.with_label_values(&["failed"]).inc();
+                    error!("IP pool allocation failed for user {}: {}", username, e);
+                    self.release_leases(&leases, &username).await;
+                    return self.create_access_reject(packet, secret, "Internal server error");
+                }
+            }
+        }
+
+        if attributes.is_empty() && intent_edits.is_empty() && policy.edits.is_empty() && hook_edits.is_empty()
+            && lease_edits.is_empty() && session_limit.is_none() && vendor.is_none() {
+            return response;
+        }
+
+        reply::amend_response(&response, packet, secret, |reply_attributes| {
+            reply_attributes.extend(attributes);
+            for edit in intent_edits.iter().chain(&policy.edits).chain(&hook_edits).chain(&lease_edits) {
+                edit.apply(reply_attributes);
+            }
+            if let Some(vendor) = vendor {
+                vendor::filter_reply(vendor, reply_attributes);
+            }
+            if let Some(limit) = session_limit {
+                clamp_session_timeout(reply_attributes, limit);
+            }
+        })
+    }
+
+    /// Attribute group attributes, authorization rule outcome, VLAN and access limits for an authenticated user
+    async fn identity_authorization(&self, packet: &RadiusPacket, src: std::net::SocketAddr, secret: &str, username: &str,
+                                    auth_method: &str)
+        -> Result<Authorization, BackendError> {
+        let backend = &self.auth_server.backend;
+        let pool = self.auth_server.get_pool();
+        let identity = backend.lookup_identity(username).await?;
+
+        let group = match &identity {
+            Some(identity) => match backend.attribute_group(identity).await? {
+                Some(group) => {
+                    debug!("Applying attribute group {} to user {}", group, username);
+                    reply::load_attribute_group(pool, &group).await?
+                }
+                None => AttributeGroup::default(),
+            },
+            None => AttributeGroup::default(),
+        };
+
+        // Schedules and time conditions are in the NAS's local time, VLAN names in its VLAN map
+        let nas = self.auth_server.find_request_nas(packet, src.ip());
+        let clock = NasClock::of(nas);
+
+        // Directory identities only have group names; Postgres ones are joined to their groups in SQL
+        let policy = &self.auth_server.policy;
+        let user_groups = match &identity {
+            Some(identity) if identity.identifier_id.is_none() || policy.uses(Field::UserGroup) => backend.fetch_groups(identity).await?,
+            _ => Vec::new(),
+        };
+        let (window, max_sessions) = match &identity {
+            Some(identity) => {
+                let directory_groups = if identity.identifier_id.is_none() { user_groups.as_slice() } else { &[] };
+                let window = schedule::check_access(pool, identity.identifier_id, directory_groups, clock).await?;
+                let max_sessions = if self.auth_server.sessions.is_enabled() {
+                    simultaneous_use::load_session_limit(pool, identity.identifier_id, directory_groups).await?
+                } else {
+                    None
+                };
+                (window, max_sessions)
+            }
+            None => (AccessWindow::Unrestricted, None),
+        };
+        let lease_request = match &identity {
+            Some(identity) if self.auth_server.ip_pools.is_enabled() => Some(LeaseRequest {
+                username: username.to_string(),
+                calling_station_id: packet.attributes.iter()
+                    .find(|attr| attr.typ == ATTR_CALLING_STATION_ID)
+                    .map(|attr| String::from_utf8_lossy(&attr.value).to_string())
+                    .unwrap_or_default(),
+                // The address accounting will come from, which is what releases the lease
+                nas_ip: match src.ip() {
+                    std::net::IpAddr::V4(ip) => Some(ip),
+                    std::net::IpAddr::V6(_) => None,
+                },
+                identifier_id: identity.identifier_id,
+                groups: if identity.identifier_id.is_none() { user_groups.clone() } else { Vec::new() },
+                nas_id: nas.map(|nas| nas.id),
+            }),
+            _ => None,
+        };
+        let identifier_vlan = match identity.as_ref().filter(|identity| identity.identifier_id.is_some()) {
+            Some(identity) => self.auth_server.subscribers.get_identifier(pool, &identity.username).await?
+                .and_then(|record| record.vlan),
+            None => None,
+        };
+
+        // Management logins need a device-admin profile from one of the user's groups
+        let admin_request = AdminRequest::of(packet.attributes.iter()
+            .find(|attr| attr.typ == ATTR_SERVICE_TYPE)
+            .and_then(|attr| attr.value.as_slice().try_into().ok().map(u32::from_be_bytes)));
+        let device_admin = match &identity {
+            Some(identity) if admin_request.is_requested() => {
+                let directory_groups = if identity.identifier_id.is_none() { user_groups.as_slice() } else { &[] };
+                device_admin::load_profile(pool, identity.identifier_id, directory_groups).await?
+            }
+            _ => None,
+        };
+
+        let outcome = if policy.is_active() {
+            let mut context = PolicyContext::from_request(packet, auth_method, clock.local_time(chrono::Utc::now()));
+            context.user_groups = user_groups;
+            if policy.uses(Field::NasGroup)
+                && let Some(nas) = nas
+            {
+                context.nas_groups = policy::load_nas_groups(pool, nas.id).await?;
+            }
+            policy.authorize(&context)
+        } else {
+            PolicyOutcome { decision: PolicyDecision::Accept, edits: Vec::new(), vlan: None, trace: Vec::new() }
+        };
+
+        // Rules override the user's own VLAN, which overrides the attribute group's
+        let vlan = outcome.vlan.as_ref().or(identifier_vlan.as_ref()).or(group.vlan.as_ref());
+        let mut intent_edits = match vlan {
+            Some(vlan) => {
+                debug!("Assigning VLAN {} to user {}", vlan, username);
+                vlan::vlan_edits(vlan, nas)
+            }
+            None => Vec::new(),
+        };
+        let vendor = VendorProfile::of(nas);
+        let intents = ReplyIntents {
+            rate_limit: group.rate_limit,
+            privilege_level: device_admin.as_ref().map(|profile| profile.privilege_level).or(group.privilege_level),
+            admin_role: device_admin.as_ref().and_then(|profile| profile.role_for(vendor)).map(str::to_string),
+            vlan: vlan.map(|vlan| vlan::resolve(vlan, nas)),
+            acl: group.acl_name.clone(),
+        };
+        if !intents.is_empty() {
+            intent_edits.extend(vendor::intent_edits(vendor, &intents));
+        }
+        if let Some(profile) = &device_admin {
+            debug!("Granting device-admin profile {} (level {}) to user {}", profile.name, profile.privilege_level, username);
+            intent_edits.push(profile.service_type_edit());
+        }
+
+        Ok(Authorization {
+            attributes: group.reply_attributes(&packet.authenticator, secret),
+            intent_edits,
+            vendor,
+            device_admin: admin_request.entitled(device_admin.as_ref()),
+            policy: outcome,
+            window,
+            max_sessions,
+            lease_request,
+        })
+    }
+
+    /// Gives back the leases of a login rejected before all of its pools could lease
+    async fn release_leases(&self, leases: &[Lease], username: &str) {
+        if leases.is_empty() {
+            return;
+        }
+        match self.auth_server.ip_pools.release(leases, username).await {
+            Ok(released) => debug!("Released {} leases of rejected user {}", released, username),
+            Err(e) => error!("Failed to release the leases of rejected user {}: {}", username, e),
+        }
+    }
+
+    /// Whether one more session fits under `max_sessions`; when configured to, the oldest sessions are
+    /// disconnected in the background and the login is admitted without waiting for the NAS
+    async fn admit_session(&self, username: &str, max_sessions: u32) -> Result<bool, BackendError> {
+        let limiter = &self.auth_server.sessions;
+        let open = limiter.open_sessions(username).await?;
+        if open.len() < max_sessions as usize {
+            return Ok(true);
+        }
+        debug!("User {} has {} open sessions, limit {}", username, open.len(), max_sessions);
+        if !limiter.kick_oldest() {
+            return Ok(false);
+        }
+
+        // Every session to kick must have a NAS to send the Disconnect-Request to before the login is admitted
+        let excess = open.len() + 1 - max_sessions as usize;
+        let mut kicks = Vec::new();
+        for session in open.into_iter().take(excess) {
+            let Some(nas) = self.auth_server.find_nas_device_by_address(&session.nas_ip).filter(|nas| nas.coa_enabled) else {
+                warn!("Cannot disconnect session {} of {}: no CoA-enabled NAS at {}", session.session_id, username, session.nas_ip);
+                return Ok(false);
+            };
+            let Ok(ip) = nas.ip_address.parse::<std::net::IpAddr>() else {
+                warn!("Cannot disconnect session {} of {}: NAS address {} is not an IP", session.session_id, username, nas.ip_address);
+                return Ok(false);
+            };
+            let Some(nas_secret) = self.auth_server.find_secret_for_ip(ip) else {
+                warn!("Cannot disconnect session {} of {}: no secret for {}", session.session_id, username, ip);
+                return Ok(false);
+            };
+            kicks.push((std::net::SocketAddr::new(ip, nas.coa_port), nas_secret.to_string(), nas.name.clone(), session));
+        }
+
+        // The NAS answers on its own time; the new login doesn't wait for it
+        for (target, nas_secret, nas_name, session) in kicks {
+            let auth_server = self.auth_server.clone();
+            tokio::spawn(async move {
+                let limiter = &auth_server.sessions;
+                match limiter.disconnect(target, &nas_secret, &session).await {
+                    Ok(true) => {
+                        if let Err(e) = limiter.close(&session).await {
+                            warn!("Failed to close disconnected session {}: {}", session.session_id, e);
+                        }
+                        info!("Disconnected session {} of {} on {} to admit a new login", session.session_id, session.username, nas_name);
+                    }
+                    Ok(false) => warn!("{} did not disconnect session {} of {}", nas_name, session.session_id, session.username),
+                    Err(e) => warn!("Disconnect-Request for session {} to {} failed: {}", session.session_id, nas_name, e),
+                }
+            });
+        }
+        crate::metrics::SIMULTANEOUS_USE.with_label_values(&["kicked"]).inc();
+        Ok(true)
+    }
+
+    /// Lockout counter keys for a request: the identifier first, then the (NAS, Calling-Station-Id) pair
+    fn lockout_keys(&self, packet: &RadiusPacket, src: std::net::SocketAddr) -> Vec<String> {
+        let mut keys = Vec::new();
+        if let Some(attr) = packet.attributes.iter().find(|attr| attr.typ == ATTR_USER_NAME) {
+            keys.push(LockoutTracker::identifier_key(&String::from_utf8_lossy(&attr.value)));
+        }
+        if let Some(attr) = packet.attributes.iter().find(|attr| attr.typ == ATTR_CALLING_STATION_ID) {
+            keys.push(LockoutTracker::station_key(src.ip(), &String::from_utf8_lossy(&attr.value)));
+        }
+        keys
+    }
+
+    fn create_access_reject_locked(&self, request: &RadiusPacket, secret: &str) -> Vec<u8> {
+        let reason = "Account locked: too many failed attempts";
+        // MS-CHAP peers only surface the MS-CHAP-Error code to the user
+        let ms_chap_ident = request.attributes.iter().find_map(|attr| {
+            if attr.typ == ATTR_VENDOR_SPECIFIC && attr.value.len() >= 7
+                && u32::from_be_bytes([attr.value[0], attr.value[1], attr.value[2], attr.value[3]]) == VENDOR_MICROSOFT
+                && matches!(attr.value[4], VENDOR_ATTR_MS_CHAP2_RESPONSE | VENDOR_ATTR_MS_CHAP2_CPW) {
+                Some(attr.value[6])
+            } else {
+                None
+            }
+        });
+
+        match ms_chap_ident {
+            Some(ident) => self.create_access_reject_mschap_error(request, secret, ident, MSCHAP_ERROR_ACCOUNT_DISABLED, reason),
+            None => self.create_access_reject(request, secret, reason),
+        }
+    }
+
+    async fn authenticate_access_request(&self, packet: &RadiusPacket, secret: &str, msg_auth_value: Option<Vec<u8>>)
+        -> (Vec<u8>, CredentialCheck) {
         let auth_method = self.detect_auth_method(packet);
         debug!("Handling Access-Request with {} authentication", auth_method);
 
@@ -697,6 +1369,8 @@ impl RadiusAuthServer {
         let mut mschap2_nt_response = None;
         let mut mschap2_identifier = None;
 
+        let mut mschap2_cpw = None;
+        let mut mschap_nt_enc_pw: Vec<(u16, Vec<u8>)> = Vec::new();
 
         // Extract all relevant attributes
         for attr in &packet.attributes {
@@ -734,15 +1408,13 @@ impl RadiusAuthServer {
                         let vendor_data = &attr.value[6..];
 
                         match vendor_type {
-                            // Check MS-CHAPv2-Challenge first (before MS-CHAP-Challenge) since they both use attribute 11
+                            // MS-CHAP-Challenge serves both versions: 8 bytes for MS-CHAP, 16 for MS-CHAPv2
                             VENDOR_ATTR_MS_CHAP2_CHALLENGE => {
-                                debug!("Found MS-CHAPv2-Challenge in VSA");
-                                // MS-CHAPv2-Challenge is 16 bytes
-                                if vendor_data.len() >= 16 {
-                                    mschap_challenge = Some(vendor_data[0..16].to_vec());
-                                    debug!("Extracted MS-CHAPv2-Challenge: {} bytes", vendor_data[0..16].len());
+                                debug!("Found MS-CHAP-Challenge in VSA: {} bytes", vendor_data.len());
+                                if vendor_data.len() == 8 || vendor_data.len() == 16 {
+                                    mschap_challenge = Some(vendor_data.to_vec());
                                 } else {
-                                    warn!("MS-CHAPv2-Challenge VSA too short: {} bytes (expected at least 16)", vendor_data.len());
+                                    warn!("MS-CHAP-Challenge VSA malformed: {} bytes (expected 8 or 16)", vendor_data.len());
                                 }
                             }
                             VENDOR_ATTR_MS_CHAP2_RESPONSE => {
@@ -765,19 +1437,30 @@ impl RadiusAuthServer {
                                     warn!("MS-CHAPv2-Response VSA too short: {} bytes (expected at least 50)", vendor_data.len());
                                 }
                             }
-                            VENDOR_ATTR_MS_CHAP_CHALLENGE => {
-                                debug!("Found MS-CHAP-Challenge in VSA (v1, not v2)");
-                                // For MS-CHAPv2, we should NOT use MS-CHAP-Challenge (v1)
-                                // We'll use the RADIUS authenticator instead
-                                // Only set this if we're doing MS-CHAP v1 (not v2)
-                                // For now, we'll ignore it for MS-CHAPv2 and use authenticator
-                            }
                             VENDOR_ATTR_MS_CHAP_RESPONSE => {
                                 debug!("Found MS-CHAP-Response in VSA");
-                                if vendor_data.len() >= 49 {
+                                // Ident, Flags, LM-Response (24) and NT-Response (24)
+                                if vendor_data.len() >= 50 {
                                     mschap_response = Some(vendor_data[26..50].to_vec());
                                 }
                             }
+                            VENDOR_ATTR_MS_CHAP2_CPW => {
+                                debug!("Found MS-CHAP2-CPW in VSA, vendor_data length: {}", vendor_data.len());
+                                mschap2_cpw = MsChap2ChangePassword::parse(vendor_data);
+                                if mschap2_cpw.is_none() {
+                                    warn!("MS-CHAP2-CPW VSA malformed: {} bytes (expected at least 68)", vendor_data.len());
+                                }
+                            }
+                            VENDOR_ATTR_MS_CHAP_NT_ENC_PW => {
+                                // Code (1) + Ident (1) + Sequence-Number (2) + String
+                                if vendor_data.len() > 4 && vendor_data[0] == 6 {
+                                    let sequence = u16::from_be_bytes([vendor_data[2], vendor_data[3]]);
+                                    debug!("Found MS-CHAP-NT-Enc-PW fragment {} ({} bytes)", sequence, vendor_data.len() - 4);
+                                    mschap_nt_enc_pw.push((sequence, vendor_data[4..].to_vec()));
+                                } else {
+                                    warn!("MS-CHAP-NT-Enc-PW VSA malformed: {} bytes", vendor_data.len());
+                                }
+                            }
 
                             _ => {
                                 debug!("Unknown Microsoft VSA type: {}", vendor_type);
@@ -791,14 +1474,19 @@ impl RadiusAuthServer {
             }
         }
 
-        match auth_method.as_str() {
+        let mut credentials = CredentialCheck::NotChecked;
+        let response = match auth_method.as_str() {
             "PAP" => {
                 if let (Some(username), Some(password)) = (username, password) {
-                    match self.authenticate_user(&username, password, &packet.authenticator, secret).await {
-                        Ok(AuthResult::Success) => self.create_access_accept(packet, secret),
+                    let result = self.authenticate_user(&username, password, &packet.authenticator, secret).await;
+                    credentials = CredentialCheck::of(result.as_ref());
+                    match result {
+                        Ok(AuthResult::Success) => self.create_access_accept(packet, secret, None),
+                        Ok(AuthResult::SuccessWithWarning(warning)) => self.create_access_accept(packet, secret, Some(&warning)),
                         Ok(AuthResult::UserNotFound) => self.create_access_reject(packet, secret, "User not found"),
                         Ok(AuthResult::InvalidPassword) => self.create_access_reject(packet, secret, "Invalid password"),
                         Ok(AuthResult::AccountDisabled) => self.create_access_reject(packet, secret, "Account is disabled"),
+                        Ok(AuthResult::PasswordExpired) => self.create_access_reject(packet, secret, "Password expired"),
                         Ok(AuthResult::DatabaseError(_)) => self.create_access_reject(packet, secret, "Internal server error"),
                         Err(_) => self.create_access_reject(packet, secret, "Internal server error"),
                     }
@@ -809,11 +1497,15 @@ impl RadiusAuthServer {
             "CHAP" => {
 
                 if let (Some(username), Some(chap_id), Some(chap_response)) = (username, chap_id, chap_response) {
-                    match self.authenticate_chap(&username, chap_id, &chap_response, &packet.authenticator, secret).await {
-                        Ok(AuthResult::Success) => self.create_access_accept(packet, secret),
+                    let result = self.authenticate_chap(&username, chap_id, &chap_response, &packet.authenticator, secret).await;
+                    credentials = CredentialCheck::of(result.as_ref());
+                    match result {
+                        Ok(AuthResult::Success) => self.create_access_accept(packet, secret, None),
+                        Ok(AuthResult::SuccessWithWarning(warning)) => self.create_access_accept(packet, secret, Some(&warning)),
                         Ok(AuthResult::UserNotFound) => self.create_access_reject(packet, secret, "User not found"),
                         Ok(AuthResult::InvalidPassword) => self.create_access_reject(packet, secret, "Invalid CHAP response"),
                         Ok(AuthResult::AccountDisabled) => self.create_access_reject(packet, secret, "Account is disabled"),
+                        Ok(AuthResult::PasswordExpired) => self.create_access_reject(packet, secret, "Password expired"),
                         Ok(AuthResult::DatabaseError(_)) => self.create_access_reject(packet, secret, "Internal server error"),
                         Err(_) => self.create_access_reject(packet, secret, "Internal server error"),
                     }
@@ -831,11 +1523,15 @@ impl RadiusAuthServer {
                 }
 
                 if let (Some(username), Some(challenge), Some(response)) = (username, mschap_challenge, mschap_response) {
-                    match self.authenticate_mschap(&username, &challenge, &response, &packet.authenticator, secret).await {
-                        Ok(AuthResult::Success) => self.create_access_accept(packet, secret),
+                    let result = self.authenticate_mschap(&username, &challenge, &response, &packet.authenticator, secret).await;
+                    credentials = CredentialCheck::of(result.as_ref());
+                    match result {
+                        Ok(AuthResult::Success) => self.create_access_accept(packet, secret, None),
+                        Ok(AuthResult::SuccessWithWarning(warning)) => self.create_access_accept(packet, secret, Some(&warning)),
                         Ok(AuthResult::UserNotFound) => self.create_access_reject(packet, secret, "User not found"),
                         Ok(AuthResult::InvalidPassword) => self.create_access_reject(packet, secret, "Invalid MS-CHAP response"),
                         Ok(AuthResult::AccountDisabled) => self.create_access_reject(packet, secret, "Account is disabled"),
+                        Ok(AuthResult::PasswordExpired) => self.create_access_reject(packet, secret, "Password expired"),
                         Ok(AuthResult::DatabaseError(_)) => self.create_access_reject(packet, secret, "Internal server error"),
                         Err(_) => self.create_access_reject(packet, secret, "Internal server error"),
                     }
@@ -853,13 +1549,13 @@ impl RadiusAuthServer {
 
                 // Validate required fields
                 if username.is_none() {
-                    return self.create_access_reject(packet, secret, "MS-CHAPv2: Missing username");
+                    return (self.create_access_reject(packet, secret, "MS-CHAPv2: Missing username"), CredentialCheck::NotChecked);
                 }
                 if mschap2_peer_challenge.is_none() {
-                    return self.create_access_reject(packet, secret, "MS-CHAPv2: Missing peer-challenge in MS-CHAPv2-Response");
+                    return (self.create_access_reject(packet, secret, "MS-CHAPv2: Missing peer-challenge in MS-CHAPv2-Response"), CredentialCheck::NotChecked);
                 }
                 if mschap2_nt_response.is_none() {
-                    return self.create_access_reject(packet, secret, "MS-CHAPv2: Missing NT-Response in MS-CHAPv2-Response");
+                    return (self.create_access_reject(packet, secret, "MS-CHAPv2: Missing NT-Response in MS-CHAPv2-Response"), CredentialCheck::NotChecked);
                 }
                 
                 // For MS-CHAPv2, the authenticator challenge should come from:
@@ -869,8 +1565,8 @@ impl RadiusAuthServer {
                 // If the client sends MS-CHAPv2-Challenge, it means it computed NT-Response using that challenge
                 let auth_challenge = if let Some(challenge) = mschap_challenge {
                     if challenge.len() != 16 {
-                        return self.create_access_reject(packet, secret, 
-                            &format!("MS-CHAPv2: Invalid challenge length: {} bytes (expected 16)", challenge.len()));
+                        return (self.create_access_reject(packet, secret, 
+                            &format!("MS-CHAPv2: Invalid challenge length: {} bytes (expected 16)", challenge.len())), CredentialCheck::NotChecked);
                     }
                     debug!("MS-CHAPv2: Using MS-CHAPv2-Challenge attribute as authenticator challenge: {:02x?}", challenge);
                     challenge
@@ -882,15 +1578,15 @@ impl RadiusAuthServer {
                 // Validate peer challenge length
                 let peer_challenge = mschap2_peer_challenge.unwrap();
                 if peer_challenge.len() != 16 {
-                    return self.create_access_reject(packet, secret, 
-                        &format!("MS-CHAPv2: Invalid peer-challenge length: {} bytes (expected 16)", peer_challenge.len()));
+                    return (self.create_access_reject(packet, secret, 
+                        &format!("MS-CHAPv2: Invalid peer-challenge length: {} bytes (expected 16)", peer_challenge.len())), CredentialCheck::NotChecked);
                 }
 
                 // Validate NT-Response length
                 let nt_response = mschap2_nt_response.unwrap();
                 if nt_response.len() < 24 {
-                    return self.create_access_reject(packet, secret, 
-                        &format!("MS-CHAPv2: Invalid NT-Response length: {} bytes (expected at least 24)", nt_response.len()));
+                    return (self.create_access_reject(packet, secret, 
+                        &format!("MS-CHAPv2: Invalid NT-Response length: {} bytes (expected at least 24)", nt_response.len())), CredentialCheck::NotChecked);
                 }
 
                 // Use the identifier we extracted from MS-CHAPv2-Response
@@ -919,18 +1615,24 @@ impl RadiusAuthServer {
                 
                 debug!("Using MS-CHAPv2 identifier: {} for MS-CHAP2-Success", ms_chap_v2_ident);
 
-                match self.authenticate_mschap2(&username.clone().unwrap(), &peer_challenge, &nt_response, &auth_challenge, secret).await {
+                let result = self.authenticate_mschap2(&username.clone().unwrap(), &peer_challenge, &nt_response, &auth_challenge, secret).await;
+                credentials = CredentialCheck::of(result.as_ref().map(|result| &result.result));
+                match result {
                     Ok(mschapv2_result) => match mschapv2_result.result {
-                        AuthResult::Success => {
+                        AuthResult::Success | AuthResult::SuccessWithWarning(_) => {
+                            let warning = match mschapv2_result.result {
+                                AuthResult::SuccessWithWarning(warning) => Some(warning),
+                                _ => None,
+                            };
                             if let Some(auth_resp) = mschapv2_result.authenticator_response {
                                 debug!("MS-CHAPv2 authentication successful for user: {}", username.unwrap());
-                                self.create_access_accept_mschapv2(
-                                    packet,
-                                    secret,
-                                    ms_chap_v2_ident,
-                                    &auth_resp,
-                                    mschapv2_result.password_hash.as_deref().unwrap_or(&[]),
-                                    &nt_response)
+                                self.create_access_accept_mschapv2(packet, secret, MsChap2Success {
+                                    ident: ms_chap_v2_ident,
+                                    authenticator_response: &auth_resp,
+                                    password_hash: mschapv2_result.password_hash.as_deref().unwrap_or(&[]),
+                                    nt_response: &nt_response,
+                                    reply_message: warning.as_deref(),
+                                })
                             } else {
                                 error!("MS-CHAPv2: Authentication succeeded but authenticator response is missing");
                                 self.create_access_reject(packet, secret, "MS-CHAPv2: Internal error - authenticator response generation failed")
@@ -948,6 +1650,12 @@ impl RadiusAuthServer {
                             debug!("MS-CHAPv2: Account disabled for user: {}", username.clone().unwrap());
                             self.create_access_reject(packet, secret, &format!("MS-CHAPv2: Account for user '{}' is disabled", username.unwrap()))
                         }
+                        AuthResult::PasswordExpired => {
+                            // 648 tells the client to prompt for a new password and send MS-CHAP2-CPW
+                            debug!("MS-CHAPv2: Password expired for user: {}", username.unwrap());
+                            self.create_access_reject_mschap_error(packet, secret, ms_chap_v2_ident,
+                                MSCHAP_ERROR_PASSWORD_EXPIRED, "MS-CHAPv2: Password expired")
+                        }
                         AuthResult::DatabaseError(e) => {
                             error!("MS-CHAPv2: Database error: {:?}", e);
                             self.create_access_reject(packet, secret, "MS-CHAPv2: Database error during authentication")
@@ -959,31 +1667,81 @@ impl RadiusAuthServer {
                     }
                 }
             }
+            "MS-CHAPv2-CPW" => {
+                debug!("Processing MS-CHAPv2 password change");
+
+                let (username, cpw) = match (username, mschap2_cpw) {
+                    (Some(username), Some(cpw)) => (username, cpw),
+                    _ => return (self.create_access_reject(packet, secret, "MS-CHAPv2: Invalid change password request"), CredentialCheck::NotChecked),
+                };
+
+                let auth_challenge = match mschap_challenge {
+                    Some(challenge) => challenge,
+                    None => packet.authenticator.to_vec(),
+                };
+
+                let encrypted_block = match reassemble_nt_enc_pw(mschap_nt_enc_pw) {
+                    Some(block) => block,
+                    None => {
+                        warn!("MS-CHAPv2: Incomplete MS-CHAP-NT-Enc-PW for user: {}", username);
+                        return (self.create_access_reject_mschap_error(packet, secret, cpw.identifier,
+                            MSCHAP_ERROR_CHANGING_PASSWORD, "MS-CHAPv2: Incomplete encrypted password"), CredentialCheck::NotChecked);
+                    }
+                };
+
+                let result = self.change_password_mschap2(&username, &cpw, &encrypted_block, &auth_challenge).await;
+                credentials = CredentialCheck::of(result.as_ref().map(|result| &result.result));
+                match result {
+                    Ok(mschapv2_result) => match mschapv2_result.result {
+                        AuthResult::Success | AuthResult::SuccessWithWarning(_) => {
+                            info!("MS-CHAPv2: Password changed for user: {}", username);
+                            self.create_access_accept_mschapv2(packet, secret, MsChap2Success {
+                                ident: cpw.identifier,
+                                authenticator_response: mschapv2_result.authenticator_response.as_deref().unwrap_or(&[]),
+                                password_hash: mschapv2_result.password_hash.as_deref().unwrap_or(&[]),
+                                nt_response: &cpw.nt_response,
+                                reply_message: None,
+                            })
+                        }
+                        AuthResult::UserNotFound => {
+                            self.create_access_reject(packet, secret, &format!("MS-CHAPv2: User '{}' not found", username))
+                        }
+                        AuthResult::AccountDisabled => {
+                            self.create_access_reject(packet, secret, &format!("MS-CHAPv2: Account for user '{}' is disabled", username))
+                        }
+                        AuthResult::InvalidPassword | AuthResult::PasswordExpired => {
+                            self.create_access_reject_mschap_error(packet, secret, cpw.identifier,
+                                MSCHAP_ERROR_AUTHENTICATION_FAILURE, "MS-CHAPv2: Old password incorrect")
+                        }
+                        AuthResult::DatabaseError(e) => {
+                            error!("MS-CHAPv2: Database error during password change: {:?}", e);
+                            self.create_access_reject_mschap_error(packet, secret, cpw.identifier,
+                                MSCHAP_ERROR_CHANGING_PASSWORD, "MS-CHAPv2: Database error during password change")
+                        }
+                    },
+                    Err(e) => {
+                        error!("MS-CHAPv2: Password change error: {:?}", e);
+                        self.create_access_reject_mschap_error(packet, secret, cpw.identifier,
+                            MSCHAP_ERROR_CHANGING_PASSWORD, &format!("MS-CHAPv2: Password change error: {}", e))
+                    }
+                }
+            }
             _ => self.create_access_reject(packet, secret, "Unsupported authentication method"),
-        }
+        };
+        (response, credentials)
     }
 
-    async fn authenticate_chap(&self, username: &str, chap_id: u8, chap_response: &[u8], authenticator: &[u8], secret: &str) -> Result<AuthResult, sqlx::Error> {
-        let pool = self.auth_server.get_pool();
-
-        let result = sqlx::query!(
-            r#"
-            SELECT id, plain_password, is_enabled
-            FROM user_identifiers 
-            WHERE value = $1 AND identifier_type_id = 1
-            "#,
-            username
-        )
-        .fetch_optional(pool)
-        .await?;
+    async fn authenticate_chap(&self, username: &str, chap_id: u8, chap_response: &[u8], authenticator: &[u8], secret: &str) -> Result<AuthResult, BackendError> {
+        let result = self.auth_server.backend.lookup_identity(username).await?;
 
         match result {
-            Some(record) => {
-                if !record.is_enabled {
+            Some(identity) => {
+                if !identity.is_enabled {
                     return Ok(AuthResult::AccountDisabled);
                 }
 
-                if let Some(stored_pass) = record.plain_password {
+                // CHAP needs the cleartext password, which only some backends can reveal
+                if let Some(stored_pass) = &identity.cleartext_password {
                     // Calculate expected CHAP response
                     let challenge = authenticator;
 
@@ -996,7 +1754,7 @@ impl RadiusAuthServer {
 
                     // Compare with the received response
                     if chap_response == expected_response.as_slice() {
-                        Ok(AuthResult::Success)
+                        Ok(self.check_password_age(&identity).await?)
                     } else {
                         Ok(AuthResult::InvalidPassword)
                     }
@@ -1009,39 +1767,21 @@ impl RadiusAuthServer {
         }
     }
 
-    async fn authenticate_mschap(&self, username: &str, challenge: &[u8], response: &[u8], authenticator: &[u8], secret: &str) -> Result<AuthResult, sqlx::Error> {
+    async fn authenticate_mschap(&self, username: &str, challenge: &[u8], response: &[u8], authenticator: &[u8], secret: &str) -> Result<AuthResult, BackendError> {
         debug!("Authenticating MS-CHAP:");
         debug!("Challenge length: {}", challenge.len());
         debug!("Response length: {}", response.len());
 
-        let pool = self.auth_server.get_pool();
-
-        let result = sqlx::query!(
-        r#"
-        SELECT id, plain_password, is_enabled
-        FROM user_identifiers
-        WHERE value = $1 AND identifier_type_id = 1
-        "#,
-        username
-    )
-            .fetch_optional(pool)
-            .await?;
+        let backend = &self.auth_server.backend;
+        let result = backend.lookup_identity(username).await?;
 
         match result {
-            Some(record) => {
-                if !record.is_enabled {
+            Some(identity) => {
+                if !identity.is_enabled {
                     return Ok(AuthResult::AccountDisabled);
                 }
 
-                if let Some(stored_pass) = record.plain_password {
-                    // Convert password to UTF-16LE bytes
-                    let utf16_bytes: Vec<u8> = stored_pass.encode_utf16()
-                        .flat_map(|x| x.to_le_bytes().to_vec())
-                        .collect();
-
-                    // Generate NT hash from password
-                    let nt_hash = nt_hash(&utf16_bytes);
-
+                if let Some(nt_hash) = backend.fetch_nt_hash(&identity).await? {
                     // Pad the hash to 21 bytes for DES
                     let mut padded_hash = nt_hash.clone();
                     padded_hash.resize(21, 0);
@@ -1054,7 +1794,7 @@ impl RadiusAuthServer {
                         let key_8 = setup_des_key(key_7);
 
                         let cipher = des::Des::new_from_slice(&key_8)
-                            .map_err(|_| sqlx::Error::Protocol("Failed to create DES cipher".into()))?;
+                            .map_err(|_| "Failed to create DES cipher")?;
 
                         let mut block_array = GenericArray::clone_from_slice(&challenge[..8]);
                         cipher.encrypt_block(&mut block_array);
@@ -1063,7 +1803,7 @@ impl RadiusAuthServer {
 
                     // Compare responses
                     if response == &challenge_response {
-                        Ok(AuthResult::Success)
+                        Ok(self.check_password_age(&identity).await?)
                     } else {
 
                         Ok(AuthResult::InvalidPassword)
@@ -1077,8 +1817,8 @@ impl RadiusAuthServer {
     }
 
 
-    async fn authenticate_mschap2(&self, username: &str, peer_challenge: &[u8], nt_response: &[u8], authenticator: &[u8], secret: &str) -> Result<Mschapv2Result, sqlx::Error> {
-        let pool = self.auth_server.get_pool();
+    async fn authenticate_mschap2(&self, username: &str, peer_challenge: &[u8], nt_response: &[u8], authenticator: &[u8], secret: &str) -> Result<Mschapv2Result, BackendError> {
+        let backend = &self.auth_server.backend;
         debug!("MS-CHAPv2: Starting authentication for user: {}", username);
         debug!("MS-CHAPv2: Input lengths - peer_challenge: {} bytes, nt_response: {} bytes, authenticator: {} bytes",
                peer_challenge.len(), nt_response.len(), authenticator.len());
@@ -1086,35 +1826,27 @@ impl RadiusAuthServer {
         // Validate input lengths
         if peer_challenge.len() != 16 {
             error!("MS-CHAPv2: Invalid peer_challenge length: {} (expected 16)", peer_challenge.len());
-            return Err(sqlx::Error::Protocol(format!("Invalid peer_challenge length: {} (expected 16)", peer_challenge.len()).into()));
+            return Err(format!("Invalid peer_challenge length: {} (expected 16)", peer_challenge.len()).into());
         }
         if nt_response.len() < 24 {
             error!("MS-CHAPv2: Invalid nt_response length: {} (expected at least 24)", nt_response.len());
-            return Err(sqlx::Error::Protocol(format!("Invalid nt_response length: {} (expected at least 24)", nt_response.len()).into()));
+            return Err(format!("Invalid nt_response length: {} (expected at least 24)", nt_response.len()).into());
         }
         if authenticator.len() != 16 {
             error!("MS-CHAPv2: Invalid authenticator length: {} (expected 16)", authenticator.len());
-            return Err(sqlx::Error::Protocol(format!("Invalid authenticator length: {} (expected 16)", authenticator.len()).into()));
+            return Err(format!("Invalid authenticator length: {} (expected 16)", authenticator.len()).into());
         }
 
-        let result = sqlx::query!(
-            r#"
-            SELECT id, plain_password, is_enabled
-            FROM user_identifiers 
-            WHERE value = $1 AND identifier_type_id = 1
-            "#,
-            username
-        )
-        .fetch_optional(pool)
-        .await?;
+        let result = backend.lookup_identity(username).await?;
 
-        debug!("MS-CHAPv2: Database query result - found: {}, enabled: {:?}",
+        debug!("MS-CHAPv2: {} lookup result - found: {}, enabled: {:?}",
+               backend.name(),
                result.is_some(),
                result.as_ref().map(|r| r.is_enabled));
 
         match result {
-            Some(record) => {
-                if !record.is_enabled {
+            Some(identity) => {
+                if !identity.is_enabled {
                     debug!("MS-CHAPv2: Account disabled for user: {}", username);
                     return Ok(Mschapv2Result {
                         result: AuthResult::AccountDisabled,
@@ -1123,19 +1855,8 @@ impl RadiusAuthServer {
                     });
                 }
 
-                if let Some(stored_pass) = record.plain_password {
-                    debug!("MS-CHAPv2: Password found for user: {} (length: {} bytes)", username, stored_pass.len());
-
-                    // Generate NT hash from password
-                    let password_utf16: Vec<u8> = stored_pass.encode_utf16()
-                        .flat_map(|c| c.to_le_bytes().to_vec())
-                        .collect();
-
-                    debug!("MS-CHAPv2: Password UTF-16LE length: {} bytes", password_utf16.len());
-
-                    // Generate NT hash from the UTF-16LE password
-                    let password_hash = nt_hash(&password_utf16);
-                    debug!("MS-CHAPv2: Generated password hash: {} bytes, value: {:02x?}", password_hash.len(), password_hash);
+                if let Some(password_hash) = backend.fetch_nt_hash(&identity).await? {
+                    debug!("MS-CHAPv2: NT hash found for user: {}", username);
 
                     // Generate the challenge using SHA1(peer_challenge + authenticator + username)
                     debug!("MS-CHAPv2: Challenge inputs - peer_challenge: {:02x?}, authenticator: {:02x?}, username: {}", 
@@ -1151,6 +1872,15 @@ impl RadiusAuthServer {
                     let received_nt_response = &nt_response[0..24];
                     if received_nt_response == &expected_response[0..24] {
                         debug!("MS-CHAPv2: NT-Response validation successful for user: {}", username);
+                        let password_age = self.check_password_age(&identity).await?;
+                        if matches!(password_age, AuthResult::PasswordExpired) {
+                            debug!("MS-CHAPv2: Password expired for user: {}", username);
+                            return Ok(Mschapv2Result {
+                                result: AuthResult::PasswordExpired,
+                                authenticator_response: None,
+                                password_hash: None,
+                            });
+                        }
                         let authenticator_response = Some(calculate_authenticator_response(
                             &password_hash,
                             received_nt_response,
@@ -1158,18 +1888,15 @@ impl RadiusAuthServer {
                             authenticator,
                             username,
                         ));
-                        debug!("MS-CHAPv2: Generated authenticator response: {} bytes, value: {:02x?}", 
-                               authenticator_response.as_ref().map(|r| r.len()).unwrap_or(0),
-                               authenticator_response.as_ref().map(|r| r.as_slice()));
+                        debug!("MS-CHAPv2: Generated authenticator response: {} bytes",
+                               authenticator_response.as_ref().map(|r| r.len()).unwrap_or(0));
                         Ok(Mschapv2Result {
-                            result: AuthResult::Success,
+                            result: password_age,
                             authenticator_response,
                             password_hash: Some(password_hash)
                         })
                     } else {
                         debug!("MS-CHAPv2: NT-Response validation failed for user: {}", username);
-                        debug!("MS-CHAPv2: Expected: {:02x?}", &expected_response[0..24]);
-                        debug!("MS-CHAPv2: Received: {:02x?}", received_nt_response);
                         Ok(Mschapv2Result {
                             result: AuthResult::InvalidPassword,
                             authenticator_response: None,
@@ -1177,7 +1904,7 @@ impl RadiusAuthServer {
                         })
                     }
                 } else {
-                    debug!("MS-CHAPv2: No password stored for user: {}", username);
+                    debug!("MS-CHAPv2: No NT hash available for user: {}", username);
                     Ok(Mschapv2Result {
                         result: AuthResult::InvalidPassword,
                         authenticator_response: None,
@@ -1186,7 +1913,7 @@ impl RadiusAuthServer {
                 }
             }
             None => {
-                debug!("MS-CHAPv2: User not found in database: {}", username);
+                debug!("MS-CHAPv2: User not found: {}", username);
                 Ok(Mschapv2Result {
                     result: AuthResult::UserNotFound,
                     authenticator_response: None,
@@ -1196,9 +1923,160 @@ impl RadiusAuthServer {
         }
     }
 
-    fn create_access_accept(&self, request: &RadiusPacket, secret: &str) -> Vec<u8> {
+    /// Applies the password ageing policy to an identifier whose password has just been verified
+    async fn check_password_age(&self, identity: &Identity) -> Result<AuthResult, sqlx::Error> {
+        let policy = &self.auth_server.config.password_policy;
+        // Ageing is tracked in user_identifiers, so it doesn't apply to directory users
+        let Some(identifier_id) = identity.identifier_id.filter(|_| policy.is_enabled()) else {
+            return Ok(AuthResult::Success);
+        };
+
+        #[derive(sqlx::FromRow)]
+        struct PasswordAgeRow {
+            password_changed_at: chrono::DateTime<chrono::Utc>,
+            grace_logins_used: i32,
+        }
+
+        let db_timer = crate::metrics::DB_QUERY_DURATION.with_label_values(&["password_age"]).start_timer();
+        let row = sqlx::query_as::<_, PasswordAgeRow>(
+            r#"
+            SELECT
+                COALESCE(password_changed_at, created_at) AS password_changed_at,
+                grace_logins_used
+            FROM user_identifiers
+            WHERE id = $1
+            "#
+        )
+        .bind(identifier_id)
+        .fetch_one(self.auth_server.get_pool())
+        .await?;
+        db_timer.observe_duration();
+
+        let status = policy.evaluate(row.password_changed_at, row.grace_logins_used.max(0) as u32, chrono::Utc::now());
+        debug!("Password status for identifier ID {}: {:?}", identifier_id, status);
+
+        match status {
+            PasswordStatus::Valid => Ok(AuthResult::Success),
+            PasswordStatus::Expiring { .. } => Ok(AuthResult::SuccessWithWarning(status.warning().unwrap_or_default())),
+            PasswordStatus::Grace { .. } => {
+                sqlx::query("UPDATE user_identifiers SET grace_logins_used = grace_logins_used + 1 WHERE id = $1")
+                    .bind(identifier_id)
+                    .execute(self.auth_server.get_pool())
+                    .await?;
+                Ok(AuthResult::SuccessWithWarning(status.warning().unwrap_or_default()))
+            }
+            PasswordStatus::Expired => Ok(AuthResult::PasswordExpired),
+        }
+    }
+
+    /// MS-CHAPv2 change password (RFC 2759, section 8.8): verifies the old password through the
+    /// encrypted hash, checks the NT-Response computed with the new password and stores it
+    async fn change_password_mschap2(&self, username: &str, cpw: &MsChap2ChangePassword, encrypted_block: &[u8],
+                                     authenticator: &[u8]) -> Result<Mschapv2Result, sqlx::Error> {
+        let pool = self.auth_server.get_pool();
+        debug!("MS-CHAPv2: Starting password change for user: {}", username);
+
+        if authenticator.len() != 16 {
+            return Err(sqlx::Error::Protocol(format!("Invalid authenticator length: {} (expected 16)", authenticator.len())));
+        }
+
+        let failed = |result| Mschapv2Result {
+            result,
+            authenticator_response: None,
+            password_hash: None,
+        };
+
+        // Passwords can only be changed where they are stored
+        if self.auth_server.config.auth_backend != BackendKind::Postgres {
+            debug!("MS-CHAPv2: Password change not supported by the {} backend", self.auth_server.backend.name());
+            return Ok(failed(AuthResult::InvalidPassword));
+        }
+
+        let result = self.auth_server.subscribers.get_identifier(pool, username).await?;
+
+        let record = match result {
+            Some(record) => record,
+            None => {
+                debug!("MS-CHAPv2: User not found in database: {}", username);
+                return Ok(failed(AuthResult::UserNotFound));
+            }
+        };
+
+        if !record.is_enabled {
+            debug!("MS-CHAPv2: Account disabled for user: {}", username);
+            return Ok(failed(AuthResult::AccountDisabled));
+        }
+
+        let old_password = match record.plain_password {
+            Some(password) => password,
+            None => {
+                debug!("MS-CHAPv2: No password stored for user: {}", username);
+                return Ok(failed(AuthResult::InvalidPassword));
+            }
+        };
+        let old_hash = nt_hash(&old_password.to_utf16le());
+
+        // The new password is RC4-encrypted with the old NT hash
+        let new_password = match decrypt_new_password(encrypted_block, &old_hash) {
+            Some(password) => password,
+            None => {
+                debug!("MS-CHAPv2: Could not decrypt new password for user: {}", username);
+                return Ok(failed(AuthResult::InvalidPassword));
+            }
+        };
+        let new_hash = nt_hash(&new_password.to_utf16le());
+
+        // Encrypted-Hash proves the client knows the old password
+        if cpw.encrypted_hash != old_nt_hash_encrypted_with_new(&old_hash, &new_hash) {
+            debug!("MS-CHAPv2: Encrypted old password hash mismatch for user: {}", username);
+            return Ok(failed(AuthResult::InvalidPassword));
+        }
+
+        let challenge = generate_nt_response_challenge(&cpw.peer_challenge, authenticator, username);
+        let expected_response = generate_nt_response(&new_hash, &challenge);
+        if cpw.nt_response != expected_response {
+            debug!("MS-CHAPv2: NT-Response for new password mismatch for user: {}", username);
+            return Ok(failed(AuthResult::InvalidPassword));
+        }
+
+        if let Err(e) = sqlx::query(
+            r#"
+            UPDATE user_identifiers
+            SET plain_password = $1, password_changed_at = NOW(), grace_logins_used = 0, updated_at = NOW()
+            WHERE id = $2
+            "#
+        )
+        .bind(&new_password)
+        .bind(record.id)
+        .execute(pool)
+        .await {
+            return Ok(failed(AuthResult::DatabaseError(e)));
+        }
+        debug!("MS-CHAPv2: Stored new password for identifier ID {}", record.id);
+        self.auth_server.subscribers.invalidate(username).await;
+
+        let authenticator_response = calculate_authenticator_response(
+            &new_hash,
+            &cpw.nt_response,
+            &cpw.peer_challenge,
+            authenticator,
+            username,
+        );
+
+        Ok(Mschapv2Result {
+            result: AuthResult::Success,
+            authenticator_response: Some(authenticator_response),
+            password_hash: Some(new_hash),
+        })
+    }
+
+    fn create_access_accept(&self, request: &RadiusPacket, secret: &str, reply_message: Option<&str>) -> Vec<u8> {
         // Create the basic Access-Accept packet
-        debug!("Creating Access-Accept response for request: {:?}", request);
+        let username = request.attributes.iter()
+            .find(|attr| attr.typ == ATTR_USER_NAME)
+            .map(|attr| String::from_utf8_lossy(&attr.value).to_string())
+            .unwrap_or_default();
+        debug!("Creating Access-Accept for {} Id {} of {}", request.code, request.identifier, username);
         let mut response = RadiusPacket {
             code: 2, // Access-Accept
             identifier: request.identifier,
@@ -1207,6 +2085,15 @@ impl RadiusAuthServer {
             attributes: Vec::new(),
         };
 
+        if let Some(message) = reply_message {
+            let mut value = message.as_bytes().to_vec();
+            value.truncate(253);
+            response.attributes.push(RadiusAttribute {
+                typ: ATTR_REPLY_MESSAGE,
+                value,
+            });
+        }
+
         // Add Message-Authenticator if it was in the request
         let has_msg_auth = request.attributes.iter().any(|attr| attr.typ == ATTR_MESSAGE_AUTHENTICATOR);
         if has_msg_auth {
@@ -1228,7 +2115,7 @@ impl RadiusAuthServer {
         // ⑥ Patch final authenticator (16 bytes)
 
         if has_msg_auth {
-            if let Some(pos) = encoded.windows(2).position(|w| w[0] == ATTR_MESSAGE_AUTHENTICATOR) {
+            if let Some(pos) = reply::message_authenticator_offset(&encoded) {
                 // ② Create temporary packet with request authenticator for Message-Authenticator calculation
                 let mut temp_for_mac = encoded.clone();
                 temp_for_mac[4..20].copy_from_slice(&request.authenticator);
@@ -1268,15 +2155,8 @@ impl RadiusAuthServer {
         encoded
     }
 
-    fn create_access_accept_mschapv2(
-        &self,
-        request: &RadiusPacket,
-        secret: &str,
-        ms_chap_v2_ident: u8,
-        authenticator_response: &[u8],
-        password_hash: &[u8],
-        nt_response: &[u8],
-    ) -> Vec<u8> {
+    fn create_access_accept_mschapv2(&self, request: &RadiusPacket, secret: &str, success: MsChap2Success) -> Vec<u8> {
+        let MsChap2Success { ident: ms_chap_v2_ident, authenticator_response, password_hash, nt_response, reply_message } = success;
         use hmac::{Hmac, Mac};
         use md5::Md5;
         type HmacMd5 = Hmac<Md5>;
@@ -1290,7 +2170,6 @@ impl RadiusAuthServer {
         };
 
         debug!("Creating Access-Accept for MS-CHAPv2");
-        debug!("Secret: {}", secret);
 
         // Service-Type: Login-User (required for Winbox authentication)
         response.attributes.push(RadiusAttribute {
@@ -1298,20 +2177,20 @@ impl RadiusAuthServer {
             value: SERVICE_TYPE_LOGIN_USER.to_be_bytes().to_vec(),
         });
 
-        // MS-CHAP2-Success format required by MikroTik: "S=<40 hex>"
-        // Format: "S=" followed by uppercase hex-encoded authenticator response
+        // MS-CHAP2-Success (RFC 2548, section 2.3.3): the Ident of the MS-CHAP2-Response,
+        // then "S=" followed by the uppercase hex-encoded authenticator response
         assert_eq!(authenticator_response.len(), 20, "Authenticator response must be exactly 20 bytes");
         
-        let mut ascii = String::from("S=");
-        ascii.push_str(&hex::encode_upper(authenticator_response));
-        let ms_chap_success = ascii.into_bytes();
+        let mut ms_chap_success = vec![ms_chap_v2_ident];
+        ms_chap_success.extend_from_slice(b"S=");
+        ms_chap_success.extend_from_slice(hex::encode_upper(authenticator_response).as_bytes());
         
         // Verify the MS-CHAP2-Success data structure
-        // "S=" (2 bytes) + 40 hex characters (20 bytes * 2) = 42 bytes total
-        assert_eq!(ms_chap_success.len(), 42, "MS-CHAP2-Success must be exactly 42 bytes (\"S=\" + 40 hex characters)");
+        // Ident (1 byte) + "S=" (2 bytes) + 40 hex characters (20 bytes * 2) = 43 bytes total
+        assert_eq!(ms_chap_success.len(), 43, "MS-CHAP2-Success must be exactly 43 bytes (Ident + \"S=\" + 40 hex characters)");
         
-        debug!("MS-CHAP2-Success: format=\"{}\", authenticator_response={:02x?}, total_length={}", 
-               String::from_utf8_lossy(&ms_chap_success), authenticator_response, ms_chap_success.len());
+        debug!("MS-CHAP2-Success: ident={}, format=\"{}\", authenticator_response={:02x?}, total_length={}", 
+               ms_chap_v2_ident, String::from_utf8_lossy(&ms_chap_success[1..]), authenticator_response, ms_chap_success.len());
 
         // VSA format: Vendor-ID (4 bytes) + Vendor-Type (1 byte) + Vendor-Length (1 byte) + Data (N bytes)
         // CRITICAL: vendor_length MUST be exactly 2 + data_length
@@ -1357,13 +2236,10 @@ impl RadiusAuthServer {
         // Session keys
         let (send_key, recv_key) = Self::get_mschapv2_session_keys(password_hash, nt_response);
         debug!("MS-CHAPv2: Generated session keys - send_key: {} bytes, recv_key: {} bytes", send_key.len(), recv_key.len());
-        debug!("MS-CHAPv2: Send key: {:02x?}, Recv key: {:02x?}", &send_key, &recv_key);
-        // Use offset 0 for send key, offset 1 for recv key (matching JavaScript implementation)
-        let enc_send = Self::encrypt_mppe_key_with_offset(&send_key, secret, &request.authenticator, 0);
-        let enc_recv = Self::encrypt_mppe_key_with_offset(&recv_key, secret, &request.authenticator, 1);
+        // Salt offset 0 for the send key and 1 for the recv key keeps their salts apart
+        let enc_send = crypto::salt_hide(&send_key, &request.authenticator, secret, crypto::new_salt(0));
+        let enc_recv = crypto::salt_hide(&recv_key, &request.authenticator, secret, crypto::new_salt(1));
         debug!("MS-CHAPv2: Encrypted MPPE keys - send: {} bytes, recv: {} bytes", enc_send.len(), enc_recv.len());
-        debug!("MS-CHAPv2: Encrypted send key (hex): {:02x?}", &enc_send);
-        debug!("MS-CHAPv2: Encrypted recv key (hex): {:02x?}", &enc_recv);
 
         // MS-CHAP-MPPE-Keys (VSA 311:12) - Combined format for MikroTik
         // This is the old combined format that contains both send and recv keys in one attribute
@@ -1401,42 +2277,13 @@ impl RadiusAuthServer {
         debug!("MS-CHAP-MPPE-Keys VSA: vendor_id={}, vendor_type={}, vendor_length={}, send_key_length={}, recv_key_length={}, total_data_length={}, total_vsa_length={}", 
                VENDOR_MICROSOFT, VENDOR_ATTR_MS_CHAP_MPPE_KEYS, mppe_keys_vendor_length,
                enc_send.len(), enc_recv.len(), combined_mppe_keys.len(), mppe_keys_vsa.len());
-        debug!("MS-CHAP-MPPE-Keys VSA (hex): {:02x?}", &mppe_keys_vsa);
-        debug!("MS-CHAP-MPPE-Keys combined data (hex): {:02x?}", &combined_mppe_keys);
         
         response.attributes.push(RadiusAttribute {
             typ: ATTR_VENDOR_SPECIFIC,
             value: mppe_keys_vsa,
         });
 
-        // MikroTik-Group attribute (Vendor ID 14988, Type 3, Value "full")
-        // Using byte string literal to ensure no null terminator
-        let mikrotik_group_value: &[u8] = b"full";
-        // Verify no null bytes in the value (data portion only)
-        assert!(!mikrotik_group_value.contains(&0u8), "MikroTik-Group value contains null byte!");
-        assert_eq!(mikrotik_group_value.len(), 4, "MikroTik-Group value should be exactly 4 bytes");
-        
-        let mikrotik_group_vendor_length = (mikrotik_group_value.len() + 2) as u8; // Vendor-Type + Vendor-Length + Data
-        let mikrotik_group_vsa = [
-            &VENDOR_MIKROTIK.to_be_bytes()[..],
-            &[VENDOR_ATTR_MIKROTIK_GROUP, mikrotik_group_vendor_length],
-            mikrotik_group_value,
-        ].concat();
-        
-        // Note: The VSA may contain null bytes in the vendor ID portion (e.g., vendor ID 14988 = [0x00, 0x00, 0x3A, 0x8C])
-        // This is normal and expected for VSA format. We only verify the data portion has no null bytes.
-        
-        debug!("MikroTik-Group VSA: vendor_id={}, vendor_type={}, vendor_length={}, data_length={}, total_vsa_length={}", 
-               VENDOR_MIKROTIK, VENDOR_ATTR_MIKROTIK_GROUP, mikrotik_group_vendor_length, 
-               mikrotik_group_value.len(), mikrotik_group_vsa.len());
-        debug!("MikroTik-Group VSA (hex): {:02x?}", &mikrotik_group_vsa);
-        debug!("MikroTik-Group value (bytes): {:?}, length: {}", mikrotik_group_value, mikrotik_group_value.len());
-        debug!("MikroTik-Group value: {}", String::from_utf8_lossy(mikrotik_group_value));
-        
-        response.attributes.push(RadiusAttribute {
-            typ: ATTR_VENDOR_SPECIFIC,
-            value: mikrotik_group_vsa,
-        });
+        // MikroTik-Group comes from the user's device-admin profile in the authorization stage
 
         // Session-Timeout (attribute type 27, value in seconds)
         // Common values: 3600 (1 hour), 7200 (2 hours), 86400 (24 hours)
@@ -1459,11 +2306,10 @@ impl RadiusAuthServer {
         debug!("Acct-Interim-Interval: {} seconds ({} minutes)", acct_interim_interval, acct_interim_interval / 60);
 
         // Reply-Message (attribute type 18, string value)
-        // Using byte string literal to ensure no null terminator
-        let reply_message: &[u8] = b"hello";
+        // Using byte string literal to ensure no null terminator; a password expiry warning replaces the greeting
+        let reply_message: &[u8] = reply_message.map(str::as_bytes).unwrap_or(b"hello");
         // Verify no null bytes in the message
         assert!(!reply_message.contains(&0u8), "Reply-Message contains null byte!");
-        assert_eq!(reply_message.len(), 5, "Reply-Message should be exactly 5 bytes");
         
         let reply_message_vec = reply_message.to_vec();
         // Double-check no null bytes in the vector
@@ -1574,9 +2420,9 @@ impl RadiusAuthServer {
         debug!("MPPE Send Key length: {}", send_key.len());
         debug!("MPPE Recv Key length: {}", recv_key.len());
 
-        // Encrypt the keys - use offset 0 for send key, offset 1 for recv key (matching JavaScript)
-        let encrypted_send_key = Self::encrypt_mppe_key_with_offset(&send_key, secret, authenticator, 0);
-        let encrypted_recv_key = Self::encrypt_mppe_key_with_offset(&recv_key, secret, authenticator, 1);
+        // Encrypt the keys - salt offset 0 for the send key, 1 for the recv key
+        let encrypted_send_key = crypto::salt_hide(&send_key, authenticator, secret, crypto::new_salt(0));
+        let encrypted_recv_key = crypto::salt_hide(&recv_key, authenticator, secret, crypto::new_salt(1));
 
         debug!("Encrypted Send Key length: {}", encrypted_send_key.len());
         debug!("Encrypted Recv Key length: {}", encrypted_recv_key.len());
@@ -1586,7 +2432,6 @@ impl RadiusAuthServer {
         send_key_attr.extend_from_slice(&VENDOR_MICROSOFT.to_be_bytes());
         send_key_attr.push(VENDOR_ATTR_MS_MPPE_SEND_KEY); // Vendor-Type: MPPE-Send-Key
 
-        // The encrypted key is already truncated if necessary in encrypt_mppe_key
         debug!("Adding MS-MPPE-Send-Key, encrypted size: {} bytes", encrypted_send_key.len());
         send_key_attr.push((encrypted_send_key.len() + 2) as u8); // Vendor-Length
         send_key_attr.extend_from_slice(&encrypted_send_key);
@@ -1601,7 +2446,6 @@ impl RadiusAuthServer {
         recv_key_attr.extend_from_slice(&VENDOR_MICROSOFT.to_be_bytes());
         recv_key_attr.push(VENDOR_ATTR_MS_MPPE_RECV_KEY); // Vendor-Type: MPPE-Recv-Key
 
-        // The encrypted key is already truncated if necessary in encrypt_mppe_key
         debug!("Adding MS-MPPE-Recv-Key, encrypted size: {} bytes", encrypted_recv_key.len());
         recv_key_attr.push((encrypted_recv_key.len() + 2) as u8); // Vendor-Length
         recv_key_attr.extend_from_slice(&encrypted_recv_key);
@@ -1643,7 +2487,10 @@ impl RadiusAuthServer {
             .map(|attr| attr.value.clone());
 
         if let Some(eap_data) = eap_data {
-            if let Some(eap_packet) = EapPacket::parse(&eap_data) {
+            if let Ok(eap_packet) = EapPacket::parse(&eap_data) {
+                if self.auth_server.config.eap.is_disabled(eap_packet.type_) {
+                    return self.create_access_reject(packet, secret, "EAP method disabled");
+                }
                 match eap_packet.type_ {
                     EAP_TYPE_TLS => {
                         match self.handle_eap_tls(packet, &eap_packet, secret).await {
@@ -1753,8 +2600,7 @@ impl RadiusAuthServer {
 
                 // Calculate Message-Authenticator
                 let mut encoded = radius_response.encode();
-                let msg_auth_pos = encoded.windows(2)
-                    .position(|w| w[0] == ATTR_MESSAGE_AUTHENTICATOR)
+                let msg_auth_pos = reply::message_authenticator_offset(&encoded)
                     .unwrap();
 
                 let mut mac = <Hmac<Md5> as Mac>::new_from_slice(secret.as_bytes())
@@ -1853,8 +2699,7 @@ impl RadiusAuthServer {
 
                 // Calculate Message-Authenticator
                 let mut encoded = radius_response.encode();
-                let msg_auth_pos = encoded.windows(2)
-                    .position(|w| w[0] == ATTR_MESSAGE_AUTHENTICATOR)
+                let msg_auth_pos = reply::message_authenticator_offset(&encoded)
                     .unwrap();
 
                 let mut mac = <Hmac<Md5> as Mac>::new_from_slice(secret.as_bytes())
@@ -1910,10 +2755,10 @@ impl RadiusAuthServer {
         }
 
         // Load server certificate and private key
-        let cert_file = File::open("certs/server.crt")
+        let cert_file = File::open(self.auth_server.config.tls.certificate_path())
             .map_err(|_| "Failed to open certificate file")?;
 
-        let key_file = File::open("certs/server.key")
+        let key_file = File::open(self.auth_server.config.tls.private_key_path())
             .map_err(|_| "Failed to open private key file")?;
 
         let mut reader = BufReader::new(cert_file);
@@ -1974,8 +2819,7 @@ impl RadiusAuthServer {
 
                 // Calculate Message-Authenticator
                 let mut encoded = radius_response.encode();
-                let msg_auth_pos = encoded.windows(2)
-                    .position(|w| w[0] == ATTR_MESSAGE_AUTHENTICATOR)
+                let msg_auth_pos = reply::message_authenticator_offset(&encoded)
                     .unwrap();
 
                 let mut mac = <Hmac<Md5> as Mac>::new_from_slice(secret.as_bytes())
@@ -2025,10 +2869,10 @@ impl RadiusAuthServer {
         }
 
         // Load server certificate and private key
-        let cert_file = File::open("certs/server.crt")
+        let cert_file = File::open(self.auth_server.config.tls.certificate_path())
             .map_err(|_| "Failed to open certificate file")?;
 
-        let key_file = File::open("certs/server.key")
+        let key_file = File::open(self.auth_server.config.tls.private_key_path())
             .map_err(|_| "Failed to open private key file")?;
 
         let mut reader = BufReader::new(cert_file);
@@ -2089,8 +2933,7 @@ impl RadiusAuthServer {
 
                 // Calculate Message-Authenticator
                 let mut encoded = radius_response.encode();
-                let msg_auth_pos = encoded.windows(2)
-                    .position(|w| w[0] == ATTR_MESSAGE_AUTHENTICATOR)
+                let msg_auth_pos = reply::message_authenticator_offset(&encoded)
                     .unwrap();
 
                 let mut mac = <Hmac<Md5> as Mac>::new_from_slice(secret.as_bytes())
@@ -2141,10 +2984,10 @@ impl RadiusAuthServer {
         }
 
         // Load server certificate and private key
-        let cert_file = File::open("certs/server.crt")
+        let cert_file = File::open(self.auth_server.config.tls.certificate_path())
             .map_err(|_| "Failed to open certificate file")?;
 
-        let key_file = File::open("certs/server.key")
+        let key_file = File::open(self.auth_server.config.tls.private_key_path())
             .map_err(|_| "Failed to open private key file")?;
 
         let mut reader = BufReader::new(cert_file);
@@ -2203,8 +3046,7 @@ impl RadiusAuthServer {
 
                 // Calculate Message-Authenticator
                 let mut encoded = radius_response.encode();
-                let msg_auth_pos = encoded.windows(2)
-                    .position(|w| w[0] == ATTR_MESSAGE_AUTHENTICATOR)
+                let msg_auth_pos = reply::message_authenticator_offset(&encoded)
                     .unwrap();
 
                 let mut mac = <Hmac<Md5> as Mac>::new_from_slice(secret.as_bytes())
@@ -2287,139 +3129,92 @@ impl RadiusAuthServer {
 
         (send_key, recv_key)
     }
+}
 
-    fn encrypt_mppe_key(key: &[u8], secret: &str, authenticator: &[u8]) -> Vec<u8> {
-        Self::encrypt_mppe_key_with_offset(key, secret, authenticator, 0)
-    }
-
-    fn encrypt_mppe_key_with_offset(key: &[u8], secret: &str, authenticator: &[u8], offset: u8) -> Vec<u8> {
-        use md5::Md5;
-        use rand::random;
-
-        // Maximum size for the encrypted key (253 bytes for VSA value - 6 bytes for VSA header)
-        const MAX_ENCRYPTED_KEY_SIZE: usize = 247;
-
-        // Generate salt - matching JavaScript implementation
-        // salt = [0x80 | ((offset & 0x0f) << 3) | (salt_value & 0x07), salt_value & 0xff]
-        let salt_value: u32 = random();
-        let salt = [
-            0x80 | ((offset & 0x0f) << 3) | ((salt_value & 0x07) as u8),
-            (salt_value & 0xff) as u8
-        ];
-
-        // Calculate maximum key size that can be encrypted without exceeding the limit
-        // Each 16-byte block of the key becomes 16 bytes of encrypted data
-        // We need to reserve 2 bytes for the salt
-        let max_key_blocks = (MAX_ENCRYPTED_KEY_SIZE - 2) / 16;
-        let max_key_size = max_key_blocks * 16;
-
-        // Truncate the key if necessary
-        let key_to_use = if key.len() > max_key_size {
-            debug!("Key size {} exceeds maximum allowed size, truncating to {} bytes", key.len(), max_key_size);
-            &key[0..max_key_size]
-        } else {
-            key
-        };
-
-        // Pad key to multiple of 16 bytes
-        let mut padded_key = key_to_use.to_vec();
-        let pad_len = 16 - (padded_key.len() % 16);
-        if pad_len < 16 {
-            padded_key.extend(vec![0u8; pad_len]);
-        }
-
-        // First block
-        let mut hasher = Md5::new();
-        hasher.update(secret.as_bytes());
-        hasher.update(authenticator);
-        hasher.update(&salt);
-        let first_hash = hasher.finalize();
-
-        let mut result = Vec::with_capacity(2 + padded_key.len());
-        result.extend_from_slice(&salt);
-
-        // XOR first block
-        let mut encrypted = Vec::new();
-        for (a, b) in padded_key[0..16].iter().zip(first_hash.iter()) {
-            encrypted.push(a ^ b);
-        }
-        result.extend_from_slice(&encrypted);
-
-        // Remaining blocks - use the last encrypted block for next hash
-        let mut last_block = encrypted.clone();
-        for chunk in padded_key[16..].chunks(16) {
-            let mut hasher = Md5::new();
-            hasher.update(secret.as_bytes());
-            hasher.update(&last_block); // Use last encrypted block, not just "encrypted"
-            let hash = hasher.finalize();
-
-            let mut block_encrypted = Vec::new();
-            for (a, b) in chunk.iter().zip(hash.iter()) {
-                block_encrypted.push(a ^ b);
-            }
-            last_block = block_encrypted.clone();
-            result.extend_from_slice(&block_encrypted);
-        }
 
-        // Final check to ensure we don't exceed the maximum size
-        if result.len() > MAX_ENCRYPTED_KEY_SIZE {
-            error!("Encrypted key size {} exceeds maximum allowed size of {} bytes, truncating", result.len(), MAX_ENCRYPTED_KEY_SIZE);
-            result.truncate(MAX_ENCRYPTED_KEY_SIZE);
-        }
+/// Lowers Session-Timeout to `limit` seconds, adding one when the reply has none
+fn clamp_session_timeout(attributes: &mut Vec<RadiusAttribute>, limit: u32) {
+    let current = attributes.iter()
+        .filter(|attr| attr.typ == ATTR_SESSION_TIMEOUT)
+        .filter_map(|attr| attr.value.as_slice().try_into().ok().map(u32::from_be_bytes))
+        .min();
+    let timeout = current.map_or(limit, |current| current.min(limit));
+    attributes.retain(|attr| attr.typ != ATTR_SESSION_TIMEOUT);
+    attributes.push(RadiusAttribute {
+        typ: ATTR_SESSION_TIMEOUT,
+        value: timeout.to_be_bytes().to_vec(),
+    });
+}
 
-        result
+/// Orders MS-CHAP-NT-Enc-PW fragments by sequence number and joins them into the
+/// 516-byte encrypted password block
+fn reassemble_nt_enc_pw(mut fragments: Vec<(u16, Vec<u8>)>) -> Option<Vec<u8>> {
+    fragments.sort_by_key(|(sequence, _)| *sequence);
+    let block: Vec<u8> = fragments.into_iter().flat_map(|(_, data)| data).collect();
+    if block.len() < MSCHAP_PW_BLOCK_SIZE {
+        return None;
     }
+    Some(block[..MSCHAP_PW_BLOCK_SIZE].to_vec())
 }
 
-
-fn decode_pap_password(encrypted: Vec<u8>, authenticator: &[u8], secret: &str) -> Result<String, Box<dyn std::error::Error>> {
-    // Try to extract password from quotes first
-
-    debug!("Decrypting PAP password: encrypted_len={}, authenticator={:?}",
-           encrypted.len(), authenticator);
-
-    let mut decrypted = Vec::with_capacity(encrypted.len());
-    let mut last_block = authenticator;
-
-    // Process the password in 16 byte blocks as per RFC 2865
-    for chunk in encrypted.chunks(16) {
-        // Calculate the MD5 hash of the secret concatenated with the last block
-        let mut hasher = Md5::new();
-        hasher.update(secret.as_bytes());
-        hasher.update(last_block);
-        let hash = hasher.finalize();
-
-        // XOR the hash with the encrypted chunk to get the plaintext
-        for (i, &byte) in chunk.iter().enumerate() {
-            decrypted.push(byte ^ hash[i]);
-        }
-
-        // The encrypted chunk becomes the "last block" for the next iteration
-        last_block = chunk;
+/// Decrypts NewPasswordEncryptedWithOldNtPasswordHash (RFC 2759, section 8.9).
+/// The password occupies the end of the 512-byte buffer, followed by its length in bytes (LE).
+fn decrypt_new_password(encrypted_block: &[u8], old_hash: &[u8]) -> Option<String> {
+    if encrypted_block.len() != MSCHAP_PW_BLOCK_SIZE {
+        return None;
     }
+    let block = rc4(old_hash, encrypted_block);
+    let length = u32::from_le_bytes([block[512], block[513], block[514], block[515]]) as usize;
+    if length == 0 || length > 512 || !length.is_multiple_of(2) {
+        return None;
+    }
+    let utf16: Vec<u16> = block[512 - length..512]
+        .chunks(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    String::from_utf16(&utf16).ok()
+}
 
-    // Remove padding (null bytes from the end)
-    while let Some(&0) = decrypted.last() {
-        decrypted.pop();
+/// OldNtPasswordHashEncryptedWithNewNtPasswordHash (RFC 2759, section 8.12)
+fn old_nt_hash_encrypted_with_new(old_hash: &[u8], new_hash: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(16);
+    for i in 0..2 {
+        let cipher = des::Des::new_from_slice(&setup_des_key(&new_hash[i * 7..(i + 1) * 7]))
+            .expect("DES key is always 8 bytes");
+        let mut block = GenericArray::clone_from_slice(&old_hash[i * 8..(i + 1) * 8]);
+        cipher.encrypt_block(&mut block);
+        out.extend_from_slice(&block);
     }
+    out
+}
 
-    // Convert to UTF-8 string
-    match String::from_utf8(decrypted) {
-        Ok(s) => {
-            debug!("Successfully decrypted PAP password, length: {}", s.len());
-            Ok(s)
-        },
-        Err(e) => {
-            error!("Failed to convert decrypted password to UTF-8: {}", e);
-            Err(Box::new(e))
-        }
+fn rc4(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut state: Vec<u8> = (0..=255).collect();
+    let mut j: u8 = 0;
+    for i in 0..256 {
+        j = j.wrapping_add(state[i]).wrapping_add(key[i % key.len()]);
+        state.swap(i, j as usize);
     }
 
+    let (mut i, mut j) = (0u8, 0u8);
+    data.iter().map(|byte| {
+        i = i.wrapping_add(1);
+        j = j.wrapping_add(state[i as usize]);
+        state.swap(i as usize, j as usize);
+        byte ^ state[state[i as usize].wrapping_add(state[j as usize]) as usize]
+    }).collect()
+}
+
+/// MS-CHAP-Error string, "E=eeeeeeeeee R=r C=cccccccccccccccccccccccccccccccc V=vvvvvvvvvv".
+/// The challenge is fresh so that a client retrying or changing its password uses a new one.
+fn mschap_error_value(error_code: u32) -> String {
+    let challenge: [u8; 16] = rand::random();
+    format!("E={} R=0 C={} V=3", error_code, hex::encode_upper(challenge))
 }
 
 // Helper function to generate the challenge for NT-Response
 // According to RFC 2759, ChallengeHash = SHA1(PeerChallenge || AuthenticatorChallenge || UserName)[0..8]
-fn generate_nt_response_challenge(peer_challenge: &[u8], authenticator_challenge: &[u8], username: &str) -> Vec<u8> {
+pub(crate) fn generate_nt_response_challenge(peer_challenge: &[u8], authenticator_challenge: &[u8], username: &str) -> Vec<u8> {
     use sha1::{Sha1, Digest};
 
     let mut hasher = Sha1::new();
@@ -2433,7 +3228,7 @@ fn generate_nt_response_challenge(peer_challenge: &[u8], authenticator_challenge
     hash[0..8].to_vec()
 }
 
-fn generate_nt_response(password_hash: &[u8], challenge: &[u8]) -> Vec<u8> {
+pub(crate) fn generate_nt_response(password_hash: &[u8], challenge: &[u8]) -> Vec<u8> {
     let mut padded_hash = password_hash.to_vec();
     padded_hash.resize(21, 0);
 
@@ -2471,7 +3266,7 @@ fn generate_nt_response(password_hash: &[u8], challenge: &[u8]) -> Vec<u8> {
 }
 
 
-fn nt_hash(password: &[u8]) -> Vec<u8> {
+pub(crate) fn nt_hash(password: &[u8]) -> Vec<u8> {
     use md4::{Md4, Digest};
 
     let mut hasher = Md4::new();
@@ -2495,7 +3290,7 @@ fn setup_des_key(key_7: &[u8]) -> [u8; 8] {
 }
 
 // Helper function to convert a string to UTF-16LE bytes
-trait ToUtf16Le {
+pub(crate) trait ToUtf16Le {
     fn to_utf16le(&self) -> Vec<u8>;
 }
 
@@ -2507,7 +3302,7 @@ impl ToUtf16Le for str {
     }
 }
 
-fn calculate_authenticator_response(
+pub(crate) fn calculate_authenticator_response(
     password_hash: &[u8],
     nt_response: &[u8],
     peer_challenge: &[u8],
@@ -2517,10 +3312,8 @@ fn calculate_authenticator_response(
     use sha1::Sha1;
     use digest::Digest;
 
-    // Generate PasswordHashHash = SHA1(password_hash)
-    let mut sha1 = Sha1::new();
-    sha1.update(password_hash);
-    let password_hash_hash = sha1.finalize();
+    // PasswordHashHash = MD4(password_hash) (RFC 2759, section 8.4 HashNtPasswordHash)
+    let password_hash_hash = nt_hash(password_hash);
 
     // Generate ChallengeHash = SHA1(PeerChallenge + AuthenticatorChallenge + Username)[0..8]
     let mut sha1 = Sha1::new();
@@ -2529,26 +3322,22 @@ fn calculate_authenticator_response(
     sha1.update(username.as_bytes());
     let challenge_hash = sha1.finalize();
 
-    // Generate the Authenticator Response
-    // According to RFC 2759 Section 8.7, the authenticator response is:
-    // SHA1(PasswordHashHash, NT-Response, Magic1, ChallengeHash[0..8], Magic2)
-    // This means SHA1 of the concatenation, not HMAC
+    // RFC 2759, section 8.7: Digest = SHA1(PasswordHashHash || NT-Response || Magic1),
+    // then the response is SHA1(Digest || ChallengeHash[0..8] || Magic2)
     let magic1 = b"Magic server to client signing constant";
     let magic2 = b"Pad to make it do more than one iteration";
 
-    // SHA1 of concatenated inputs (not HMAC)
-    // According to RFC 2759 and JavaScript implementation:
-    // SHA1(PasswordHashHash || NT-Response || Magic1 || ChallengeHash[0..8] || Magic2)
     let mut sha1 = Sha1::new();
-    sha1.update(&password_hash_hash);             // PasswordHashHash (20 bytes)
-    sha1.update(nt_response);                    // NT-Response (24 bytes)
-    sha1.update(magic1);                         // Magic1 constant
-    sha1.update(&challenge_hash[0..8]);          // First 8 bytes of ChallengeHash
-    sha1.update(magic2);                         // Magic2 constant
+    sha1.update(&password_hash_hash);
+    sha1.update(nt_response);
+    sha1.update(magic1);
+    let digest = sha1.finalize();
+
+    let mut sha1 = Sha1::new();
+    sha1.update(digest);
+    sha1.update(&challenge_hash[0..8]);
+    sha1.update(magic2);
     let authenticator_response = sha1.finalize();
-    
-    debug!("Authenticator Response calculation: PasswordHashHash={:02x?}, NT-Response={:02x?}, ChallengeHash[0..8]={:02x?}, result={:02x?}",
-           &password_hash_hash[..], &nt_response[..], &challenge_hash[0..8], &authenticator_response[..]);
 
     // Return single 20-byte authenticator response
     authenticator_response.to_vec()
@@ -2564,27 +3353,26 @@ pub struct EapPacket {
 }
 
 impl EapPacket {
-    pub fn parse(data: &[u8]) -> Option<Self> {
+    /// Parses an EAP packet (RFC 3748, section 4); Success and Failure have no Type, so `type_` is 0
+    pub fn parse(data: &[u8]) -> Result<Self, ParseError> {
         if data.len() < 4 {
-            return None;
+            return Err(ParseError::TooShort(data.len()));
         }
 
         let code = data[0];
         let identifier = data[1];
         let length = u16::from_be_bytes([data[2], data[3]]);
-
-        if data.len() < length as usize {
-            return None;
+        if length < 4 || data.len() < length as usize {
+            return Err(ParseError::BadLength { length, received: data.len() });
         }
 
-        let type_ = if data.len() > 4 { data[4] } else { 0 };
-        let data = if data.len() > 5 {
-            data[5..length as usize].to_vec()
-        } else {
-            Vec::new()
+        let data = &data[..length as usize];
+        let (type_, data) = match data.get(4..) {
+            Some([type_, rest @ ..]) => (*type_, rest.to_vec()),
+            _ => (0, Vec::new()),
         };
 
-        Some(Self {
+        Ok(Self {
             code,
             identifier,
             length,
@@ -2593,14 +3381,19 @@ impl EapPacket {
         })
     }
 
-
+    /// Encodes the packet with its Length computed from the contents; a Success or Failure
+    /// without Type or data is just the four-byte header
     pub fn encode(&self) -> Vec<u8> {
-        let mut out = Vec::with_capacity(5 + self.data.len());
+        let header_only = matches!(self.code, EAP_SUCCESS | EAP_FAILURE) && self.type_ == 0 && self.data.is_empty();
+        let length = if header_only { 4 } else { 5 + self.data.len() };
+        let mut out = Vec::with_capacity(length);
         out.push(self.code);
         out.push(self.identifier);
-        out.extend_from_slice(&self.length.to_be_bytes());
-        out.push(self.type_);
-        out.extend_from_slice(&self.data);
+        out.extend_from_slice(&(length as u16).to_be_bytes());
+        if !header_only {
+            out.push(self.type_);
+            out.extend_from_slice(&self.data);
+        }
         out
     }
 }
@@ -2640,3 +3433,146 @@ pub struct EapAkaAttributes {
     pub notification: Option<u16>,
     pub client_error_code: Option<u16>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_tagged_attributes() {
+        let tunnel_type = RadiusAttribute::tagged(ATTR_TUNNEL_TYPE, 1, &13u32.to_be_bytes()).unwrap();
+        assert_eq!(tunnel_type.value, vec![1, 0, 0, 13]);
+        assert_eq!(tunnel_type.tagged_value(), Some((1, 13u32.to_be_bytes().to_vec())));
+
+        let group_id = RadiusAttribute::tagged(ATTR_TUNNEL_PRIVATE_GROUP_ID, 0, b"120").unwrap();
+        assert_eq!(group_id.value, b"120".to_vec());
+        assert_eq!(group_id.tagged_value(), Some((0, b"120".to_vec())));
+        let group_id = RadiusAttribute::tagged(ATTR_TUNNEL_PRIVATE_GROUP_ID, 2, b"120").unwrap();
+        assert_eq!(group_id.value, b"\x02120".to_vec());
+        assert_eq!(group_id.tagged_value(), Some((2, b"120".to_vec())));
+
+        assert!(RadiusAttribute::tagged(ATTR_TUNNEL_TYPE, 0x20, &13u32.to_be_bytes()).is_none());
+        assert!(RadiusAttribute::tagged(ATTR_TUNNEL_TYPE, 1, &0x0100_0000u32.to_be_bytes()).is_none());
+        assert!(RadiusAttribute::tagged(ATTR_SESSION_TIMEOUT, 1, &60u32.to_be_bytes()).is_none());
+        assert_eq!(RadiusAttribute { typ: ATTR_SESSION_TIMEOUT, value: vec![0; 4] }.tagged_value(), None);
+    }
+
+    #[test]
+    fn test_clamp_session_timeout() {
+        let mut attributes = vec![RadiusAttribute { typ: ATTR_SESSION_TIMEOUT, value: 3600u32.to_be_bytes().to_vec() }];
+        clamp_session_timeout(&mut attributes, 600);
+        assert_eq!(attributes[0].value, 600u32.to_be_bytes().to_vec());
+
+        clamp_session_timeout(&mut attributes, 7200);
+        assert_eq!(attributes.len(), 1);
+        assert_eq!(attributes[0].value, 600u32.to_be_bytes().to_vec());
+    }
+
+    #[test]
+    fn test_rc4_known_vector() {
+        let encrypted = rc4(b"Key", b"Plaintext");
+        assert_eq!(hex::encode_upper(encrypted), "BBF316E8D940AF0AD3");
+    }
+
+    #[test]
+    fn test_nt_hash_rfc2759_vector() {
+        let hash = nt_hash(&"clientPass".to_utf16le());
+        assert_eq!(hex::encode_upper(&hash), "44EBBA8D5312B8D611474411F56989AE");
+
+        let peer_challenge = hex::decode("21402324255E262A28295F2B3A337C7E").unwrap();
+        let authenticator_challenge = hex::decode("5B5D7C7D7B3F2F3E3C2C602132262628").unwrap();
+        let nt_response = hex::decode("82309ECD8D708B5EA08FAA3981CD83544233114A3D85D6DF").unwrap();
+        let response = calculate_authenticator_response(&hash, &nt_response, &peer_challenge, &authenticator_challenge, "User");
+        assert_eq!(hex::encode_upper(response), "407A5589115FD0D6209F510FE9C04566932CDA56");
+    }
+
+    #[test]
+    fn test_change_password_block_roundtrip() {
+        let old_hash = nt_hash(&"oldPass".to_utf16le());
+        let new_password = "n3wPassw0rd";
+        let utf16 = new_password.to_utf16le();
+
+        let mut block = vec![0x5Au8; MSCHAP_PW_BLOCK_SIZE];
+        block[512 - utf16.len()..512].copy_from_slice(&utf16);
+        block[512..].copy_from_slice(&(utf16.len() as u32).to_le_bytes());
+        let encrypted = rc4(&old_hash, &block);
+
+        // Split across fragments the way a NAS would and deliver them out of order
+        let fragments = vec![
+            (3, encrypted[486..].to_vec()),
+            (1, encrypted[..243].to_vec()),
+            (2, encrypted[243..486].to_vec()),
+        ];
+        let reassembled = reassemble_nt_enc_pw(fragments).unwrap();
+        assert_eq!(reassembled, encrypted);
+        assert_eq!(decrypt_new_password(&reassembled, &old_hash).as_deref(), Some(new_password));
+
+        let wrong_hash = nt_hash(&"wrongPass".to_utf16le());
+        assert_ne!(decrypt_new_password(&reassembled, &wrong_hash).as_deref(), Some(new_password));
+    }
+
+    #[test]
+    fn test_reassemble_rejects_short_block() {
+        assert!(reassemble_nt_enc_pw(vec![(1, vec![0u8; 243])]).is_none());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(RadiusPacket::parse(&[1; 19]).unwrap_err(), ParseError::TooShort(19));
+        let mut data = vec![1, 7, 0, 24];
+        data.extend_from_slice(&[0; 16]);
+        data.extend_from_slice(&[ATTR_SESSION_TIMEOUT, 6, 0, 0]);
+        assert_eq!(RadiusPacket::parse(&data).unwrap_err(), ParseError::BadAttribute { offset: 20 });
+        data[3] = 30;
+        assert_eq!(RadiusPacket::parse(&data).unwrap_err(), ParseError::BadLength { length: 30, received: 24 });
+        assert_eq!(EapPacket::parse(&[EAP_RESPONSE, 1, 0, 3, 1]).unwrap_err(), ParseError::BadLength { length: 3, received: 5 });
+    }
+
+    fn arb_attribute() -> impl Strategy<Value = RadiusAttribute> {
+        (any::<u8>(), prop::collection::vec(any::<u8>(), 0..=253)).prop_map(|(typ, value)| RadiusAttribute { typ, value })
+    }
+
+    proptest! {
+        #[test]
+        fn prop_radius_packet_roundtrip(code: u8, identifier: u8, authenticator: [u8; 16],
+                                        attributes in prop::collection::vec(arb_attribute(), 0..15)) {
+            let packet = RadiusPacket { code, identifier, length: 0, authenticator, attributes };
+            let encoded = packet.encode();
+            let parsed = RadiusPacket::parse(&encoded).unwrap();
+            prop_assert_eq!(parsed.length as usize, encoded.len());
+            prop_assert_eq!((parsed.code, parsed.identifier, parsed.authenticator), (code, identifier, authenticator));
+            prop_assert_eq!(parsed.attributes.len(), packet.attributes.len());
+            prop_assert_eq!(parsed.encode(), encoded);
+        }
+
+        #[test]
+        fn prop_radius_parse_reencodes_input(data in prop::collection::vec(any::<u8>(), 0..512)) {
+            if let Ok(packet) = RadiusPacket::parse(&data) {
+                prop_assert_eq!(packet.encode(), &data[..packet.length as usize]);
+            }
+        }
+
+        #[test]
+        fn prop_eap_packet_roundtrip(code in 1u8..=4, identifier: u8, type_: u8,
+                                     data in prop::collection::vec(any::<u8>(), 0..1020)) {
+            let packet = match code {
+                EAP_SUCCESS | EAP_FAILURE => EapPacket { code, identifier, length: 0, type_: 0, data: Vec::new() },
+                _ => EapPacket { code, identifier, length: 0, type_, data },
+            };
+            let encoded = packet.encode();
+            let parsed = EapPacket::parse(&encoded).unwrap();
+            prop_assert_eq!(parsed.length as usize, encoded.len());
+            prop_assert_eq!((parsed.code, parsed.identifier, parsed.type_), (packet.code, packet.identifier, packet.type_));
+            prop_assert_eq!(&parsed.data, &packet.data);
+        }
+
+        #[test]
+        fn prop_eap_parse_never_overreads(data in prop::collection::vec(any::<u8>(), 0..64)) {
+            if let Ok(packet) = EapPacket::parse(&data) {
+                prop_assert!(packet.length as usize <= data.len());
+                prop_assert_eq!(packet.data.len(), (packet.length as usize).saturating_sub(5));
+            }
+        }
+    }
+}
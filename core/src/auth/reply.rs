@@ -0,0 +1,521 @@
+use std::net::{Ipv4Addr, Ipv6Addr};
+use hmac::{Hmac, Mac};
+use md5::{Digest, Md5};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tracing::{debug, warn};
+
+use super::crypto::Encryption;
+use super::radius_server::{RadiusAttribute, RadiusPacket};
+use super::vendor::{RateLimit, MAX_PRIVILEGE_LEVEL};
+
+type HmacMd5 = Hmac<Md5>;
+
+const ATTR_VENDOR_SPECIFIC: u8 = 26;
+const ATTR_MESSAGE_AUTHENTICATOR: u8 = 80;
+const ATTR_TUNNEL_PASSWORD: u8 = 69;
+
+// Longest value that fits in one attribute, and in one vendor-specific sub-attribute
+const MAX_VALUE_LEN: usize = 253;
+const MAX_VSA_VALUE_LEN: usize = MAX_VALUE_LEN - 6;
+
+// Salt offsets 0 and 1 belong to the MS-MPPE keys of the authentication stage
+const FIRST_GROUP_SALT_OFFSET: u8 = 2;
+
+/// Computes the Message-Authenticator of an encoded packet in place, if it has one.
+/// The header must already carry the authenticator the HMAC is computed over.
+pub(crate) fn sign_message_authenticator(encoded: &mut [u8], secret: &str) {
+    if let Some(pos) = message_authenticator_offset(encoded) {
+        encoded[pos + 2..pos + 18].fill(0);
+        let mut mac = <HmacMd5 as Mac>::new_from_slice(secret.as_bytes())
+            .expect("HMAC can take key of any size");
+        mac.update(encoded);
+        let msg_auth = mac.finalize().into_bytes();
+        encoded[pos + 2..pos + 18].copy_from_slice(&msg_auth);
+    }
+}
+
+/// Offset of the Message-Authenticator attribute in an encoded packet, found by walking the
+/// attributes: its type byte can also turn up in the header and in other attributes' values
+pub(super) fn message_authenticator_offset(encoded: &[u8]) -> Option<usize> {
+    let mut pos = 20;
+    while pos + 2 <= encoded.len() {
+        let len = encoded[pos + 1] as usize;
+        if len < 2 {
+            return None;
+        }
+        if encoded[pos] == ATTR_MESSAGE_AUTHENTICATOR && len == 18 && pos + 18 <= encoded.len() {
+            return Some(pos);
+        }
+        pos += len;
+    }
+    None
+}
+
+/// Encodes a reply to `request`: signs the Message-Authenticator (if present) and
+/// replaces the header authenticator with the Response Authenticator (RFC 2865, section 3)
+pub(super) fn encode_response(mut response: RadiusPacket, request: &RadiusPacket, secret: &str) -> Vec<u8> {
+    response.identifier = request.identifier;
+    response.authenticator = request.authenticator;
+    let mut encoded = response.encode();
+    sign_message_authenticator(&mut encoded, secret);
+
+    let mut hasher = Md5::new();
+    hasher.update(&encoded);
+    hasher.update(secret.as_bytes());
+    let response_auth = hasher.finalize();
+    encoded[4..20].copy_from_slice(&response_auth);
+    encoded
+}
+
+/// Lets an authorization stage edit the attributes of a reply that has already been
+/// encoded and signed. Message-Authenticator stays last and is recomputed.
+pub(super) fn amend_response(response: &[u8], request: &RadiusPacket, secret: &str,
+                             edit: impl FnOnce(&mut Vec<RadiusAttribute>)) -> Vec<u8> {
+    let Ok(mut packet) = RadiusPacket::parse(response) else {
+        warn!("Cannot amend a malformed reply, sending it unchanged");
+        return response.to_vec();
+    };
+
+    let had_msg_auth = packet.attributes.iter().any(|attr| attr.typ == ATTR_MESSAGE_AUTHENTICATOR);
+    packet.attributes.retain(|attr| attr.typ != ATTR_MESSAGE_AUTHENTICATOR);
+    edit(&mut packet.attributes);
+    if had_msg_auth {
+        packet.attributes.push(RadiusAttribute { typ: ATTR_MESSAGE_AUTHENTICATOR, value: vec![0u8; 16] });
+    }
+
+    encode_response(packet, request, secret)
+}
+
+/// Builds a standard attribute, or a Vendor-Specific one when `vendor_id` isn't 0
+pub(crate) fn build_attribute(vendor_id: u32, attribute_id: u8, value: Vec<u8>) -> Option<RadiusAttribute> {
+    if vendor_id == 0 {
+        return (value.len() <= MAX_VALUE_LEN).then_some(RadiusAttribute { typ: attribute_id, value });
+    }
+    if value.len() > MAX_VSA_VALUE_LEN {
+        return None;
+    }
+
+    let mut vsa = vendor_id.to_be_bytes().to_vec();
+    vsa.push(attribute_id);
+    vsa.push((value.len() + 2) as u8);
+    vsa.extend_from_slice(&value);
+    Some(RadiusAttribute { typ: ATTR_VENDOR_SPECIFIC, value: vsa })
+}
+
+/// Like `build_attribute`, with the value tagged when `tag` is set (standard tunnel attributes only).
+/// Tunnel-Password always carries a tag octet (RFC 2868, section 3.5), 0 when none is configured.
+pub(super) fn build_tagged_attribute(vendor_id: u32, attribute_id: u8, tag: Option<u8>, value: Vec<u8>) -> Option<RadiusAttribute> {
+    let tag = tag.or((vendor_id == 0 && attribute_id == ATTR_TUNNEL_PASSWORD).then_some(0));
+    match tag {
+        Some(tag) if vendor_id == 0 => RadiusAttribute::tagged(attribute_id, tag, &value),
+        Some(_) => None,
+        None => build_attribute(vendor_id, attribute_id, value),
+    }
+}
+
+/// How an authorization stage changes a reply attribute
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AttributeOp {
+    /// Replaces every existing instance of the attribute
+    #[default]
+    Set,
+    /// Appends another instance
+    Add,
+    /// Drops every instance
+    Remove,
+}
+
+/// One change to the reply attributes; `attribute` is the encoded value for set and add
+#[derive(Debug, Clone)]
+pub(super) struct AttributeEdit {
+    pub op: AttributeOp,
+    pub vendor_id: u32,
+    pub attribute_id: u8,
+    pub attribute: Option<RadiusAttribute>,
+}
+
+impl AttributeEdit {
+    pub fn apply(&self, attributes: &mut Vec<RadiusAttribute>) {
+        if self.op != AttributeOp::Add {
+            attributes.retain(|attr| !is_attribute(attr, self.vendor_id, self.attribute_id));
+        }
+        if self.op != AttributeOp::Remove
+            && let Some(attribute) = &self.attribute
+        {
+            attributes.push(attribute.clone());
+        }
+    }
+}
+
+/// An attribute change as written in JSON by the REST hook and authorization rules:
+/// `{"vendor_id": 0, "attribute_id": 27, "type": "integer", "value": "3600", "op": "set"}`,
+/// with an optional RFC 2868 `"tag"` for Tunnel-* attributes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct AttributeEditSpec {
+    #[serde(default)]
+    pub vendor_id: u32,
+    pub attribute_id: u8,
+    #[serde(default = "default_attribute_type", rename = "type")]
+    pub attribute_type: String,
+    #[serde(default)]
+    pub value: String,
+    #[serde(default)]
+    pub op: AttributeOp,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<u8>,
+}
+
+fn default_attribute_type() -> String { "string".to_string() }
+
+impl AttributeEditSpec {
+    pub fn compile(&self) -> Result<AttributeEdit, String> {
+        let attribute = if self.op == AttributeOp::Remove {
+            None
+        } else {
+            let attribute = encode_value(&self.attribute_type, &self.value)
+                .and_then(|value| build_tagged_attribute(self.vendor_id, self.attribute_id, self.tag, value))
+                .ok_or_else(|| format!("cannot encode attribute {} value {:?} as {}",
+                                       self.attribute_id, self.value, self.attribute_type))?;
+            Some(attribute)
+        };
+        Ok(AttributeEdit { op: self.op, vendor_id: self.vendor_id, attribute_id: self.attribute_id, attribute })
+    }
+}
+
+/// Whether `attr` is the standard attribute `attribute_id`, or that vendor attribute when `vendor_id` isn't 0
+pub(super) fn is_attribute(attr: &RadiusAttribute, vendor_id: u32, attribute_id: u8) -> bool {
+    if vendor_id == 0 {
+        return attr.typ == attribute_id;
+    }
+    attr.typ == ATTR_VENDOR_SPECIFIC && attr.value.len() >= 6
+        && u32::from_be_bytes([attr.value[0], attr.value[1], attr.value[2], attr.value[3]]) == vendor_id
+        && attr.value[4] == attribute_id
+}
+
+/// Encodes a configured attribute value according to its dictionary type
+/// (string, integer, ipaddr, ipv6addr, date or octets)
+pub(crate) fn encode_value(attribute_type: &str, value: &str) -> Option<Vec<u8>> {
+    match attribute_type {
+        "string" => Some(value.as_bytes().to_vec()),
+        "integer" => value.trim().parse::<u32>().ok().map(|v| v.to_be_bytes().to_vec()),
+        "ipaddr" => value.trim().parse::<Ipv4Addr>().ok().map(|ip| ip.octets().to_vec()),
+        "ipv6addr" => value.trim().parse::<Ipv6Addr>().ok().map(|ip| ip.octets().to_vec()),
+        "ipv6prefix" => {
+            let (prefix, length) = value.trim().split_once('/')?;
+            encode_ipv6_prefix(prefix.parse().ok()?, length.parse().ok()?)
+        }
+        "ifid" => parse_interface_id(value).map(|id| id.to_vec()),
+        "date" => {
+            let value = value.trim();
+            value.parse::<u32>().ok()
+                .or_else(|| chrono::DateTime::parse_from_rfc3339(value).ok().and_then(|dt| u32::try_from(dt.timestamp()).ok()))
+                .map(|ts| ts.to_be_bytes().to_vec())
+        }
+        "octets" => match value.strip_prefix("0x") {
+            Some(hex_value) => hex::decode(hex_value).ok(),
+            None => Some(value.as_bytes().to_vec()),
+        },
+        _ => None,
+    }
+}
+
+/// RFC 8044 ipv6prefix: a reserved byte, the prefix length, then the bytes the length covers.
+/// Bits past the length are cleared, as receivers may reject the attribute otherwise.
+pub(super) fn encode_ipv6_prefix(prefix: Ipv6Addr, length: u8) -> Option<Vec<u8>> {
+    if length > 128 {
+        return None;
+    }
+    let masked = u128::from(prefix) & u128::MAX.checked_shl(128 - length as u32).unwrap_or(0);
+    let mut value = vec![0, length];
+    value.extend_from_slice(&masked.to_be_bytes()[..(length as usize).div_ceil(8)]);
+    Some(value)
+}
+
+/// RFC 8044 ifid, written as four groups of hex digits like the low half of an IPv6 address: `0:0:0:1`
+pub(super) fn parse_interface_id(text: &str) -> Option<[u8; 8]> {
+    let groups: Vec<&str> = text.trim().split(':').collect();
+    if groups.len() != 4 {
+        return None;
+    }
+    let mut id = [0u8; 8];
+    for (i, group) in groups.iter().enumerate() {
+        let bytes = u16::from_str_radix(group, 16).ok()?.to_be_bytes();
+        id[i * 2..i * 2 + 2].copy_from_slice(&bytes);
+    }
+    Some(id)
+}
+
+/// A configured attribute whose value is encrypted with the authenticator of each request
+#[derive(Debug)]
+pub(super) struct EncryptedAttribute {
+    pub vendor_id: u32,
+    pub attribute_id: u8,
+    pub tag: Option<u8>,
+    pub encryption: Encryption,
+    pub value: Vec<u8>,
+}
+
+/// What an `AuthAttributeGroup` adds to the Access-Accept
+#[derive(Debug, Default)]
+pub(super) struct AttributeGroup {
+    pub attributes: Vec<RadiusAttribute>,
+    pub encrypted: Vec<EncryptedAttribute>,
+    /// VLAN ID or name, resolved per NAS
+    pub vlan: Option<String>,
+    /// Vendor-neutral intents, translated for each NAS by its vendor profile
+    pub rate_limit: Option<RateLimit>,
+    pub acl_name: Option<String>,
+    pub privilege_level: Option<u8>,
+}
+
+impl AttributeGroup {
+    /// Reply attributes for the request with `authenticator`, encrypting the ones that need it
+    pub fn reply_attributes(&self, authenticator: &[u8], secret: &str) -> Vec<RadiusAttribute> {
+        let mut attributes = self.attributes.clone();
+        for (offset, attribute) in (FIRST_GROUP_SALT_OFFSET..).zip(&self.encrypted) {
+            let encoded = attribute.encryption.encrypt(&attribute.value, authenticator, secret, offset)
+                .and_then(|value| build_tagged_attribute(attribute.vendor_id, attribute.attribute_id, attribute.tag, value));
+            match encoded {
+                Some(encoded) => attributes.push(encoded),
+                None => warn!("Skipping attribute {}:{}: value too long for {:?} encryption",
+                              attribute.vendor_id, attribute.attribute_id, attribute.encryption),
+            }
+        }
+        attributes
+    }
+}
+
+/// Reply attributes and VLAN of an `AuthAttributeGroup`, in the order the admin UI lists them
+pub(super) async fn load_attribute_group(pool: &PgPool, group_name: &str) -> Result<AttributeGroup, sqlx::Error> {
+    #[derive(sqlx::FromRow)]
+    struct AttributeRow {
+        vlan: String,
+        rate_limit: String,
+        acl_name: String,
+        privilege_level: Option<i16>,
+        vendor_id: Option<i32>,
+        attribute_id: Option<i32>,
+        attribute_name: Option<String>,
+        attribute_type: Option<String>,
+        attribute_value: Option<String>,
+        tag: Option<i16>,
+        encryption: Option<String>,
+    }
+
+    let db_timer = crate::metrics::DB_QUERY_DURATION.with_label_values(&["attribute_group"]).start_timer();
+    let rows = sqlx::query_as::<_, AttributeRow>(
+        r#"
+        SELECT g.vlan, g.rate_limit, g.acl_name, g.privilege_level, a.vendor_id, a.attribute_id, a.attribute_name, a.attribute_type, a.attribute_value, a.tag,
+               COALESCE(NULLIF(a.encryption, ''), NULLIF(d.encryption, '')) AS encryption
+        FROM radius_auth_attribute_group g
+        LEFT JOIN radius_radius_attribute a ON a.group_id = g.id
+        LEFT JOIN nas_vendor v ON v.vendor_id = a.vendor_id
+        LEFT JOIN nas_vendor_attribute d ON d.vendor_id = v.id AND d.attribute_id = a.attribute_id
+        WHERE g.name = $1
+        ORDER BY a.vendor_id, a.attribute_id
+        "#
+    )
+    .bind(group_name)
+    .fetch_all(pool)
+    .await?;
+    db_timer.observe_duration();
+
+    let mut group = AttributeGroup {
+        attributes: Vec::with_capacity(rows.len()),
+        ..AttributeGroup::default()
+    };
+    if let Some(row) = rows.first() {
+        group.vlan = Some(row.vlan.clone()).filter(|vlan| !vlan.is_empty());
+        group.acl_name = Some(row.acl_name.clone()).filter(|acl| !acl.is_empty());
+        group.privilege_level = row.privilege_level.and_then(|level| u8::try_from(level).ok()).filter(|level| *level <= MAX_PRIVILEGE_LEVEL);
+        if !row.rate_limit.is_empty() {
+            match row.rate_limit.parse::<RateLimit>() {
+                Ok(rate_limit) => group.rate_limit = Some(rate_limit),
+                Err(e) => warn!("Ignoring rate limit of group {}: {}", group_name, e),
+            }
+        }
+    }
+    for row in rows {
+        let (Some(vendor_id), Some(attribute_id), Some(name), Some(attribute_type), Some(value)) =
+            (row.vendor_id, row.attribute_id, row.attribute_name, row.attribute_type, row.attribute_value) else {
+            // A group without attributes
+            continue;
+        };
+        let tag = row.tag.and_then(|tag| u8::try_from(tag).ok());
+        let (Ok(attribute_id), Some(encoded)) = (u8::try_from(attribute_id), encode_value(&attribute_type, &value)) else {
+            warn!("Skipping attribute {} in group {}: cannot encode {:?} as {}", name, group_name, value, attribute_type);
+            continue;
+        };
+
+        // The attribute's own scheme, then the dictionary's, then the one the RFCs mandate
+        let encryption = match row.encryption.as_deref().map(str::parse::<Encryption>).transpose() {
+            Ok(encryption) => encryption.or(Encryption::default_for(vendor_id as u32, attribute_id)),
+            Err(e) => {
+                warn!("Skipping attribute {} in group {}: {}", name, group_name, e);
+                continue;
+            }
+        };
+        if let Some(encryption) = encryption {
+            group.encrypted.push(EncryptedAttribute { vendor_id: vendor_id as u32, attribute_id, tag, encryption, value: encoded });
+            continue;
+        }
+
+        match build_tagged_attribute(vendor_id as u32, attribute_id, tag, encoded) {
+            Some(attribute) => group.attributes.push(attribute),
+            None => warn!("Skipping attribute {} in group {}: cannot encode {:?} as {}",
+                          name, group_name, value, attribute_type),
+        }
+    }
+    debug!("Attribute group {} has {} reply attributes ({} encrypted), VLAN {:?}",
+           group_name, group.attributes.len() + group.encrypted.len(), group.encrypted.len(), group.vlan);
+    Ok(group)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_authenticator_offset() {
+        // Identifier, authenticator and Reply-Message all contain the attribute's type and length
+        let mut packet = RadiusPacket {
+            code: 2,
+            identifier: ATTR_MESSAGE_AUTHENTICATOR,
+            length: 0,
+            authenticator: [ATTR_MESSAGE_AUTHENTICATOR, 18, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+            attributes: vec![RadiusAttribute { typ: 18, value: vec![ATTR_MESSAGE_AUTHENTICATOR, 18] }],
+        };
+        assert_eq!(message_authenticator_offset(&packet.encode()), None);
+        packet.attributes.push(RadiusAttribute { typ: ATTR_MESSAGE_AUTHENTICATOR, value: vec![0; 16] });
+        assert_eq!(message_authenticator_offset(&packet.encode()), Some(24));
+    }
+
+    #[test]
+    fn test_encode_value() {
+        assert_eq!(encode_value("integer", "3600"), Some(vec![0, 0, 0x0e, 0x10]));
+        assert_eq!(encode_value("ipaddr", "10.0.0.1"), Some(vec![10, 0, 0, 1]));
+        assert_eq!(encode_value("date", "1970-01-01T00:01:00Z"), Some(vec![0, 0, 0, 60]));
+        assert_eq!(encode_value("octets", "0x0a0b"), Some(vec![0x0a, 0x0b]));
+        assert_eq!(encode_value("ipv6prefix", "2001:db8:ab:cd00::/56"), Some(vec![0, 56, 0x20, 0x01, 0x0d, 0xb8, 0, 0xab, 0xcd]));
+        assert_eq!(encode_value("ipv6prefix", "2001:db8::1/0"), Some(vec![0, 0]));
+        assert_eq!(encode_value("ipv6prefix", "2001:db8::/129"), None);
+        assert_eq!(encode_value("ifid", "0:0:0:1"), Some(vec![0, 0, 0, 0, 0, 0, 0, 1]));
+        assert_eq!(encode_value("ifid", "::1"), None);
+        assert_eq!(encode_value("integer", "lots"), None);
+        assert_eq!(encode_value("unknown", "x"), None);
+
+        let vsa = build_attribute(14988, 2, b"admins".to_vec()).unwrap();
+        assert_eq!(vsa.typ, ATTR_VENDOR_SPECIFIC);
+        assert_eq!(vsa.value, [&[0, 0, 0x3a, 0x8c, 2, 8][..], b"admins"].concat());
+        assert!(build_attribute(0, 18, vec![b'x'; 254]).is_none());
+    }
+
+    #[test]
+    fn test_attribute_edits() {
+        let mut attributes = vec![
+            RadiusAttribute { typ: 27, value: 3600u32.to_be_bytes().to_vec() },
+            build_attribute(14988, 2, b"admins".to_vec()).unwrap(),
+            RadiusAttribute { typ: 18, value: b"hello".to_vec() },
+        ];
+        let edit = |op, vendor_id, attribute_id, value: &[u8]| AttributeEdit {
+            op,
+            vendor_id,
+            attribute_id,
+            attribute: build_attribute(vendor_id, attribute_id, value.to_vec()),
+        };
+
+        edit(AttributeOp::Set, 0, 27, &60u32.to_be_bytes()).apply(&mut attributes);
+        edit(AttributeOp::Add, 0, 18, b"world").apply(&mut attributes);
+        edit(AttributeOp::Remove, 14988, 2, b"").apply(&mut attributes);
+
+        let summary: Vec<(u8, Vec<u8>)> = attributes.into_iter().map(|attr| (attr.typ, attr.value)).collect();
+        assert_eq!(summary, vec![
+            (18, b"hello".to_vec()),
+            (27, 60u32.to_be_bytes().to_vec()),
+            (18, b"world".to_vec()),
+        ]);
+    }
+
+    #[test]
+    fn test_encrypted_group_attributes() {
+        let group = AttributeGroup {
+            attributes: vec![RadiusAttribute { typ: 27, value: 60u32.to_be_bytes().to_vec() }],
+            encrypted: vec![EncryptedAttribute {
+                vendor_id: 0,
+                attribute_id: 69,
+                tag: Some(1),
+                encryption: Encryption::default_for(0, 69).unwrap(),
+                value: b"tunnel-pass".to_vec(),
+            }],
+            ..AttributeGroup::default()
+        };
+        let authenticator = [5u8; 16];
+        let attributes = group.reply_attributes(&authenticator, "testing123");
+        assert_eq!(attributes.len(), 2);
+
+        // Tag, then a salt with the high bit set and the group's first offset
+        let (tag, value) = attributes[1].tagged_value().unwrap();
+        assert_eq!((attributes[1].typ, tag), (69, 1));
+        assert_eq!(value[0] & 0xf8, 0x80 | (FIRST_GROUP_SALT_OFFSET << 3));
+        let string = crate::auth::crypto::salt_decrypt(&value[2..], &authenticator, "testing123", &value[..2]);
+        assert_eq!(&string[..12], b"\x0btunnel-pass");
+    }
+
+    #[test]
+    fn test_untagged_tunnel_password_gets_tag_zero() {
+        let group = AttributeGroup {
+            encrypted: vec![EncryptedAttribute {
+                vendor_id: 0,
+                attribute_id: 69,
+                tag: None,
+                encryption: Encryption::default_for(0, 69).unwrap(),
+                value: b"tunnel-pass".to_vec(),
+            }],
+            ..AttributeGroup::default()
+        };
+        let authenticator = [5u8; 16];
+        let attributes = group.reply_attributes(&authenticator, "testing123");
+
+        // Tag 0, then the 2-byte salt and one 16-byte block
+        assert_eq!(attributes[0].value.len(), 1 + 2 + 16);
+        let (tag, value) = attributes[0].tagged_value().unwrap();
+        assert_eq!(tag, 0);
+        let string = crate::auth::crypto::salt_decrypt(&value[2..], &authenticator, "testing123", &value[..2]);
+        assert_eq!(&string[..12], b"\x0btunnel-pass");
+    }
+
+    #[test]
+    fn test_amend_response_resigns() {
+        let secret = "testing123";
+        let request = RadiusPacket { code: 1, identifier: 9, length: 0, authenticator: [7u8; 16], attributes: Vec::new() };
+        let accept = RadiusPacket {
+            code: 2,
+            identifier: 9,
+            length: 0,
+            authenticator: [0u8; 16],
+            attributes: vec![RadiusAttribute { typ: ATTR_MESSAGE_AUTHENTICATOR, value: vec![0u8; 16] }],
+        };
+        let encoded = encode_response(accept, &request, secret);
+
+        let amended = amend_response(&encoded, &request, secret, |attributes| {
+            attributes.push(RadiusAttribute { typ: 27, value: 60u32.to_be_bytes().to_vec() });
+        });
+        let packet = RadiusPacket::parse(&amended).unwrap();
+        assert_eq!(packet.attributes[0].typ, 27);
+        assert_eq!(packet.attributes[1].typ, ATTR_MESSAGE_AUTHENTICATOR);
+
+        // Response Authenticator and Message-Authenticator both verify against the request
+        let mut check = amended.clone();
+        check[4..20].copy_from_slice(&request.authenticator);
+        let mut hasher = Md5::new();
+        hasher.update(&check);
+        hasher.update(secret.as_bytes());
+        assert_eq!(&amended[4..20], hasher.finalize().as_slice());
+
+        let received_mac = packet.attributes[1].value.clone();
+        sign_message_authenticator(&mut check, secret);
+        assert_eq!(&check[check.len() - 16..], received_mac.as_slice());
+    }
+}
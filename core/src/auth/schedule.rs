@@ -0,0 +1,299 @@
+use chrono::{DateTime, Datelike, Duration, FixedOffset, Local, NaiveDateTime, NaiveTime, TimeZone, Timelike, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use sqlx::PgPool;
+use sqlx::types::JsonValue;
+use tracing::{debug, warn};
+
+use super::models::NasDevice;
+
+const SECONDS_PER_DAY: u32 = 24 * 60 * 60;
+
+/// Windows are followed across boundaries for at most this many steps; a longer chain means access is never cut
+const MAX_CHAINED_WINDOWS: usize = 8;
+
+/// Clock of a NAS: its IANA zone when known, otherwise the fixed offset stored with it
+#[derive(Debug, Clone, Copy)]
+pub enum NasClock {
+    Zone(Tz),
+    Offset(FixedOffset),
+    ServerLocal,
+}
+
+impl NasClock {
+    pub fn of(nas: Option<&NasDevice>) -> Self {
+        let Some(nas) = nas else {
+            return NasClock::ServerLocal;
+        };
+        if let Some(zone) = nas.timezone_name.as_deref().and_then(|name| name.parse::<Tz>().ok()) {
+            return NasClock::Zone(zone);
+        }
+        match nas.timezone_offset.and_then(|minutes| FixedOffset::east_opt(minutes * 60)) {
+            Some(offset) => NasClock::Offset(offset),
+            None => NasClock::ServerLocal,
+        }
+    }
+
+    pub fn local_time(&self, now: DateTime<Utc>) -> NaiveDateTime {
+        match self {
+            NasClock::Zone(zone) => now.with_timezone(zone).naive_local(),
+            NasClock::Offset(offset) => now.with_timezone(offset).naive_local(),
+            NasClock::ServerLocal => now.with_timezone(&Local).naive_local(),
+        }
+    }
+
+    /// Instant of a local wall-clock time; the earlier one when a DST change makes it ambiguous
+    fn to_utc(self, local: NaiveDateTime) -> Option<DateTime<Utc>> {
+        match self {
+            NasClock::Zone(zone) => zone.from_local_datetime(&local).earliest().map(|time| time.with_timezone(&Utc)),
+            NasClock::Offset(offset) => offset.from_local_datetime(&local).earliest().map(|time| time.with_timezone(&Utc)),
+            NasClock::ServerLocal => Local.from_local_datetime(&local).earliest().map(|time| time.with_timezone(&Utc)),
+        }
+    }
+}
+
+/// Result of checking the access schedules of an identity at login time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessWindow {
+    Unrestricted,
+    Outside,
+    /// Inside a window; the session must end after this many seconds
+    Until(u32),
+}
+
+impl AccessWindow {
+    /// Both restrictions at once: the stricter of the two
+    fn and(self, other: AccessWindow) -> AccessWindow {
+        match (self, other) {
+            (AccessWindow::Outside, _) | (_, AccessWindow::Outside) => AccessWindow::Outside,
+            (AccessWindow::Until(a), AccessWindow::Until(b)) => AccessWindow::Until(a.min(b)),
+            (AccessWindow::Until(a), _) | (_, AccessWindow::Until(a)) => AccessWindow::Until(a),
+            _ => AccessWindow::Unrestricted,
+        }
+    }
+}
+
+/// `{"days": ["mon", "tue"], "hours": "08:00-18:00"}`; no days means every day, no hours the whole day
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WindowSpec {
+    #[serde(default)]
+    days: Vec<String>,
+    #[serde(default)]
+    hours: Option<String>,
+}
+
+#[derive(Debug)]
+struct Window {
+    days: Vec<Weekday>,
+    /// Seconds since midnight; `end` may be 24:00, and is before `start` for windows that wrap past midnight
+    start: u32,
+    end: u32,
+}
+
+impl Window {
+    fn compile(spec: WindowSpec) -> Result<Self, String> {
+        let days = spec.days.iter()
+            .map(|day| day.parse::<Weekday>().map_err(|_| format!("unknown day {:?}", day)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let (start, end) = match spec.hours.as_deref() {
+            None => (0, SECONDS_PER_DAY),
+            Some(hours) => {
+                let (start, end) = hours.split_once('-')
+                    .and_then(|(start, end)| Some((parse_clock(start)?, parse_clock(end)?)))
+                    .ok_or_else(|| format!("expected hours like 08:00-18:00, got {:?}", hours))?;
+                if start == end || start == SECONDS_PER_DAY {
+                    return Err(format!("empty window {:?}", hours));
+                }
+                (start, end)
+            }
+        };
+        Ok(Self { days, start, end })
+    }
+
+    fn on(&self, day: Weekday) -> bool {
+        self.days.is_empty() || self.days.contains(&day)
+    }
+
+    /// End of this window if `time` falls inside it
+    fn end_after(&self, time: NaiveDateTime) -> Option<NaiveDateTime> {
+        let date = time.date();
+        let now = time.num_seconds_from_midnight();
+        let at = |date: chrono::NaiveDate, seconds: u32| date.and_time(NaiveTime::MIN) + Duration::seconds(seconds as i64);
+
+        if self.start < self.end {
+            return (self.on(date.weekday()) && self.start <= now && now < self.end).then(|| at(date, self.end));
+        }
+        // Wrapping windows belong to the day they start on
+        if self.on(date.weekday()) && now >= self.start {
+            return date.succ_opt().map(|next| at(next, self.end));
+        }
+        if self.on(date.weekday().pred()) && now < self.end {
+            return Some(at(date, self.end));
+        }
+        None
+    }
+}
+
+fn parse_clock(text: &str) -> Option<u32> {
+    let text = text.trim();
+    if text == "24:00" {
+        return Some(SECONDS_PER_DAY);
+    }
+    NaiveTime::parse_from_str(text, "%H:%M").ok().map(|time| time.num_seconds_from_midnight())
+}
+
+/// Weekly access windows of a user group or identifier, in the NAS's local time
+#[derive(Debug)]
+pub struct AccessSchedule {
+    windows: Vec<Window>,
+}
+
+impl AccessSchedule {
+    pub fn parse(value: &JsonValue) -> Result<Self, String> {
+        let specs: Vec<WindowSpec> = serde_json::from_value(value.clone()).map_err(|e| e.to_string())?;
+        let windows = specs.into_iter().map(Window::compile).collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { windows })
+    }
+
+    /// Latest end among the windows containing `time`
+    fn window_end(&self, time: NaiveDateTime) -> Option<NaiveDateTime> {
+        self.windows.iter().filter_map(|window| window.end_after(time)).max()
+    }
+
+    /// Access at `time`, following windows that start where the current one ends
+    fn check(&self, time: NaiveDateTime, clock: NasClock) -> AccessWindow {
+        if self.windows.is_empty() {
+            return AccessWindow::Unrestricted;
+        }
+        let Some(mut end) = self.window_end(time) else {
+            return AccessWindow::Outside;
+        };
+        for _ in 0..MAX_CHAINED_WINDOWS {
+            match self.window_end(end) {
+                Some(next) => end = next,
+                None => {
+                    let seconds = match (clock.to_utc(time), clock.to_utc(end)) {
+                        (Some(start), Some(end)) => (end - start).num_seconds(),
+                        _ => (end - time).num_seconds(),
+                    };
+                    return AccessWindow::Until(seconds.clamp(1, u32::MAX as i64) as u32);
+                }
+            }
+        }
+        AccessWindow::Unrestricted
+    }
+}
+
+/// Combined schedule check for an identity: its own schedule replaces those of its groups, which all apply.
+///
+/// `groups` are the names of directory groups, for identities that don't live in `user_identifiers`.
+pub async fn check_access(pool: &PgPool, identifier_id: Option<i64>, groups: &[String], clock: NasClock)
+    -> Result<AccessWindow, sqlx::Error> {
+    // Neither stored in Postgres nor in any group: nothing could match
+    if identifier_id.is_none() && groups.is_empty() {
+        return Ok(AccessWindow::Unrestricted);
+    }
+
+    #[derive(sqlx::FromRow)]
+    struct ScheduleRow {
+        source: String,
+        access_schedule: JsonValue,
+    }
+
+    let rows = sqlx::query_as::<_, ScheduleRow>(
+        r#"
+        SELECT 'identifier' AS source, ui.access_schedule
+        FROM user_identifiers ui
+        WHERE ui.id = $1 AND ui.access_schedule IS NOT NULL
+        UNION ALL
+        SELECT 'group ' || g.name, g.access_schedule
+        FROM user_groups g
+        WHERE g.access_schedule IS NOT NULL
+          AND (g.name = ANY($2) OR g.id IN (
+              SELECT ug.usergroup_id
+              FROM user_identifiers ui
+              JOIN users_groups ug ON ug.user_id = ui.user_id
+              WHERE ui.id = $1
+          ))
+        "#
+    )
+    .bind(identifier_id)
+    .bind(groups)
+    .fetch_all(pool)
+    .await?;
+
+    let own = rows.iter().any(|row| row.source == "identifier");
+    let time = clock.local_time(Utc::now());
+    let mut access = AccessWindow::Unrestricted;
+    for row in rows.iter().filter(|row| !own || row.source == "identifier") {
+        match AccessSchedule::parse(&row.access_schedule) {
+            Ok(schedule) => {
+                let window = schedule.check(time, clock);
+                debug!("Access schedule of {} at {}: {:?}", row.source, time, window);
+                access = access.and(window);
+            }
+            Err(e) => warn!("Ignoring invalid access schedule of {}: {}", row.source, e),
+        }
+    }
+    Ok(access)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn at(time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_office_hours() {
+        let clock = NasClock::Offset(FixedOffset::east_opt(0).unwrap());
+        let schedule = AccessSchedule::parse(&json!([
+            { "days": ["mon", "tue", "wed", "thu", "fri"], "hours": "08:00-18:00" },
+        ])).unwrap();
+
+        // 2024-06-03 is a Monday
+        assert_eq!(schedule.check(at("2024-06-03 17:30"), clock), AccessWindow::Until(30 * 60));
+        assert_eq!(schedule.check(at("2024-06-03 18:00"), clock), AccessWindow::Outside);
+        assert_eq!(schedule.check(at("2024-06-08 10:00"), clock), AccessWindow::Outside);
+
+        // Night shift wrapping past midnight, chained into a morning window
+        let schedule = AccessSchedule::parse(&json!([
+            { "days": ["fri"], "hours": "22:00-06:00" },
+            { "days": ["sat"], "hours": "06:00-07:00" },
+        ])).unwrap();
+        assert_eq!(schedule.check(at("2024-06-07 23:00"), clock), AccessWindow::Until(8 * 3600));
+        assert_eq!(schedule.check(at("2024-06-08 05:00"), clock), AccessWindow::Until(2 * 3600));
+        assert_eq!(schedule.check(at("2024-06-09 05:00"), clock), AccessWindow::Outside);
+
+        assert_eq!(AccessSchedule::parse(&json!([{}])).unwrap().check(at("2024-06-03 12:00"), clock), AccessWindow::Unrestricted);
+        assert!(AccessSchedule::parse(&json!([{ "hours": "18:00-18:00" }])).is_err());
+        assert!(AccessSchedule::parse(&json!([{ "days": ["someday"] }])).is_err());
+    }
+
+    #[test]
+    fn test_nas_clock() {
+        let nas = NasDevice {
+            id: 1,
+            name: "ap-1".to_string(),
+            nas_identifier: "ap-1".to_string(),
+            ip_address: "192.0.2.1".to_string(),
+            vendor_id: None,
+            is_active: true,
+            coa_enabled: false,
+            coa_port: 3799,
+            timezone_name: Some("Europe/Paris".to_string()),
+            timezone_offset: Some(60),
+            vlan_map: Default::default(),
+        };
+        let now = Utc.with_ymd_and_hms(2024, 7, 1, 12, 0, 0).unwrap();
+        // Summer time, which the fixed offset doesn't know about
+        assert_eq!(NasClock::of(Some(&nas)).local_time(now), at("2024-07-01 14:00"));
+
+        let nas = NasDevice { timezone_name: Some("UTC+1".to_string()), ..nas };
+        assert_eq!(NasClock::of(Some(&nas)).local_time(now), at("2024-07-01 13:00"));
+    }
+}
@@ -0,0 +1,210 @@
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use mongodb::{Client, Collection, bson::{doc, DateTime as BsonDateTime}};
+use openrdx_dynauth::{Request, SessionSelector};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tracing::{debug, info};
+
+use super::backend::BackendError;
+use crate::config::EnvOverrides;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SimultaneousUseConfig {
+    pub enabled: bool,
+    /// Disconnect the oldest session through the NAS's CoA port instead of rejecting the new login
+    pub kick_oldest: bool,
+    /// Per attempt; a Disconnect-Request is retransmitted `disconnect_retries` times before giving up
+    pub disconnect_timeout_ms: u64,
+    pub disconnect_retries: u32,
+}
+
+impl Default for SimultaneousUseConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            kick_oldest: false,
+            disconnect_timeout_ms: 2000,
+            disconnect_retries: 2,
+        }
+    }
+}
+
+impl SimultaneousUseConfig {
+    pub fn apply_env(&mut self, env: &mut EnvOverrides) {
+        env.set_bool("SIMULTANEOUS_USE_ENABLED", &mut self.enabled);
+        env.set_bool("SIMULTANEOUS_USE_KICK_OLDEST", &mut self.kick_oldest);
+        env.set("SIMULTANEOUS_USE_DISCONNECT_TIMEOUT_MS", &mut self.disconnect_timeout_ms);
+        env.set("SIMULTANEOUS_USE_DISCONNECT_RETRIES", &mut self.disconnect_retries);
+    }
+}
+
+/// The part of an accounting `sessions` document needed to count and disconnect it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LiveSession {
+    pub session_id: String,
+    pub username: String,
+    pub nas_ip: String,
+    pub nas_port: u32,
+    pub start_time: DateTime<Utc>,
+}
+
+/// Open sessions of a user, read from the `sessions` collection the accounting service maintains
+pub struct SessionLimiter {
+    config: SimultaneousUseConfig,
+    sessions: Option<Collection<LiveSession>>,
+}
+
+impl SessionLimiter {
+    pub async fn new(config: SimultaneousUseConfig, mongo_url: &str, mongo_db_name: &str)
+        -> Result<Self, Box<dyn std::error::Error>> {
+        if !config.enabled {
+            info!("Simultaneous-Use checks disabled");
+            return Ok(Self { config, sessions: None });
+        }
+
+        let client = Client::with_uri_str(mongo_url).await?;
+        let sessions = client.database(mongo_db_name).collection::<LiveSession>("sessions");
+        Ok(Self { config, sessions: Some(sessions) })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.sessions.is_some()
+    }
+
+    pub fn kick_oldest(&self) -> bool {
+        self.config.kick_oldest
+    }
+
+    /// Sessions without an Accounting-Stop, oldest first
+    pub async fn open_sessions(&self, username: &str) -> Result<Vec<LiveSession>, BackendError> {
+        let Some(sessions) = &self.sessions else {
+            return Ok(Vec::new());
+        };
+
+        let db_timer = crate::metrics::DB_QUERY_DURATION.with_label_values(&["open_sessions"]).start_timer();
+        let mut cursor = sessions.find(doc! { "username": username, "stop_time": null }).await?;
+        let mut open = Vec::new();
+        while cursor.advance().await? {
+            open.push(cursor.deserialize_current()?);
+        }
+        db_timer.observe_duration();
+
+        open.sort_by_key(|session: &LiveSession| session.start_time);
+        Ok(open)
+    }
+
+    /// Marks a session the NAS acknowledged disconnecting as stopped, ahead of its Accounting-Stop
+    pub async fn close(&self, session: &LiveSession) -> Result<(), BackendError> {
+        if let Some(sessions) = &self.sessions {
+            sessions.update_one(
+                doc! { "session_id": &session.session_id, "stop_time": null },
+                doc! { "$set": { "stop_time": BsonDateTime::now(), "termination_cause": "Admin-Reset" } },
+            ).await?;
+        }
+        Ok(())
+    }
+
+    /// Sends a Disconnect-Request for `session`, identified by User-Name, Acct-Session-Id and NAS-IP-Address;
+    /// true when the NAS acknowledged it
+    pub async fn disconnect(&self, target: SocketAddr, secret: &str, session: &LiveSession) -> Result<bool, BackendError> {
+        let request = Request::disconnect(SessionSelector {
+            acct_session_id: Some(session.session_id.clone()),
+            username: Some(session.username.clone()),
+            nas_ip: session.nas_ip.parse::<Ipv4Addr>().ok(),
+            ..Default::default()
+        });
+        let client = openrdx_dynauth::Client::new(Duration::from_millis(self.config.disconnect_timeout_ms), self.config.disconnect_retries);
+        let reply = client.send(target, secret, &request).await?;
+
+        if reply.acked {
+            debug!("{} disconnected session {} of {}", target, session.session_id, session.username);
+        } else {
+            match reply.error_cause {
+                Some(cause) => debug!("{} refused to disconnect session {} of {}: {}", target, session.session_id, session.username, cause),
+                None => debug!("{} refused to disconnect session {} of {}", target, session.session_id, session.username),
+            }
+        }
+        Ok(reply.acked)
+    }
+}
+
+/// Session limit of an identity: its own `max_sessions`, otherwise the smallest limit among its groups.
+///
+/// `groups` are the names of directory groups, for identities that don't live in `user_identifiers`.
+pub async fn load_session_limit(pool: &PgPool, identifier_id: Option<i64>, groups: &[String])
+    -> Result<Option<u32>, sqlx::Error> {
+    let limit = sqlx::query_scalar::<_, Option<i32>>(
+        r#"
+        SELECT COALESCE(
+            (SELECT NULLIF(ui.max_sessions, 0) FROM user_identifiers ui WHERE ui.id = $1),
+            (SELECT MIN(g.max_sessions)
+             FROM user_groups g
+             WHERE g.max_sessions > 0
+               AND (g.name = ANY($2) OR g.id IN (
+                   SELECT ug.usergroup_id
+                   FROM user_identifiers ui
+                   JOIN users_groups ug ON ug.user_id = ui.user_id
+                   WHERE ui.id = $1
+               )))
+        )
+        "#
+    )
+    .bind(identifier_id)
+    .bind(groups)
+    .fetch_one(pool)
+    .await?;
+    Ok(limit.map(|limit| limit as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use md5::{Digest, Md5};
+    use tokio::net::UdpSocket;
+
+    #[tokio::test]
+    async fn test_disconnect_request() {
+        let secret = "coa-secret";
+        let nas = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let target = nas.local_addr().unwrap();
+
+        // A NAS that checks the request authenticator and acknowledges
+        let responder = tokio::spawn(async move {
+            let mut buf = [0u8; 4096];
+            let (len, peer) = nas.recv_from(&mut buf).await.unwrap();
+            let request = buf[..len].to_vec();
+            let mut zeroed = request.clone();
+            zeroed[4..20].fill(0);
+            let mut hasher = Md5::new();
+            hasher.update(&zeroed);
+            hasher.update(secret.as_bytes());
+            assert_eq!(hasher.finalize().as_slice(), &request[4..20]);
+
+            let mut reply = vec![41, request[1], 0, 20];
+            let mut hasher = Md5::new();
+            hasher.update(&reply);
+            hasher.update(&request[4..20]);
+            hasher.update(secret.as_bytes());
+            reply.extend_from_slice(&hasher.finalize());
+            nas.send_to(&reply, peer).await.unwrap();
+            request
+        });
+
+        let limiter = SessionLimiter { config: SimultaneousUseConfig::default(), sessions: None };
+        let session = LiveSession {
+            session_id: "5A3F0001".to_string(),
+            username: "alice".to_string(),
+            nas_ip: "192.0.2.1".to_string(),
+            nas_port: 7,
+            start_time: Utc::now(),
+        };
+        assert!(limiter.disconnect(target, secret, &session).await.unwrap());
+
+        let request = responder.await.unwrap();
+        assert_eq!(request[0], 40);
+        assert_eq!(&request[20..], b"\x01\x07alice\x04\x06\xc0\x00\x02\x01\x2c\x0a5A3F0001");
+    }
+}
@@ -0,0 +1,188 @@
+use async_trait::async_trait;
+use ipnetwork::IpNetwork;
+use sqlx::PgPool;
+use sqlx::types::JsonValue;
+use std::net::IpAddr;
+use tracing::{debug, warn};
+
+use super::backend::BackendError;
+use super::models::NasDevice;
+use super::vlan;
+
+/// A shared secret and the source subnets of the clients using it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientSecret {
+    pub secret: String,
+    pub subnets: Vec<IpNetwork>,
+}
+
+impl ClientSecret {
+    /// The secret of the most specific subnet containing `ip`
+    pub fn lookup(secrets: &[ClientSecret], ip: IpAddr) -> Option<&str> {
+        secrets.iter()
+            .flat_map(|secret| secret.subnets.iter().map(move |subnet| (subnet, secret)))
+            .filter(|(subnet, _)| subnet.contains(ip))
+            .max_by_key(|(subnet, _)| subnet.prefix())
+            .map(|(_, secret)| secret.secret.as_str())
+    }
+}
+
+/// Source of the NAS devices and shared secrets the services accept requests from
+#[async_trait]
+pub trait ClientStore: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Active NAS devices that have a NAS-Identifier
+    async fn load_nas_devices(&self) -> Result<Vec<NasDevice>, BackendError>;
+
+    async fn load_secrets(&self) -> Result<Vec<ClientSecret>, BackendError>;
+}
+
+/// NAS devices and secrets managed in the admin UI
+pub struct PostgresClientStore {
+    pool: PgPool,
+}
+
+impl PostgresClientStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl ClientStore for PostgresClientStore {
+    fn name(&self) -> &'static str {
+        "postgres"
+    }
+
+    async fn load_nas_devices(&self) -> Result<Vec<NasDevice>, BackendError> {
+        #[derive(sqlx::FromRow)]
+        struct NasDeviceRow {
+            id: i64,
+            name: String,
+            nas_identifier: String,
+            ip_address: String,
+            vendor_id: Option<i32>,
+            is_active: bool,
+            coa_enabled: bool,
+            coa_port: i32,
+            timezone_name: Option<String>,
+            timezone_offset: Option<i32>,
+            vlan_map: JsonValue,
+        }
+
+        let db_timer = crate::metrics::DB_QUERY_DURATION.with_label_values(&["load_nas_devices"]).start_timer();
+        let rows = sqlx::query_as::<_, NasDeviceRow>(
+            r#"
+            SELECT
+                nas_nas.id,
+                nas_nas.name,
+                nas_nas.nas_identifier,
+                nas_nas.ip_address,
+                nas_vendor.vendor_id,
+                nas_nas.is_active,
+                nas_nas.coa_enabled,
+                nas_nas.coa_port,
+                timezones.name AS timezone_name,
+                timezones.offset AS timezone_offset,
+                nas_nas.vlan_map
+            FROM nas_nas
+            LEFT JOIN timezones ON timezones.id = nas_nas.timezone_id
+            LEFT JOIN nas_vendor ON nas_vendor.id = nas_nas.vendor_id
+            WHERE nas_nas.is_active = true AND nas_identifier IS NOT NULL AND nas_identifier != ''
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        db_timer.observe_duration();
+
+        debug!("Query returned {} NAS devices", rows.len());
+        Ok(rows.into_iter()
+            .map(|row| NasDevice {
+                id: row.id,
+                name: row.name,
+                nas_identifier: row.nas_identifier,
+                ip_address: row.ip_address,
+                vendor_id: row.vendor_id.map(|vendor_id| vendor_id as u32),
+                is_active: row.is_active,
+                coa_enabled: row.coa_enabled,
+                coa_port: u16::try_from(row.coa_port).unwrap_or(3799),
+                timezone_name: row.timezone_name,
+                timezone_offset: row.timezone_offset,
+                vlan_map: vlan::parse_vlan_map(&row.vlan_map),
+            })
+            .collect())
+    }
+
+    async fn load_secrets(&self) -> Result<Vec<ClientSecret>, BackendError> {
+        #[derive(sqlx::FromRow)]
+        struct SecretRow {
+            id: i64,
+            secret: Option<String>,
+            source_subnets: Option<JsonValue>,
+        }
+
+        let db_timer = crate::metrics::DB_QUERY_DURATION.with_label_values(&["load_secrets"]).start_timer();
+        let rows = sqlx::query_as::<_, SecretRow>(
+            r#"
+            SELECT
+                id,
+                secret,
+                source_subnets
+            FROM radius_secret
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        db_timer.observe_duration();
+
+        debug!("Query returned {} secrets", rows.len());
+        let mut secrets = Vec::new();
+        for row in rows {
+            let Some(subnets_json) = row.source_subnets else {
+                warn!("No subnets found for secret ID {}", row.id);
+                continue;
+            };
+            let Some(secret) = row.secret else {
+                warn!("No secret found for ID {}", row.id);
+                continue;
+            };
+
+            let subnets: Vec<String> = serde_json::from_value(subnets_json)?;
+            let subnets = subnets.iter()
+                .filter_map(|subnet| match subnet.parse::<IpNetwork>() {
+                    Ok(network) => {
+                        debug!("Mapping subnet {} to secret ID {}", network, row.id);
+                        Some(network)
+                    }
+                    Err(e) => {
+                        warn!("Invalid subnet format '{}' for secret ID {}: {}", subnet, row.id, e);
+                        None
+                    }
+                })
+                .collect();
+            secrets.push(ClientSecret { secret, subnets });
+        }
+        Ok(secrets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_prefers_the_most_specific_subnet() {
+        let secrets = vec![
+            ClientSecret { secret: "campus".to_string(), subnets: vec!["10.0.0.0/8".parse().unwrap()] },
+            ClientSecret {
+                secret: "lab".to_string(),
+                subnets: vec!["10.1.0.0/16".parse().unwrap(), "2001:db8::/32".parse().unwrap()],
+            },
+        ];
+        assert_eq!(ClientSecret::lookup(&secrets, "10.1.2.3".parse().unwrap()), Some("lab"));
+        assert_eq!(ClientSecret::lookup(&secrets, "10.2.0.1".parse().unwrap()), Some("campus"));
+        assert_eq!(ClientSecret::lookup(&secrets, "2001:db8::1".parse().unwrap()), Some("lab"));
+        assert_eq!(ClientSecret::lookup(&secrets, "192.0.2.1".parse().unwrap()), None);
+    }
+}
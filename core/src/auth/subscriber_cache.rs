@@ -0,0 +1,411 @@
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Utc};
+use lru::LruCache;
+use redis::AsyncCommands;
+use redis::aio::MultiplexedConnection;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use sqlx::postgres::PgListener;
+use tracing::{debug, error, info, warn};
+
+use crate::config::EnvOverrides;
+
+/// Channel the `user_identifiers` trigger notifies with the changed identifier value
+pub const INVALIDATION_CHANNEL: &str = "openrdx_subscriber_changed";
+
+const REDIS_CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+const REDIS_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Credential and authorization record of a username identifier, as cached locally and in Redis.
+/// The password stays in process memory: Redis copies leave it out, and it is read back by ID on a Redis hit.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, sqlx::FromRow)]
+pub struct IdentifierRecord {
+    pub id: i64,
+    #[serde(skip)]
+    pub plain_password: Option<String>,
+    pub is_enabled: bool,
+    pub vlan: Option<String>,
+    pub attribute_group: Option<String>,
+    pub expired_attribute_group: Option<String>,
+    pub expiration_date: Option<DateTime<Utc>>,
+    pub reject_expired: bool,
+}
+
+impl IdentifierRecord {
+    /// Name of the attribute group in force at `now`; same choice as
+    /// UserIdentifier.get_attribute_group() in the admin backend
+    pub fn attribute_group(&self, now: DateTime<Utc>) -> Option<&str> {
+        let expired = self.expiration_date.is_some_and(|date| date < now) && !self.reject_expired;
+        expired.then_some(self.expired_attribute_group.as_deref()).flatten()
+            .or(self.attribute_group.as_deref())
+    }
+}
+
+/// What is stored in Redis: a record is only used while its generation is the identifier's current one,
+/// so a lookup that raced with an invalidation can't bring a stale record back
+#[derive(Debug, Serialize, Deserialize)]
+struct RedisEntry {
+    generation: u64,
+    record: Option<IdentifierRecord>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SubscriberCacheConfig {
+    /// Seconds a found identifier stays cached; 0 disables caching
+    pub ttl: u64,
+    /// Seconds an unknown identifier stays cached
+    pub negative_ttl: u64,
+    /// Entries kept in the in-process LRU in front of Redis
+    pub local_capacity: usize,
+}
+
+impl Default for SubscriberCacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl: 3600,
+            negative_ttl: 60,
+            local_capacity: 10_000,
+        }
+    }
+}
+
+impl SubscriberCacheConfig {
+    pub fn apply_env(&mut self, env: &mut EnvOverrides) {
+        env.set("SUBSCRIBER_CACHE_TTL", &mut self.ttl);
+        env.set("SUBSCRIBER_NEGATIVE_CACHE_TTL", &mut self.negative_ttl);
+        env.set("SUBSCRIBER_LOCAL_CACHE_SIZE", &mut self.local_capacity);
+    }
+}
+
+struct LocalEntry {
+    record: Option<IdentifierRecord>,
+    expires_at: Instant,
+}
+
+/// Read-through cache of identifier records: in-process LRU, then Redis, then Postgres.
+/// Unknown identifiers are cached too (for `negative_ttl`) so that scans of random
+/// usernames don't reach the database.
+pub struct SubscriberCache {
+    config: SubscriberCacheConfig,
+    redis: Option<redis::Client>,
+    connection: tokio::sync::Mutex<Option<MultiplexedConnection>>,
+    retry_at: std::sync::Mutex<Option<Instant>>,
+    local: std::sync::Mutex<LruCache<String, LocalEntry>>,
+    // Bumped on every invalidation, with `local` locked; lookups that started before one don't fill the LRU
+    invalidations: AtomicU64,
+}
+
+impl SubscriberCache {
+    pub fn new(config: SubscriberCacheConfig, redis_url: &str) -> Self {
+        let redis = if config.ttl > 0 {
+            match redis::Client::open(redis_url) {
+                Ok(client) => Some(client),
+                Err(e) => {
+                    warn!("Invalid REDIS_URL for subscriber cache, using local cache only: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        let capacity = NonZeroUsize::new(config.local_capacity).unwrap_or(NonZeroUsize::MIN);
+
+        Self {
+            config,
+            redis,
+            connection: tokio::sync::Mutex::new(None),
+            retry_at: std::sync::Mutex::new(None),
+            local: std::sync::Mutex::new(LruCache::new(capacity)),
+            invalidations: AtomicU64::new(0),
+        }
+    }
+
+    fn redis_key(username: &str) -> String {
+        format!("subscriber:identifier:{}", username)
+    }
+
+    fn generation_key(username: &str) -> String {
+        format!("subscriber:generation:{}", username)
+    }
+
+    pub async fn get_identifier(&self, pool: &PgPool, username: &str) -> Result<Option<IdentifierRecord>, sqlx::Error> {
+        if self.config.ttl == 0 {
+            return Self::load_identifier(pool, username).await;
+        }
+
+        if let Some(entry) = self.local.lock().unwrap().get(username)
+            && entry.expires_at > Instant::now()
+        {
+            debug!("Subscriber cache: local hit for {}", username);
+            return Ok(entry.record.clone());
+        }
+
+        let epoch = self.invalidations.load(Ordering::Acquire);
+        // None when Redis is unavailable, and nothing is written back to it
+        let mut generation = None;
+        if let Some(mut conn) = self.connection().await {
+            let keys = [Self::redis_key(username), Self::generation_key(username)];
+            match conn.mget::<_, (Option<String>, Option<u64>)>(&keys).await {
+                Ok((cached, current)) => {
+                    let current = current.unwrap_or(0);
+                    if let Some(entry) = cached.and_then(|cached| serde_json::from_str::<RedisEntry>(&cached).ok())
+                        && entry.generation == current
+                    {
+                        debug!("Subscriber cache: Redis hit for {}", username);
+                        let record = match entry.record {
+                            Some(mut record) => match Self::load_password(pool, record.id).await? {
+                                Some(password) => {
+                                    record.plain_password = password;
+                                    Some(record)
+                                }
+                                // Deleted since it was cached
+                                None => return Ok(None),
+                            },
+                            None => None,
+                        };
+                        self.store_local(username, record.clone(), epoch);
+                        return Ok(record);
+                    }
+                    generation = Some(current);
+                }
+                Err(e) => self.connection_failed(&e).await,
+            }
+        }
+
+        let record = Self::load_identifier(pool, username).await?;
+        self.store(username, record.clone(), epoch, generation).await;
+        Ok(record)
+    }
+
+    /// Drops an identifier from the local LRU and moves it to a new generation in Redis,
+    /// which retires whatever a concurrent lookup writes for the old one
+    pub async fn invalidate(&self, username: &str) {
+        {
+            let mut local = self.local.lock().unwrap();
+            self.invalidations.fetch_add(1, Ordering::AcqRel);
+            local.pop(username);
+        }
+        if let Some(mut conn) = self.connection().await {
+            // Outlives every entry written for an older generation
+            let generation_ttl = self.config.ttl.max(self.config.negative_ttl) * 2;
+            let result = redis::pipe().atomic()
+                .incr(Self::generation_key(username), 1).ignore()
+                .expire(Self::generation_key(username), generation_ttl as i64).ignore()
+                .del(Self::redis_key(username)).ignore()
+                .query_async::<()>(&mut conn).await;
+            if let Err(e) = result {
+                self.connection_failed(&e).await;
+            }
+        }
+        debug!("Subscriber cache: invalidated {}", username);
+    }
+
+    /// Listens for change notifications from the `user_identifiers` trigger and
+    /// invalidates the affected identifiers. Reconnects when the listener drops.
+    pub async fn listen_for_invalidations(&self, pool: PgPool) {
+        if self.config.ttl == 0 {
+            return;
+        }
+
+        loop {
+            match PgListener::connect_with(&pool).await {
+                Ok(mut listener) => {
+                    if let Err(e) = listener.listen(INVALIDATION_CHANNEL).await {
+                        error!("Failed to LISTEN on {}: {}", INVALIDATION_CHANNEL, e);
+                    } else {
+                        info!("Listening for subscriber changes on {}", INVALIDATION_CHANNEL);
+                        // Anything cached before the listener was up may be stale
+                        {
+                            let mut local = self.local.lock().unwrap();
+                            self.invalidations.fetch_add(1, Ordering::AcqRel);
+                            local.clear();
+                        }
+                        loop {
+                            match listener.recv().await {
+                                Ok(notification) => self.invalidate(notification.payload()).await,
+                                Err(e) => {
+                                    warn!("Subscriber change listener failed: {}", e);
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                }
+                Err(e) => warn!("Failed to connect subscriber change listener: {}", e),
+            }
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        }
+    }
+
+    async fn load_identifier(pool: &PgPool, username: &str) -> Result<Option<IdentifierRecord>, sqlx::Error> {
+        let db_timer = crate::metrics::DB_QUERY_DURATION.with_label_values(&["identifier_lookup"]).start_timer();
+        let record = sqlx::query_as::<_, IdentifierRecord>(
+            r#"
+            SELECT
+                ui.id,
+                ui.plain_password,
+                ui.is_enabled,
+                NULLIF(TRIM(ui.vlan), '') AS vlan,
+                current.name AS attribute_group,
+                expired.name AS expired_attribute_group,
+                ui.expiration_date,
+                ui.reject_expired
+            FROM user_identifiers ui
+            LEFT JOIN radius_auth_attribute_group current ON current.id = ui.auth_attribute_group_id
+            LEFT JOIN radius_auth_attribute_group expired ON expired.id = ui.expired_auth_attribute_group_id
+            WHERE ui.value = $1 AND ui.identifier_type_id = 1
+            "#
+        )
+        .bind(username)
+        .fetch_optional(pool)
+        .await?;
+        db_timer.observe_duration();
+        Ok(record)
+    }
+
+    /// The password of an identifier found in Redis; None when the identifier is gone
+    async fn load_password(pool: &PgPool, id: i64) -> Result<Option<Option<String>>, sqlx::Error> {
+        let db_timer = crate::metrics::DB_QUERY_DURATION.with_label_values(&["identifier_password"]).start_timer();
+        let password = sqlx::query_scalar::<_, Option<String>>("SELECT plain_password FROM user_identifiers WHERE id = $1")
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+        db_timer.observe_duration();
+        Ok(password)
+    }
+
+    async fn store(&self, username: &str, record: Option<IdentifierRecord>, epoch: u64, generation: Option<u64>) {
+        let ttl = if record.is_some() { self.config.ttl } else { self.config.negative_ttl };
+        if ttl == 0 {
+            return;
+        }
+        self.store_local(username, record.clone(), epoch);
+
+        if let Some(generation) = generation
+            && let Some(mut conn) = self.connection().await
+        {
+            let value = serde_json::to_string(&RedisEntry { generation, record }).unwrap_or_default();
+            if let Err(e) = conn.set_ex::<_, _, ()>(Self::redis_key(username), value, ttl).await {
+                self.connection_failed(&e).await;
+            }
+        }
+    }
+
+    /// Keeps a record in the LRU unless an invalidation came in since `epoch`
+    fn store_local(&self, username: &str, record: Option<IdentifierRecord>, epoch: u64) {
+        let ttl = if record.is_some() { self.config.ttl } else { self.config.negative_ttl };
+        // The local copy can't see invalidations issued while Redis is unreachable,
+        // so it never outlives the negative TTL
+        let local_ttl = ttl.min(self.config.negative_ttl.max(1));
+        let mut local = self.local.lock().unwrap();
+        if self.invalidations.load(Ordering::Acquire) != epoch {
+            debug!("Subscriber cache: not caching {}, invalidated during the lookup", username);
+            return;
+        }
+        local.put(username.to_string(), LocalEntry {
+            record,
+            expires_at: Instant::now() + Duration::from_secs(local_ttl),
+        });
+    }
+
+    async fn connection(&self) -> Option<MultiplexedConnection> {
+        let client = self.redis.as_ref()?;
+        let mut connection = self.connection.lock().await;
+        if let Some(conn) = connection.as_ref() {
+            return Some(conn.clone());
+        }
+
+        if self.retry_at.lock().unwrap().is_some_and(|at| at > Instant::now()) {
+            return None;
+        }
+
+        match tokio::time::timeout(REDIS_CONNECT_TIMEOUT, client.get_multiplexed_async_connection()).await {
+            Ok(Ok(conn)) => {
+                debug!("Connected to Redis for subscriber cache");
+                *connection = Some(conn.clone());
+                Some(conn)
+            }
+            _ => {
+                warn!("Redis unavailable, subscriber cache using local LRU only");
+                *self.retry_at.lock().unwrap() = Some(Instant::now() + REDIS_RETRY_INTERVAL);
+                None
+            }
+        }
+    }
+
+    async fn connection_failed(&self, e: &redis::RedisError) {
+        warn!("Redis error on subscriber cache: {}", e);
+        *self.connection.lock().await = None;
+        *self.retry_at.lock().unwrap() = Some(Instant::now() + REDIS_RETRY_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(expiration_date: Option<DateTime<Utc>>, reject_expired: bool) -> IdentifierRecord {
+        IdentifierRecord {
+            id: 7,
+            plain_password: Some("secret".to_string()),
+            is_enabled: true,
+            vlan: None,
+            attribute_group: Some("gold".to_string()),
+            expired_attribute_group: Some("walled-garden".to_string()),
+            expiration_date,
+            reject_expired,
+        }
+    }
+
+    #[test]
+    fn test_redis_entries_leave_out_the_password() {
+        let value = serde_json::to_string(&RedisEntry { generation: 3, record: Some(record(None, false)) }).unwrap();
+        assert!(!value.contains("secret"));
+
+        let entry: RedisEntry = serde_json::from_str(&value).unwrap();
+        assert_eq!(entry.generation, 3);
+        assert_eq!(entry.record.unwrap().plain_password, None);
+    }
+
+    #[test]
+    fn test_attribute_group_after_expiration() {
+        let now = Utc::now();
+        let past = Some(now - chrono::Duration::days(1));
+        assert_eq!(record(None, false).attribute_group(now), Some("gold"));
+        assert_eq!(record(Some(now + chrono::Duration::days(1)), false).attribute_group(now), Some("gold"));
+        assert_eq!(record(past, false).attribute_group(now), Some("walled-garden"));
+        assert_eq!(record(past, true).attribute_group(now), Some("gold"));
+        assert_eq!(IdentifierRecord { expired_attribute_group: None, ..record(past, false) }.attribute_group(now), Some("gold"));
+    }
+
+    #[tokio::test]
+    async fn test_local_entries_and_invalidation() {
+        let config = SubscriberCacheConfig { ttl: 3600, negative_ttl: 60, local_capacity: 2 };
+        let cache = SubscriberCache::new(config, "redis://127.0.0.1:1");
+        let record = record(None, false);
+
+        cache.store_local("alice", Some(record.clone()), 0);
+        cache.store_local("mallory", None, 0);
+        {
+            let mut local = cache.local.lock().unwrap();
+            assert_eq!(local.get("alice").unwrap().record, Some(record.clone()));
+            // Local copies never outlive the negative TTL
+            assert!(local.get("alice").unwrap().expires_at <= Instant::now() + Duration::from_secs(60));
+            assert_eq!(local.get("mallory").unwrap().record, None);
+        }
+
+        cache.invalidate("alice").await;
+        assert!(cache.local.lock().unwrap().get("alice").is_none());
+
+        // A lookup that started before the invalidation doesn't bring the old record back
+        cache.store_local("alice", Some(record), 0);
+        assert!(cache.local.lock().unwrap().get("alice").is_none());
+
+        cache.store_local("bob", None, 1);
+        cache.store_local("carol", None, 1);
+        assert_eq!(cache.local.lock().unwrap().len(), 2);
+    }
+}
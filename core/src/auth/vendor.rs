@@ -0,0 +1,326 @@
+use std::str::FromStr;
+use tracing::debug;
+
+use super::models::NasDevice;
+use super::radius_server::RadiusAttribute;
+use super::reply::{self, AttributeEdit, AttributeOp};
+
+const ATTR_FILTER_ID: u8 = 11;
+const ATTR_VENDOR_SPECIFIC: u8 = 26;
+
+// Vendors whose attributes every profile passes through
+const VENDOR_MICROSOFT: u32 = 311;
+const VENDOR_WISPR: u32 = 14122;
+const VENDOR_ERX: u32 = 4874;
+
+const VENDOR_CISCO: u32 = 9;
+const VENDOR_JUNIPER: u32 = 2636;
+const VENDOR_MIKROTIK: u32 = 14988;
+const VENDOR_ARUBA: u32 = 14823;
+const VENDOR_UBIQUITI: u32 = 41112;
+const VENDOR_FORTINET: u32 = 12356;
+
+const CISCO_AVPAIR: u8 = 1;
+const JUNIPER_LOCAL_USER_NAME: u8 = 1;
+const MIKROTIK_GROUP: u8 = 3;
+const MIKROTIK_RATE_LIMIT: u8 = 8;
+const MIKROTIK_ADDRESS_LIST: u8 = 19;
+const MIKROTIK_WIRELESS_VLAN_ID: u8 = 26;
+const MIKROTIK_WIRELESS_VLAN_ID_TYPE: u8 = 27;
+const ARUBA_USER_ROLE: u8 = 1;
+const ARUBA_USER_VLAN: u8 = 2;
+const ARUBA_ADMIN_ROLE: u8 = 4;
+const ARUBA_NAMED_USER_VLAN: u8 = 9;
+const WISPR_BANDWIDTH_MAX_UP: u8 = 7;
+const WISPR_BANDWIDTH_MAX_DOWN: u8 = 8;
+const FORTINET_GROUP_NAME: u8 = 1;
+const FORTINET_ACCESS_PROFILE: u8 = 6;
+
+/// Highest privilege level, as Cisco numbers them
+pub const MAX_PRIVILEGE_LEVEL: u8 = 15;
+
+/// Upload and download limits in bits per second, written like `10M/50M` (k, M and G suffixes)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub up: u64,
+    pub down: u64,
+}
+
+impl FromStr for RateLimit {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let parse = |rate: &str| {
+            let rate = rate.trim();
+            let (digits, multiplier) = match rate.char_indices().last() {
+                Some((i, 'k' | 'K')) => (&rate[..i], 1_000),
+                Some((i, 'M')) => (&rate[..i], 1_000_000),
+                Some((i, 'G')) => (&rate[..i], 1_000_000_000),
+                _ => (rate, 1),
+            };
+            digits.parse::<u64>().ok().filter(|rate| *rate > 0).map(|rate| rate.saturating_mul(multiplier))
+        };
+        text.split_once('/')
+            .and_then(|(up, down)| Some(RateLimit { up: parse(up)?, down: parse(down)? }))
+            .ok_or_else(|| format!("expected a rate limit like 10M/50M, got {:?}", text))
+    }
+}
+
+/// What the reply should achieve, independent of the NAS that receives it
+#[derive(Debug, Clone, Default)]
+pub struct ReplyIntents {
+    pub rate_limit: Option<RateLimit>,
+    /// 0 to 15, 15 being full administrative access
+    pub privilege_level: Option<u8>,
+    /// Role to grant instead of the vendor's built-in one for `privilege_level`
+    pub admin_role: Option<String>,
+    /// VLAN as the NAS knows it, on top of the RFC 3580 tunnel attributes
+    pub vlan: Option<String>,
+    pub acl: Option<String>,
+}
+
+impl ReplyIntents {
+    pub fn is_empty(&self) -> bool {
+        self.rate_limit.is_none() && self.privilege_level.is_none() && self.vlan.is_none() && self.acl.is_none()
+    }
+}
+
+/// Vendors whose NAS get their own reply attributes, selected by the IANA number in `nas_vendor`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VendorProfile {
+    Mikrotik,
+    Cisco,
+    Juniper,
+    Aruba,
+    Ubiquiti,
+    Fortinet,
+}
+
+impl VendorProfile {
+    pub fn of(nas: Option<&NasDevice>) -> Option<Self> {
+        match nas?.vendor_id? {
+            VENDOR_MIKROTIK => Some(VendorProfile::Mikrotik),
+            VENDOR_CISCO => Some(VendorProfile::Cisco),
+            VENDOR_JUNIPER => Some(VendorProfile::Juniper),
+            VENDOR_ARUBA => Some(VendorProfile::Aruba),
+            VENDOR_UBIQUITI => Some(VendorProfile::Ubiquiti),
+            VENDOR_FORTINET => Some(VendorProfile::Fortinet),
+            _ => None,
+        }
+    }
+
+    /// Key of the vendor in device-admin role maps
+    pub fn name(self) -> &'static str {
+        match self {
+            VendorProfile::Mikrotik => "mikrotik",
+            VendorProfile::Cisco => "cisco",
+            VendorProfile::Juniper => "juniper",
+            VendorProfile::Aruba => "aruba",
+            VendorProfile::Ubiquiti => "ubiquiti",
+            VendorProfile::Fortinet => "fortinet",
+        }
+    }
+
+    fn vendor_id(self) -> u32 {
+        match self {
+            VendorProfile::Mikrotik => VENDOR_MIKROTIK,
+            VendorProfile::Cisco => VENDOR_CISCO,
+            VendorProfile::Juniper => VENDOR_JUNIPER,
+            VendorProfile::Aruba => VENDOR_ARUBA,
+            VendorProfile::Ubiquiti => VENDOR_UBIQUITI,
+            VendorProfile::Fortinet => VENDOR_FORTINET,
+        }
+    }
+
+    /// Whether the NAS makes sense of `attr`: standard attributes, its own vendor's, and the
+    /// Microsoft ones every EAP-capable NAS knows
+    pub fn understands(self, attr: &RadiusAttribute) -> bool {
+        if attr.typ != ATTR_VENDOR_SPECIFIC || attr.value.len() < 4 {
+            return true;
+        }
+        let vendor_id = u32::from_be_bytes([attr.value[0], attr.value[1], attr.value[2], attr.value[3]]);
+        vendor_id == self.vendor_id() || vendor_id == VENDOR_MICROSOFT || match self {
+            VendorProfile::Mikrotik | VendorProfile::Ubiquiti => vendor_id == VENDOR_WISPR,
+            VendorProfile::Juniper => vendor_id == VENDOR_ERX,
+            _ => false,
+        }
+    }
+
+    /// Attribute granting a privilege level: Cisco's own, otherwise `role` or the built-in admin role closest to it
+    fn privilege_attribute(self, level: u8, role: Option<&str>) -> Option<(u32, u8, Vec<u8>)> {
+        let (attribute_id, builtin) = match (self, level) {
+            (VendorProfile::Cisco, _) => return Some((VENDOR_CISCO, CISCO_AVPAIR, format!("shell:priv-lvl={}", level).into_bytes())),
+            (VendorProfile::Mikrotik, MAX_PRIVILEGE_LEVEL) => (MIKROTIK_GROUP, "full"),
+            (VendorProfile::Mikrotik, 7..) => (MIKROTIK_GROUP, "write"),
+            (VendorProfile::Mikrotik, _) => (MIKROTIK_GROUP, "read"),
+            (VendorProfile::Juniper, MAX_PRIVILEGE_LEVEL) => (JUNIPER_LOCAL_USER_NAME, "super-user"),
+            (VendorProfile::Juniper, 7..) => (JUNIPER_LOCAL_USER_NAME, "operator"),
+            (VendorProfile::Juniper, 1..) => (JUNIPER_LOCAL_USER_NAME, "read-only"),
+            (VendorProfile::Juniper, _) => (JUNIPER_LOCAL_USER_NAME, "unauthorized"),
+            (VendorProfile::Aruba, MAX_PRIVILEGE_LEVEL) => (ARUBA_ADMIN_ROLE, "root"),
+            (VendorProfile::Aruba, 7..) => (ARUBA_ADMIN_ROLE, "network-operations"),
+            (VendorProfile::Aruba, _) => (ARUBA_ADMIN_ROLE, "read-only"),
+            (VendorProfile::Fortinet, MAX_PRIVILEGE_LEVEL) => (FORTINET_ACCESS_PROFILE, "super_admin"),
+            (VendorProfile::Fortinet, _) => (FORTINET_ACCESS_PROFILE, "prof_admin"),
+            (VendorProfile::Ubiquiti, _) => return None,
+        };
+        Some((self.vendor_id(), attribute_id, role.unwrap_or(builtin).as_bytes().to_vec()))
+    }
+}
+
+/// Attributes carrying `intents` to a NAS of `vendor`; intents the vendor has no attribute for are dropped.
+/// Without a profile only the standard Filter-Id is sent.
+pub(super) fn intent_edits(vendor: Option<VendorProfile>, intents: &ReplyIntents) -> Vec<AttributeEdit> {
+    let mut attributes: Vec<(u32, u8, Vec<u8>)> = Vec::new();
+    let mut unsupported = Vec::new();
+
+    if let Some(rate) = intents.rate_limit {
+        match vendor {
+            // Rates as the router sees them: what it receives from the user first
+            Some(VendorProfile::Mikrotik) => attributes.push((VENDOR_MIKROTIK, MIKROTIK_RATE_LIMIT,
+                                                              format!("{}/{}", rate.up, rate.down).into_bytes())),
+            Some(VendorProfile::Ubiquiti) => {
+                let clamp = |rate: u64| u32::try_from(rate).unwrap_or(u32::MAX).to_be_bytes().to_vec();
+                attributes.push((VENDOR_WISPR, WISPR_BANDWIDTH_MAX_UP, clamp(rate.up)));
+                attributes.push((VENDOR_WISPR, WISPR_BANDWIDTH_MAX_DOWN, clamp(rate.down)));
+            }
+            _ => unsupported.push("rate limit"),
+        }
+    }
+
+    if let Some(level) = intents.privilege_level.map(|level| level.min(MAX_PRIVILEGE_LEVEL)) {
+        match vendor.and_then(|vendor| vendor.privilege_attribute(level, intents.admin_role.as_deref())) {
+            Some(attribute) => attributes.push(attribute),
+            None => unsupported.push("privilege level"),
+        }
+    }
+
+    if let Some(vlan) = &intents.vlan {
+        let vlan_id = vlan.parse::<u32>().ok();
+        match (vendor, vlan_id) {
+            // Wireless interfaces ignore the tunnel attributes
+            (Some(VendorProfile::Mikrotik), Some(vlan_id)) => {
+                attributes.push((VENDOR_MIKROTIK, MIKROTIK_WIRELESS_VLAN_ID, vlan_id.to_be_bytes().to_vec()));
+                attributes.push((VENDOR_MIKROTIK, MIKROTIK_WIRELESS_VLAN_ID_TYPE, 0u32.to_be_bytes().to_vec()));
+            }
+            (Some(VendorProfile::Aruba), Some(vlan_id)) => attributes.push((VENDOR_ARUBA, ARUBA_USER_VLAN, vlan_id.to_be_bytes().to_vec())),
+            (Some(VendorProfile::Aruba), None) => attributes.push((VENDOR_ARUBA, ARUBA_NAMED_USER_VLAN, vlan.clone().into_bytes())),
+            _ => {}
+        }
+    }
+
+    if let Some(acl) = &intents.acl {
+        match vendor {
+            Some(VendorProfile::Mikrotik) => attributes.push((VENDOR_MIKROTIK, MIKROTIK_ADDRESS_LIST, acl.clone().into_bytes())),
+            Some(VendorProfile::Aruba) => attributes.push((VENDOR_ARUBA, ARUBA_USER_ROLE, acl.clone().into_bytes())),
+            Some(VendorProfile::Fortinet) => attributes.push((VENDOR_FORTINET, FORTINET_GROUP_NAME, acl.clone().into_bytes())),
+            // IOS applies Filter-Id as an inbound ACL when it says so
+            Some(VendorProfile::Cisco) if !acl.contains('.') => attributes.push((0, ATTR_FILTER_ID, format!("{}.in", acl).into_bytes())),
+            _ => attributes.push((0, ATTR_FILTER_ID, acl.clone().into_bytes())),
+        }
+    }
+
+    if !unsupported.is_empty() {
+        debug!("No attribute for {} on {:?} NAS, skipping", unsupported.join(", "),
+               vendor.map_or("unknown".to_string(), |vendor| format!("{:?}", vendor)));
+    }
+    attributes.into_iter()
+        .map(|(vendor_id, attribute_id, value)| AttributeEdit {
+            op: AttributeOp::Set,
+            vendor_id,
+            attribute_id,
+            attribute: reply::build_attribute(vendor_id, attribute_id, value),
+        })
+        .collect()
+}
+
+/// Drops the vendor-specific attributes a NAS of `vendor` wouldn't understand
+pub fn filter_reply(vendor: VendorProfile, attributes: &mut Vec<RadiusAttribute>) {
+    let before = attributes.len();
+    attributes.retain(|attr| vendor.understands(attr));
+    if attributes.len() < before {
+        debug!("Dropped {} attributes a {:?} NAS doesn't understand", before - attributes.len(), vendor);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::{AuthServer, MemoryStore, RadiusPacket};
+    use crate::config::Config;
+
+    #[test]
+    fn test_intents_per_vendor() {
+        let intents = ReplyIntents {
+            rate_limit: Some("2M/10M".parse().unwrap()),
+            privilege_level: Some(15),
+            admin_role: None,
+            vlan: Some("120".to_string()),
+            acl: Some("staff".to_string()),
+        };
+        let summary = |vendor| -> Vec<(u32, u8, Vec<u8>)> {
+            intent_edits(vendor, &intents).into_iter()
+                .map(|edit| (edit.vendor_id, edit.attribute_id, edit.attribute.unwrap().value))
+                .collect()
+        };
+
+        let cisco = summary(Some(VendorProfile::Cisco));
+        assert_eq!(cisco.len(), 2);
+        assert_eq!(&cisco[0].2[6..], b"shell:priv-lvl=15");
+        assert_eq!(cisco[1], (0, ATTR_FILTER_ID, b"staff.in".to_vec()));
+
+        let mikrotik = summary(Some(VendorProfile::Mikrotik));
+        assert_eq!(mikrotik.iter().map(|(_, id, _)| *id).collect::<Vec<_>>(),
+                   vec![MIKROTIK_RATE_LIMIT, MIKROTIK_GROUP, MIKROTIK_WIRELESS_VLAN_ID, MIKROTIK_WIRELESS_VLAN_ID_TYPE, MIKROTIK_ADDRESS_LIST]);
+        assert_eq!(&mikrotik[0].2[6..], b"2000000/10000000");
+        assert_eq!(&mikrotik[1].2[6..], b"full");
+
+        assert_eq!(summary(None), vec![(0, ATTR_FILTER_ID, b"staff".to_vec())]);
+        assert!("10M".parse::<RateLimit>().is_err());
+        assert_eq!("512k/1G".parse::<RateLimit>(), Ok(RateLimit { up: 512_000, down: 1_000_000_000 }));
+    }
+
+    #[test]
+    fn test_filter_reply() {
+        let mut attributes = vec![
+            RadiusAttribute { typ: 27, value: 60u32.to_be_bytes().to_vec() },
+            reply::build_attribute(VENDOR_MIKROTIK, MIKROTIK_GROUP, b"full".to_vec()).unwrap(),
+            reply::build_attribute(VENDOR_CISCO, CISCO_AVPAIR, b"shell:priv-lvl=15".to_vec()).unwrap(),
+            reply::build_attribute(VENDOR_MICROSOFT, 16, vec![0x80; 34]).unwrap(),
+        ];
+        filter_reply(VendorProfile::Cisco, &mut attributes);
+        assert_eq!(attributes.len(), 3);
+        assert!(attributes.iter().all(|attr| attr.typ != ATTR_VENDOR_SPECIFIC || attr.value[3] != 0x8c));
+    }
+
+    #[tokio::test]
+    async fn test_profile_of_nas_found_by_address() {
+        let mut config = Config::default();
+        config.postauth.enabled = false;
+        config.simultaneous_use.enabled = false;
+        config.policy.enabled = false;
+        let mut store = MemoryStore::new();
+        store.add_nas(NasDevice {
+            id: 1,
+            name: "sw-1".to_string(),
+            nas_identifier: "sw-1".to_string(),
+            ip_address: "192.0.2.1".to_string(),
+            vendor_id: Some(VENDOR_CISCO),
+            is_active: true,
+            coa_enabled: false,
+            coa_port: 3799,
+            timezone_name: None,
+            timezone_offset: None,
+            vlan_map: Default::default(),
+        });
+        let server = AuthServer::in_memory(config, store).await.unwrap();
+
+        // A NAS-Identifier nobody configured doesn't hide the NAS behind the source address
+        let packet = RadiusPacket {
+            code: 1, identifier: 1, length: 0, authenticator: [0; 16],
+            attributes: vec![RadiusAttribute { typ: 32, value: b"switch-1".to_vec() }],
+        };
+        let vendor = VendorProfile::of(server.find_request_nas(&packet, "192.0.2.1".parse().unwrap()));
+        assert_eq!(vendor, Some(VendorProfile::Cisco));
+    }
+}
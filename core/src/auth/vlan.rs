@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use sqlx::types::JsonValue;
+use tracing::{debug, warn};
+
+use super::models::NasDevice;
+use super::radius_server::RadiusAttribute;
+use super::reply::{AttributeEdit, AttributeOp};
+
+const ATTR_TUNNEL_TYPE: u8 = 64;
+const ATTR_TUNNEL_MEDIUM_TYPE: u8 = 65;
+const ATTR_TUNNEL_PRIVATE_GROUP_ID: u8 = 81;
+
+const TUNNEL_TYPE_VLAN: u32 = 13;
+const TUNNEL_MEDIUM_TYPE_IEEE_802: u32 = 6;
+// RFC 3580 dynamic VLANs use a single tunnel; tag 0 is accepted by every switch we know of
+const VLAN_TUNNEL_TAG: u8 = 0;
+
+/// VLAN name to ID map of a NAS, from `nas_nas.vlan_map`: `{"staff": 120, "guests": "300"}`
+pub fn parse_vlan_map(value: &JsonValue) -> HashMap<String, String> {
+    let Some(entries) = value.as_object() else {
+        return HashMap::new();
+    };
+    entries.iter()
+        .filter_map(|(name, id)| {
+            let id = match id {
+                JsonValue::Number(number) => number.to_string(),
+                JsonValue::String(text) => text.trim().to_string(),
+                _ => {
+                    warn!("Ignoring VLAN map entry {:?}: {} is not a VLAN ID or name", name, id);
+                    return None;
+                }
+            };
+            Some((name.to_lowercase(), id))
+        })
+        .collect()
+}
+
+/// The Tunnel-Private-Group-Id to send to `nas` for `vlan`. IDs go out unchanged, names through the
+/// NAS's VLAN map; names missing from the map are sent as they are, which Cisco and Aruba accept.
+pub fn resolve(vlan: &str, nas: Option<&NasDevice>) -> String {
+    let vlan = vlan.trim();
+    if vlan.parse::<u16>().is_ok() {
+        return vlan.to_string();
+    }
+    match nas.and_then(|nas| nas.vlan_map.get(&vlan.to_lowercase())) {
+        Some(id) => id.clone(),
+        None => {
+            debug!("VLAN {} is not in the map of NAS {}, sending the name", vlan, nas.map(|nas| nas.name.as_str()).unwrap_or("-"));
+            vlan.to_string()
+        }
+    }
+}
+
+/// Tunnel-Type, Tunnel-Medium-Type and Tunnel-Private-Group-Id edits that put the user in `vlan`,
+/// replacing any tunnel attributes set before
+pub(super) fn vlan_edits(vlan: &str, nas: Option<&NasDevice>) -> Vec<AttributeEdit> {
+    let group_id = resolve(vlan, nas);
+    [
+        (ATTR_TUNNEL_TYPE, TUNNEL_TYPE_VLAN.to_be_bytes().to_vec()),
+        (ATTR_TUNNEL_MEDIUM_TYPE, TUNNEL_MEDIUM_TYPE_IEEE_802.to_be_bytes().to_vec()),
+        (ATTR_TUNNEL_PRIVATE_GROUP_ID, group_id.into_bytes()),
+    ]
+    .into_iter()
+    .map(|(attribute_id, value)| AttributeEdit {
+        op: AttributeOp::Set,
+        vendor_id: 0,
+        attribute_id,
+        attribute: RadiusAttribute::tagged(attribute_id, VLAN_TUNNEL_TAG, &value),
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::auth::{AuthServer, MemoryStore, RadiusPacket};
+    use crate::config::Config;
+
+    fn switch() -> NasDevice {
+        NasDevice {
+            id: 1,
+            name: "sw-1".to_string(),
+            nas_identifier: "sw-1".to_string(),
+            ip_address: "192.0.2.1".to_string(),
+            vendor_id: None,
+            is_active: true,
+            coa_enabled: false,
+            coa_port: 3799,
+            timezone_name: None,
+            timezone_offset: None,
+            vlan_map: parse_vlan_map(&json!({ "Staff": 120, "guests": " 300 ", "bad": [1] })),
+        }
+    }
+
+    #[test]
+    fn test_vlan_edits_use_nas_map() {
+        let nas = switch();
+        assert_eq!(resolve("staff", Some(&nas)), "120");
+        assert_eq!(resolve("42", Some(&nas)), "42");
+        assert_eq!(resolve("voice", Some(&nas)), "voice");
+        assert_eq!(resolve("staff", None), "staff");
+        assert_eq!(nas.vlan_map.len(), 2);
+
+        let mut attributes = vec![RadiusAttribute { typ: ATTR_TUNNEL_PRIVATE_GROUP_ID, value: b"10".to_vec() }];
+        for edit in vlan_edits("guests", Some(&nas)) {
+            edit.apply(&mut attributes);
+        }
+        let summary: Vec<(u8, Vec<u8>)> = attributes.into_iter().map(|attr| (attr.typ, attr.value)).collect();
+        assert_eq!(summary, vec![
+            (ATTR_TUNNEL_TYPE, vec![0, 0, 0, 13]),
+            (ATTR_TUNNEL_MEDIUM_TYPE, vec![0, 0, 0, 6]),
+            (ATTR_TUNNEL_PRIVATE_GROUP_ID, b"300".to_vec()),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_nas_without_identifier_keeps_its_map() {
+        let mut config = Config::default();
+        config.postauth.enabled = false;
+        config.simultaneous_use.enabled = false;
+        config.policy.enabled = false;
+        let mut store = MemoryStore::new();
+        store.add_nas(switch());
+        let server = AuthServer::in_memory(config, store).await.unwrap();
+
+        // Only NAS-IP-Address, which isn't trusted: the NAS is the one configured for the source address
+        let packet = RadiusPacket {
+            code: 1, identifier: 1, length: 0, authenticator: [0; 16],
+            attributes: vec![RadiusAttribute { typ: 4, value: vec![198, 51, 100, 1] }],
+        };
+        let nas = server.find_request_nas(&packet, "192.0.2.1".parse().unwrap());
+        assert_eq!(nas.map(|nas| nas.id), Some(1));
+        assert_eq!(resolve("guests", nas), "300");
+        assert!(server.find_request_nas(&packet, "192.0.2.2".parse().unwrap()).is_none());
+    }
+}
@@ -0,0 +1,182 @@
+//! radclient-style test client for OpenRDX.
+//!
+//! Exit status: 0 for Access-Accept, Accounting-Response or ACK, 1 for Access-Reject, Access-Challenge
+//! or NAK, 2 when no valid reply arrived, 64 for usage errors.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::process::ExitCode;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+use openrdx_core::client::{self, AcctStatus, AuthMethod, Command, Credentials};
+
+const USAGE: &str = "\
+Usage: openrdx-client [options] <server[:port]> <auth|acct|status|coa|disconnect> <secret>
+
+Options:
+  -u, --user NAME          User-Name
+  -p, --password PASS      password for auth (or User-Password in the attribute list)
+  -m, --method METHOD      auth method: pap (default), chap, mschap or mschapv2
+  -s, --status STATUS      acct status: start (default), interim or stop
+  -f, --file FILE          attribute list, one `Name = value` per line (- for stdin)
+  -a, --attr 'NAME = VAL'  extra attribute, may be repeated
+  -t, --timeout SECS       seconds to wait for each reply (default 3)
+  -r, --retries N          retransmissions after the first attempt (default 3)
+  -q, --quiet              print only the reply code
+  -h, --help               show this help
+
+Default ports: 1812 for auth and status, 1813 for acct, 3799 for coa and disconnect.";
+
+const EXIT_USAGE: u8 = 64;
+
+struct Options {
+    server: String,
+    command: Command,
+    secret: String,
+    credentials: Credentials,
+    file: Option<String>,
+    attributes: Vec<String>,
+    timeout: Duration,
+    retries: u32,
+    quiet: bool,
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut positional = Vec::new();
+    let mut credentials = Credentials::default();
+    let mut method = AuthMethod::Pap;
+    let mut status = AcctStatus::Start;
+    let mut file = None;
+    let mut attributes = Vec::new();
+    let mut timeout = Duration::from_secs(3);
+    let mut retries = 3;
+    let mut quiet = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().cloned().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "-u" | "--user" => credentials.username = Some(value()?),
+            "-p" | "--password" => credentials.password = Some(value()?),
+            "-m" | "--method" => method = value()?.parse()?,
+            "-s" | "--status" => status = value()?.parse()?,
+            "-f" | "--file" => file = Some(value()?),
+            "-a" | "--attr" => attributes.push(value()?),
+            "-t" | "--timeout" => {
+                let secs = value()?.parse::<f64>().map_err(|_| "timeout must be a number of seconds")?;
+                timeout = Duration::try_from_secs_f64(secs).map_err(|_| "timeout must be a number of seconds")?;
+            }
+            "-r" | "--retries" => retries = value()?.parse().map_err(|_| "retries must be a number")?,
+            "-q" | "--quiet" => quiet = true,
+            "-h" | "--help" => return Err(String::new()),
+            option if option.starts_with('-') && option.len() > 1 => return Err(format!("unknown option {}", option)),
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    let [server, command, secret] = <[String; 3]>::try_from(positional)
+        .map_err(|_| "expected server, command and secret".to_string())?;
+    let command = match command.as_str() {
+        "auth" => Command::Auth(method),
+        "acct" => Command::Acct(status),
+        "status" => Command::Status,
+        "coa" => Command::Coa,
+        "disconnect" => Command::Disconnect,
+        other => return Err(format!("unknown command {}", other)),
+    };
+
+    Ok(Options { server, command, secret, credentials, file, attributes, timeout, retries, quiet })
+}
+
+/// `host`, `host:port`, `[v6]:port` or a bare IPv6 address
+async fn resolve(server: &str, default_port: u16) -> Result<SocketAddr, String> {
+    if let Ok(ip) = server.parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, default_port));
+    }
+    let target = match server.rsplit_once(':') {
+        Some((_, port)) if port.parse::<u16>().is_ok() => server.to_string(),
+        _ => format!("{}:{}", server, default_port),
+    };
+    tokio::net::lookup_host(&target).await
+        .map_err(|e| format!("cannot resolve {}: {}", server, e))?
+        .next()
+        .ok_or_else(|| format!("cannot resolve {}", server))
+}
+
+fn read_attributes(options: &Options) -> Result<Vec<openrdx_core::auth::RadiusAttribute>, String> {
+    let mut text = match options.file.as_deref() {
+        Some("-") => std::io::read_to_string(std::io::stdin()).map_err(|e| format!("cannot read stdin: {}", e))?,
+        Some(path) => std::fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?,
+        None => String::new(),
+    };
+    for attr in &options.attributes {
+        text.push('\n');
+        text.push_str(attr);
+    }
+    client::parse_attribute_list(&text)
+}
+
+async fn run(options: Options) -> Result<u8, String> {
+    let target = resolve(&options.server, options.command.default_port()).await?;
+    let attributes = read_attributes(&options)?;
+    let request = client::build_request(options.command, rand::random(), &options.secret, &options.credentials, attributes)?;
+
+    let bind: SocketAddr = match target.ip() {
+        IpAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        IpAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = UdpSocket::bind(bind).await.map_err(|e| e.to_string())?;
+    socket.connect(target).await.map_err(|e| e.to_string())?;
+    if !options.quiet {
+        println!("Sent to {}: {}\n", target, client::format_packet(&request.packet));
+    }
+
+    let mut buf = [0u8; 4096];
+    for attempt in 0..=options.retries {
+        if attempt > 0 && !options.quiet {
+            eprintln!("No reply from {}, retransmitting ({}/{})", target, attempt, options.retries);
+        }
+        socket.send(&request.encoded).await.map_err(|e| format!("{}: {}", target, e))?;
+
+        let deadline = tokio::time::Instant::now() + options.timeout;
+        while let Ok(received) = tokio::time::timeout_at(deadline, socket.recv(&mut buf)).await {
+            let len = received.map_err(|e| format!("{}: {}", target, e))?;
+            match client::verify_reply(&request, &buf[..len], &options.secret) {
+                Ok(reply) => {
+                    if options.quiet {
+                        println!("{}", client::code_name(reply.code));
+                    } else {
+                        println!("Received from {}: {}", target, client::format_packet(&reply));
+                    }
+                    return Ok(if options.command.is_positive(reply.code) { 0 } else { 1 });
+                }
+                Err(e) => eprintln!("Ignoring reply from {}: {}", target, e),
+            }
+        }
+    }
+    Err(format!("no valid reply from {} after {} attempts", target, options.retries + 1))
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(e) if e.is_empty() => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+
+    match run(options).await {
+        Ok(status) => ExitCode::from(status),
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::from(2)
+        }
+    }
+}
@@ -0,0 +1,235 @@
+//! Replays the RADIUS requests of a pcap/pcapng capture against a running core and diffs its replies
+//! against the recorded ones.
+//!
+//! Exit status: 0 when every reply matches, 1 when any differs, 2 when a request went unanswered or
+//! could not be replayed, 64 for usage errors.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::process::ExitCode;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+use openrdx_core::auth::RadiusPacket;
+use openrdx_core::client::{self, CODE_ACCOUNTING_REQUEST, CODE_COA_REQUEST, CODE_DISCONNECT_REQUEST};
+use openrdx_core::replay::{self, Exchange, Secrets};
+
+const USAGE: &str = "\
+Usage: openrdx-replay [options] --secrets FILE <capture.pcap|capture.pcapng>
+
+Options:
+  -S, --secrets FILE       `<ip-or-cidr> <secret>` lines: the secrets of the recorded NAS
+  -s, --secret SECRET      secret shared with the core under test (default: the recorded one)
+      --auth ADDR          where Access-Request and Status-Server go (default 127.0.0.1:1812)
+      --acct ADDR          where Accounting-Request goes (default 127.0.0.1:1813)
+  -i, --ignore NAME        compare NAME by presence only, may be repeated; added to
+                           Message-Authenticator, State, Class, Event-Timestamp, Tunnel-Password,
+                           MS-MPPE-Send-Key and MS-MPPE-Recv-Key
+  -t, --timeout SECS       seconds to wait for each reply (default 3)
+  -r, --retries N          retransmissions after the first attempt (default 2)
+  -v, --verbose            print every request and both replies
+  -h, --help               show this help
+
+CoA and Disconnect requests, and requests without a recorded response, are skipped.";
+
+const EXIT_USAGE: u8 = 64;
+
+struct Options {
+    capture: String,
+    secrets: String,
+    secret: Option<String>,
+    auth: SocketAddr,
+    acct: SocketAddr,
+    ignored: Vec<String>,
+    timeout: Duration,
+    retries: u32,
+    verbose: bool,
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut capture = None;
+    let mut secrets = None;
+    let mut secret = None;
+    let mut auth = SocketAddr::from((Ipv4Addr::LOCALHOST, 1812));
+    let mut acct = SocketAddr::from((Ipv4Addr::LOCALHOST, 1813));
+    let mut ignored: Vec<String> = replay::DEFAULT_IGNORED.iter().map(|name| name.to_string()).collect();
+    let mut timeout = Duration::from_secs(3);
+    let mut retries = 2;
+    let mut verbose = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().cloned().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "-S" | "--secrets" => secrets = Some(value()?),
+            "-s" | "--secret" => secret = Some(value()?),
+            "--auth" => auth = value()?.parse().map_err(|_| "--auth must be an ip:port")?,
+            "--acct" => acct = value()?.parse().map_err(|_| "--acct must be an ip:port")?,
+            "-i" | "--ignore" => ignored.push(value()?),
+            "-t" | "--timeout" => {
+                let secs = value()?.parse::<f64>().map_err(|_| "timeout must be a number of seconds")?;
+                timeout = Duration::try_from_secs_f64(secs).map_err(|_| "timeout must be a number of seconds")?;
+            }
+            "-r" | "--retries" => retries = value()?.parse().map_err(|_| "retries must be a number")?,
+            "-v" | "--verbose" => verbose = true,
+            "-h" | "--help" => return Err(String::new()),
+            option if option.starts_with('-') && option.len() > 1 => return Err(format!("unknown option {}", option)),
+            _ if capture.is_none() => capture = Some(arg.clone()),
+            _ => return Err("expected a single capture file".to_string()),
+        }
+    }
+
+    let capture = capture.ok_or("expected a capture file")?;
+    let secrets = secrets.ok_or("--secrets is required")?;
+    Ok(Options { capture, secrets, secret, auth, acct, ignored, timeout, retries, verbose })
+}
+
+/// Verdict on one exchange; Pass and Fail carry the lines printed under it
+enum Outcome {
+    Pass(Vec<String>),
+    Fail(Vec<String>),
+    Skipped(String),
+    Error(String),
+}
+
+/// Sends a re-signed request and waits for a reply that verifies against it
+async fn send(options: &Options, target: SocketAddr, request: &RadiusPacket, encoded: &[u8], secret: &str)
+              -> Result<RadiusPacket, String> {
+    let bind: SocketAddr = match target.ip() {
+        IpAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        IpAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = UdpSocket::bind(bind).await.map_err(|e| e.to_string())?;
+    socket.connect(target).await.map_err(|e| format!("{}: {}", target, e))?;
+
+    let mut buf = [0u8; 4096];
+    for _ in 0..=options.retries {
+        socket.send(encoded).await.map_err(|e| format!("{}: {}", target, e))?;
+        let deadline = tokio::time::Instant::now() + options.timeout;
+        while let Ok(received) = tokio::time::timeout_at(deadline, socket.recv(&mut buf)).await {
+            let len = received.map_err(|e| format!("{}: {}", target, e))?;
+            match client::check_reply(request, &buf[..len], secret) {
+                Ok(reply) => return Ok(reply),
+                Err(e) => eprintln!("Ignoring reply from {}: {}", target, e),
+            }
+        }
+    }
+    Err(format!("no valid reply from {} after {} attempts", target, options.retries + 1))
+}
+
+async fn replay_exchange(options: &Options, secrets: &Secrets, exchange: &Exchange) -> Outcome {
+    let recorded_request = match RadiusPacket::parse(&exchange.request.payload) {
+        Ok(request) => request,
+        Err(e) => return Outcome::Error(format!("request does not parse: {}", e)),
+    };
+    if matches!(recorded_request.code, CODE_COA_REQUEST | CODE_DISCONNECT_REQUEST) {
+        return Outcome::Skipped("the core does not serve CoA or Disconnect".to_string());
+    }
+    let Some(response) = &exchange.response else {
+        return Outcome::Skipped("no recorded response".to_string());
+    };
+    let Some(recorded_secret) = secrets.lookup(exchange.nas().ip()) else {
+        return Outcome::Error(format!("no secret for {}", exchange.nas().ip()));
+    };
+
+    let recorded_reply = match client::check_reply(&recorded_request, &response.payload, recorded_secret) {
+        Ok(reply) => reply,
+        Err(e) => {
+            // A wrong entry in the secrets file shows up here first; the attributes can still be compared
+            eprintln!("frame {}: recorded response fails verification: {}", response.frame, e);
+            match RadiusPacket::parse(&response.payload) {
+                Ok(reply) => reply,
+                Err(e) => return Outcome::Error(format!("recorded response does not parse: {}", e)),
+            }
+        }
+    };
+
+    let secret = options.secret.as_deref().unwrap_or(recorded_secret);
+    let encoded = replay::resign_request(&recorded_request, recorded_secret, secret);
+    let Ok(request) = RadiusPacket::parse(&encoded) else {
+        return Outcome::Error("re-signed request does not parse".to_string());
+    };
+    let target = if request.code == CODE_ACCOUNTING_REQUEST { options.acct } else { options.auth };
+    let actual = match send(options, target, &request, &encoded, secret).await {
+        Ok(reply) => reply,
+        Err(e) => return Outcome::Error(e),
+    };
+
+    let mut details = Vec::new();
+    if options.verbose {
+        details.push(format!("Request: {}", client::format_packet(&request).replace('\n', "\n\t")));
+        details.push(format!("Recorded: {}", client::format_packet(&recorded_reply).replace('\n', "\n\t")));
+        details.push(format!("Actual: {}", client::format_packet(&actual).replace('\n', "\n\t")));
+    }
+    let diff = replay::diff_replies(&recorded_reply, &actual, &options.ignored);
+    if diff.is_empty() {
+        Outcome::Pass(details)
+    } else {
+        details.extend(diff);
+        Outcome::Fail(details)
+    }
+}
+
+async fn run(options: Options) -> Result<u8, String> {
+    let data = std::fs::read(&options.capture).map_err(|e| format!("cannot read {}: {}", options.capture, e))?;
+    let secrets = std::fs::read_to_string(&options.secrets)
+        .map_err(|e| format!("cannot read {}: {}", options.secrets, e))
+        .and_then(|text| Secrets::parse(&text).map_err(|e| format!("{}: {}", options.secrets, e)))?;
+    let datagrams = replay::read_datagrams(&data).map_err(|e| format!("{}: {}", options.capture, e))?;
+    let exchanges = replay::pair_exchanges(&datagrams);
+
+    let (mut passed, mut failed, mut skipped, mut errors) = (0, 0, 0, 0);
+    for exchange in &exchanges {
+        let header = match RadiusPacket::parse(&exchange.request.payload) {
+            Ok(request) => format!("frame {} {} Id {} from {}",
+                exchange.request.frame, client::code_name(request.code), request.identifier, exchange.nas()),
+            Err(_) => format!("frame {}", exchange.request.frame),
+        };
+        match replay_exchange(&options, &secrets, exchange).await {
+            Outcome::Pass(details) => {
+                passed += 1;
+                println!("PASS {}", header);
+                details.iter().for_each(|line| println!("\t{}", line));
+            }
+            Outcome::Fail(details) => {
+                failed += 1;
+                println!("FAIL {}", header);
+                details.iter().for_each(|line| println!("\t{}", line));
+            }
+            Outcome::Skipped(reason) => {
+                skipped += 1;
+                println!("SKIP {}: {}", header, reason);
+            }
+            Outcome::Error(e) => {
+                errors += 1;
+                println!("ERROR {}: {}", header, e);
+            }
+        }
+    }
+
+    println!("\n{} passed, {} failed, {} skipped, {} errors", passed, failed, skipped, errors);
+    Ok(if errors > 0 { 2 } else if failed > 0 { 1 } else { 0 })
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(e) if e.is_empty() => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+
+    match run(options).await {
+        Ok(status) => ExitCode::from(status),
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::from(2)
+        }
+    }
+}
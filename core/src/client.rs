@@ -0,0 +1,684 @@
+//! Request building and reply checking for `openrdx-client`, a radclient-style tool for exercising the
+//! services: Access-Request with PAP, CHAP, MS-CHAP or MS-CHAPv2, Accounting-Request, Status-Server and
+//! CoA/Disconnect-Request, with extra attributes given as `Name = value` lines.
+
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+use md5::{Digest, Md5};
+use openrdx_dynauth::{Request, RequestKind, SessionSelector};
+
+use crate::auth::{self, RadiusAttribute, RadiusPacket, ToUtf16Le};
+
+pub const CODE_ACCESS_REQUEST: u8 = 1;
+pub const CODE_ACCESS_ACCEPT: u8 = 2;
+pub const CODE_ACCESS_REJECT: u8 = 3;
+pub const CODE_ACCOUNTING_REQUEST: u8 = 4;
+pub const CODE_ACCOUNTING_RESPONSE: u8 = 5;
+pub const CODE_ACCESS_CHALLENGE: u8 = 11;
+pub const CODE_STATUS_SERVER: u8 = 12;
+pub const CODE_DISCONNECT_REQUEST: u8 = 40;
+pub const CODE_DISCONNECT_ACK: u8 = 41;
+pub const CODE_DISCONNECT_NAK: u8 = 42;
+pub const CODE_COA_REQUEST: u8 = 43;
+pub const CODE_COA_ACK: u8 = 44;
+pub const CODE_COA_NAK: u8 = 45;
+
+const ATTR_USER_NAME: u8 = 1;
+const ATTR_USER_PASSWORD: u8 = 2;
+const ATTR_CHAP_PASSWORD: u8 = 3;
+const ATTR_NAS_IP_ADDRESS: u8 = 4;
+const ATTR_NAS_PORT: u8 = 5;
+const ATTR_FRAMED_IP_ADDRESS: u8 = 8;
+const ATTR_VENDOR_SPECIFIC: u8 = 26;
+const ATTR_CALLING_STATION_ID: u8 = 31;
+const ATTR_NAS_IDENTIFIER: u8 = 32;
+const ATTR_ACCT_STATUS_TYPE: u8 = 40;
+const ATTR_ACCT_SESSION_ID: u8 = 44;
+const ATTR_MESSAGE_AUTHENTICATOR: u8 = 80;
+
+const VENDOR_MICROSOFT: u32 = 311;
+const VENDOR_ATTR_MS_CHAP_RESPONSE: u8 = 1;
+const VENDOR_ATTR_MS_CHAP_CHALLENGE: u8 = 11;
+const VENDOR_ATTR_MS_CHAP2_RESPONSE: u8 = 25;
+const VENDOR_ATTR_MS_CHAP2_SUCCESS: u8 = 26;
+
+/// Standard attributes the tool reads and prints by name, with their RFC 8044 data types
+const DICTIONARY: &[(u8, &str, &str)] = &[
+    (1, "User-Name", "string"),
+    (2, "User-Password", "string"),
+    (3, "CHAP-Password", "octets"),
+    (4, "NAS-IP-Address", "ipaddr"),
+    (5, "NAS-Port", "integer"),
+    (6, "Service-Type", "integer"),
+    (7, "Framed-Protocol", "integer"),
+    (8, "Framed-IP-Address", "ipaddr"),
+    (9, "Framed-IP-Netmask", "ipaddr"),
+    (11, "Filter-Id", "string"),
+    (12, "Framed-MTU", "integer"),
+    (18, "Reply-Message", "string"),
+    (24, "State", "octets"),
+    (25, "Class", "octets"),
+    (26, "Vendor-Specific", "octets"),
+    (27, "Session-Timeout", "integer"),
+    (28, "Idle-Timeout", "integer"),
+    (30, "Called-Station-Id", "string"),
+    (31, "Calling-Station-Id", "string"),
+    (32, "NAS-Identifier", "string"),
+    (33, "Proxy-State", "octets"),
+    (40, "Acct-Status-Type", "integer"),
+    (41, "Acct-Delay-Time", "integer"),
+    (42, "Acct-Input-Octets", "integer"),
+    (43, "Acct-Output-Octets", "integer"),
+    (44, "Acct-Session-Id", "string"),
+    (45, "Acct-Authentic", "integer"),
+    (46, "Acct-Session-Time", "integer"),
+    (47, "Acct-Input-Packets", "integer"),
+    (48, "Acct-Output-Packets", "integer"),
+    (49, "Acct-Terminate-Cause", "integer"),
+    (52, "Acct-Input-Gigawords", "integer"),
+    (53, "Acct-Output-Gigawords", "integer"),
+    (55, "Event-Timestamp", "date"),
+    (60, "CHAP-Challenge", "octets"),
+    (61, "NAS-Port-Type", "integer"),
+    (64, "Tunnel-Type", "integer"),
+    (65, "Tunnel-Medium-Type", "integer"),
+    (69, "Tunnel-Password", "octets"),
+    (79, "EAP-Message", "octets"),
+    (80, "Message-Authenticator", "octets"),
+    (81, "Tunnel-Private-Group-Id", "string"),
+    (85, "Acct-Interim-Interval", "integer"),
+    (87, "NAS-Port-Id", "string"),
+    (88, "Framed-Pool", "string"),
+    (95, "NAS-IPv6-Address", "ipv6addr"),
+    (96, "Framed-Interface-Id", "ifid"),
+    (97, "Framed-IPv6-Prefix", "ipv6prefix"),
+    (101, "Error-Cause", "integer"),
+    (123, "Delegated-IPv6-Prefix", "ipv6prefix"),
+    (168, "Framed-IPv6-Address", "ipv6addr"),
+];
+
+/// Microsoft attributes (RFC 2548) printed by name
+const MICROSOFT_DICTIONARY: &[(u8, &str)] = &[
+    (1, "MS-CHAP-Response"),
+    (2, "MS-CHAP-Error"),
+    (7, "MS-MPPE-Encryption-Policy"),
+    (8, "MS-MPPE-Encryption-Types"),
+    (11, "MS-CHAP-Challenge"),
+    (16, "MS-MPPE-Send-Key"),
+    (17, "MS-MPPE-Recv-Key"),
+    (25, "MS-CHAP2-Response"),
+    (26, "MS-CHAP2-Success"),
+];
+
+pub fn code_name(code: u8) -> String {
+    match code {
+        CODE_ACCESS_REQUEST => "Access-Request".to_string(),
+        CODE_ACCESS_ACCEPT => "Access-Accept".to_string(),
+        CODE_ACCESS_REJECT => "Access-Reject".to_string(),
+        CODE_ACCOUNTING_REQUEST => "Accounting-Request".to_string(),
+        CODE_ACCOUNTING_RESPONSE => "Accounting-Response".to_string(),
+        CODE_ACCESS_CHALLENGE => "Access-Challenge".to_string(),
+        CODE_STATUS_SERVER => "Status-Server".to_string(),
+        CODE_DISCONNECT_REQUEST => "Disconnect-Request".to_string(),
+        CODE_DISCONNECT_ACK => "Disconnect-ACK".to_string(),
+        CODE_DISCONNECT_NAK => "Disconnect-NAK".to_string(),
+        CODE_COA_REQUEST => "CoA-Request".to_string(),
+        CODE_COA_ACK => "CoA-ACK".to_string(),
+        CODE_COA_NAK => "CoA-NAK".to_string(),
+        code => format!("Code-{}", code),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthMethod {
+    Pap,
+    Chap,
+    MsChap,
+    MsChapV2,
+}
+
+impl FromStr for AuthMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "pap" => Ok(AuthMethod::Pap),
+            "chap" => Ok(AuthMethod::Chap),
+            "mschap" => Ok(AuthMethod::MsChap),
+            "mschapv2" => Ok(AuthMethod::MsChapV2),
+            _ => Err(format!("unknown authentication method {:?} (pap, chap, mschap or mschapv2)", s)),
+        }
+    }
+}
+
+/// Acct-Status-Type of an Accounting-Request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AcctStatus {
+    Start,
+    Interim,
+    Stop,
+}
+
+impl FromStr for AcctStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "start" => Ok(AcctStatus::Start),
+            "interim" | "interim-update" => Ok(AcctStatus::Interim),
+            "stop" => Ok(AcctStatus::Stop),
+            _ => Err(format!("unknown accounting status {:?} (start, interim or stop)", s)),
+        }
+    }
+}
+
+impl AcctStatus {
+    fn value(self) -> u32 {
+        match self {
+            AcctStatus::Start => 1,
+            AcctStatus::Stop => 2,
+            AcctStatus::Interim => 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Auth(AuthMethod),
+    Acct(AcctStatus),
+    Status,
+    Coa,
+    Disconnect,
+}
+
+impl Command {
+    pub fn code(self) -> u8 {
+        match self {
+            Command::Auth(_) => CODE_ACCESS_REQUEST,
+            Command::Acct(_) => CODE_ACCOUNTING_REQUEST,
+            Command::Status => CODE_STATUS_SERVER,
+            Command::Coa => CODE_COA_REQUEST,
+            Command::Disconnect => CODE_DISCONNECT_REQUEST,
+        }
+    }
+
+    /// Port the request goes to when the server address doesn't name one
+    pub fn default_port(self) -> u16 {
+        match self {
+            Command::Auth(_) | Command::Status => 1812,
+            Command::Acct(_) => 1813,
+            Command::Coa | Command::Disconnect => 3799,
+        }
+    }
+
+    fn dynauth_kind(self) -> Option<RequestKind> {
+        match self {
+            Command::Coa => Some(RequestKind::Coa),
+            Command::Disconnect => Some(RequestKind::Disconnect),
+            _ => None,
+        }
+    }
+
+    /// Whether `code` is the answer that counts as success
+    pub fn is_positive(self, code: u8) -> bool {
+        match self {
+            Command::Auth(_) => code == CODE_ACCESS_ACCEPT,
+            Command::Acct(_) => code == CODE_ACCOUNTING_RESPONSE,
+            // Answered on the authentication port with Access-Accept, on the accounting port with Accounting-Response
+            Command::Status => code == CODE_ACCESS_ACCEPT || code == CODE_ACCOUNTING_RESPONSE,
+            Command::Coa => code == CODE_COA_ACK,
+            Command::Disconnect => code == CODE_DISCONNECT_ACK,
+        }
+    }
+}
+
+/// Parses one `Name = value` line. Besides dictionary names, `Attr-N` sets standard attribute N and
+/// `Vendor-V-Attr-N` vendor attribute N of vendor V; their values are strings, or hex with a `0x` prefix.
+pub fn parse_attribute(line: &str) -> Result<RadiusAttribute, String> {
+    let (name, value) = line.split_once('=').ok_or_else(|| format!("expected Name = value, got {:?}", line))?;
+    let (name, value) = (name.trim(), value.trim());
+    let value = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')).unwrap_or(value);
+
+    let (vendor_id, attribute_id, attribute_type) = if let Some((id, _, typ)) = DICTIONARY.iter().find(|(_, n, _)| n.eq_ignore_ascii_case(name)) {
+        (0, *id, *typ)
+    } else if let Some((vendor, attribute)) = name.strip_prefix("Vendor-").and_then(|rest| rest.split_once("-Attr-")) {
+        let vendor = vendor.parse::<u32>().map_err(|_| format!("bad vendor id in {}", name))?;
+        let attribute = attribute.parse::<u8>().map_err(|_| format!("bad attribute number in {}", name))?;
+        (vendor, attribute, "octets")
+    } else if let Some(attribute) = name.strip_prefix("Attr-") {
+        (0, attribute.parse::<u8>().map_err(|_| format!("bad attribute number in {}", name))?, "octets")
+    } else if let Some((id, _)) = MICROSOFT_DICTIONARY.iter().find(|(_, n)| n.eq_ignore_ascii_case(name)) {
+        (VENDOR_MICROSOFT, *id, "octets")
+    } else {
+        return Err(format!("unknown attribute {}", name));
+    };
+
+    let encoded = auth::encode_value(attribute_type, value)
+        .ok_or_else(|| format!("invalid {} value for {}: {:?}", attribute_type, name, value))?;
+    auth::build_attribute(vendor_id, attribute_id, encoded).ok_or_else(|| format!("value of {} is too long", name))
+}
+
+/// Parses an attribute list: one `Name = value` per line, or several separated by commas as radclient
+/// accepts them. Blank lines and `#` comments are skipped.
+pub fn parse_attribute_list(text: &str) -> Result<Vec<RadiusAttribute>, String> {
+    let mut attributes = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        for item in split_items(line) {
+            attributes.push(parse_attribute(item).map_err(|e| format!("line {}: {}", number + 1, e))?);
+        }
+    }
+    Ok(attributes)
+}
+
+/// Splits on commas outside double quotes
+fn split_items(line: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                items.push(line[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(line[start..].trim());
+    items.into_iter().filter(|item| !item.is_empty()).collect()
+}
+
+/// `Name = value` for printing, decoding the value by its dictionary type
+pub fn format_attribute(attr: &RadiusAttribute) -> String {
+    if attr.typ == ATTR_VENDOR_SPECIFIC && attr.value.len() >= 6 && attr.value[5] as usize == attr.value.len() - 4 {
+        let vendor_id = u32::from_be_bytes([attr.value[0], attr.value[1], attr.value[2], attr.value[3]]);
+        let vendor_type = attr.value[4];
+        let value = &attr.value[6..];
+        let name = MICROSOFT_DICTIONARY.iter()
+            .find(|(id, _)| vendor_id == VENDOR_MICROSOFT && *id == vendor_type)
+            .map(|(_, name)| name.to_string())
+            .unwrap_or_else(|| format!("Vendor-{}-Attr-{}", vendor_id, vendor_type));
+        return format!("{} = {}", name, format_value("octets", value));
+    }
+
+    match DICTIONARY.iter().find(|(id, _, _)| *id == attr.typ) {
+        Some((_, name, typ)) => format!("{} = {}", name, format_value(typ, &attr.value)),
+        None => format!("Attr-{} = {}", attr.typ, format_value("octets", &attr.value)),
+    }
+}
+
+fn format_value(attribute_type: &str, value: &[u8]) -> String {
+    let hex = || format!("0x{}", hex::encode(value));
+    match attribute_type {
+        "integer" | "date" => <[u8; 4]>::try_from(value).map(|v| u32::from_be_bytes(v).to_string()).unwrap_or_else(|_| hex()),
+        "ipaddr" => <[u8; 4]>::try_from(value).map(|v| Ipv4Addr::from(v).to_string()).unwrap_or_else(|_| hex()),
+        "ipv6addr" => <[u8; 16]>::try_from(value).map(|v| Ipv6Addr::from(v).to_string()).unwrap_or_else(|_| hex()),
+        "ipv6prefix" if value.len() >= 2 && value.len() <= 18 => {
+            let mut octets = [0u8; 16];
+            octets[..value.len() - 2].copy_from_slice(&value[2..]);
+            format!("{}/{}", Ipv6Addr::from(octets), value[1])
+        }
+        "ifid" if value.len() == 8 => value.chunks(2).map(|g| format!("{:x}", u16::from_be_bytes([g[0], g[1]]))).collect::<Vec<_>>().join(":"),
+        "string" => match std::str::from_utf8(value) {
+            Ok(text) if !text.chars().any(char::is_control) => format!("\"{}\"", text),
+            _ => hex(),
+        },
+        _ => hex(),
+    }
+}
+
+/// Credentials for an Access-Request
+#[derive(Debug, Clone, Default)]
+pub struct Credentials {
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+/// A request ready to send, with what checking its reply needs
+#[derive(Debug, Clone)]
+pub struct OutgoingRequest {
+    pub command: Command,
+    pub packet: RadiusPacket,
+    pub encoded: Vec<u8>,
+    /// MS-CHAPv2 values the MS-CHAP2-Success of an Access-Accept is checked against
+    mschapv2: Option<MsChapV2Exchange>,
+}
+
+#[derive(Debug, Clone)]
+struct MsChapV2Exchange {
+    username: String,
+    password_hash: Vec<u8>,
+    nt_response: Vec<u8>,
+    peer_challenge: [u8; 16],
+    authenticator_challenge: [u8; 16],
+}
+
+/// Builds and signs a request. Access-Request and Status-Server carry a Message-Authenticator;
+/// Accounting-Request is signed with a Request Authenticator computed over the packet (RFC 2866), and
+/// CoA and Disconnect-Request are built by `openrdx_dynauth`, like the ones the services send.
+pub fn build_request(command: Command, identifier: u8, secret: &str, credentials: &Credentials,
+                     mut attributes: Vec<RadiusAttribute>) -> Result<OutgoingRequest, String> {
+    if let Some(kind) = command.dynauth_kind() {
+        if let Some(username) = &credentials.username {
+            attributes.retain(|attr| attr.typ != ATTR_USER_NAME);
+            attributes.insert(0, RadiusAttribute { typ: ATTR_USER_NAME, value: username.as_bytes().to_vec() });
+        }
+        let encoded = dynauth_request(kind, attributes).encode(identifier, secret).map_err(|e| e.to_string())?;
+        let packet = RadiusPacket::parse(&encoded).map_err(|e| format!("request does not parse back: {}", e))?;
+        return Ok(OutgoingRequest { command, packet, encoded, mschapv2: None });
+    }
+
+    let random_authenticator = matches!(command, Command::Auth(_) | Command::Status);
+    let authenticator: [u8; 16] = if random_authenticator { rand::random() } else { [0u8; 16] };
+    let mut mschapv2 = None;
+
+    if let Some(username) = &credentials.username {
+        attributes.retain(|attr| attr.typ != ATTR_USER_NAME);
+        attributes.insert(0, RadiusAttribute { typ: ATTR_USER_NAME, value: username.as_bytes().to_vec() });
+    }
+
+    match command {
+        Command::Auth(method) => {
+            let username = attributes.iter().find(|attr| attr.typ == ATTR_USER_NAME)
+                .map(|attr| String::from_utf8_lossy(&attr.value).to_string())
+                .ok_or("Access-Request needs a User-Name")?;
+            // A User-Password in the attribute list stands in for the password option
+            let password = match &credentials.password {
+                Some(password) => password.clone(),
+                None => attributes.iter().find(|attr| attr.typ == ATTR_USER_PASSWORD)
+                    .map(|attr| String::from_utf8_lossy(&attr.value).to_string())
+                    .ok_or("Access-Request needs a password")?,
+            };
+            attributes.retain(|attr| attr.typ != ATTR_USER_PASSWORD);
+
+            match method {
+                AuthMethod::Pap => {
+                    let hidden = auth::hide_password(password.as_bytes(), &authenticator, secret);
+                    attributes.push(RadiusAttribute { typ: ATTR_USER_PASSWORD, value: hidden });
+                }
+                AuthMethod::Chap => {
+                    // The Request Authenticator doubles as the CHAP challenge (RFC 2865, section 2.2)
+                    let chap_id = rand::random::<u8>();
+                    let mut hasher = Md5::new();
+                    hasher.update([chap_id]);
+                    hasher.update(password.as_bytes());
+                    hasher.update(authenticator);
+                    let mut value = vec![chap_id];
+                    value.extend_from_slice(&hasher.finalize());
+                    attributes.push(RadiusAttribute { typ: ATTR_CHAP_PASSWORD, value });
+                }
+                AuthMethod::MsChap => {
+                    // RFC 2548, section 2.1.3: Ident, Flags (1 = use NT-Response), LM-Response, NT-Response
+                    let challenge: [u8; 8] = rand::random();
+                    let nt_response = auth::generate_nt_response(&auth::nt_hash(&password.to_utf16le()), &challenge);
+                    let mut response = vec![rand::random::<u8>(), 1];
+                    response.extend_from_slice(&[0u8; 24]);
+                    response.extend_from_slice(&nt_response);
+                    attributes.extend(auth::build_attribute(VENDOR_MICROSOFT, VENDOR_ATTR_MS_CHAP_CHALLENGE, challenge.to_vec()));
+                    attributes.extend(auth::build_attribute(VENDOR_MICROSOFT, VENDOR_ATTR_MS_CHAP_RESPONSE, response));
+                }
+                AuthMethod::MsChapV2 => {
+                    // RFC 2548, section 2.3.2: Ident, Flags, Peer-Challenge, Reserved, NT-Response
+                    let authenticator_challenge: [u8; 16] = rand::random();
+                    let peer_challenge: [u8; 16] = rand::random();
+                    let password_hash = auth::nt_hash(&password.to_utf16le());
+                    let challenge = auth::generate_nt_response_challenge(&peer_challenge, &authenticator_challenge, &username);
+                    let nt_response = auth::generate_nt_response(&password_hash, &challenge);
+                    let mut response = vec![rand::random::<u8>(), 0];
+                    response.extend_from_slice(&peer_challenge);
+                    response.extend_from_slice(&[0u8; 8]);
+                    response.extend_from_slice(&nt_response);
+                    attributes.extend(auth::build_attribute(VENDOR_MICROSOFT, VENDOR_ATTR_MS_CHAP_CHALLENGE, authenticator_challenge.to_vec()));
+                    attributes.extend(auth::build_attribute(VENDOR_MICROSOFT, VENDOR_ATTR_MS_CHAP2_RESPONSE, response));
+                    mschapv2 = Some(MsChapV2Exchange { username, password_hash, nt_response, peer_challenge, authenticator_challenge });
+                }
+            }
+        }
+        Command::Acct(status) => {
+            attributes.retain(|attr| attr.typ != ATTR_ACCT_STATUS_TYPE);
+            attributes.insert(0, RadiusAttribute { typ: ATTR_ACCT_STATUS_TYPE, value: status.value().to_be_bytes().to_vec() });
+            if !attributes.iter().any(|attr| attr.typ == ATTR_ACCT_SESSION_ID) {
+                let session_id = format!("{:08X}", rand::random::<u32>());
+                attributes.push(RadiusAttribute { typ: ATTR_ACCT_SESSION_ID, value: session_id.into_bytes() });
+            }
+        }
+        Command::Status | Command::Coa | Command::Disconnect => {}
+    }
+
+    attributes.retain(|attr| attr.typ != ATTR_MESSAGE_AUTHENTICATOR);
+    if random_authenticator {
+        attributes.push(RadiusAttribute { typ: ATTR_MESSAGE_AUTHENTICATOR, value: vec![0u8; 16] });
+    }
+
+    let packet = RadiusPacket { code: command.code(), identifier, length: 0, authenticator, attributes };
+    let mut encoded = packet.encode();
+    if encoded.len() > 4096 {
+        return Err("request exceeds 4096 bytes".to_string());
+    }
+    if random_authenticator {
+        auth::sign_message_authenticator(&mut encoded, secret);
+    } else {
+        let mut hasher = Md5::new();
+        hasher.update(&encoded);
+        hasher.update(secret.as_bytes());
+        encoded[4..20].copy_from_slice(&hasher.finalize());
+    }
+    let packet = RadiusPacket::parse(&encoded).map_err(|e| format!("request does not parse back: {}", e))?;
+
+    Ok(OutgoingRequest { command, packet, encoded, mschapv2 })
+}
+
+/// The attribute list as an RFC 5176 request: the session identification attributes select the session,
+/// the others are sent as the changes, and a Message-Authenticator in the list asks for one to be added
+fn dynauth_request(kind: RequestKind, attributes: Vec<RadiusAttribute>) -> Request {
+    let mut session = SessionSelector::default();
+    let mut changes = Vec::new();
+    let mut message_authenticator = false;
+    for attr in attributes {
+        let text = String::from_utf8(attr.value.clone()).ok();
+        let number = <[u8; 4]>::try_from(attr.value.as_slice()).ok();
+        match (attr.typ, text, number) {
+            (ATTR_USER_NAME, Some(text), _) => session.username = Some(text),
+            (ATTR_ACCT_SESSION_ID, Some(text), _) => session.acct_session_id = Some(text),
+            (ATTR_CALLING_STATION_ID, Some(text), _) => session.calling_station_id = Some(text),
+            (ATTR_NAS_IDENTIFIER, Some(text), _) => session.nas_identifier = Some(text),
+            (ATTR_NAS_PORT, _, Some(number)) => session.nas_port = Some(u32::from_be_bytes(number)),
+            (ATTR_FRAMED_IP_ADDRESS, _, Some(number)) => session.framed_ip = Some(Ipv4Addr::from(number)),
+            (ATTR_NAS_IP_ADDRESS, _, Some(number)) => session.nas_ip = Some(Ipv4Addr::from(number)),
+            (ATTR_MESSAGE_AUTHENTICATOR, _, _) => message_authenticator = true,
+            _ => changes.push(openrdx_dynauth::Attribute::new(attr.typ, &attr.value)),
+        }
+    }
+    Request { kind, session, attributes: changes, message_authenticator }
+}
+
+/// Why a reply was not accepted as the answer to a request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplyError {
+    Malformed,
+    WrongIdentifier(u8),
+    BadResponseAuthenticator,
+    BadMessageAuthenticator,
+    /// The Access-Accept's MS-CHAP2-Success is missing or doesn't prove the server knows the password
+    BadMsChap2Success,
+    /// A CoA or Disconnect reply `openrdx_dynauth` didn't accept
+    DynAuth(String),
+}
+
+impl fmt::Display for ReplyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplyError::Malformed => write!(f, "malformed reply"),
+            ReplyError::WrongIdentifier(id) => write!(f, "reply has identifier {}", id),
+            ReplyError::BadResponseAuthenticator => write!(f, "response authenticator mismatch, check the secret"),
+            ReplyError::BadMessageAuthenticator => write!(f, "Message-Authenticator mismatch"),
+            ReplyError::BadMsChap2Success => write!(f, "MS-CHAP2-Success does not match the password"),
+            ReplyError::DynAuth(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ReplyError {}
+
+/// Checks a reply against the request it answers: identifier, Response Authenticator and
+/// Message-Authenticator when present. Replies to CoA and Disconnect-Request are checked by `openrdx_dynauth`.
+pub fn check_reply(request: &RadiusPacket, reply: &[u8], secret: &str) -> Result<RadiusPacket, ReplyError> {
+    let packet = RadiusPacket::parse(reply).map_err(|_| ReplyError::Malformed)?;
+    let reply = &reply[..packet.length as usize];
+    if reply.len() < 20 {
+        return Err(ReplyError::Malformed);
+    }
+    if matches!(request.code, CODE_COA_REQUEST | CODE_DISCONNECT_REQUEST) {
+        openrdx_dynauth::parse_reply(reply, &request.encode(), secret).map_err(|e| ReplyError::DynAuth(e.to_string()))?;
+        return Ok(packet);
+    }
+    if packet.identifier != request.identifier {
+        return Err(ReplyError::WrongIdentifier(packet.identifier));
+    }
+
+    let mut hasher = Md5::new();
+    hasher.update(&reply[..4]);
+    hasher.update(request.authenticator);
+    hasher.update(&reply[20..]);
+    hasher.update(secret.as_bytes());
+    if hasher.finalize().as_slice() != &reply[4..20] {
+        return Err(ReplyError::BadResponseAuthenticator);
+    }
+
+    if packet.attributes.iter().any(|attr| attr.typ == ATTR_MESSAGE_AUTHENTICATOR) {
+        let mut signed = reply.to_vec();
+        signed[4..20].copy_from_slice(&request.authenticator);
+        auth::sign_message_authenticator(&mut signed, secret);
+        if signed[20..] != reply[20..] {
+            return Err(ReplyError::BadMessageAuthenticator);
+        }
+    }
+
+    Ok(packet)
+}
+
+/// `check_reply`, plus the MS-CHAP2-Success of an MS-CHAPv2 Access-Accept
+pub fn verify_reply(request: &OutgoingRequest, reply: &[u8], secret: &str) -> Result<RadiusPacket, ReplyError> {
+    let packet = check_reply(&request.packet, reply, secret)?;
+
+    if let Some(exchange) = &request.mschapv2 && packet.code == CODE_ACCESS_ACCEPT {
+        let expected = auth::calculate_authenticator_response(&exchange.password_hash, &exchange.nt_response,
+            &exchange.peer_challenge, &exchange.authenticator_challenge, &exchange.username);
+        let expected = format!("S={}", hex::encode_upper(expected));
+        // Ident, then the 42-character "S=" string
+        let matches = packet.attributes.iter().any(|attr| {
+            attr.typ == ATTR_VENDOR_SPECIFIC && attr.value.len() >= 7
+                && u32::from_be_bytes([attr.value[0], attr.value[1], attr.value[2], attr.value[3]]) == VENDOR_MICROSOFT
+                && attr.value[4] == VENDOR_ATTR_MS_CHAP2_SUCCESS
+                && attr.value[7..].starts_with(expected.as_bytes())
+        });
+        if !matches {
+            return Err(ReplyError::BadMsChap2Success);
+        }
+    }
+
+    Ok(packet)
+}
+
+/// Human-readable dump of a packet: a header line, then one indented attribute per line
+pub fn format_packet(packet: &RadiusPacket) -> String {
+    let mut out = format!("{} Id {} length {}", code_name(packet.code), packet.identifier, packet.length);
+    for attr in &packet.attributes {
+        out.push_str("\n\t");
+        out.push_str(&format_attribute(attr));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attribute_list_roundtrip() {
+        let attributes = parse_attribute_list(
+            "# NAS\nNAS-IP-Address = 192.0.2.1, NAS-Port = 7\nCalling-Station-Id = \"02-00-00-00-00-01\"\n\
+             Framed-IPv6-Prefix = 2001:db8::/56\nVendor-9-Attr-1 = shell:priv-lvl=15\nAttr-250 = 0x0102\n",
+        ).unwrap();
+        let printed: Vec<String> = attributes.iter().map(format_attribute).collect();
+        assert_eq!(printed, [
+            "NAS-IP-Address = 192.0.2.1",
+            "NAS-Port = 7",
+            "Calling-Station-Id = \"02-00-00-00-00-01\"",
+            "Framed-IPv6-Prefix = 2001:db8::/56",
+            "Vendor-9-Attr-1 = 0x7368656c6c3a707269762d6c766c3d3135",
+            "Attr-250 = 0x0102",
+        ]);
+        assert!(parse_attribute_list("NAS-Port = seven").unwrap_err().starts_with("line 1:"));
+        assert!(parse_attribute("No-Such-Attribute = 1").is_err());
+    }
+
+    #[test]
+    fn test_mschap2_success_verification() {
+        // RFC 2759, section 9.2
+        let password_hash = auth::nt_hash(&"clientPass".to_utf16le());
+        let peer_challenge: [u8; 16] = hex::decode("21402324255E262A28295F2B3A337C7E").unwrap().try_into().unwrap();
+        let authenticator_challenge: [u8; 16] = hex::decode("5B5D7C7D7B3F2F3E3C2C602132262628").unwrap().try_into().unwrap();
+        let challenge = auth::generate_nt_response_challenge(&peer_challenge, &authenticator_challenge, "User");
+        let nt_response = auth::generate_nt_response(&password_hash, &challenge);
+        assert_eq!(hex::encode_upper(&nt_response), "82309ECD8D708B5EA08FAA3981CD83544233114A3D85D6DF");
+
+        let secret = "testing123";
+        let credentials = Credentials { username: Some("User".to_string()), password: Some("clientPass".to_string()) };
+        let mut request = build_request(Command::Auth(AuthMethod::MsChapV2), 9, secret, &credentials, Vec::new()).unwrap();
+        request.mschapv2 = Some(MsChapV2Exchange {
+            username: "User".to_string(), password_hash, nt_response, peer_challenge, authenticator_challenge,
+        });
+
+        let accept = |success: &str| {
+            let mut value = vec![1];
+            value.extend_from_slice(success.as_bytes());
+            let reply = RadiusPacket {
+                code: CODE_ACCESS_ACCEPT, identifier: 9, length: 0, authenticator: request.packet.authenticator,
+                attributes: auth::build_attribute(VENDOR_MICROSOFT, VENDOR_ATTR_MS_CHAP2_SUCCESS, value).into_iter().collect(),
+            };
+            let mut encoded = reply.encode();
+            let mut hasher = Md5::new();
+            hasher.update(&encoded);
+            hasher.update(secret.as_bytes());
+            encoded[4..20].copy_from_slice(&hasher.finalize());
+            encoded
+        };
+        assert!(verify_reply(&request, &accept("S=407A5589115FD0D6209F510FE9C04566932CDA56"), secret).is_ok());
+        assert_eq!(verify_reply(&request, &accept("S=0000000000000000000000000000000000000000"), secret).unwrap_err(),
+                   ReplyError::BadMsChap2Success);
+        assert_eq!(verify_reply(&request, &accept("S=407A5589115FD0D6209F510FE9C04566932CDA56"), "wrong").unwrap_err(),
+                   ReplyError::BadResponseAuthenticator);
+    }
+
+    #[test]
+    fn test_coa_through_dynauth() {
+        let secret = "testing123";
+        let credentials = Credentials { username: Some("alice".to_string()), password: None };
+        let attributes = parse_attribute_list("Acct-Session-Id = \"S1\"\nSession-Timeout = 3600").unwrap();
+        let request = build_request(Command::Coa, 3, secret, &credentials, attributes).unwrap();
+        let printed: Vec<String> = request.packet.attributes.iter().map(format_attribute).collect();
+        assert_eq!(printed, ["User-Name = \"alice\"", "Acct-Session-Id = \"S1\"", "Session-Timeout = 3600"]);
+
+        let ack = |identifier: u8| {
+            let reply = RadiusPacket {
+                code: CODE_COA_ACK, identifier, length: 0, authenticator: request.packet.authenticator, attributes: Vec::new(),
+            };
+            let mut encoded = reply.encode();
+            let mut hasher = Md5::new();
+            hasher.update(&encoded);
+            hasher.update(secret.as_bytes());
+            encoded[4..20].copy_from_slice(&hasher.finalize());
+            encoded
+        };
+        assert_eq!(verify_reply(&request, &ack(3), secret).unwrap().code, CODE_COA_ACK);
+        assert!(matches!(verify_reply(&request, &ack(3), "wrong"), Err(ReplyError::DynAuth(_))));
+        assert!(matches!(verify_reply(&request, &ack(4), secret), Err(ReplyError::DynAuth(_))));
+    }
+}
@@ -0,0 +1,10 @@
+//! OpenRDX RADIUS services. `main.rs` starts the authentication or accounting service;
+//! the `openrdx-client` and `openrdx-replay` tools build their packets with the same code.
+
+pub mod auth;
+pub mod accounting;
+pub mod status_server;
+pub mod metrics;
+pub mod config;
+pub mod client;
+pub mod replay;
@@ -6,8 +6,7 @@ use std::sync::Arc;
 use tokio::signal;
 use std::time::Duration;
 
-mod auth;
-mod accounting;
+use openrdx_core::{accounting, auth, config, metrics};
 
 #[derive(Debug)]
 enum ServiceType {
@@ -85,30 +84,80 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     debug!("Starting service type: {:?}", service_type);
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let check_only = args.iter().any(|arg| arg == "--check-config");
+    let config_path = args.iter()
+        .position(|arg| arg == "--config")
+        .and_then(|i| args.get(i + 1).cloned())
+        .or_else(|| std::env::var("CONFIG_PATH").ok().filter(|path| !path.is_empty()));
+
+    let loaded = config::Config::load(config_path.as_deref().map(Path::new)).and_then(|mut config| {
+        // RADIUS_BIND_ADDR predates the per-listener settings and applies to the service being started
+        if let Ok(addr) = std::env::var("RADIUS_BIND_ADDR") {
+            match service_type {
+                ServiceType::Auth => config.listeners.auth = addr,
+                ServiceType::Accounting => config.listeners.accounting = addr,
+            }
+        }
+        config.validate()?;
+        Ok(config)
+    });
+
+    let config = match loaded {
+        Ok(config) => config,
+        Err(e) if check_only => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    if check_only {
+        println!("Configuration OK ({})", config_path.as_deref().unwrap_or("environment only"));
+        return Ok(());
+    }
+    debug!("Configuration loaded: {}", config.summary());
+
+    // Prometheus metrics are served for both service types; an empty address disables them
+    if !config.listeners.metrics.is_empty() {
+        let listener = tokio::net::TcpListener::bind(&config.listeners.metrics).await?;
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve(listener).await {
+                error!("Metrics listener error: {}", e);
+            }
+        });
+    }
+
     // Create shutdown signal handler
     let shutdown = shutdown_signal();
 
     match service_type {
         ServiceType::Auth => {
-            // Load auth configuration
-            let config = if let Ok(config_path) = std::env::var("CONFIG_PATH") {
-                debug!("Loading config from file: {}", config_path);
-                auth::Config::from_file(Path::new(&config_path))?
-            } else {
-                debug!("Loading config from environment variables");
-                auth::Config::from_env()?
-            };
-
             info!("Starting RADIUS authorization service");
-            debug!("Configuration loaded: {:?}", config);
-            debug!("RADIUS bind address: {}", config.radius_bind_addr);
+            debug!("RADIUS bind address: {}", config.listeners.auth);
 
             // Create and start the auth server
             let auth_server = Arc::new(auth::AuthServer::new(config.clone()).await?);
+
+            // Drop cached subscribers as soon as they change in the database
+            let listener_server = auth_server.clone();
+            tokio::spawn(async move {
+                let pool = listener_server.get_pool().clone();
+                listener_server.subscribers.listen_for_invalidations(pool).await;
+            });
+
+            // Pick up authorization rule changes made in the admin UI
+            let policy_server = auth_server.clone();
+            tokio::spawn(async move {
+                let pool = policy_server.get_pool().clone();
+                policy_server.policy.run_reloader(pool).await;
+            });
             
             // Start the RADIUS server
             debug!("Initializing RADIUS server");
-            let radius_server = auth::RadiusAuthServer::new(config.radius_bind_addr, auth_server).await?;
+            let radius_server = auth::RadiusAuthServer::new(config.listeners.auth.clone(), auth_server).await?;
             debug!("Starting RADIUS server loop");
 
             // Run the server until shutdown signal is received
@@ -125,11 +174,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
         ServiceType::Accounting => {
-            // Load accounting configuration
-            let config = accounting::Config::from_env()?;
-
             info!("Starting RADIUS accounting service");
-            info!("Configuration loaded: {:?}", config);
 
             // Create and start the accounting server
             let mut server = accounting::AccountingServer::new(config).await?;
@@ -0,0 +1,168 @@
+use std::sync::LazyLock;
+use prometheus::{
+    Encoder, HistogramVec, IntCounterVec, IntGauge, TextEncoder,
+    register_histogram_vec, register_int_counter_vec, register_int_gauge,
+};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, info, warn};
+
+pub static REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "openrdx_radius_requests_total",
+        "RADIUS packets received, by NAS and packet code",
+        &["service", "nas", "code"]
+    ).unwrap()
+});
+
+pub static AUTH_RESULTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "openrdx_auth_results_total",
+        "Authentication decisions, by NAS, auth method and result",
+        &["nas", "method", "result"]
+    ).unwrap()
+});
+
+pub static REQUEST_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "openrdx_request_duration_seconds",
+        "Time from receiving a packet to sending its reply",
+        &["service"],
+        vec![0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0]
+    ).unwrap()
+});
+
+pub static DB_QUERY_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "openrdx_db_query_duration_seconds",
+        "Database query time, by query",
+        &["query"],
+        vec![0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0]
+    ).unwrap()
+});
+
+pub static INVALID_MESSAGE_AUTHENTICATOR: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "openrdx_invalid_message_authenticator_total",
+        "Packets dropped because of an invalid Message-Authenticator",
+        &["service", "nas"]
+    ).unwrap()
+});
+
+pub static UNKNOWN_CLIENT_DROPS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "openrdx_unknown_client_drops_total",
+        "Packets dropped because no secret is configured for the source address",
+        &["service"]
+    ).unwrap()
+});
+
+pub static REST_HOOK_RESULTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "openrdx_rest_hook_results_total",
+        "REST authorization hook outcomes: accept, reject, error or circuit_open",
+        &["result"]
+    ).unwrap()
+});
+
+pub static SIMULTANEOUS_USE: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "openrdx_simultaneous_use_total",
+        "Logins over the session limit: rejected, or admitted after disconnecting the oldest session (kicked)",
+        &["result"]
+    ).unwrap()
+});
+
+pub static IP_POOL_ALLOCATIONS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "openrdx_ip_pool_allocations_total",
+        "Address allocations for accepted users: leased, exhausted (every applicable pool full) or failed",
+        &["result"]
+    ).unwrap()
+});
+
+pub static NAS_DEVICES_LOADED: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!("openrdx_nas_devices_loaded", "NAS devices currently loaded").unwrap()
+});
+
+pub static SECRETS_LOADED: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!("openrdx_secrets_loaded", "Subnet to secret mappings currently loaded").unwrap()
+});
+
+/// Name used for the result label of an authentication reply
+pub fn result_name(code: u8) -> &'static str {
+    match code {
+        2 => "accept",
+        3 => "reject",
+        11 => "challenge",
+        _ => "other",
+    }
+}
+
+/// Serves the text exposition format on GET /metrics until the listener fails
+pub async fn serve(listener: TcpListener) -> std::io::Result<()> {
+    info!("Metrics listener on {}", listener.local_addr()?);
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream).await {
+                debug!("Metrics request from {} failed: {}", peer, e);
+            }
+        });
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream) -> std::io::Result<()> {
+    let mut buf = [0u8; 1024];
+    let len = stream.read(&mut buf).await?;
+    let request = String::from_utf8_lossy(&buf[..len]);
+    let path = request.split_whitespace().nth(1).unwrap_or("");
+
+    let (status, content_type, body) = if request.starts_with("GET ") && path == "/metrics" {
+        let mut body = Vec::new();
+        let encoder = TextEncoder::new();
+        if let Err(e) = encoder.encode(&prometheus::gather(), &mut body) {
+            warn!("Failed to encode metrics: {}", e);
+        }
+        ("200 OK", encoder.format_type().to_string(), body)
+    } else {
+        ("404 Not Found", "text/plain".to_string(), b"not found\n".to_vec())
+    };
+
+    let header = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status, content_type, body.len()
+    );
+    stream.write_all(header.as_bytes()).await?;
+    stream.write_all(&body).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_metrics_endpoint() {
+        REQUESTS.with_label_values(&["auth", "test-nas", "1"]).inc();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener));
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n").await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains(r#"openrdx_radius_requests_total{code="1",nas="test-nas",service="auth"} 1"#));
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 404"));
+    }
+}
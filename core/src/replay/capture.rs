@@ -0,0 +1,294 @@
+//! Minimal pcap and pcapng reader: just enough to pull UDP datagrams out of captures taken on
+//! Ethernet (with VLAN tags), Linux cooked, loopback and raw IP links.
+
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+const PCAP_MAGIC_MICROS: u32 = 0xa1b2_c3d4;
+const PCAP_MAGIC_NANOS: u32 = 0xa1b2_3c4d;
+const PCAPNG_SECTION_HEADER: u32 = 0x0a0d_0d0a;
+const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1a2b_3c4d;
+const PCAPNG_INTERFACE_DESCRIPTION: u32 = 1;
+const PCAPNG_SIMPLE_PACKET: u32 = 3;
+const PCAPNG_ENHANCED_PACKET: u32 = 6;
+
+const LINKTYPE_NULL: u16 = 0;
+const LINKTYPE_ETHERNET: u16 = 1;
+const LINKTYPE_RAW_BSD: u16 = 12;
+const LINKTYPE_RAW: u16 = 101;
+const LINKTYPE_LOOP: u16 = 108;
+const LINKTYPE_LINUX_SLL: u16 = 113;
+const LINKTYPE_IPV4: u16 = 228;
+const LINKTYPE_IPV6: u16 = 229;
+const LINKTYPE_LINUX_SLL2: u16 = 276;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const ETHERTYPE_QINQ: u16 = 0x88a8;
+
+const IPPROTO_UDP: u8 = 17;
+// IPv6 extension headers walked past to reach UDP; fragments are not reassembled
+const IPV6_HOP_BY_HOP: u8 = 0;
+const IPV6_ROUTING: u8 = 43;
+const IPV6_DESTINATION_OPTIONS: u8 = 60;
+
+#[derive(Debug)]
+pub enum CaptureError {
+    UnknownFormat,
+    Truncated,
+    UnsupportedLinkType(u16),
+}
+
+impl fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptureError::UnknownFormat => write!(f, "not a pcap or pcapng file"),
+            CaptureError::Truncated => write!(f, "capture file is truncated"),
+            CaptureError::UnsupportedLinkType(link_type) => write!(f, "unsupported link type {}", link_type),
+        }
+    }
+}
+
+impl std::error::Error for CaptureError {}
+
+/// A UDP datagram from a capture; `frame` is its 1-based position in the file, as Wireshark numbers it
+#[derive(Debug, Clone)]
+pub struct Datagram {
+    pub frame: usize,
+    pub src: SocketAddr,
+    pub dst: SocketAddr,
+    pub payload: Vec<u8>,
+}
+
+/// Byte order of the file being read
+#[derive(Clone, Copy)]
+struct Endian(bool);
+
+impl Endian {
+    fn u16(self, data: &[u8], at: usize) -> Result<u16, CaptureError> {
+        let bytes: [u8; 2] = data.get(at..at + 2).ok_or(CaptureError::Truncated)?.try_into().unwrap();
+        Ok(if self.0 { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    }
+
+    fn u32(self, data: &[u8], at: usize) -> Result<u32, CaptureError> {
+        let bytes: [u8; 4] = data.get(at..at + 4).ok_or(CaptureError::Truncated)?.try_into().unwrap();
+        Ok(if self.0 { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    }
+}
+
+/// UDP datagrams of a pcap or pcapng capture, in file order. Frames that aren't UDP over IP are skipped.
+pub fn read_datagrams(data: &[u8]) -> Result<Vec<Datagram>, CaptureError> {
+    let magic = data.get(..4).ok_or(CaptureError::UnknownFormat)?;
+    let magic_be = u32::from_be_bytes(magic.try_into().unwrap());
+    let magic_le = u32::from_le_bytes(magic.try_into().unwrap());
+
+    let frames = if magic_be == PCAPNG_SECTION_HEADER {
+        read_pcapng(data)?
+    } else if magic_be == PCAP_MAGIC_MICROS || magic_be == PCAP_MAGIC_NANOS {
+        read_pcap(data, Endian(true))?
+    } else if magic_le == PCAP_MAGIC_MICROS || magic_le == PCAP_MAGIC_NANOS {
+        read_pcap(data, Endian(false))?
+    } else {
+        return Err(CaptureError::UnknownFormat);
+    };
+
+    let mut datagrams = Vec::new();
+    for (index, (link_type, frame)) in frames.into_iter().enumerate() {
+        if let Some((src, dst, payload)) = decode_frame(link_type, frame)? {
+            datagrams.push(Datagram { frame: index + 1, src, dst, payload: payload.to_vec() });
+        }
+    }
+    Ok(datagrams)
+}
+
+fn read_pcap(data: &[u8], endian: Endian) -> Result<Vec<(u16, &[u8])>, CaptureError> {
+    let link_type = endian.u32(data, 20)? as u16;
+    let mut frames = Vec::new();
+    let mut pos = 24;
+    while pos < data.len() {
+        let captured = endian.u32(data, pos + 8)? as usize;
+        let frame = data.get(pos + 16..pos + 16 + captured).ok_or(CaptureError::Truncated)?;
+        frames.push((link_type, frame));
+        pos += 16 + captured;
+    }
+    Ok(frames)
+}
+
+fn read_pcapng(data: &[u8]) -> Result<Vec<(u16, &[u8])>, CaptureError> {
+    let mut frames = Vec::new();
+    let mut endian = Endian(true);
+    // Link types of the interfaces of the current section, by interface id
+    let mut interfaces: Vec<u16> = Vec::new();
+    let mut pos = 0;
+    while pos + 12 <= data.len() {
+        if u32::from_be_bytes(data[pos..pos + 4].try_into().unwrap()) == PCAPNG_SECTION_HEADER {
+            endian = Endian(u32::from_be_bytes(data[pos + 8..pos + 12].try_into().unwrap()) == PCAPNG_BYTE_ORDER_MAGIC);
+            interfaces.clear();
+        }
+        let block_type = endian.u32(data, pos)?;
+        let block_len = endian.u32(data, pos + 4)? as usize;
+        if block_len < 12 || !block_len.is_multiple_of(4) {
+            return Err(CaptureError::Truncated);
+        }
+        let block = data.get(pos..pos + block_len).ok_or(CaptureError::Truncated)?;
+
+        match block_type {
+            PCAPNG_INTERFACE_DESCRIPTION => interfaces.push(endian.u16(block, 8)?),
+            PCAPNG_ENHANCED_PACKET => {
+                let interface = endian.u32(block, 8)? as usize;
+                let captured = endian.u32(block, 20)? as usize;
+                let frame = block.get(28..28 + captured).ok_or(CaptureError::Truncated)?;
+                frames.push((interfaces.get(interface).copied().unwrap_or(LINKTYPE_ETHERNET), frame));
+            }
+            PCAPNG_SIMPLE_PACKET => {
+                let original = endian.u32(block, 8)? as usize;
+                let frame = block.get(12..(12 + original).min(block_len - 4)).unwrap_or_default();
+                frames.push((interfaces.first().copied().unwrap_or(LINKTYPE_ETHERNET), frame));
+            }
+            _ => {}
+        }
+        pos += block_len;
+    }
+    Ok(frames)
+}
+
+/// Source, destination and payload of a UDP datagram
+type Udp<'a> = (SocketAddr, SocketAddr, &'a [u8]);
+
+/// The UDP datagram a frame carries, None for anything else
+fn decode_frame(link_type: u16, frame: &[u8]) -> Result<Option<Udp<'_>>, CaptureError> {
+    let (ethertype, packet) = match link_type {
+        LINKTYPE_ETHERNET => {
+            let mut pos = 12;
+            let mut ethertype = be16(frame, pos);
+            while matches!(ethertype, Some(ETHERTYPE_VLAN | ETHERTYPE_QINQ)) {
+                pos += 4;
+                ethertype = be16(frame, pos);
+            }
+            (ethertype, frame.get(pos + 2..))
+        }
+        LINKTYPE_LINUX_SLL => (be16(frame, 14), frame.get(16..)),
+        LINKTYPE_LINUX_SLL2 => (be16(frame, 0), frame.get(20..)),
+        // The address family is in the capturing host's byte order for NULL and in network order for LOOP;
+        // the IP version nibble says the same thing either way
+        LINKTYPE_NULL | LINKTYPE_LOOP => (None, frame.get(4..)),
+        LINKTYPE_RAW | LINKTYPE_RAW_BSD | LINKTYPE_IPV4 | LINKTYPE_IPV6 => (None, Some(frame)),
+        other => return Err(CaptureError::UnsupportedLinkType(other)),
+    };
+    let Some(packet) = packet else {
+        return Ok(None);
+    };
+    let version = match ethertype {
+        Some(ETHERTYPE_IPV4) => 4,
+        Some(ETHERTYPE_IPV6) => 6,
+        Some(_) => return Ok(None),
+        None => packet.first().map_or(0, |byte| byte >> 4),
+    };
+
+    let udp = match version {
+        4 => ipv4_udp(packet),
+        6 => ipv6_udp(packet),
+        _ => None,
+    };
+    let Some((src_ip, dst_ip, udp)) = udp else {
+        return Ok(None);
+    };
+    let (Some(src_port), Some(dst_port), Some(length)) = (be16(udp, 0), be16(udp, 2), be16(udp, 4)) else {
+        return Ok(None);
+    };
+    let end = (length as usize).clamp(8, udp.len().max(8));
+    let payload = udp.get(8..end).unwrap_or_default();
+    Ok(Some((SocketAddr::new(src_ip, src_port), SocketAddr::new(dst_ip, dst_port), payload)))
+}
+
+fn ipv4_udp(packet: &[u8]) -> Option<(IpAddr, IpAddr, &[u8])> {
+    let header_len = (*packet.first()? & 0x0f) as usize * 4;
+    let total_len = be16(packet, 2)? as usize;
+    let fragment = be16(packet, 6)?;
+    // More-fragments set or a non-zero offset
+    if packet.get(9)? != &IPPROTO_UDP || fragment & 0x3fff != 0 || header_len < 20 {
+        return None;
+    }
+    let src = Ipv4Addr::from(<[u8; 4]>::try_from(packet.get(12..16)?).ok()?);
+    let dst = Ipv4Addr::from(<[u8; 4]>::try_from(packet.get(16..20)?).ok()?);
+    let end = total_len.clamp(header_len, packet.len());
+    Some((src.into(), dst.into(), packet.get(header_len..end)?))
+}
+
+fn ipv6_udp(packet: &[u8]) -> Option<(IpAddr, IpAddr, &[u8])> {
+    let src = Ipv6Addr::from(<[u8; 16]>::try_from(packet.get(8..24)?).ok()?);
+    let dst = Ipv6Addr::from(<[u8; 16]>::try_from(packet.get(24..40)?).ok()?);
+    let end = (40 + be16(packet, 4)? as usize).min(packet.len());
+    let mut next = *packet.get(6)?;
+    let mut pos = 40;
+    while matches!(next, IPV6_HOP_BY_HOP | IPV6_ROUTING | IPV6_DESTINATION_OPTIONS) {
+        next = *packet.get(pos)?;
+        pos += (*packet.get(pos + 1)? as usize + 1) * 8;
+    }
+    (next == IPPROTO_UDP).then_some((src.into(), dst.into(), packet.get(pos..end)?))
+}
+
+fn be16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An Ethernet frame with a VLAN tag carrying IPv4/UDP
+    fn ethernet_frame(payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0u8; 12];
+        frame.extend_from_slice(&[0x81, 0x00, 0x00, 0x64, 0x08, 0x00]);
+        let total = (20 + 8 + payload.len()) as u16;
+        frame.extend_from_slice(&[0x45, 0, (total >> 8) as u8, total as u8, 0, 0, 0x40, 0, 64, IPPROTO_UDP, 0, 0]);
+        frame.extend_from_slice(&[192, 0, 2, 1, 198, 51, 100, 10]);
+        frame.extend_from_slice(&[0xc0, 0x01, 0x07, 0x14]);
+        frame.extend_from_slice(&((8 + payload.len()) as u16).to_be_bytes());
+        frame.extend_from_slice(&[0, 0]);
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    #[test]
+    fn test_pcap_and_pcapng() {
+        let frame = ethernet_frame(b"radius");
+
+        let mut pcap = Vec::new();
+        for value in [PCAP_MAGIC_MICROS, 0x0004_0002, 0, 0, 65535, LINKTYPE_ETHERNET as u32] {
+            pcap.extend_from_slice(&value.to_le_bytes());
+        }
+        pcap.truncate(24);
+        pcap[4..8].copy_from_slice(&[2, 0, 4, 0]);
+        for value in [0, 0, frame.len() as u32, frame.len() as u32] {
+            pcap.extend_from_slice(&value.to_le_bytes());
+        }
+        pcap.extend_from_slice(&frame);
+
+        let mut pcapng = Vec::new();
+        for value in [PCAPNG_SECTION_HEADER, 28, PCAPNG_BYTE_ORDER_MAGIC, 0x0000_0001, u32::MAX, u32::MAX, 28] {
+            pcapng.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in [PCAPNG_INTERFACE_DESCRIPTION, 20, LINKTYPE_ETHERNET as u32, 65535, 20] {
+            pcapng.extend_from_slice(&value.to_le_bytes());
+        }
+        let padded = frame.len().div_ceil(4) * 4;
+        let block_len = (32 + padded) as u32;
+        for value in [PCAPNG_ENHANCED_PACKET, block_len, 0, 0, 0, frame.len() as u32, frame.len() as u32] {
+            pcapng.extend_from_slice(&value.to_le_bytes());
+        }
+        pcapng.extend_from_slice(&frame);
+        pcapng.resize(pcapng.len() + padded - frame.len(), 0);
+        pcapng.extend_from_slice(&block_len.to_le_bytes());
+
+        for capture in [pcap, pcapng] {
+            let datagrams = read_datagrams(&capture).unwrap();
+            assert_eq!(datagrams.len(), 1);
+            assert_eq!(datagrams[0].src, "192.0.2.1:49153".parse().unwrap());
+            assert_eq!(datagrams[0].dst, "198.51.100.10:1812".parse().unwrap());
+            assert_eq!(datagrams[0].payload, b"radius");
+        }
+        assert!(matches!(read_datagrams(b"not a capture"), Err(CaptureError::UnknownFormat)));
+    }
+}
@@ -0,0 +1,254 @@
+//! Capture replay for `openrdx-replay`: pairs the RADIUS requests of a pcap/pcapng capture with their
+//! recorded responses, re-signs the requests for the core under test and diffs its replies against
+//! the recorded ones, attribute by attribute.
+
+mod capture;
+
+pub use capture::{read_datagrams, CaptureError, Datagram};
+
+use std::collections::HashSet;
+use std::net::{IpAddr, SocketAddr};
+use ipnetwork::IpNetwork;
+use md5::{Digest, Md5};
+
+use crate::auth::{self, RadiusPacket};
+use crate::client::{self, CODE_ACCESS_REQUEST, CODE_ACCOUNTING_REQUEST, CODE_COA_REQUEST, CODE_DISCONNECT_REQUEST,
+    CODE_STATUS_SERVER};
+
+const ATTR_USER_PASSWORD: u8 = 2;
+
+/// Attributes whose values differ from run to run: compared by presence only
+pub const DEFAULT_IGNORED: &[&str] = &[
+    "Message-Authenticator",
+    "State",
+    "Class",
+    "Event-Timestamp",
+    "Tunnel-Password",
+    "MS-MPPE-Send-Key",
+    "MS-MPPE-Recv-Key",
+];
+
+/// Shared secrets by NAS address, read from `<ip-or-cidr> <secret>` lines
+#[derive(Debug, Default)]
+pub struct Secrets {
+    entries: Vec<(IpNetwork, String)>,
+}
+
+impl Secrets {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut entries = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (network, secret) = line.split_once(char::is_whitespace)
+                .ok_or_else(|| format!("line {}: expected `<ip-or-cidr> <secret>`", number + 1))?;
+            let network = network.parse::<IpNetwork>()
+                .map_err(|e| format!("line {}: {}: {}", number + 1, network, e))?;
+            entries.push((network, secret.trim().to_string()));
+        }
+        Ok(Self { entries })
+    }
+
+    /// The secret of the most specific network containing `ip`
+    pub fn lookup(&self, ip: IpAddr) -> Option<&str> {
+        self.entries.iter()
+            .filter(|(network, _)| network.contains(ip))
+            .max_by_key(|(network, _)| network.prefix())
+            .map(|(_, secret)| secret.as_str())
+    }
+}
+
+/// A request from the capture and the response recorded for it, if any
+#[derive(Debug, Clone)]
+pub struct Exchange {
+    pub request: Datagram,
+    pub response: Option<Datagram>,
+}
+
+impl Exchange {
+    /// The NAS that sent the request
+    pub fn nas(&self) -> SocketAddr {
+        self.request.src
+    }
+}
+
+fn answers(request_code: u8, response_code: u8) -> bool {
+    match request_code {
+        CODE_ACCESS_REQUEST => matches!(response_code, 2 | 3 | 11),
+        CODE_ACCOUNTING_REQUEST => response_code == 5,
+        CODE_STATUS_SERVER => matches!(response_code, 2 | 3 | 5),
+        CODE_DISCONNECT_REQUEST => matches!(response_code, 41 | 42),
+        CODE_COA_REQUEST => matches!(response_code, 44 | 45),
+        _ => false,
+    }
+}
+
+/// Pairs each request with the first later response coming back from the server with the same
+/// identifier. Retransmissions (same NAS, identifier and authenticator) are dropped.
+pub fn pair_exchanges(datagrams: &[Datagram]) -> Vec<Exchange> {
+    // Other UDP traffic in the capture rarely survives the length check
+    let packets: Vec<Option<RadiusPacket>> = datagrams.iter()
+        .map(|d| RadiusPacket::parse(&d.payload).ok().filter(|packet| packet.length as usize == d.payload.len()))
+        .collect();
+    let mut seen = HashSet::new();
+    let mut used = vec![false; datagrams.len()];
+    let mut exchanges = Vec::new();
+
+    for (index, (datagram, packet)) in datagrams.iter().zip(&packets).enumerate() {
+        let Some(packet) = packet else { continue };
+        if !matches!(packet.code, CODE_ACCESS_REQUEST | CODE_ACCOUNTING_REQUEST | CODE_STATUS_SERVER
+                | CODE_DISCONNECT_REQUEST | CODE_COA_REQUEST) {
+            continue;
+        }
+        if !seen.insert((datagram.src, datagram.dst, packet.identifier, packet.authenticator)) {
+            continue;
+        }
+        let response = (index + 1..datagrams.len()).find(|&candidate| {
+            let reply = &datagrams[candidate];
+            !used[candidate] && reply.src == datagram.dst && reply.dst == datagram.src
+                && packets[candidate].as_ref()
+                    .is_some_and(|reply| reply.identifier == packet.identifier && answers(packet.code, reply.code))
+        });
+        if let Some(response) = response {
+            used[response] = true;
+        }
+        exchanges.push(Exchange { request: datagram.clone(), response: response.map(|r| datagrams[r].clone()) });
+    }
+    exchanges
+}
+
+/// Re-signs a recorded request with the secret of the core under test. Access-Request and Status-Server
+/// keep their Request Authenticator, so CHAP and MS-CHAP challenges stay valid; User-Password is
+/// re-hidden and Message-Authenticator recomputed. Other codes get a fresh Request Authenticator.
+pub fn resign_request(recorded: &RadiusPacket, recorded_secret: &str, secret: &str) -> Vec<u8> {
+    let mut packet = recorded.clone();
+    if matches!(packet.code, CODE_ACCESS_REQUEST | CODE_STATUS_SERVER) {
+        if recorded_secret != secret {
+            for attr in packet.attributes.iter_mut().filter(|attr| attr.typ == ATTR_USER_PASSWORD) {
+                let plain = auth::unhide_password(&attr.value, &packet.authenticator, recorded_secret);
+                attr.value = auth::hide_password(&plain, &packet.authenticator, secret);
+            }
+        }
+        let mut encoded = packet.encode();
+        auth::sign_message_authenticator(&mut encoded, secret);
+        return encoded;
+    }
+
+    // RFC 5176, section 3.5: the Message-Authenticator is computed over a zero Request Authenticator
+    packet.authenticator = [0u8; 16];
+    let mut encoded = packet.encode();
+    auth::sign_message_authenticator(&mut encoded, secret);
+    let mut hasher = Md5::new();
+    hasher.update(&encoded);
+    hasher.update(secret.as_bytes());
+    encoded[4..20].copy_from_slice(&hasher.finalize());
+    encoded
+}
+
+/// Differences between the recorded and the actual reply, as `- recorded` / `+ actual` lines;
+/// empty when they match. Attributes named in `ignored` only have to be present the same number of times.
+pub fn diff_replies(recorded: &RadiusPacket, actual: &RadiusPacket, ignored: &[String]) -> Vec<String> {
+    let mut diff = Vec::new();
+    if recorded.code != actual.code {
+        diff.push(format!("- {}", client::code_name(recorded.code)));
+        diff.push(format!("+ {}", client::code_name(actual.code)));
+    }
+
+    let split = |packet: &RadiusPacket| -> Vec<(String, String)> {
+        packet.attributes.iter()
+            .map(|attr| {
+                let line = client::format_attribute(attr);
+                let (name, _) = line.split_once(" = ").unwrap_or((&line, ""));
+                (name.to_string(), line)
+            })
+            .collect()
+    };
+    let recorded_attrs = split(recorded);
+    let actual_attrs = split(actual);
+
+    let mut names: Vec<&String> = Vec::new();
+    for (name, _) in recorded_attrs.iter().chain(&actual_attrs) {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    for name in names {
+        let expected: Vec<&String> = recorded_attrs.iter().filter(|(n, _)| n == name).map(|(_, line)| line).collect();
+        let got: Vec<&String> = actual_attrs.iter().filter(|(n, _)| n == name).map(|(_, line)| line).collect();
+        if ignored.iter().any(|ignored| ignored == name) {
+            if expected.len() != got.len() {
+                diff.push(format!("- {} x{}", name, expected.len()));
+                diff.push(format!("+ {} x{}", name, got.len()));
+            }
+            continue;
+        }
+        diff.extend(expected.iter().filter(|line| !got.contains(line)).map(|line| format!("- {}", line)));
+        diff.extend(got.iter().filter(|line| !expected.contains(line)).map(|line| format!("+ {}", line)));
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::RadiusAttribute;
+
+    fn datagram(frame: usize, src: &str, dst: &str, packet: &RadiusPacket) -> Datagram {
+        Datagram { frame, src: src.parse().unwrap(), dst: dst.parse().unwrap(), payload: packet.encode() }
+    }
+
+    fn packet(code: u8, identifier: u8, attributes: Vec<RadiusAttribute>) -> RadiusPacket {
+        RadiusPacket { code, identifier, length: 0, authenticator: [identifier; 16], attributes }
+    }
+
+    #[test]
+    fn test_pairing_and_resigning() {
+        let nas = "192.0.2.1:50000";
+        let server = "198.51.100.10:1812";
+        let request = packet(CODE_ACCESS_REQUEST, 7, vec![
+            RadiusAttribute { typ: ATTR_USER_PASSWORD, value: auth::hide_password(b"secret", &[7; 16], "old") },
+        ]);
+        let datagrams = vec![
+            datagram(1, nas, server, &request),
+            datagram(2, nas, server, &request),
+            datagram(3, server, nas, &packet(3, 8, vec![])),
+            datagram(4, server, nas, &packet(2, 7, vec![])),
+            datagram(5, nas, server, &packet(CODE_ACCOUNTING_REQUEST, 9, vec![])),
+        ];
+        let exchanges = pair_exchanges(&datagrams);
+        assert_eq!(exchanges.len(), 2);
+        assert_eq!(exchanges[0].response.as_ref().map(|r| r.frame), Some(4));
+        assert!(exchanges[1].response.is_none());
+
+        let secrets = Secrets::parse("# lab\n192.0.2.0/24 old\n192.0.2.1/32 old\n0.0.0.0/0 other\n").unwrap();
+        assert_eq!(secrets.lookup(exchanges[0].nas().ip()), Some("old"));
+        let resigned = RadiusPacket::parse(&resign_request(&request, "old", "new")).unwrap();
+        assert_eq!(resigned.authenticator, request.authenticator);
+        let password = auth::unhide_password(&resigned.attributes[0].value, &resigned.authenticator, "new");
+        assert!(password.starts_with(b"secret\0"));
+    }
+
+    #[test]
+    fn test_diff_tolerates_ignored_attributes() {
+        let ignored: Vec<String> = DEFAULT_IGNORED.iter().map(|name| name.to_string()).collect();
+        let recorded = packet(2, 1, vec![
+            RadiusAttribute { typ: 27, value: 3600u32.to_be_bytes().to_vec() },
+            RadiusAttribute { typ: 25, value: b"abc".to_vec() },
+            RadiusAttribute { typ: 69, value: vec![0, 0x81, 0x02, 0x11, 0x22] },
+        ]);
+        let mut actual = packet(2, 1, vec![
+            RadiusAttribute { typ: 25, value: b"xyz".to_vec() },
+            RadiusAttribute { typ: 27, value: 3600u32.to_be_bytes().to_vec() },
+            RadiusAttribute { typ: 69, value: vec![0, 0x93, 0x47, 0x33, 0x44] },
+        ]);
+        assert!(diff_replies(&recorded, &actual, &ignored).is_empty());
+        let mut stripped = actual.clone();
+        stripped.attributes.pop();
+        assert_eq!(diff_replies(&recorded, &stripped, &ignored), ["- Tunnel-Password x1", "+ Tunnel-Password x0"]);
+
+        actual.attributes[1].value = 7200u32.to_be_bytes().to_vec();
+        assert_eq!(diff_replies(&recorded, &actual, &ignored), ["- Session-Timeout = 3600", "+ Session-Timeout = 7200"]);
+    }
+}
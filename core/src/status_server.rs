@@ -0,0 +1,260 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use hmac::{Hmac, Mac};
+use md5::{Digest, Md5};
+use tracing::debug;
+
+type HmacMd5 = Hmac<Md5>;
+
+pub const CODE_STATUS_SERVER: u8 = 12;
+pub const CODE_ACCESS_ACCEPT: u8 = 2;
+pub const CODE_ACCOUNTING_RESPONSE: u8 = 5;
+
+const ATTR_MESSAGE_AUTHENTICATOR: u8 = 80;
+const ATTR_VENDOR_SPECIFIC: u8 = 26;
+
+const VENDOR_FREERADIUS: u32 = 11344;
+const FREERADIUS_STATISTICS_TYPE: u8 = 127;
+const FREERADIUS_TOTAL_ACCESS_REQUESTS: u8 = 128;
+const FREERADIUS_TOTAL_ACCESS_ACCEPTS: u8 = 129;
+const FREERADIUS_TOTAL_ACCESS_REJECTS: u8 = 130;
+const FREERADIUS_TOTAL_ACCESS_CHALLENGES: u8 = 131;
+const FREERADIUS_TOTAL_AUTH_RESPONSES: u8 = 132;
+const FREERADIUS_TOTAL_AUTH_MALFORMED_REQUESTS: u8 = 134;
+const FREERADIUS_TOTAL_AUTH_INVALID_REQUESTS: u8 = 135;
+const FREERADIUS_TOTAL_AUTH_DROPPED_REQUESTS: u8 = 136;
+const FREERADIUS_TOTAL_ACCOUNTING_REQUESTS: u8 = 144;
+const FREERADIUS_TOTAL_ACCOUNTING_RESPONSES: u8 = 145;
+const FREERADIUS_TOTAL_ACCT_MALFORMED_REQUESTS: u8 = 147;
+const FREERADIUS_TOTAL_ACCT_INVALID_REQUESTS: u8 = 148;
+const FREERADIUS_TOTAL_ACCT_DROPPED_REQUESTS: u8 = 149;
+
+// FreeRADIUS-Statistics-Type bits
+const STATISTICS_TYPE_AUTHENTICATION: u32 = 0x01;
+const STATISTICS_TYPE_ACCOUNTING: u32 = 0x02;
+
+/// Request counters kept by a listener and reported through Status-Server
+#[derive(Debug, Default)]
+pub struct RadiusStats {
+    pub access_requests: AtomicU64,
+    pub access_accepts: AtomicU64,
+    pub access_rejects: AtomicU64,
+    pub access_challenges: AtomicU64,
+    pub auth_malformed: AtomicU64,
+    pub auth_invalid: AtomicU64,
+    pub auth_dropped: AtomicU64,
+    pub acct_requests: AtomicU64,
+    pub acct_responses: AtomicU64,
+    pub acct_malformed: AtomicU64,
+    pub acct_invalid: AtomicU64,
+    pub acct_dropped: AtomicU64,
+}
+
+impl RadiusStats {
+    pub fn increment(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records the response code of an authentication reply
+    pub fn record_auth_response(&self, code: u8) {
+        match code {
+            2 => Self::increment(&self.access_accepts),
+            3 => Self::increment(&self.access_rejects),
+            11 => Self::increment(&self.access_challenges),
+            _ => {}
+        }
+    }
+
+    fn auth_attributes(&self) -> Vec<(u8, u64)> {
+        let accepts = self.access_accepts.load(Ordering::Relaxed);
+        let rejects = self.access_rejects.load(Ordering::Relaxed);
+        let challenges = self.access_challenges.load(Ordering::Relaxed);
+        vec![
+            (FREERADIUS_TOTAL_ACCESS_REQUESTS, self.access_requests.load(Ordering::Relaxed)),
+            (FREERADIUS_TOTAL_ACCESS_ACCEPTS, accepts),
+            (FREERADIUS_TOTAL_ACCESS_REJECTS, rejects),
+            (FREERADIUS_TOTAL_ACCESS_CHALLENGES, challenges),
+            (FREERADIUS_TOTAL_AUTH_RESPONSES, accepts + rejects + challenges),
+            (FREERADIUS_TOTAL_AUTH_MALFORMED_REQUESTS, self.auth_malformed.load(Ordering::Relaxed)),
+            (FREERADIUS_TOTAL_AUTH_INVALID_REQUESTS, self.auth_invalid.load(Ordering::Relaxed)),
+            (FREERADIUS_TOTAL_AUTH_DROPPED_REQUESTS, self.auth_dropped.load(Ordering::Relaxed)),
+        ]
+    }
+
+    fn acct_attributes(&self) -> Vec<(u8, u64)> {
+        vec![
+            (FREERADIUS_TOTAL_ACCOUNTING_REQUESTS, self.acct_requests.load(Ordering::Relaxed)),
+            (FREERADIUS_TOTAL_ACCOUNTING_RESPONSES, self.acct_responses.load(Ordering::Relaxed)),
+            (FREERADIUS_TOTAL_ACCT_MALFORMED_REQUESTS, self.acct_malformed.load(Ordering::Relaxed)),
+            (FREERADIUS_TOTAL_ACCT_INVALID_REQUESTS, self.acct_invalid.load(Ordering::Relaxed)),
+            (FREERADIUS_TOTAL_ACCT_DROPPED_REQUESTS, self.acct_dropped.load(Ordering::Relaxed)),
+        ]
+    }
+}
+
+/// Answers a Status-Server request with `response_code` (Access-Accept on the auth port,
+/// Accounting-Response on the accounting port).
+///
+/// Requests without a valid Message-Authenticator are refused, and the caller is expected
+/// to silently discard them as RFC 5997 requires.
+pub fn handle_status_server(data: &[u8], secret: &str, response_code: u8, stats: &RadiusStats)
+    -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if data.len() < 20 || data[0] != CODE_STATUS_SERVER {
+        return Err("Not a Status-Server packet".into());
+    }
+    let length = u16::from_be_bytes([data[2], data[3]]) as usize;
+    if length < 20 || length > data.len() {
+        return Err("Invalid Status-Server length".into());
+    }
+    let data = &data[..length];
+
+    let mut msg_auth_pos = None;
+    let mut statistics_type = 0u32;
+    let mut pos = 20;
+    while pos + 2 <= data.len() {
+        let typ = data[pos];
+        let len = data[pos + 1] as usize;
+        if len < 2 || pos + len > data.len() {
+            return Err("Malformed attribute in Status-Server".into());
+        }
+        let value = &data[pos + 2..pos + len];
+
+        match typ {
+            ATTR_MESSAGE_AUTHENTICATOR if len == 18 => msg_auth_pos = Some(pos + 2),
+            ATTR_VENDOR_SPECIFIC if value.len() == 10
+                && u32::from_be_bytes([value[0], value[1], value[2], value[3]]) == VENDOR_FREERADIUS
+                && value[4] == FREERADIUS_STATISTICS_TYPE => {
+                statistics_type = u32::from_be_bytes([value[6], value[7], value[8], value[9]]);
+            }
+            _ => {}
+        }
+        pos += len;
+    }
+
+    let msg_auth_pos = msg_auth_pos.ok_or("Status-Server without Message-Authenticator")?;
+    let mut zeroed = data.to_vec();
+    zeroed[msg_auth_pos..msg_auth_pos + 16].fill(0);
+    let mut mac = <HmacMd5 as Mac>::new_from_slice(secret.as_bytes())
+        .expect("HMAC can take key of any size");
+    mac.update(&zeroed);
+    mac.verify_slice(&data[msg_auth_pos..msg_auth_pos + 16])
+        .map_err(|_| "Invalid Message-Authenticator in Status-Server")?;
+
+    debug!("Status-Server request verified, statistics type: {:#x}", statistics_type);
+
+    let mut stats_values = Vec::new();
+    if statistics_type & STATISTICS_TYPE_AUTHENTICATION != 0 {
+        stats_values.extend(stats.auth_attributes());
+    }
+    if statistics_type & STATISTICS_TYPE_ACCOUNTING != 0 {
+        stats_values.extend(stats.acct_attributes());
+    }
+
+    let mut attributes = Vec::new();
+    for (typ, value) in stats_values {
+        attributes.push(ATTR_VENDOR_SPECIFIC);
+        attributes.push(12);
+        attributes.extend_from_slice(&VENDOR_FREERADIUS.to_be_bytes());
+        attributes.push(typ);
+        attributes.push(6);
+        // FreeRADIUS defines the counters as 32-bit integers
+        attributes.extend_from_slice(&(value as u32).to_be_bytes());
+    }
+    let msg_auth_offset = 20 + attributes.len() + 2;
+    attributes.push(ATTR_MESSAGE_AUTHENTICATOR);
+    attributes.push(18);
+    attributes.extend_from_slice(&[0u8; 16]);
+
+    let mut response = Vec::with_capacity(20 + attributes.len());
+    response.push(response_code);
+    response.push(data[1]);
+    response.extend_from_slice(&((20 + attributes.len()) as u16).to_be_bytes());
+    response.extend_from_slice(&data[4..20]);
+    response.extend_from_slice(&attributes);
+
+    // Message-Authenticator is computed with the request authenticator in place (RFC 3579, 3.2)
+    let mut mac = <HmacMd5 as Mac>::new_from_slice(secret.as_bytes())
+        .expect("HMAC can take key of any size");
+    mac.update(&response);
+    let msg_auth = mac.finalize().into_bytes();
+    response[msg_auth_offset..msg_auth_offset + 16].copy_from_slice(&msg_auth);
+
+    let mut hasher = Md5::new();
+    hasher.update(&response);
+    hasher.update(secret.as_bytes());
+    let response_auth = hasher.finalize();
+    response[4..20].copy_from_slice(&response_auth);
+
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status_request(secret: &str, statistics_type: Option<u32>) -> Vec<u8> {
+        let mut packet = vec![CODE_STATUS_SERVER, 42, 0, 0];
+        packet.extend_from_slice(&[7u8; 16]);
+        if let Some(statistics_type) = statistics_type {
+            packet.extend_from_slice(&[ATTR_VENDOR_SPECIFIC, 12]);
+            packet.extend_from_slice(&VENDOR_FREERADIUS.to_be_bytes());
+            packet.extend_from_slice(&[FREERADIUS_STATISTICS_TYPE, 6]);
+            packet.extend_from_slice(&statistics_type.to_be_bytes());
+        }
+        let msg_auth_pos = packet.len() + 2;
+        packet.extend_from_slice(&[ATTR_MESSAGE_AUTHENTICATOR, 18]);
+        packet.extend_from_slice(&[0u8; 16]);
+        let length = packet.len() as u16;
+        packet[2..4].copy_from_slice(&length.to_be_bytes());
+
+        let mut mac = <HmacMd5 as Mac>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(&packet);
+        let msg_auth = mac.finalize().into_bytes();
+        packet[msg_auth_pos..msg_auth_pos + 16].copy_from_slice(&msg_auth);
+        packet
+    }
+
+    #[test]
+    fn test_status_server_requires_message_authenticator() {
+        let stats = RadiusStats::default();
+        let mut packet = vec![CODE_STATUS_SERVER, 1, 0, 20];
+        packet.extend_from_slice(&[0u8; 16]);
+        assert!(handle_status_server(&packet, "secret", CODE_ACCESS_ACCEPT, &stats).is_err());
+
+        let packet = status_request("other", None);
+        assert!(handle_status_server(&packet, "secret", CODE_ACCESS_ACCEPT, &stats).is_err());
+    }
+
+    #[test]
+    fn test_status_server_response() {
+        let stats = RadiusStats::default();
+        let packet = status_request("secret", None);
+        let response = handle_status_server(&packet, "secret", CODE_ACCOUNTING_RESPONSE, &stats).unwrap();
+
+        assert_eq!(response[0], CODE_ACCOUNTING_RESPONSE);
+        assert_eq!(response[1], 42);
+        assert_eq!(response.len(), 20 + 18);
+
+        let mut hasher = Md5::new();
+        hasher.update(&response[0..4]);
+        hasher.update(&packet[4..20]);
+        hasher.update(&response[20..]);
+        hasher.update(b"secret");
+        assert_eq!(&response[4..20], hasher.finalize().as_slice());
+    }
+
+    #[test]
+    fn test_status_server_statistics() {
+        let stats = RadiusStats::default();
+        RadiusStats::increment(&stats.access_requests);
+        stats.record_auth_response(2);
+
+        let packet = status_request("secret", Some(STATISTICS_TYPE_AUTHENTICATION));
+        let response = handle_status_server(&packet, "secret", CODE_ACCESS_ACCEPT, &stats).unwrap();
+
+        // Total-Access-Requests is the first counter
+        assert_eq!(&response[20..26], &[ATTR_VENDOR_SPECIFIC, 12, 0, 0, 0x2c, 0x50]);
+        assert_eq!(&response[26..28], &[FREERADIUS_TOTAL_ACCESS_REQUESTS, 6]);
+        assert_eq!(&response[28..32], &1u32.to_be_bytes());
+        assert_eq!(response.len(), 20 + 8 * 12 + 18);
+    }
+}
@@ -0,0 +1,261 @@
+//! End-to-end tests: the authentication and accounting services on ephemeral ports, backed by the
+//! in-memory stores and driven with the request code of `openrdx-client`. Needs neither PostgreSQL
+//! nor MongoDB.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use md5::{Digest, Md5};
+use tokio::net::UdpSocket;
+
+use openrdx_core::accounting::{AccountingServer, MemorySessionStore};
+use openrdx_core::auth::{AuthServer, MemoryStore, RadiusAttribute, RadiusAuthServer, RadiusPacket};
+use openrdx_core::client::{self, AcctStatus, AuthMethod, Command, Credentials, OutgoingRequest, CODE_ACCESS_ACCEPT,
+    CODE_ACCESS_REJECT, CODE_ACCOUNTING_RESPONSE};
+use openrdx_core::config::Config;
+
+const SECRET: &str = "testing123";
+
+const ATTR_USER_PASSWORD: u8 = 2;
+const ATTR_NAS_IP_ADDRESS: u8 = 4;
+const ATTR_MESSAGE_AUTHENTICATOR: u8 = 80;
+const ATTR_ACCT_STATUS_TYPE: u8 = 40;
+const ATTR_ACCT_INPUT_OCTETS: u8 = 42;
+const ATTR_ACCT_SESSION_ID: u8 = 44;
+const ATTR_ACCT_SESSION_TIME: u8 = 46;
+const ATTR_ACCT_INPUT_GIGAWORDS: u8 = 52;
+
+struct Harness {
+    auth: SocketAddr,
+    acct: SocketAddr,
+    sessions: MemorySessionStore,
+}
+
+impl Harness {
+    async fn start() -> Self {
+        Self::start_with(|_| {}).await
+    }
+
+    /// Both services on 127.0.0.1, with the features that keep their data in a database turned off
+    /// before `configure` gets to adjust the configuration.
+    /// Their futures aren't Send, so they get a thread and a runtime of their own.
+    async fn start_with(configure: impl FnOnce(&mut Config)) -> Self {
+        let mut config = Config::default();
+        config.listeners.auth = "127.0.0.1:0".to_string();
+        config.listeners.accounting = "127.0.0.1:0".to_string();
+        config.postauth.enabled = false;
+        config.simultaneous_use.enabled = false;
+        config.ip_pool.enabled = false;
+        config.policy.enabled = false;
+        configure(&mut config);
+
+        let mut store = MemoryStore::new();
+        // The most specific subnet wins, so the wider one must not get in the way
+        store.add_secret("127.0.0.0/8".parse().unwrap(), "not-this-one")
+            .add_secret("127.0.0.1/32".parse().unwrap(), SECRET)
+            .add_user("alice", "wonderland")
+            .add_user("bob", "builder")
+            .set_enabled("bob", false);
+
+        let sessions = MemorySessionStore::new();
+        let (ready, addresses) = tokio::sync::oneshot::channel();
+        let server_sessions = sessions.clone();
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+            runtime.block_on(async move {
+                let auth_server = Arc::new(AuthServer::in_memory(config.clone(), store.clone()).await.unwrap());
+                let radius_server = RadiusAuthServer::new(config.listeners.auth.clone(), auth_server).await.unwrap();
+                let mut accounting_server = AccountingServer::in_memory(config, store, server_sessions).await.unwrap();
+                let _ = ready.send((radius_server.local_addr().unwrap(), accounting_server.local_addr().unwrap()));
+                let _ = tokio::join!(radius_server.run(), accounting_server.start());
+            });
+        });
+
+        let (auth, acct) = addresses.await.expect("servers failed to start");
+        Self { auth, acct, sessions }
+    }
+}
+
+/// Sends `encoded` and returns the first reply, None when nothing came back in time
+async fn send(server: SocketAddr, encoded: &[u8], wait: Duration) -> Option<Vec<u8>> {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    socket.send_to(encoded, server).await.unwrap();
+    let mut buf = [0u8; 4096];
+    let (len, _) = tokio::time::timeout(wait, socket.recv_from(&mut buf)).await.ok()?.unwrap();
+    Some(buf[..len].to_vec())
+}
+
+async fn exchange(server: SocketAddr, request: &OutgoingRequest) -> RadiusPacket {
+    let reply = send(server, &request.encoded, Duration::from_secs(5)).await.expect("no reply");
+    client::verify_reply(request, &reply, SECRET).unwrap()
+}
+
+fn access_request(method: AuthMethod, username: &str, password: &str) -> OutgoingRequest {
+    let credentials = Credentials { username: Some(username.to_string()), password: Some(password.to_string()) };
+    client::build_request(Command::Auth(method), rand::random(), SECRET, &credentials, Vec::new()).unwrap()
+}
+
+fn accounting_request(status: AcctStatus, session_id: &str, mut attributes: Vec<RadiusAttribute>) -> OutgoingRequest {
+    attributes.push(RadiusAttribute { typ: ATTR_NAS_IP_ADDRESS, value: vec![192, 0, 2, 1] });
+    attributes.push(RadiusAttribute { typ: ATTR_ACCT_SESSION_ID, value: session_id.as_bytes().to_vec() });
+    client::build_request(Command::Acct(status), rand::random(), SECRET, &Credentials::default(), attributes).unwrap()
+}
+
+/// Accounting-On (7) or Accounting-Off (8) from the NAS of `request`, signed with `secret`.
+/// The client has no such command, so the request is signed here (RFC 2866, section 3).
+fn nas_status(request: &OutgoingRequest, status: u32, secret: &str) -> (RadiusPacket, Vec<u8>) {
+    let mut packet = request.packet.clone();
+    packet.authenticator = [0; 16];
+    packet.attributes.retain(|attr| attr.typ != ATTR_ACCT_STATUS_TYPE && attr.typ != ATTR_ACCT_SESSION_ID);
+    packet.attributes.push(RadiusAttribute { typ: ATTR_ACCT_STATUS_TYPE, value: status.to_be_bytes().to_vec() });
+    let mut encoded = packet.encode();
+    let mut hasher = Md5::new();
+    hasher.update(&encoded);
+    hasher.update(secret.as_bytes());
+    encoded[4..20].copy_from_slice(&hasher.finalize());
+    packet.authenticator.copy_from_slice(&encoded[4..20]);
+    (packet, encoded)
+}
+
+#[tokio::test]
+async fn test_authentication_methods() {
+    let harness = Harness::start().await;
+
+    for method in [AuthMethod::Pap, AuthMethod::Chap, AuthMethod::MsChap, AuthMethod::MsChapV2] {
+        let cases = [
+            ("alice", "wonderland", CODE_ACCESS_ACCEPT),
+            ("alice", "looking-glass", CODE_ACCESS_REJECT),
+            ("carol", "wonderland", CODE_ACCESS_REJECT),
+            ("bob", "builder", CODE_ACCESS_REJECT),
+        ];
+        for (username, password, expected) in cases {
+            // verify_reply also checks the MS-CHAP2-Success of an MS-CHAPv2 Access-Accept
+            let reply = exchange(harness.auth, &access_request(method, username, password)).await;
+            assert_eq!(reply.code, expected, "{:?} {}/{}", method, username, password);
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_message_authenticator() {
+    let harness = Harness::start().await;
+
+    // Replies to a request carrying a Message-Authenticator carry one too
+    let reply = exchange(harness.auth, &access_request(AuthMethod::Pap, "alice", "wonderland")).await;
+    assert!(reply.attributes.iter().any(|attr| attr.typ == ATTR_MESSAGE_AUTHENTICATOR));
+
+    // It stays optional for PAP
+    let request = access_request(AuthMethod::Pap, "alice", "wonderland");
+    let mut packet = request.packet.clone();
+    packet.attributes.retain(|attr| attr.typ != ATTR_MESSAGE_AUTHENTICATOR);
+    let reply = send(harness.auth, &packet.encode(), Duration::from_secs(5)).await.expect("no reply");
+    assert_eq!(client::check_reply(&packet, &reply, SECRET).unwrap().code, CODE_ACCESS_ACCEPT);
+
+    // A wrong one is silently discarded, as is a request signed with another secret
+    let mut tampered = access_request(AuthMethod::Pap, "alice", "wonderland").encoded;
+    let last = tampered.len() - 1;
+    tampered[last] ^= 0xff;
+    assert!(send(harness.auth, &tampered, Duration::from_millis(500)).await.is_none());
+    let credentials = Credentials { username: Some("alice".to_string()), password: Some("wonderland".to_string()) };
+    let request = client::build_request(Command::Auth(AuthMethod::Pap), 1, "not-this-one", &credentials, Vec::new()).unwrap();
+    assert!(send(harness.auth, &request.encoded, Duration::from_millis(500)).await.is_none());
+}
+
+#[tokio::test]
+async fn test_lockout_counts_only_credential_failures() {
+    // Without Redis the counters are kept in memory
+    let harness = Harness::start_with(|config| {
+        config.redis.url = "redis://127.0.0.1:1".to_string();
+        config.lockout.max_failures = 2;
+    }).await;
+
+    // Rejected before the password is looked at, as a policy or schedule reject would be
+    let mut packet = access_request(AuthMethod::Pap, "alice", "wonderland").packet;
+    packet.attributes.retain(|attr| attr.typ != ATTR_USER_PASSWORD && attr.typ != ATTR_MESSAGE_AUTHENTICATOR);
+    for _ in 0..3 {
+        let reply = send(harness.auth, &packet.encode(), Duration::from_secs(5)).await.expect("no reply");
+        assert_eq!(client::check_reply(&packet, &reply, SECRET).unwrap().code, CODE_ACCESS_REJECT);
+    }
+    let reply = exchange(harness.auth, &access_request(AuthMethod::Pap, "alice", "wonderland")).await;
+    assert_eq!(reply.code, CODE_ACCESS_ACCEPT);
+
+    for _ in 0..2 {
+        let reply = exchange(harness.auth, &access_request(AuthMethod::Pap, "alice", "looking-glass")).await;
+        assert_eq!(reply.code, CODE_ACCESS_REJECT);
+    }
+    let reply = exchange(harness.auth, &access_request(AuthMethod::Pap, "alice", "wonderland")).await;
+    assert_eq!(reply.code, CODE_ACCESS_REJECT);
+}
+
+#[tokio::test]
+async fn test_status_server() {
+    let harness = Harness::start().await;
+
+    let request = client::build_request(Command::Status, 1, SECRET, &Credentials::default(), Vec::new()).unwrap();
+    assert_eq!(exchange(harness.auth, &request).await.code, CODE_ACCESS_ACCEPT);
+    assert_eq!(exchange(harness.acct, &request).await.code, CODE_ACCOUNTING_RESPONSE);
+}
+
+#[tokio::test]
+async fn test_accounting_session() {
+    let harness = Harness::start().await;
+    let counters = |session_time: u32, octets: u32, gigawords: u32| vec![
+        RadiusAttribute { typ: ATTR_ACCT_SESSION_TIME, value: session_time.to_be_bytes().to_vec() },
+        RadiusAttribute { typ: ATTR_ACCT_INPUT_OCTETS, value: octets.to_be_bytes().to_vec() },
+        RadiusAttribute { typ: ATTR_ACCT_INPUT_GIGAWORDS, value: gigawords.to_be_bytes().to_vec() },
+    ];
+
+    for request in [
+        accounting_request(AcctStatus::Start, "S1", Vec::new()),
+        accounting_request(AcctStatus::Start, "S2", Vec::new()),
+        accounting_request(AcctStatus::Interim, "S1", counters(60, 1000, 0)),
+        accounting_request(AcctStatus::Stop, "S1", counters(120, 5, 1)),
+    ] {
+        assert_eq!(exchange(harness.acct, &request).await.code, CODE_ACCOUNTING_RESPONSE);
+    }
+
+    let sessions = harness.sessions.sessions();
+    assert_eq!(sessions.len(), 2);
+    // Sessions belong to the NAS the requests came from, whatever NAS-IP-Address they carry
+    assert_eq!((sessions[0].session_id.as_str(), sessions[0].nas_ip.as_str()), ("S1", "127.0.0.1"));
+    assert_eq!((sessions[0].session_time, sessions[0].input_octets), (120, (1 << 32) + 5));
+    assert!(sessions[0].stop_time.is_some());
+    assert!(sessions[1].stop_time.is_none());
+}
+
+#[tokio::test]
+async fn test_accounting_on_closes_nas_sessions() {
+    let harness = Harness::start().await;
+    let request = accounting_request(AcctStatus::Start, "S1", Vec::new());
+    assert_eq!(exchange(harness.acct, &request).await.code, CODE_ACCOUNTING_RESPONSE);
+
+    // A forged Accounting-On is discarded and closes nothing
+    let (_, forged) = nas_status(&request, 7, "guessed");
+    assert!(send(harness.acct, &forged, Duration::from_millis(500)).await.is_none());
+    assert!(harness.sessions.sessions()[0].stop_time.is_none());
+
+    let (packet, encoded) = nas_status(&request, 7, SECRET);
+    let reply = send(harness.acct, &encoded, Duration::from_secs(5)).await.expect("no reply");
+    assert_eq!(client::check_reply(&packet, &reply, SECRET).unwrap().code, CODE_ACCOUNTING_RESPONSE);
+    let sessions = harness.sessions.sessions();
+    assert_eq!(sessions[0].termination_cause.as_deref(), Some("NAS-Reboot"));
+    assert!(sessions[0].stop_time.is_some());
+}
+
+#[tokio::test]
+async fn test_forged_accounting_off_is_discarded() {
+    // IP pools need PostgreSQL: the session that stays open shows the Accounting-Off released nothing
+    let harness = Harness::start().await;
+    let request = accounting_request(AcctStatus::Start, "S1", Vec::new());
+    assert_eq!(exchange(harness.acct, &request).await.code, CODE_ACCOUNTING_RESPONSE);
+
+    let (_, forged) = nas_status(&request, 8, "guessed");
+    assert!(send(harness.acct, &forged, Duration::from_millis(500)).await.is_none());
+    let mut unsigned = forged.clone();
+    unsigned[4..20].fill(0);
+    assert!(send(harness.acct, &unsigned, Duration::from_millis(500)).await.is_none());
+
+    let sessions = harness.sessions.sessions();
+    assert_eq!(sessions.len(), 1);
+    assert!(sessions[0].stop_time.is_none());
+}
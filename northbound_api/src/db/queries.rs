@@ -378,6 +378,8 @@ pub async fn update_user_identifier(
         updates.push(format!("plain_password = ${}", param_index));
         string_params.push(plain_password.unwrap().to_string());
         param_index += 1;
+        updates.push("password_changed_at = NOW()".to_string());
+        updates.push("grace_logins_used = 0".to_string());
     }
     if is_enabled.is_some() {
         updates.push(format!("is_enabled = ${}", param_index));
@@ -631,6 +633,20 @@ pub async fn get_nas_device(pool: &Pool, id: i64) -> Result<Option<Row>> {
     Ok(row)
 }
 
+/// CoA target of a NAS device with its shared secret
+pub async fn get_nas_coa_target(pool: &Pool, id: i64) -> Result<Option<Row>> {
+    let client = pool.get().await?;
+    let row = client.query_opt(
+        "SELECT n.ip_address, n.coa_enabled, n.coa_port, s.secret 
+         FROM nas_nas n 
+         LEFT JOIN radius_secret s ON s.id = n.secret_id 
+         WHERE n.id = $1",
+        &[&id],
+    ).await?;
+    
+    Ok(row)
+}
+
 pub async fn create_nas_device(
     pool: &Pool,
     name: &str,
@@ -2,12 +2,17 @@ use actix_web::{web, HttpResponse};
 use deadpool_postgres::Pool;
 use tokio_postgres::Row;
 use chrono::{DateTime, Utc};
-use std::time::SystemTime;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::{Duration, SystemTime};
 use crate::auth::Claims;
 use crate::models::*;
 use crate::db::queries;
 use crate::error::ApiError;
 use log::{info, error};
+use openrdx_dynauth::{
+    Attribute, Client as DynAuthClient, DynAuthError, Request as DynAuthClientRequest, RequestKind, SessionSelector,
+    DEFAULT_COA_PORT,
+};
 use utoipa;
 
 type HandlerResult = Result<HttpResponse, ApiError>;
@@ -1349,6 +1354,159 @@ pub async fn delete_nas(
     }
 }
 
+// Dynamic authorization handlers (RFC 5176)
+const DYNAUTH_TIMEOUT: Duration = Duration::from_secs(2);
+const DYNAUTH_RETRIES: u32 = 2;
+
+fn encode_dynauth_attribute(attribute: &DynAuthAttribute) -> Result<Attribute, String> {
+    let value = match attribute.value_type.as_deref().unwrap_or("string") {
+        "string" => attribute.value.as_bytes().to_vec(),
+        "integer" => attribute.value.parse::<u32>().map_err(|e| e.to_string())?.to_be_bytes().to_vec(),
+        "ipaddr" => attribute.value.parse::<Ipv4Addr>().map_err(|e| e.to_string())?.octets().to_vec(),
+        "octets" => {
+            let hex = attribute.value.trim_start_matches("0x");
+            // Checked first so the byte slicing below stays on character boundaries
+            if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err("octets must be hex digits".to_string());
+            }
+            if hex.len() % 2 != 0 {
+                return Err("odd number of hex digits".to_string());
+            }
+            (0..hex.len()).step_by(2)
+                .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|e| e.to_string()))
+                .collect::<Result<Vec<u8>, String>>()?
+        }
+        other => return Err(format!("unknown value type {}", other)),
+    };
+    Ok(Attribute { typ: attribute.attribute_type, value })
+}
+
+async fn send_dynauth(pool: &Pool, nas_id: u64, kind: RequestKind, payload: DynAuthRequest) -> HandlerResult {
+    let row = match queries::get_nas_coa_target(pool, nas_id as i64).await {
+        Ok(Some(row)) => row,
+        Ok(None) => return Err(ApiError::NotFound("NAS device not found".to_string())),
+        Err(e) => {
+            error!("Database error getting NAS CoA target: {}", e);
+            return Err(ApiError::Internal(format!("Failed to get NAS device: {}", e)));
+        }
+    };
+    if !row.get::<_, bool>("coa_enabled") {
+        return Err(ApiError::BadRequest("CoA is not enabled on this NAS device".to_string()));
+    }
+    let ip_address: String = row.get("ip_address");
+    let ip = ip_address.parse::<IpAddr>()
+        .map_err(|_| ApiError::BadRequest(format!("NAS address {} is not a single IP address", ip_address)))?;
+    let coa_port = match row.get::<_, i32>("coa_port") {
+        port if port > 0 && port <= u16::MAX as i32 => port as u16,
+        _ => DEFAULT_COA_PORT,
+    };
+    let Some(secret) = row.get::<_, Option<String>>("secret") else {
+        return Err(ApiError::BadRequest("No RADIUS secret configured for this NAS device".to_string()));
+    };
+
+    let framed_ip = payload.framed_ip_address.as_deref()
+        .map(|ip| ip.parse::<Ipv4Addr>())
+        .transpose()
+        .map_err(|_| ApiError::BadRequest("framed_ip_address must be an IPv4 address".to_string()))?;
+    let session = SessionSelector {
+        acct_session_id: payload.acct_session_id,
+        username: payload.username,
+        framed_ip,
+        nas_port: payload.nas_port,
+        calling_station_id: payload.calling_station_id,
+        ..Default::default()
+    };
+    let attributes = payload.attributes.unwrap_or_default().iter()
+        .map(encode_dynauth_attribute)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| ApiError::BadRequest(format!("Invalid attribute: {}", e)))?;
+    let request = match kind {
+        RequestKind::Disconnect if !attributes.is_empty() => {
+            return Err(ApiError::BadRequest("Disconnect requests don't carry authorization attributes".to_string()));
+        }
+        RequestKind::Disconnect => DynAuthClientRequest::disconnect(session),
+        RequestKind::Coa => DynAuthClientRequest::coa(session, attributes),
+    };
+
+    let target = SocketAddr::new(ip, coa_port);
+    match DynAuthClient::new(DYNAUTH_TIMEOUT, DYNAUTH_RETRIES).send(target, &secret, &request).await {
+        Ok(reply) => {
+            info!("{} to {}: {}", kind.name(), target, if reply.acked { "ACK" } else { "NAK" });
+            Ok(HttpResponse::Ok().json(DynAuthResponse {
+                acked: reply.acked,
+                error_cause: reply.error_cause.map(|cause| cause.0),
+                error_cause_description: reply.error_cause.map(|cause| cause.description().to_string()),
+            }))
+        }
+        Err(DynAuthError::NoSessionIdentification) => {
+            Err(ApiError::BadRequest("At least one session attribute is required".to_string()))
+        }
+        Err(e @ (DynAuthError::AttributeTooLong(_) | DynAuthError::PacketTooLarge(_))) => {
+            Err(ApiError::BadRequest(e.to_string()))
+        }
+        Err(e) => {
+            error!("{} to {} failed: {}", kind.name(), target, e);
+            Err(ApiError::Internal(format!("{} failed: {}", kind.name(), e)))
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/nas/{id}/disconnect",
+    tag = "NAS",
+    security(
+        ("Bearer" = [])
+    ),
+    params(
+        ("id" = u64, Path, description = "NAS Device ID")
+    ),
+    request_body = DynAuthRequest,
+    responses(
+        (status = 200, description = "Disconnect-ACK or Disconnect-NAK from the NAS", body = DynAuthResponse),
+        (status = 400, description = "Bad request or CoA disabled on the NAS"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "NAS device not found"),
+        (status = 500, description = "No valid reply from the NAS")
+    )
+)]
+pub async fn disconnect_nas_session(
+    _claims: Claims,
+    pool: web::Data<Pool>,
+    path: web::Path<u64>,
+    payload: web::Json<DynAuthRequest>,
+) -> HandlerResult {
+    send_dynauth(pool.get_ref(), path.into_inner(), RequestKind::Disconnect, payload.into_inner()).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/nas/{id}/coa",
+    tag = "NAS",
+    security(
+        ("Bearer" = [])
+    ),
+    params(
+        ("id" = u64, Path, description = "NAS Device ID")
+    ),
+    request_body = DynAuthRequest,
+    responses(
+        (status = 200, description = "CoA-ACK or CoA-NAK from the NAS", body = DynAuthResponse),
+        (status = 400, description = "Bad request or CoA disabled on the NAS"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "NAS device not found"),
+        (status = 500, description = "No valid reply from the NAS")
+    )
+)]
+pub async fn coa_nas_session(
+    _claims: Claims,
+    pool: web::Data<Pool>,
+    path: web::Path<u64>,
+    payload: web::Json<DynAuthRequest>,
+) -> HandlerResult {
+    send_dynauth(pool.get_ref(), path.into_inner(), RequestKind::Coa, payload.into_inner()).await
+}
+
 // Vendors handlers
 #[utoipa::path(
     get,
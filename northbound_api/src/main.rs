@@ -113,6 +113,8 @@ async fn main() -> std::io::Result<()> {
                     .route("/nas/{id}", web::get().to(get_nas))
                     .route("/nas/{id}", web::put().to(update_nas))
                     .route("/nas/{id}", web::delete().to(delete_nas))
+                    .route("/nas/{id}/disconnect", web::post().to(disconnect_nas_session))
+                    .route("/nas/{id}/coa", web::post().to(coa_nas_session))
                     // Vendor endpoints
                     .route("/vendors", web::get().to(list_vendors))
                     // Secret endpoints
@@ -214,6 +214,32 @@ pub struct NasListResponse {
     pub results: Vec<Nas>,
 }
 
+/// Attribute of a CoA-Request; `value_type` is one of string (default), integer, ipaddr or octets (hex)
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DynAuthAttribute {
+    pub attribute_type: u8,
+    pub value_type: Option<String>,
+    pub value: String,
+}
+
+/// Session to disconnect or change, and for CoA the new authorization attributes
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DynAuthRequest {
+    pub acct_session_id: Option<String>,
+    pub username: Option<String>,
+    pub framed_ip_address: Option<String>,
+    pub nas_port: Option<u32>,
+    pub calling_station_id: Option<String>,
+    pub attributes: Option<Vec<DynAuthAttribute>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DynAuthResponse {
+    pub acked: bool,
+    pub error_cause: Option<u32>,
+    pub error_cause_description: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct Vendor {
     pub id: u64,
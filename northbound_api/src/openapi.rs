@@ -42,6 +42,8 @@ use crate::models::*;
         crate::handlers::create_nas,
         crate::handlers::update_nas,
         crate::handlers::delete_nas,
+        crate::handlers::disconnect_nas_session,
+        crate::handlers::coa_nas_session,
         crate::handlers::list_vendors,
         crate::handlers::list_secrets
     ),
@@ -65,6 +67,9 @@ use crate::models::*;
         NasCreate,
         NasUpdate,
         NasListResponse,
+        DynAuthAttribute,
+        DynAuthRequest,
+        DynAuthResponse,
         Vendor,
         Secret,
         ErrorResponse,
@@ -0,0 +1,16 @@
+from django.db import migrations, models
+
+
+class Migration(migrations.Migration):
+
+    dependencies = [
+        ('nas', '0009_add_nas_identifier'),
+    ]
+
+    operations = [
+        migrations.AddField(
+            model_name='nas',
+            name='vlan_map',
+            field=models.JSONField(blank=True, default=dict, help_text='VLAN names as this NAS numbers them, e.g. {"staff": 120, "guests": 300}', verbose_name='VLAN Map'),
+        ),
+    ]
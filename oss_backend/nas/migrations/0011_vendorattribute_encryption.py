@@ -0,0 +1,16 @@
+from django.db import migrations, models
+
+
+class Migration(migrations.Migration):
+
+    dependencies = [
+        ('nas', '0010_nas_vlan_map'),
+    ]
+
+    operations = [
+        migrations.AddField(
+            model_name='vendorattribute',
+            name='encryption',
+            field=models.CharField(blank=True, choices=[('', 'None'), ('user-password', 'User-Password (RFC 2865)'), ('salt', 'Salt (RFC 2868)'), ('ascend-secret', 'Ascend-Send-Secret')], default='', help_text='How reply values of this attribute are encrypted', max_length=20, verbose_name='Encryption'),
+        ),
+    ]
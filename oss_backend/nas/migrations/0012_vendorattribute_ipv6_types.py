@@ -0,0 +1,16 @@
+from django.db import migrations, models
+
+
+class Migration(migrations.Migration):
+
+    dependencies = [
+        ('nas', '0011_vendorattribute_encryption'),
+    ]
+
+    operations = [
+        migrations.AlterField(
+            model_name='vendorattribute',
+            name='attribute_type',
+            field=models.CharField(choices=[('string', 'String'), ('integer', 'Integer'), ('ipaddr', 'IP Address'), ('ipv6addr', 'IPv6 Address'), ('ipv6prefix', 'IPv6 Prefix'), ('ifid', 'Interface ID'), ('date', 'Date'), ('octets', 'Octets')], max_length=20, verbose_name='Attribute Type'),
+        ),
+    ]
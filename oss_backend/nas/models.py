@@ -34,9 +34,18 @@ class VendorAttribute(models.Model):
         ('string', _('String')),
         ('integer', _('Integer')),
         ('ipaddr', _('IP Address')),
+        ('ipv6addr', _('IPv6 Address')),
+        ('ipv6prefix', _('IPv6 Prefix')),
+        ('ifid', _('Interface ID')),
         ('date', _('Date')),
         ('octets', _('Octets')),
     )
+    ENCRYPTION_SCHEMES = (
+        ('', _('None')),
+        ('user-password', _('User-Password (RFC 2865)')),
+        ('salt', _('Salt (RFC 2868)')),
+        ('ascend-secret', _('Ascend-Send-Secret')),
+    )
 
     vendor = models.ForeignKey(Vendor, on_delete=models.CASCADE, 
                               related_name='attributes', verbose_name=_("Vendor"))
@@ -44,6 +53,8 @@ class VendorAttribute(models.Model):
     description = models.TextField(_("Description"), blank=True)
     attribute_id = models.PositiveIntegerField(_("Attribute ID"))
     attribute_type = models.CharField(_("Attribute Type"), max_length=20, choices=ATTRIBUTE_TYPES)
+    encryption = models.CharField(_("Encryption"), max_length=20, choices=ENCRYPTION_SCHEMES, blank=True, default='',
+                                  help_text=_("How reply values of this attribute are encrypted"))
     created_at = models.DateTimeField(_("Created At"), auto_now_add=True)
     updated_at = models.DateTimeField(_("Updated At"), auto_now=True)
 
@@ -108,6 +119,8 @@ class Nas(models.Model):
     updated_at = models.DateTimeField(_("Updated At"), auto_now=True)
     is_active = models.BooleanField(_("Is Active"), default=True)
     timezone = models.ForeignKey('shared.Timezone', on_delete=models.SET_NULL, null=True, blank=True)
+    vlan_map = models.JSONField(_("VLAN Map"), default=dict, blank=True,
+                                help_text=_('VLAN names as this NAS numbers them, e.g. {"staff": 120, "guests": 300}'))
 
 
     class Meta:
@@ -7,6 +7,20 @@ from shared.serializers import TimezoneSerializer
 from radius.serializers import SecretSerializer
 from .models import Nas, NasGroup, Vendor
 
+
+def validate_vlan_map(value):
+    """Validate a VLAN map: names mapped to a VLAN ID (1-4094) or the name the NAS uses"""
+    if not isinstance(value, dict):
+        raise serializers.ValidationError("VLAN map must be an object of VLAN names to IDs.")
+    for name, vlan in value.items():
+        if isinstance(vlan, bool) or not isinstance(vlan, (int, str)):
+            raise serializers.ValidationError(f"VLAN {name}: must map to a VLAN ID or name.")
+        if isinstance(vlan, int) and not 1 <= vlan <= 4094:
+            raise serializers.ValidationError(f"VLAN {name}: ID must be between 1 and 4094.")
+        if isinstance(vlan, str) and not vlan.strip():
+            raise serializers.ValidationError(f"VLAN {name}: must not be empty.")
+    return value
+
 class VendorSerializer(serializers.ModelSerializer):
     """
     Serializer for the Vendor model.
@@ -71,7 +85,7 @@ class NasSerializer(serializers.ModelSerializer):
         model = Nas
         fields = ['id', 'name', 'description', 'ip_address', 'nas_identifier', 'coa_enabled', 'coa_port', 
                  'groups', 'group_ids', 'created_at', 'updated_at', 'is_active',
-                  'vendor', 'vendor_id', 'timezone_id', 'timezone', 'secret', 'secret_id']
+                  'vendor', 'vendor_id', 'timezone_id', 'timezone', 'secret', 'secret_id', 'vlan_map']
 
         read_only_fields = ['created_at', 'updated_at', 'timezone', 'secret']
 
@@ -128,6 +142,9 @@ class NasCreateSerializer(serializers.ModelSerializer):
             "IP address must be a valid IPv4 or IPv6 address, or a valid hostname."
         )
 
+    def validate_vlan_map(self, value):
+        return validate_vlan_map(value)
+
     def validate_secret_id(self, value):
         """Validate that the secret exists if provided"""
         if value is not None:
@@ -157,7 +174,7 @@ class NasCreateSerializer(serializers.ModelSerializer):
     class Meta:
         model = Nas
         fields = ['name', 'description', 'ip_address', 'nas_identifier', 'coa_enabled', 'coa_port', 
-                 'group_ids', 'is_active', 'timezone_id', 'vendor_id', 'secret_id']
+                 'group_ids', 'is_active', 'timezone_id', 'vendor_id', 'secret_id', 'vlan_map']
 
 
 class NasUpdateSerializer(serializers.ModelSerializer):
@@ -212,6 +229,9 @@ class NasUpdateSerializer(serializers.ModelSerializer):
             "IP address must be a valid IPv4 or IPv6 address, or a valid hostname."
         )
 
+    def validate_vlan_map(self, value):
+        return validate_vlan_map(value)
+
     def validate_secret_id(self, value):
         """Validate that the secret exists if provided"""
         if value is not None:
@@ -245,4 +265,4 @@ class NasUpdateSerializer(serializers.ModelSerializer):
     class Meta:
         model = Nas
         fields = ['name', 'description', 'ip_address', 'nas_identifier', 'coa_enabled', 'coa_port', 
-                 'group_ids', 'is_active', 'vendor_id', 'timezone_id', 'secret_id']
+                 'group_ids', 'is_active', 'vendor_id', 'timezone_id', 'secret_id', 'vlan_map']
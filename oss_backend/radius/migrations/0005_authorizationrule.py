@@ -0,0 +1,34 @@
+from django.db import migrations, models
+
+
+class Migration(migrations.Migration):
+
+    dependencies = [
+        ('radius', '0004_remove_secret_rad_sec_and_more'),
+    ]
+
+    operations = [
+        migrations.CreateModel(
+            name='AuthorizationRule',
+            fields=[
+                ('id', models.BigAutoField(auto_created=True, primary_key=True, serialize=False, verbose_name='ID')),
+                ('name', models.CharField(max_length=255, unique=True, verbose_name='Name')),
+                ('description', models.TextField(blank=True, verbose_name='Description')),
+                ('priority', models.IntegerField(default=100, help_text='Rules with lower values are evaluated first', verbose_name='Priority')),
+                ('is_enabled', models.BooleanField(default=True, verbose_name='Enabled')),
+                ('conditions', models.JSONField(blank=True, default=list, help_text='All must match, e.g. [{"field": "ssid", "operator": "equals", "value": "Corp"}]', verbose_name='Conditions')),
+                ('action', models.CharField(choices=[('continue', 'Continue'), ('accept', 'Accept'), ('reject', 'Reject')], default='continue', max_length=20, verbose_name='Action')),
+                ('reply_message', models.CharField(blank=True, default='', help_text='Sent with reject actions', max_length=255, verbose_name='Reply Message')),
+                ('attribute_edits', models.JSONField(blank=True, default=list, help_text='e.g. [{"attribute_id": 27, "type": "integer", "value": "3600", "op": "set"}]', verbose_name='Attribute Edits')),
+                ('created_at', models.DateTimeField(auto_now_add=True, verbose_name='Created At')),
+                ('updated_at', models.DateTimeField(auto_now=True, verbose_name='Updated At')),
+            ],
+            options={
+                'verbose_name': 'Authorization Rule',
+                'verbose_name_plural': 'Authorization Rules',
+                'db_table': 'radius_authorization_rule',
+                'ordering': ['priority', 'id'],
+                'indexes': [models.Index(fields=['priority'], name='rad_auth_rule_priority_idx')],
+            },
+        ),
+    ]
@@ -0,0 +1,27 @@
+import django.core.validators
+from django.db import migrations, models
+
+
+class Migration(migrations.Migration):
+
+    dependencies = [
+        ('radius', '0005_authorizationrule'),
+    ]
+
+    operations = [
+        migrations.AddField(
+            model_name='authattributegroup',
+            name='vlan',
+            field=models.CharField(blank=True, default='', help_text="VLAN ID or name for members; names are translated through each NAS's VLAN map", max_length=64, verbose_name='VLAN'),
+        ),
+        migrations.AddField(
+            model_name='radiusattribute',
+            name='tag',
+            field=models.PositiveSmallIntegerField(blank=True, help_text='RFC 2868 tag (0-31) for Tunnel-* attributes', null=True, validators=[django.core.validators.MaxValueValidator(31)], verbose_name='Tag'),
+        ),
+        migrations.AddField(
+            model_name='authorizationrule',
+            name='vlan',
+            field=models.CharField(blank=True, default='', help_text='VLAN ID or name assigned to matching users, overriding their own', max_length=64, verbose_name='VLAN'),
+        ),
+    ]
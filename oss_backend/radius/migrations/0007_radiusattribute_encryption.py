@@ -0,0 +1,16 @@
+from django.db import migrations, models
+
+
+class Migration(migrations.Migration):
+
+    dependencies = [
+        ('radius', '0006_vlan_and_attribute_tag'),
+    ]
+
+    operations = [
+        migrations.AddField(
+            model_name='radiusattribute',
+            name='encryption',
+            field=models.CharField(blank=True, choices=[('', 'As in the dictionary'), ('user-password', 'User-Password (RFC 2865)'), ('salt', 'Salt (RFC 2868)'), ('ascend-secret', 'Ascend-Send-Secret')], default='', help_text='Tunnel-Password and MS-MPPE keys are salt-encrypted unless set otherwise', max_length=20, verbose_name='Encryption'),
+        ),
+    ]
@@ -0,0 +1,27 @@
+import django.core.validators
+from django.db import migrations, models
+
+
+class Migration(migrations.Migration):
+
+    dependencies = [
+        ('radius', '0007_radiusattribute_encryption'),
+    ]
+
+    operations = [
+        migrations.AddField(
+            model_name='authattributegroup',
+            name='rate_limit',
+            field=models.CharField(blank=True, default='', help_text='Upload/download limit, e.g. 10M/50M', max_length=32, validators=[django.core.validators.RegexValidator('^[1-9]\\d*[kKMG]?/[1-9]\\d*[kKMG]?$', 'Use upload/download in bits per second, e.g. 10M/50M')], verbose_name='Rate Limit'),
+        ),
+        migrations.AddField(
+            model_name='authattributegroup',
+            name='acl_name',
+            field=models.CharField(blank=True, default='', help_text='Filter, role or address list the NAS applies to members', max_length=128, verbose_name='ACL Name'),
+        ),
+        migrations.AddField(
+            model_name='authattributegroup',
+            name='privilege_level',
+            field=models.PositiveSmallIntegerField(blank=True, help_text="Device administration level (0-15), mapped to each vendor's admin roles", null=True, validators=[django.core.validators.MaxValueValidator(15)], verbose_name='Privilege Level'),
+        ),
+    ]
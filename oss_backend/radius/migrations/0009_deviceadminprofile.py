@@ -0,0 +1,30 @@
+import django.core.validators
+from django.db import migrations, models
+
+
+class Migration(migrations.Migration):
+
+    dependencies = [
+        ('radius', '0008_authattributegroup_intents'),
+    ]
+
+    operations = [
+        migrations.CreateModel(
+            name='DeviceAdminProfile',
+            fields=[
+                ('id', models.BigAutoField(auto_created=True, primary_key=True, serialize=False, verbose_name='ID')),
+                ('name', models.CharField(max_length=255, unique=True, verbose_name='Name')),
+                ('description', models.TextField(blank=True, verbose_name='Description')),
+                ('privilege_level', models.PositiveSmallIntegerField(default=1, help_text='0-15; only 15 allows Administrative-User logins', validators=[django.core.validators.MaxValueValidator(15)], verbose_name='Privilege Level')),
+                ('vendor_roles', models.JSONField(blank=True, default=dict, help_text='Roles replacing the built-in ones, e.g. {"mikrotik": "write", "juniper": "noc"}', verbose_name='Vendor Roles')),
+                ('created_at', models.DateTimeField(auto_now_add=True, verbose_name='Created At')),
+                ('updated_at', models.DateTimeField(auto_now=True, verbose_name='Updated At')),
+            ],
+            options={
+                'verbose_name': 'Device Admin Profile',
+                'verbose_name_plural': 'Device Admin Profiles',
+                'db_table': 'radius_device_admin_profile',
+                'ordering': ['name'],
+            },
+        ),
+    ]
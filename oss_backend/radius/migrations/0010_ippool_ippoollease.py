@@ -0,0 +1,60 @@
+import django.db.models.deletion
+from django.db import migrations, models
+
+
+class Migration(migrations.Migration):
+
+    dependencies = [
+        ('nas', '0011_vendorattribute_encryption'),
+        ('radius', '0009_deviceadminprofile'),
+        ('users', '0020_usergroup_device_admin_profile'),
+    ]
+
+    operations = [
+        migrations.CreateModel(
+            name='IpPool',
+            fields=[
+                ('id', models.BigAutoField(auto_created=True, primary_key=True, serialize=False, verbose_name='ID')),
+                ('name', models.CharField(max_length=255, unique=True, verbose_name='Name')),
+                ('description', models.TextField(blank=True, verbose_name='Description')),
+                ('range_start', models.GenericIPAddressField(protocol='IPv4', verbose_name='Range Start')),
+                ('range_end', models.GenericIPAddressField(protocol='IPv4', verbose_name='Range End')),
+                ('netmask', models.GenericIPAddressField(default='255.255.255.255', help_text='Sent as Framed-IP-Netmask', protocol='IPv4', verbose_name='Netmask')),
+                ('priority', models.IntegerField(default=100, help_text='Pools with lower values are tried first', verbose_name='Priority')),
+                ('lease_timeout', models.PositiveIntegerField(default=3600, help_text='Seconds a lease lasts without accounting; keep it above the interim interval', verbose_name='Lease Timeout')),
+                ('is_active', models.BooleanField(default=True, verbose_name='Active')),
+                ('created_at', models.DateTimeField(auto_now_add=True, verbose_name='Created At')),
+                ('updated_at', models.DateTimeField(auto_now=True, verbose_name='Updated At')),
+                ('nas_group', models.ForeignKey(blank=True, null=True, on_delete=django.db.models.deletion.CASCADE, related_name='ip_pools', to='nas.nasgroup', verbose_name='NAS Group')),
+                ('user_group', models.ForeignKey(blank=True, null=True, on_delete=django.db.models.deletion.CASCADE, related_name='ip_pools', to='users.usergroup', verbose_name='User Group')),
+            ],
+            options={
+                'verbose_name': 'IP Pool',
+                'verbose_name_plural': 'IP Pools',
+                'db_table': 'radius_ip_pool',
+                'ordering': ['priority', 'name'],
+            },
+        ),
+        migrations.CreateModel(
+            name='IpPoolLease',
+            fields=[
+                ('id', models.BigAutoField(auto_created=True, primary_key=True, serialize=False, verbose_name='ID')),
+                ('address', models.GenericIPAddressField(protocol='IPv4', verbose_name='Address')),
+                ('username', models.CharField(max_length=255, verbose_name='Username')),
+                ('calling_station_id', models.CharField(blank=True, default='', max_length=255, verbose_name='Calling Station ID')),
+                ('nas_ip', models.GenericIPAddressField(blank=True, null=True, protocol='IPv4', verbose_name='NAS IP')),
+                ('acct_session_id', models.CharField(blank=True, default='', max_length=255, verbose_name='Accounting Session ID')),
+                ('allocated_at', models.DateTimeField(verbose_name='Allocated At')),
+                ('expires_at', models.DateTimeField(verbose_name='Expires At')),
+                ('pool', models.ForeignKey(on_delete=django.db.models.deletion.CASCADE, related_name='leases', to='radius.ippool', verbose_name='Pool')),
+            ],
+            options={
+                'verbose_name': 'IP Pool Lease',
+                'verbose_name_plural': 'IP Pool Leases',
+                'db_table': 'radius_ip_pool_lease',
+                'ordering': ['pool', 'address'],
+                'indexes': [models.Index(fields=['acct_session_id'], name='rad_ip_lease_session_idx'), models.Index(fields=['pool', 'username'], name='rad_ip_lease_user_idx'), models.Index(fields=['pool', 'expires_at'], name='rad_ip_lease_expiry_idx')],
+                'unique_together': {('pool', 'address')},
+            },
+        ),
+    ]
@@ -0,0 +1,69 @@
+import django.core.validators
+import django.db.models.deletion
+from django.db import migrations, models
+
+
+class Migration(migrations.Migration):
+
+    dependencies = [
+        ('nas', '0012_vendorattribute_ipv6_types'),
+        ('radius', '0010_ippool_ippoollease'),
+        ('users', '0020_usergroup_device_admin_profile'),
+    ]
+
+    operations = [
+        migrations.AlterField(
+            model_name='radiusattribute',
+            name='attribute_type',
+            field=models.CharField(choices=[('string', 'String'), ('integer', 'Integer'), ('ipaddr', 'IP Address'), ('ipv6addr', 'IPv6 Address'), ('ipv6prefix', 'IPv6 Prefix'), ('ifid', 'Interface ID'), ('date', 'Date'), ('octets', 'Octets')], max_length=20, verbose_name='Attribute Type'),
+        ),
+        migrations.CreateModel(
+            name='Ipv6PrefixPool',
+            fields=[
+                ('id', models.BigAutoField(auto_created=True, primary_key=True, serialize=False, verbose_name='ID')),
+                ('name', models.CharField(max_length=255, unique=True, verbose_name='Name')),
+                ('description', models.TextField(blank=True, verbose_name='Description')),
+                ('kind', models.CharField(choices=[('framed', 'Framed-IPv6-Prefix'), ('delegated', 'Delegated-IPv6-Prefix')], default='delegated', max_length=20, verbose_name='Kind')),
+                ('network', models.GenericIPAddressField(protocol='IPv6', verbose_name='Network')),
+                ('prefix_length', models.PositiveSmallIntegerField(validators=[django.core.validators.MaxValueValidator(128)], verbose_name='Prefix Length')),
+                ('delegated_length', models.PositiveSmallIntegerField(default=56, help_text='Length of the prefixes handed out, e.g. 56 or 64', validators=[django.core.validators.MaxValueValidator(128)], verbose_name='Delegated Length')),
+                ('interface_id', models.CharField(blank=True, default='', help_text='Framed-Interface-Id sent with framed prefixes, e.g. 0:0:0:1', max_length=19, verbose_name='Interface ID')),
+                ('priority', models.IntegerField(default=100, help_text='Pools with lower values are tried first', verbose_name='Priority')),
+                ('lease_timeout', models.PositiveIntegerField(default=3600, help_text='Seconds a lease lasts without accounting; keep it above the interim interval', verbose_name='Lease Timeout')),
+                ('is_active', models.BooleanField(default=True, verbose_name='Active')),
+                ('created_at', models.DateTimeField(auto_now_add=True, verbose_name='Created At')),
+                ('updated_at', models.DateTimeField(auto_now=True, verbose_name='Updated At')),
+                ('nas_group', models.ForeignKey(blank=True, null=True, on_delete=django.db.models.deletion.CASCADE, related_name='ipv6_prefix_pools', to='nas.nasgroup', verbose_name='NAS Group')),
+                ('user_group', models.ForeignKey(blank=True, null=True, on_delete=django.db.models.deletion.CASCADE, related_name='ipv6_prefix_pools', to='users.usergroup', verbose_name='User Group')),
+            ],
+            options={
+                'verbose_name': 'IPv6 Prefix Pool',
+                'verbose_name_plural': 'IPv6 Prefix Pools',
+                'db_table': 'radius_ipv6_prefix_pool',
+                'ordering': ['priority', 'name'],
+            },
+        ),
+        migrations.CreateModel(
+            name='Ipv6PrefixLease',
+            fields=[
+                ('id', models.BigAutoField(auto_created=True, primary_key=True, serialize=False, verbose_name='ID')),
+                ('slot', models.BigIntegerField(verbose_name='Slot')),
+                ('address', models.GenericIPAddressField(protocol='IPv6', verbose_name='Prefix')),
+                ('username', models.CharField(max_length=255, verbose_name='Username')),
+                ('calling_station_id', models.CharField(blank=True, default='', max_length=255, verbose_name='Calling Station ID')),
+                ('nas_ip', models.GenericIPAddressField(blank=True, null=True, protocol='IPv4', verbose_name='NAS IP')),
+                ('acct_session_id', models.CharField(blank=True, default='', max_length=255, verbose_name='Accounting Session ID')),
+                ('allocated_at', models.DateTimeField(verbose_name='Allocated At')),
+                ('expires_at', models.DateTimeField(verbose_name='Expires At')),
+                ('pool', models.ForeignKey(on_delete=django.db.models.deletion.CASCADE, related_name='leases', to='radius.ipv6prefixpool', verbose_name='Pool')),
+            ],
+            options={
+                'verbose_name': 'IPv6 Prefix Lease',
+                'verbose_name_plural': 'IPv6 Prefix Leases',
+                'db_table': 'radius_ipv6_prefix_lease',
+                'ordering': ['pool', 'slot'],
+                'indexes': [models.Index(fields=['address'], name='rad_ip6_lease_address_idx'), models.Index(fields=['acct_session_id'], name='rad_ip6_lease_session_idx'), models.Index(fields=['pool', 'username'], name='rad_ip6_lease_user_idx'), models.Index(fields=['pool', 'expires_at'], name='rad_ip6_lease_expiry_idx')],
+                'unique_together': {('pool', 'slot')},
+            },
+        ),
+    ]
@@ -1,3 +1,4 @@
+from django.core.validators import MaxValueValidator, RegexValidator
 from django.db import models
 from django.utils.translation import gettext_lazy as _
 from users.models import User
@@ -12,6 +13,17 @@ class AuthAttributeGroup(models.Model):
     description = models.TextField(_("Description"), blank=True)
     is_system = models.BooleanField(_("System Group"), default=False, 
                                    help_text=_("System groups cannot be deleted"))
+    vlan = models.CharField(_("VLAN"), max_length=64, blank=True, default='',
+                            help_text=_("VLAN ID or name for members; names are translated through each NAS's VLAN map"))
+    rate_limit = models.CharField(_("Rate Limit"), max_length=32, blank=True, default='',
+                                  validators=[RegexValidator(r'^[1-9]\d*[kKMG]?/[1-9]\d*[kKMG]?$',
+                                                             _("Use upload/download in bits per second, e.g. 10M/50M"))],
+                                  help_text=_("Upload/download limit, e.g. 10M/50M"))
+    acl_name = models.CharField(_("ACL Name"), max_length=128, blank=True, default='',
+                                help_text=_("Filter, role or address list the NAS applies to members"))
+    privilege_level = models.PositiveSmallIntegerField(_("Privilege Level"), null=True, blank=True,
+                                                       validators=[MaxValueValidator(15)],
+                                                       help_text=_("Device administration level (0-15), mapped to each vendor's admin roles"))
     created_at = models.DateTimeField(_("Created At"), auto_now_add=True)
     updated_at = models.DateTimeField(_("Updated At"), auto_now=True)
 
@@ -33,9 +45,18 @@ class RadiusAttribute(models.Model):
         ('string', _('String')),
         ('integer', _('Integer')),
         ('ipaddr', _('IP Address')),
+        ('ipv6addr', _('IPv6 Address')),
+        ('ipv6prefix', _('IPv6 Prefix')),
+        ('ifid', _('Interface ID')),
         ('date', _('Date')),
         ('octets', _('Octets')),
     )
+    ENCRYPTION_SCHEMES = (
+        ('', _('As in the dictionary')),
+        ('user-password', _('User-Password (RFC 2865)')),
+        ('salt', _('Salt (RFC 2868)')),
+        ('ascend-secret', _('Ascend-Send-Secret')),
+    )
 
     group = models.ForeignKey(AuthAttributeGroup, on_delete=models.CASCADE, 
                              related_name='attributes', verbose_name=_("Attribute Group"))
@@ -45,6 +66,10 @@ class RadiusAttribute(models.Model):
     attribute_name = models.CharField(_("Attribute Name"), max_length=255)
     attribute_type = models.CharField(_("Attribute Type"), max_length=20, choices=ATTRIBUTE_TYPES)
     attribute_value = models.CharField(_("Attribute Value"), max_length=255)
+    tag = models.PositiveSmallIntegerField(_("Tag"), null=True, blank=True, validators=[MaxValueValidator(31)],
+                                           help_text=_("RFC 2868 tag (0-31) for Tunnel-* attributes"))
+    encryption = models.CharField(_("Encryption"), max_length=20, choices=ENCRYPTION_SCHEMES, blank=True, default='',
+                                  help_text=_("Tunnel-Password and MS-MPPE keys are salt-encrypted unless set otherwise"))
     created_at = models.DateTimeField(_("Created At"), auto_now_add=True)
     updated_at = models.DateTimeField(_("Updated At"), auto_now=True)
 
@@ -117,3 +142,209 @@ class Secret(models.Model):
     def __str__(self):
         return self.name
 
+
+class DeviceAdminProfile(models.Model):
+    """
+    Model representing the device administration rights a user group grants on switches,
+    routers and firewalls. The core replies with the matching Service-Type and each vendor's
+    privilege attribute, and rejects management logins the profile doesn't allow.
+    """
+    name = models.CharField(_("Name"), max_length=255, unique=True)
+    description = models.TextField(_("Description"), blank=True)
+    privilege_level = models.PositiveSmallIntegerField(_("Privilege Level"), default=1,
+                                                       validators=[MaxValueValidator(15)],
+                                                       help_text=_("0-15; only 15 allows Administrative-User logins"))
+    vendor_roles = models.JSONField(_("Vendor Roles"), default=dict, blank=True,
+                                    help_text=_('Roles replacing the built-in ones, e.g. {"mikrotik": "write", "juniper": "noc"}'))
+    created_at = models.DateTimeField(_("Created At"), auto_now_add=True)
+    updated_at = models.DateTimeField(_("Updated At"), auto_now=True)
+
+    class Meta:
+        verbose_name = _("Device Admin Profile")
+        verbose_name_plural = _("Device Admin Profiles")
+        ordering = ['name']
+        db_table = 'radius_device_admin_profile'
+
+    def __str__(self):
+        return f"{self.name} ({self.privilege_level})"
+
+
+class AuthorizationRule(models.Model):
+    """
+    Model representing an authorization rule evaluated by the core after authentication.
+    Rules run in priority order (lowest first); the first matching accept or reject rule
+    decides, while continue rules only contribute their attribute edits.
+    """
+    ACTIONS = (
+        ('continue', _('Continue')),
+        ('accept', _('Accept')),
+        ('reject', _('Reject')),
+    )
+
+    name = models.CharField(_("Name"), max_length=255, unique=True)
+    description = models.TextField(_("Description"), blank=True)
+    priority = models.IntegerField(_("Priority"), default=100,
+                                   help_text=_("Rules with lower values are evaluated first"))
+    is_enabled = models.BooleanField(_("Enabled"), default=True)
+    conditions = models.JSONField(_("Conditions"), default=list, blank=True,
+                                  help_text=_('All must match, e.g. [{"field": "ssid", "operator": "equals", "value": "Corp"}]'))
+    action = models.CharField(_("Action"), max_length=20, choices=ACTIONS, default='continue')
+    reply_message = models.CharField(_("Reply Message"), max_length=255, blank=True, default='',
+                                     help_text=_("Sent with reject actions"))
+    attribute_edits = models.JSONField(_("Attribute Edits"), default=list, blank=True,
+                                       help_text=_('e.g. [{"attribute_id": 27, "type": "integer", "value": "3600", "op": "set"}]'))
+    vlan = models.CharField(_("VLAN"), max_length=64, blank=True, default='',
+                            help_text=_("VLAN ID or name assigned to matching users, overriding their own"))
+    created_at = models.DateTimeField(_("Created At"), auto_now_add=True)
+    updated_at = models.DateTimeField(_("Updated At"), auto_now=True)
+
+    class Meta:
+        verbose_name = _("Authorization Rule")
+        verbose_name_plural = _("Authorization Rules")
+        ordering = ['priority', 'id']
+        indexes = [
+            models.Index(fields=['priority'], name='rad_auth_rule_priority_idx'),
+        ]
+        db_table = 'radius_authorization_rule'
+
+    def __str__(self):
+        return f"{self.name} ({self.priority})"
+
+
+class IpPool(models.Model):
+    """
+    Model representing a range of IPv4 addresses the core leases to users as Framed-IP-Address.
+    A pool applies to the members of its user group, to the NASes of its NAS group, or only
+    where both match when it names both. User group pools are tried first, then by priority.
+    """
+    name = models.CharField(_("Name"), max_length=255, unique=True)
+    description = models.TextField(_("Description"), blank=True)
+    range_start = models.GenericIPAddressField(_("Range Start"), protocol='IPv4')
+    range_end = models.GenericIPAddressField(_("Range End"), protocol='IPv4')
+    netmask = models.GenericIPAddressField(_("Netmask"), protocol='IPv4', default='255.255.255.255',
+                                           help_text=_("Sent as Framed-IP-Netmask"))
+    user_group = models.ForeignKey('users.UserGroup', on_delete=models.CASCADE, null=True, blank=True,
+                                   related_name='ip_pools', verbose_name=_("User Group"))
+    nas_group = models.ForeignKey('nas.NasGroup', on_delete=models.CASCADE, null=True, blank=True,
+                                  related_name='ip_pools', verbose_name=_("NAS Group"))
+    priority = models.IntegerField(_("Priority"), default=100,
+                                   help_text=_("Pools with lower values are tried first"))
+    lease_timeout = models.PositiveIntegerField(_("Lease Timeout"), default=3600,
+                                                help_text=_("Seconds a lease lasts without accounting; keep it above the interim interval"))
+    is_active = models.BooleanField(_("Active"), default=True)
+    created_at = models.DateTimeField(_("Created At"), auto_now_add=True)
+    updated_at = models.DateTimeField(_("Updated At"), auto_now=True)
+
+    class Meta:
+        verbose_name = _("IP Pool")
+        verbose_name_plural = _("IP Pools")
+        ordering = ['priority', 'name']
+        db_table = 'radius_ip_pool'
+
+    def __str__(self):
+        return f"{self.name} ({self.range_start}-{self.range_end})"
+
+
+class IpPoolLease(models.Model):
+    """
+    Model representing an address leased from an IP pool. Leases are kept after they expire,
+    so users get the same address back when it is still free.
+    """
+    pool = models.ForeignKey(IpPool, on_delete=models.CASCADE, related_name='leases', verbose_name=_("Pool"))
+    address = models.GenericIPAddressField(_("Address"), protocol='IPv4')
+    username = models.CharField(_("Username"), max_length=255)
+    calling_station_id = models.CharField(_("Calling Station ID"), max_length=255, blank=True, default='')
+    nas_ip = models.GenericIPAddressField(_("NAS IP"), protocol='IPv4', null=True, blank=True)
+    acct_session_id = models.CharField(_("Accounting Session ID"), max_length=255, blank=True, default='')
+    allocated_at = models.DateTimeField(_("Allocated At"))
+    expires_at = models.DateTimeField(_("Expires At"))
+
+    class Meta:
+        verbose_name = _("IP Pool Lease")
+        verbose_name_plural = _("IP Pool Leases")
+        ordering = ['pool', 'address']
+        unique_together = [['pool', 'address']]
+        indexes = [
+            models.Index(fields=['acct_session_id'], name='rad_ip_lease_session_idx'),
+            models.Index(fields=['pool', 'username'], name='rad_ip_lease_user_idx'),
+            models.Index(fields=['pool', 'expires_at'], name='rad_ip_lease_expiry_idx'),
+        ]
+        db_table = 'radius_ip_pool_lease'
+
+    def __str__(self):
+        return f"{self.address} -> {self.username}"
+
+
+class Ipv6PrefixPool(models.Model):
+    """
+    Model representing an IPv6 network the core splits into prefixes of one length, handed out
+    as Framed-IPv6-Prefix (with an optional Framed-Interface-Id) or Delegated-IPv6-Prefix.
+    Pools apply to user and NAS groups like IP pools.
+    """
+    KINDS = (
+        ('framed', _('Framed-IPv6-Prefix')),
+        ('delegated', _('Delegated-IPv6-Prefix')),
+    )
+
+    name = models.CharField(_("Name"), max_length=255, unique=True)
+    description = models.TextField(_("Description"), blank=True)
+    kind = models.CharField(_("Kind"), max_length=20, choices=KINDS, default='delegated')
+    network = models.GenericIPAddressField(_("Network"), protocol='IPv6')
+    prefix_length = models.PositiveSmallIntegerField(_("Prefix Length"), validators=[MaxValueValidator(128)])
+    delegated_length = models.PositiveSmallIntegerField(_("Delegated Length"), default=56,
+                                                        validators=[MaxValueValidator(128)],
+                                                        help_text=_("Length of the prefixes handed out, e.g. 56 or 64"))
+    interface_id = models.CharField(_("Interface ID"), max_length=19, blank=True, default='',
+                                    help_text=_("Framed-Interface-Id sent with framed prefixes, e.g. 0:0:0:1"))
+    user_group = models.ForeignKey('users.UserGroup', on_delete=models.CASCADE, null=True, blank=True,
+                                   related_name='ipv6_prefix_pools', verbose_name=_("User Group"))
+    nas_group = models.ForeignKey('nas.NasGroup', on_delete=models.CASCADE, null=True, blank=True,
+                                  related_name='ipv6_prefix_pools', verbose_name=_("NAS Group"))
+    priority = models.IntegerField(_("Priority"), default=100,
+                                   help_text=_("Pools with lower values are tried first"))
+    lease_timeout = models.PositiveIntegerField(_("Lease Timeout"), default=3600,
+                                                help_text=_("Seconds a lease lasts without accounting; keep it above the interim interval"))
+    is_active = models.BooleanField(_("Active"), default=True)
+    created_at = models.DateTimeField(_("Created At"), auto_now_add=True)
+    updated_at = models.DateTimeField(_("Updated At"), auto_now=True)
+
+    class Meta:
+        verbose_name = _("IPv6 Prefix Pool")
+        verbose_name_plural = _("IPv6 Prefix Pools")
+        ordering = ['priority', 'name']
+        db_table = 'radius_ipv6_prefix_pool'
+
+    def __str__(self):
+        return f"{self.name} ({self.network}/{self.prefix_length} by /{self.delegated_length})"
+
+
+class Ipv6PrefixLease(models.Model):
+    """
+    Model representing a prefix leased from an IPv6 prefix pool. `slot` numbers the prefix
+    within the pool's network; `address` is its first address.
+    """
+    pool = models.ForeignKey(Ipv6PrefixPool, on_delete=models.CASCADE, related_name='leases', verbose_name=_("Pool"))
+    slot = models.BigIntegerField(_("Slot"))
+    address = models.GenericIPAddressField(_("Prefix"), protocol='IPv6')
+    username = models.CharField(_("Username"), max_length=255)
+    calling_station_id = models.CharField(_("Calling Station ID"), max_length=255, blank=True, default='')
+    nas_ip = models.GenericIPAddressField(_("NAS IP"), protocol='IPv4', null=True, blank=True)
+    acct_session_id = models.CharField(_("Accounting Session ID"), max_length=255, blank=True, default='')
+    allocated_at = models.DateTimeField(_("Allocated At"))
+    expires_at = models.DateTimeField(_("Expires At"))
+
+    class Meta:
+        verbose_name = _("IPv6 Prefix Lease")
+        verbose_name_plural = _("IPv6 Prefix Leases")
+        ordering = ['pool', 'slot']
+        unique_together = [['pool', 'slot']]
+        indexes = [
+            models.Index(fields=['address'], name='rad_ip6_lease_address_idx'),
+            models.Index(fields=['acct_session_id'], name='rad_ip6_lease_session_idx'),
+            models.Index(fields=['pool', 'username'], name='rad_ip6_lease_user_idx'),
+            models.Index(fields=['pool', 'expires_at'], name='rad_ip6_lease_expiry_idx'),
+        ]
+        db_table = 'radius_ipv6_prefix_lease'
+
+    def __str__(self):
+        return f"{self.address}/{self.pool.delegated_length} -> {self.username}"
@@ -1,7 +1,12 @@
+import ipaddress
+import re
 from rest_framework import serializers
 
 from users.serializers import UserSerializer
-from .models import AuthAttributeGroup, RadiusAttribute, UserNasRelationship, Secret
+from .models import (
+    AuthAttributeGroup, RadiusAttribute, UserNasRelationship, Secret, AuthorizationRule, DeviceAdminProfile,
+    IpPool, IpPoolLease, Ipv6PrefixPool, Ipv6PrefixLease
+)
 
 
 
@@ -12,7 +17,7 @@ class RadiusAttributeSerializer(serializers.ModelSerializer):
     class Meta:
         model = RadiusAttribute
         fields = ['id', 'group', 'vendor_id', 'attribute_id', 'attribute_name', 
-                 'attribute_type', 'attribute_value', 'created_at', 'updated_at']
+                 'attribute_type', 'attribute_value', 'tag', 'encryption', 'created_at', 'updated_at']
         read_only_fields = ['created_at', 'updated_at']
 
 
@@ -24,7 +29,8 @@ class AuthAttributeGroupSerializer(serializers.ModelSerializer):
 
     class Meta:
         model = AuthAttributeGroup
-        fields = ['id', 'name', 'description', 'created_at', 'updated_at', 'attributes']
+        fields = ['id', 'name', 'description', 'vlan', 'rate_limit', 'acl_name', 'privilege_level',
+                  'created_at', 'updated_at', 'attributes']
         read_only_fields = ['created_at', 'updated_at']
 
 
@@ -34,7 +40,7 @@ class AuthAttributeGroupCreateSerializer(serializers.ModelSerializer):
     """
     class Meta:
         model = AuthAttributeGroup
-        fields = ['id', 'name', 'description']
+        fields = ['id', 'name', 'description', 'vlan', 'rate_limit', 'acl_name', 'privilege_level']
 
 
 class RadiusAttributeCreateSerializer(serializers.ModelSerializer):
@@ -44,7 +50,7 @@ class RadiusAttributeCreateSerializer(serializers.ModelSerializer):
     class Meta:
         model = RadiusAttribute
         fields = ['group', 'vendor_id', 'attribute_id', 'attribute_name', 
-                 'attribute_type', 'attribute_value']
+                 'attribute_type', 'attribute_value', 'tag', 'encryption']
 
 
 class UserNasRelationshipSerializer(serializers.ModelSerializer):
@@ -117,3 +123,167 @@ class SecretUpdateSerializer(serializers.ModelSerializer):
     class Meta:
         model = Secret
         fields = ['name', 'secret', 'description', 'source_subnets']
+
+
+class DeviceAdminProfileSerializer(serializers.ModelSerializer):
+    """
+    Serializer for the DeviceAdminProfile model.
+    """
+    VENDORS = {'mikrotik', 'cisco', 'juniper', 'aruba', 'ubiquiti', 'fortinet'}
+
+    class Meta:
+        model = DeviceAdminProfile
+        fields = ['id', 'name', 'description', 'privilege_level', 'vendor_roles', 'created_at', 'updated_at']
+        read_only_fields = ['created_at', 'updated_at']
+
+    def validate_vendor_roles(self, value):
+        if not isinstance(value, dict):
+            raise serializers.ValidationError("Vendor roles must be an object of vendor names to roles.")
+        for vendor, role in value.items():
+            if vendor.lower() not in self.VENDORS:
+                raise serializers.ValidationError(f"Unknown vendor: {vendor}")
+            if not isinstance(role, str) or not role.strip():
+                raise serializers.ValidationError(f"Role for {vendor} must be a non-empty string.")
+        return value
+
+
+class IpPoolSerializer(serializers.ModelSerializer):
+    """
+    Serializer for the IpPool model.
+    """
+    MAX_POOL_SIZE = 1 << 24
+
+    class Meta:
+        model = IpPool
+        fields = ['id', 'name', 'description', 'range_start', 'range_end', 'netmask', 'user_group',
+                  'nas_group', 'priority', 'lease_timeout', 'is_active', 'created_at', 'updated_at']
+        read_only_fields = ['created_at', 'updated_at']
+
+    def validate(self, data):
+        def current(field):
+            return data[field] if field in data else getattr(self.instance, field, None)
+
+        start, end = current('range_start'), current('range_end')
+        if start and end:
+            size = int(ipaddress.IPv4Address(end)) - int(ipaddress.IPv4Address(start)) + 1
+            if size < 1:
+                raise serializers.ValidationError({'range_end': "The range must not end before it starts."})
+            if size > self.MAX_POOL_SIZE:
+                raise serializers.ValidationError({'range_end': "Pools are limited to a /8."})
+        netmask = current('netmask')
+        if netmask:
+            try:
+                ipaddress.IPv4Network(f"0.0.0.0/{netmask}")
+            except ValueError:
+                raise serializers.ValidationError({'netmask': "Not a valid netmask."})
+        if not current('user_group') and not current('nas_group'):
+            raise serializers.ValidationError("A pool needs a user group, a NAS group or both.")
+        return data
+
+
+class IpPoolLeaseSerializer(serializers.ModelSerializer):
+    """
+    Serializer for the IpPoolLease model. Leases are managed by the core.
+    """
+    pool_name = serializers.CharField(source='pool.name', read_only=True)
+
+    class Meta:
+        model = IpPoolLease
+        fields = ['id', 'pool', 'pool_name', 'address', 'username', 'calling_station_id', 'nas_ip',
+                  'acct_session_id', 'allocated_at', 'expires_at']
+        read_only_fields = fields
+
+
+class Ipv6PrefixPoolSerializer(serializers.ModelSerializer):
+    """
+    Serializer for the Ipv6PrefixPool model.
+    """
+    INTERFACE_ID = re.compile(r'^[0-9a-fA-F]{1,4}(:[0-9a-fA-F]{1,4}){3}$')
+
+    class Meta:
+        model = Ipv6PrefixPool
+        fields = ['id', 'name', 'description', 'kind', 'network', 'prefix_length', 'delegated_length',
+                  'interface_id', 'user_group', 'nas_group', 'priority', 'lease_timeout', 'is_active',
+                  'created_at', 'updated_at']
+        read_only_fields = ['created_at', 'updated_at']
+
+    def validate_interface_id(self, value):
+        if value and not self.INTERFACE_ID.match(value):
+            raise serializers.ValidationError("Interface IDs are four groups of hex digits, e.g. 0:0:0:1.")
+        return value
+
+    def validate(self, data):
+        def current(field):
+            return data[field] if field in data else getattr(self.instance, field, None)
+
+        network, prefix_length = current('network'), current('prefix_length')
+        delegated_length = current('delegated_length')
+        if network and prefix_length is not None:
+            try:
+                ipaddress.IPv6Network(f"{network}/{prefix_length}")
+            except ValueError:
+                raise serializers.ValidationError({'network': "Host bits are set past the prefix length."})
+        if prefix_length is not None and delegated_length is not None and delegated_length < prefix_length:
+            raise serializers.ValidationError({'delegated_length': "Must not be shorter than the prefix length."})
+        if not current('user_group') and not current('nas_group'):
+            raise serializers.ValidationError("A pool needs a user group, a NAS group or both.")
+        return data
+
+
+class Ipv6PrefixLeaseSerializer(serializers.ModelSerializer):
+    """
+    Serializer for the Ipv6PrefixLease model. Leases are managed by the core.
+    """
+    pool_name = serializers.CharField(source='pool.name', read_only=True)
+    prefix_length = serializers.IntegerField(source='pool.delegated_length', read_only=True)
+
+    class Meta:
+        model = Ipv6PrefixLease
+        fields = ['id', 'pool', 'pool_name', 'slot', 'address', 'prefix_length', 'username',
+                  'calling_station_id', 'nas_ip', 'acct_session_id', 'allocated_at', 'expires_at']
+        read_only_fields = fields
+
+
+class AuthorizationRuleSerializer(serializers.ModelSerializer):
+    """
+    Serializer for the AuthorizationRule model. The core compiles the rules itself and
+    skips invalid ones, so only the shape of the JSON fields is checked here.
+    """
+    CONDITION_FIELDS = {
+        'username', 'auth_method', 'nas_port_type', 'called_station_id', 'ssid',
+        'calling_station_id', 'nas_identifier', 'nas_ip_address', 'nas_group',
+        'user_group', 'time_of_day', 'day_of_week',
+    }
+    CONDITION_OPERATORS = {'equals', 'not_equals', 'in', 'not_in', 'matches', 'between'}
+    EDIT_OPS = {'set', 'add', 'remove'}
+
+    class Meta:
+        model = AuthorizationRule
+        fields = ['id', 'name', 'description', 'priority', 'is_enabled', 'conditions', 'action',
+                  'reply_message', 'attribute_edits', 'vlan', 'created_at', 'updated_at']
+        read_only_fields = ['created_at', 'updated_at']
+
+    def validate_conditions(self, value):
+        if not isinstance(value, list):
+            raise serializers.ValidationError("Conditions must be a list.")
+        for condition in value:
+            if not isinstance(condition, dict) or 'value' not in condition:
+                raise serializers.ValidationError("Each condition needs a field, an operator and a value.")
+            if condition.get('field') not in self.CONDITION_FIELDS:
+                raise serializers.ValidationError(f"Unknown condition field: {condition.get('field')}")
+            if condition.get('operator', 'equals') not in self.CONDITION_OPERATORS:
+                raise serializers.ValidationError(f"Unknown condition operator: {condition.get('operator')}")
+        return value
+
+    def validate_attribute_edits(self, value):
+        if not isinstance(value, list):
+            raise serializers.ValidationError("Attribute edits must be a list.")
+        for edit in value:
+            if not isinstance(edit, dict) or not isinstance(edit.get('attribute_id'), int):
+                raise serializers.ValidationError("Each attribute edit needs an attribute_id.")
+            if edit.get('op', 'set') not in self.EDIT_OPS:
+                raise serializers.ValidationError(f"Unknown attribute edit op: {edit.get('op')}")
+            tag = edit.get('tag')
+            if tag is not None and (not isinstance(tag, int) or not 0 <= tag <= 31):
+                raise serializers.ValidationError("Attribute edit tags must be between 0 and 31.")
+        return value
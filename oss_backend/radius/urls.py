@@ -4,7 +4,13 @@ from .views import (
     AuthAttributeGroupViewSet,
     RadiusAttributeViewSet,
     UserNasRelationshipViewSet,
-    SecretViewSet
+    SecretViewSet,
+    AuthorizationRuleViewSet,
+    DeviceAdminProfileViewSet,
+    IpPoolViewSet,
+    IpPoolLeaseViewSet,
+    Ipv6PrefixPoolViewSet,
+    Ipv6PrefixLeaseViewSet
 )
 
 router = DefaultRouter()
@@ -12,6 +18,12 @@ router.register(r'attribute-groups', AuthAttributeGroupViewSet)
 router.register(r'attributes', RadiusAttributeViewSet)
 router.register(r'user-nas', UserNasRelationshipViewSet)
 router.register(r'secrets', SecretViewSet)
+router.register(r'authorization-rules', AuthorizationRuleViewSet)
+router.register(r'device-admin-profiles', DeviceAdminProfileViewSet)
+router.register(r'ip-pools', IpPoolViewSet)
+router.register(r'ip-pool-leases', IpPoolLeaseViewSet)
+router.register(r'ipv6-prefix-pools', Ipv6PrefixPoolViewSet)
+router.register(r'ipv6-prefix-leases', Ipv6PrefixLeaseViewSet)
 
 
 urlpatterns = [
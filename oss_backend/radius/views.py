@@ -7,13 +7,18 @@ import json
 import redis
 import os
 
-from .models import AuthAttributeGroup, RadiusAttribute, UserNasRelationship, Secret
+from .models import (
+    AuthAttributeGroup, RadiusAttribute, UserNasRelationship, Secret, AuthorizationRule, DeviceAdminProfile,
+    IpPool, IpPoolLease, Ipv6PrefixPool, Ipv6PrefixLease
+)
 from .serializers import (
     AuthAttributeGroupSerializer, AuthAttributeGroupCreateSerializer,
     RadiusAttributeSerializer, RadiusAttributeCreateSerializer,
     UserNasRelationshipSerializer, UserNasRelationshipCreateSerializer,
     UserNasRelationshipUpdateSerializer,
-    SecretSerializer, SecretCreateSerializer, SecretUpdateSerializer
+    SecretSerializer, SecretCreateSerializer, SecretUpdateSerializer,
+    AuthorizationRuleSerializer, DeviceAdminProfileSerializer,
+    IpPoolSerializer, IpPoolLeaseSerializer, Ipv6PrefixPoolSerializer, Ipv6PrefixLeaseSerializer
 )
 
 class AuthAttributeGroupViewSet(viewsets.ModelViewSet):
@@ -263,3 +268,81 @@ class SecretViewSet(viewsets.ModelViewSet):
         elif self.action in ['update', 'partial_update']:
             return SecretUpdateSerializer
         return self.serializer_class
+
+
+class AuthorizationRuleViewSet(viewsets.ModelViewSet):
+    """
+    ViewSet for managing authorization rules. The core reloads them periodically.
+    """
+    queryset = AuthorizationRule.objects.all()
+    serializer_class = AuthorizationRuleSerializer
+    filter_backends = [DjangoFilterBackend, SearchFilter, OrderingFilter]
+    filterset_fields = ['action', 'is_enabled']
+    search_fields = ['name', 'description']
+    ordering_fields = ['priority', 'name', 'created_at']
+    ordering = ['priority', 'id']
+
+
+class DeviceAdminProfileViewSet(viewsets.ModelViewSet):
+    """
+    ViewSet for managing device administration profiles.
+    """
+    queryset = DeviceAdminProfile.objects.all()
+    serializer_class = DeviceAdminProfileSerializer
+    filter_backends = [DjangoFilterBackend, SearchFilter, OrderingFilter]
+    filterset_fields = ['privilege_level']
+    search_fields = ['name', 'description']
+    ordering_fields = ['name', 'privilege_level', 'created_at']
+    ordering = ['name']
+
+
+class IpPoolViewSet(viewsets.ModelViewSet):
+    """
+    ViewSet for managing IP pools.
+    """
+    queryset = IpPool.objects.select_related('user_group', 'nas_group').all()
+    serializer_class = IpPoolSerializer
+    filter_backends = [DjangoFilterBackend, SearchFilter, OrderingFilter]
+    filterset_fields = ['user_group', 'nas_group', 'is_active']
+    search_fields = ['name', 'description']
+    ordering_fields = ['name', 'priority', 'created_at']
+    ordering = ['priority', 'name']
+
+
+class IpPoolLeaseViewSet(viewsets.ReadOnlyModelViewSet):
+    """
+    ViewSet for viewing the addresses leased from IP pools.
+    """
+    queryset = IpPoolLease.objects.select_related('pool').all()
+    serializer_class = IpPoolLeaseSerializer
+    filter_backends = [DjangoFilterBackend, SearchFilter, OrderingFilter]
+    filterset_fields = ['pool', 'username', 'nas_ip', 'acct_session_id']
+    search_fields = ['username', 'address', 'calling_station_id', 'acct_session_id']
+    ordering_fields = ['address', 'allocated_at', 'expires_at']
+    ordering = ['pool', 'address']
+
+
+class Ipv6PrefixPoolViewSet(viewsets.ModelViewSet):
+    """
+    ViewSet for managing IPv6 prefix pools.
+    """
+    queryset = Ipv6PrefixPool.objects.select_related('user_group', 'nas_group').all()
+    serializer_class = Ipv6PrefixPoolSerializer
+    filter_backends = [DjangoFilterBackend, SearchFilter, OrderingFilter]
+    filterset_fields = ['kind', 'user_group', 'nas_group', 'is_active']
+    search_fields = ['name', 'description']
+    ordering_fields = ['name', 'priority', 'created_at']
+    ordering = ['priority', 'name']
+
+
+class Ipv6PrefixLeaseViewSet(viewsets.ReadOnlyModelViewSet):
+    """
+    ViewSet for viewing the prefixes leased from IPv6 prefix pools.
+    """
+    queryset = Ipv6PrefixLease.objects.select_related('pool').all()
+    serializer_class = Ipv6PrefixLeaseSerializer
+    filter_backends = [DjangoFilterBackend, SearchFilter, OrderingFilter]
+    filterset_fields = ['pool', 'username', 'nas_ip', 'acct_session_id']
+    search_fields = ['username', 'address', 'calling_station_id', 'acct_session_id']
+    ordering_fields = ['slot', 'allocated_at', 'expires_at']
+    ordering = ['pool', 'slot']
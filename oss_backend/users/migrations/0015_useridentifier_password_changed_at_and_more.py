@@ -0,0 +1,22 @@
+import django.db.models.functions.datetime
+from django.db import migrations, models
+
+
+class Migration(migrations.Migration):
+
+    dependencies = [
+        ('users', '0014_user_allow_any_nas'),
+    ]
+
+    operations = [
+        migrations.AddField(
+            model_name='useridentifier',
+            name='password_changed_at',
+            field=models.DateTimeField(blank=True, db_default=django.db.models.functions.datetime.Now(), null=True),
+        ),
+        migrations.AddField(
+            model_name='useridentifier',
+            name='grace_logins_used',
+            field=models.PositiveIntegerField(db_default=0, default=0),
+        ),
+    ]
@@ -0,0 +1,38 @@
+from django.db import migrations
+
+
+# The RADIUS core caches identifier lookups and listens on this channel to
+# drop entries whose value, password or enabled flag changed.
+CREATE_TRIGGER = """
+CREATE OR REPLACE FUNCTION user_identifiers_notify_change() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP IN ('UPDATE', 'DELETE') THEN
+        PERFORM pg_notify('openrdx_subscriber_changed', OLD.value);
+    END IF;
+    IF TG_OP IN ('INSERT', 'UPDATE') AND (TG_OP = 'INSERT' OR NEW.value IS DISTINCT FROM OLD.value) THEN
+        PERFORM pg_notify('openrdx_subscriber_changed', NEW.value);
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER user_identifiers_notify_change
+AFTER INSERT OR UPDATE OR DELETE ON user_identifiers
+FOR EACH ROW EXECUTE FUNCTION user_identifiers_notify_change();
+"""
+
+DROP_TRIGGER = """
+DROP TRIGGER IF EXISTS user_identifiers_notify_change ON user_identifiers;
+DROP FUNCTION IF EXISTS user_identifiers_notify_change();
+"""
+
+
+class Migration(migrations.Migration):
+
+    dependencies = [
+        ('users', '0015_useridentifier_password_changed_at_and_more'),
+    ]
+
+    operations = [
+        migrations.RunSQL(CREATE_TRIGGER, reverse_sql=DROP_TRIGGER),
+    ]
@@ -0,0 +1,21 @@
+from django.db import migrations, models
+
+
+class Migration(migrations.Migration):
+
+    dependencies = [
+        ('users', '0016_useridentifier_change_notify'),
+    ]
+
+    operations = [
+        migrations.AddField(
+            model_name='usergroup',
+            name='access_schedule',
+            field=models.JSONField(blank=True, help_text='Allowed login windows in the NAS local time, e.g. [{"days": ["mon", "tue", "wed", "thu", "fri"], "hours": "08:00-18:00"}]; empty allows any time', null=True, verbose_name='Access Schedule'),
+        ),
+        migrations.AddField(
+            model_name='useridentifier',
+            name='access_schedule',
+            field=models.JSONField(blank=True, help_text='Allowed login windows in the NAS local time; replaces the schedules of the user groups', null=True),
+        ),
+    ]
@@ -0,0 +1,22 @@
+import django.core.validators
+from django.db import migrations, models
+
+
+class Migration(migrations.Migration):
+
+    dependencies = [
+        ('users', '0017_access_schedule'),
+    ]
+
+    operations = [
+        migrations.AddField(
+            model_name='usergroup',
+            name='max_sessions',
+            field=models.PositiveIntegerField(blank=True, help_text='Concurrent sessions allowed to each member; empty means unlimited', null=True, validators=[django.core.validators.MinValueValidator(1)], verbose_name='Max Sessions'),
+        ),
+        migrations.AddField(
+            model_name='useridentifier',
+            name='max_sessions',
+            field=models.PositiveIntegerField(blank=True, help_text='Concurrent sessions allowed; replaces the limits of the user groups', null=True, validators=[django.core.validators.MinValueValidator(1)]),
+        ),
+    ]
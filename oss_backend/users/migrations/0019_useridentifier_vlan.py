@@ -0,0 +1,16 @@
+from django.db import migrations, models
+
+
+class Migration(migrations.Migration):
+
+    dependencies = [
+        ('users', '0018_max_sessions'),
+    ]
+
+    operations = [
+        migrations.AddField(
+            model_name='useridentifier',
+            name='vlan',
+            field=models.CharField(blank=True, default='', help_text='VLAN ID or name for this identifier, overriding its attribute group', max_length=64),
+        ),
+    ]
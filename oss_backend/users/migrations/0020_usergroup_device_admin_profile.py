@@ -0,0 +1,18 @@
+import django.db.models.deletion
+from django.db import migrations, models
+
+
+class Migration(migrations.Migration):
+
+    dependencies = [
+        ('radius', '0009_deviceadminprofile'),
+        ('users', '0019_useridentifier_vlan'),
+    ]
+
+    operations = [
+        migrations.AddField(
+            model_name='usergroup',
+            name='device_admin_profile',
+            field=models.ForeignKey(blank=True, null=True, on_delete=django.db.models.deletion.SET_NULL, related_name='user_groups', to='radius.deviceadminprofile', verbose_name='Device Admin Profile'),
+        ),
+    ]
@@ -1,4 +1,6 @@
+from django.core.validators import MinValueValidator
 from django.db import models
+from django.db.models.functions import Now
 from django.utils.translation import gettext_lazy as _
 from mptt.models import MPTTModel, TreeForeignKey
 from django.utils import timezone
@@ -16,6 +18,19 @@ class UserGroup(MPTTModel):
     updated_at = models.DateTimeField(_("Updated At"), auto_now=True)
 
     allow_any_nas = models.BooleanField(_("Allow All NAS"), default=False)
+    access_schedule = models.JSONField(
+        _("Access Schedule"), null=True, blank=True,
+        help_text=_('Allowed login windows in the NAS local time, e.g. '
+                    '[{"days": ["mon", "tue", "wed", "thu", "fri"], "hours": "08:00-18:00"}]; empty allows any time')
+    )
+    max_sessions = models.PositiveIntegerField(
+        _("Max Sessions"), null=True, blank=True, validators=[MinValueValidator(1)],
+        help_text=_("Concurrent sessions allowed to each member; empty means unlimited")
+    )
+    device_admin_profile = models.ForeignKey(
+        'radius.DeviceAdminProfile', on_delete=models.SET_NULL, null=True, blank=True,
+        related_name='user_groups', verbose_name=_("Device Admin Profile")
+    )
 
     class MPTTMeta:
         order_insertion_by = ['name']
@@ -116,6 +131,20 @@ class UserIdentifier(models.Model):
         blank=True,
         related_name='expired_identifiers'
     )
+    password_changed_at = models.DateTimeField(null=True, blank=True, db_default=Now())
+    grace_logins_used = models.PositiveIntegerField(default=0, db_default=0)
+    access_schedule = models.JSONField(
+        null=True, blank=True,
+        help_text='Allowed login windows in the NAS local time; replaces the schedules of the user groups'
+    )
+    max_sessions = models.PositiveIntegerField(
+        null=True, blank=True, validators=[MinValueValidator(1)],
+        help_text='Concurrent sessions allowed; replaces the limits of the user groups'
+    )
+    vlan = models.CharField(
+        max_length=64, blank=True, default='',
+        help_text='VLAN ID or name for this identifier, overriding its attribute group'
+    )
     created_at = models.DateTimeField(auto_now_add=True)
     updated_at = models.DateTimeField(auto_now=True)
 
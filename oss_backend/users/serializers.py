@@ -1,11 +1,42 @@
+import re
+
 from rest_framework import serializers
 from mptt.models import TreeForeignKey
 from .models import User, UserGroup, UserIdentifierType, UserIdentifier, UserIdentifierNasAuthorization
 from django.apps import apps
+from django.utils import timezone
 
 AuthAttributeGroup = apps.get_model('radius', 'AuthAttributeGroup')
 RadiusAttribute = apps.get_model('radius', 'RadiusAttribute')
 
+SCHEDULE_DAYS = ('mon', 'tue', 'wed', 'thu', 'fri', 'sat', 'sun')
+SCHEDULE_HOURS = re.compile(r'^([01]\d|2[0-3]):[0-5]\d-(([01]\d|2[0-3]):[0-5]\d|24:00)$')
+
+
+def validate_access_schedule(value):
+    """
+    Checks the shape of an access schedule, a list of {"days": [...], "hours": "HH:MM-HH:MM"} windows.
+    """
+    if value is None:
+        return value
+    if not isinstance(value, list):
+        raise serializers.ValidationError("Access schedule must be a list of windows.")
+    for window in value:
+        if not isinstance(window, dict) or set(window) - {'days', 'hours'}:
+            raise serializers.ValidationError("Each window may only have days and hours.")
+        days = window.get('days', [])
+        if not isinstance(days, list) or any(str(day).lower() not in SCHEDULE_DAYS for day in days):
+            raise serializers.ValidationError(f"Window days must be a list of {', '.join(SCHEDULE_DAYS)}.")
+        hours = window.get('hours')
+        if hours is not None:
+            if not isinstance(hours, str) or not SCHEDULE_HOURS.match(hours):
+                raise serializers.ValidationError("Window hours must look like 08:00-18:00.")
+            start, end = hours.split('-')
+            if start == end:
+                raise serializers.ValidationError("Window hours must not be empty.")
+    return value
+
+
 class UserGroupSerializer(serializers.ModelSerializer):
     """
     Serializer for the UserGroup model.
@@ -16,10 +47,13 @@ class UserGroupSerializer(serializers.ModelSerializer):
     class Meta:
         model = UserGroup
         fields = ['id', 'name', 'description',
-                  'allow_any_nas',
+                  'allow_any_nas', 'access_schedule', 'max_sessions', 'device_admin_profile',
                   'parent', 'created_at', 'updated_at']
         read_only_fields = ['created_at', 'updated_at']
 
+    def validate_access_schedule(self, value):
+        return validate_access_schedule(value)
+
 
 class UserGroupTreeSerializer(serializers.ModelSerializer):
     """
@@ -52,7 +86,7 @@ class RadiusAttributeSerializer(serializers.ModelSerializer):
     class Meta:
         model = RadiusAttribute
         fields = ['id', 'group', 'vendor_id', 'attribute_id', 'attribute_name',
-                 'attribute_type', 'attribute_value', 'created_at', 'updated_at']
+                 'attribute_type', 'attribute_value', 'tag', 'encryption', 'created_at', 'updated_at']
         read_only_fields = ['created_at', 'updated_at']
 
 
@@ -64,7 +98,8 @@ class AuthAttributeGroupSerializer(serializers.ModelSerializer):
 
     class Meta:
         model = AuthAttributeGroup
-        fields = ['id', 'name', 'description', 'created_at', 'updated_at', 'attributes']
+        fields = ['id', 'name', 'description', 'vlan', 'rate_limit', 'acl_name', 'privilege_level',
+                  'created_at', 'updated_at', 'attributes']
         read_only_fields = ['created_at', 'updated_at']
 
 
@@ -104,13 +139,17 @@ class UserIdentifierSerializer(serializers.ModelSerializer):
             'is_enabled', 'comment', 'auth_attribute_group', 'auth_attribute_group_id',
             'expiration_date', 'reject_expired', 'expired_auth_attribute_group',
             'expired_auth_attribute_group_id', 'created_at', 'updated_at',
-            'is_expired', 'plain_password'
+            'is_expired', 'plain_password', 'password_changed_at', 'grace_logins_used',
+            'access_schedule', 'max_sessions', 'vlan'
         ]
-        read_only_fields = ['created_at', 'updated_at']
+        read_only_fields = ['created_at', 'updated_at', 'password_changed_at', 'grace_logins_used']
 
     def get_is_expired(self, obj):
         return obj.is_expired()
 
+    def validate_access_schedule(self, value):
+        return validate_access_schedule(value)
+
     def get_auth_attribute_group(self, obj):
         if obj.auth_attribute_group:
             return AuthAttributeGroupSerializer(obj.auth_attribute_group).data
@@ -121,6 +160,14 @@ class UserIdentifierSerializer(serializers.ModelSerializer):
             return AuthAttributeGroupSerializer(obj.expired_auth_attribute_group).data
         return None
 
+    def update(self, instance, validated_data):
+        new_password = validated_data.get('plain_password')
+        if new_password is not None and new_password != instance.plain_password:
+            # Restart password ageing whenever the password is changed
+            validated_data['password_changed_at'] = timezone.now()
+            validated_data['grace_logins_used'] = 0
+        return super().update(instance, validated_data)
+
     def validate(self, data):
         if data.get('expiration_date') and not data.get('reject_expired'):
             if not data.get('expired_auth_attribute_group'):
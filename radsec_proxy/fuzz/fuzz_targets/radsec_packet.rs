@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use openrdx_radsec_proxy::radius::RadiusPacket;
+
+// A packet read off the TLS stream is forwarded byte for byte
+fuzz_target!(|data: &[u8]| {
+    if let Ok(packet) = RadiusPacket::from_bytes(data) {
+        assert_eq!(packet.to_bytes(), data);
+    }
+});
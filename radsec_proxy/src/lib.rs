@@ -0,0 +1,4 @@
+//! OpenRDX RadSec proxy. `main.rs` terminates TLS and forwards each packet to the RADIUS server;
+//! the packet code is a library so the fuzz targets can reach it.
+
+pub mod radius;
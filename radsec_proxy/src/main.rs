@@ -9,8 +9,7 @@ use tracing::{info, error};
 use base64::engine::general_purpose::STANDARD;
 use base64::Engine;
 
-mod radius;
-use radius::{RadiusPacket, forward_packet};
+use openrdx_radsec_proxy::radius::{RadiusPacket, forward_packet};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
@@ -80,19 +79,22 @@ async fn handle_connection(
     }
     
     // Parse the RADIUS packet
-    if let Some(packet) = RadiusPacket::from_bytes(&buf[..n]) {
-        // Forward the packet to the RADIUS server
-        match forward_packet(&packet, &radius_server, &radius_secret).await {
-            Ok(response) => {
-                // Send the response back to the client
-                tls_stream.write_all(&response).await?;
-            }
-            Err(e) => {
-                error!("Error forwarding packet: {}", e);
+    match RadiusPacket::from_bytes(&buf[..n]) {
+        Ok(packet) => {
+            // Forward the packet to the RADIUS server
+            match forward_packet(&packet, &radius_server, &radius_secret).await {
+                Ok(response) => {
+                    // Send the response back to the client
+                    tls_stream.write_all(&response).await?;
+                }
+                Err(e) => {
+                    error!("Error forwarding packet: {}", e);
+                }
             }
         }
-    } else {
-        error!("Invalid RADIUS packet received");
+        Err(e) => {
+            error!("Invalid RADIUS packet received: {}", e);
+        }
     }
     
     Ok(())
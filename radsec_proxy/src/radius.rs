@@ -1,7 +1,30 @@
+use std::fmt;
 use std::net::SocketAddr;
 use tokio::net::UdpSocket;
 use tracing::{debug, error};
 
+/// Why the bytes read from a RadSec stream are not a single RADIUS packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketError {
+    /// Fewer bytes than the 20-byte header
+    TooShort(usize),
+    /// The Length field disagrees with the number of bytes read
+    LengthMismatch { length: u16, received: usize },
+}
+
+impl fmt::Display for PacketError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PacketError::TooShort(len) => write!(f, "packet too short ({} bytes)", len),
+            PacketError::LengthMismatch { length, received } => {
+                write!(f, "length field {} does not match the {} bytes read", length, received)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PacketError {}
+
 #[derive(Debug)]
 pub struct RadiusPacket {
     pub code: u8,
@@ -12,20 +35,20 @@ pub struct RadiusPacket {
 }
 
 impl RadiusPacket {
-    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+    pub fn from_bytes(data: &[u8]) -> Result<Self, PacketError> {
         if data.len() < 20 {
-            return None;
+            return Err(PacketError::TooShort(data.len()));
         }
 
         let length = u16::from_be_bytes([data[2], data[3]]);
         if data.len() != length as usize {
-            return None;
+            return Err(PacketError::LengthMismatch { length, received: data.len() });
         }
 
         let mut authenticator = [0u8; 16];
         authenticator.copy_from_slice(&data[4..20]);
 
-        Some(RadiusPacket {
+        Ok(RadiusPacket {
             code: data[0],
             identifier: data[1],
             length,